@@ -1,10 +1,286 @@
-use std::io::Read;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::exit;
+
+use minicc_ir::{OptLevel, PassManager};
+use minicc_pp::Preprocessor;
+
+const USAGE: &str = "\
+usage: ccom [options] [FILE]
+       ccom repl [--target=TRIPLE]
+
+Compiles FILE, or standard input if omitted, to assembly, or a WebAssembly
+text module for `wasm32`, written to standard output unless -o is given.
+`ccom repl` reads and interprets definitions and statements interactively.
+
+options:
+  -D NAME[=VALUE]  define NAME as VALUE, 1 if omitted
+  -U NAME          undefine NAME
+  -I DIR           add DIR to the include search path
+  -isystem DIR     add DIR to the include search path after -I
+  -E               only preprocess
+  -c               assemble into an ELF relocatable object, for `i386-linux`
+                   and `x86_64-linux`; FILE may then be assembly ending in
+                   `.s`, like the runtime in `lib/rt`
+  -o FILE          write the output to FILE
+  -O[LEVEL]        optimise the IR at LEVEL 0 (default), 1 or 2, 1 if omitted
+  --emit=KIND      output `asm` (default), the intermediate representation
+                   `ir`, LLVM IR `llvm` or the checked program as C `c`
+  --run            interpret the program instead, printing `dbg` to standard
+                   error and exiting with the status of `main`
+  --jit            compile the program for `x86_64-linux` and run it in
+                   memory, exiting with the status of `main`; other
+                   functions are taken from the C library
+  --target=TRIPLE  generate code for `i386-linux` (default),
+                   `x86_64-linux`, `aarch64-linux`, `riscv64-linux` or
+                   `wasm32`";
+
+mod repl;
+
+/// Stack for the interpreter, deep enough for `minicc_interp::MAX_DEPTH`
+/// calls.
+const STACK: usize = 1 << 30;
+
+enum Emit {
+    Asm,
+    Ir,
+    Llvm,
+    C,
+    Obj,
+}
 
 fn main() {
-    let mut src = String::new();
-    std::io::stdin().read_to_string(&mut src).unwrap();
+    if std::env::args().nth(1).as_deref() == Some("repl") {
+        let mut target = minicc_gen::TARGETS[0];
+        for arg in std::env::args().skip(2) {
+            match arg.strip_prefix("--target=") {
+                Some(name) => target = target_named(name),
+                None => {
+                    eprintln!("ccom: unknown option `{}`\n\n{}", arg, USAGE);
+                    exit(1);
+                }
+            }
+        }
+        return on_big_stack(|| repl::main(target));
+    }
+
+    let mut pp = Preprocessor::new();
+    let mut file = None;
+    let mut output = None;
+    let mut only_pp = false;
+    let mut emit = Emit::Asm;
+    let mut opt = OptLevel::O0;
+    let mut run = false;
+    let mut jit = false;
+    let mut target = None;
+    // `-D` and `-U` in order, `None` for `-U`, applied after the target's
+    // own macros so that they can override them.
+    let mut defines = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        // Value of an option given as `-Xvalue` or `-X value`.
+        let mut value = |opt: &str| match arg.strip_prefix(opt) {
+            Some("") => args.next().unwrap_or_else(|| {
+                eprintln!("ccom: missing argument to `{}`", opt);
+                exit(1);
+            }),
+            Some(v) => v.to_string(),
+            None => unreachable!(),
+        };
+
+        match &*arg {
+            "-E" => only_pp = true,
+            "-c" => emit = Emit::Obj,
+            "--emit=asm" => emit = Emit::Asm,
+            "--emit=ir" => emit = Emit::Ir,
+            "--emit=llvm" => emit = Emit::Llvm,
+            "--emit=c" => emit = Emit::C,
+            "--run" => run = true,
+            "--jit" => jit = true,
+            "-O" | "-O1" => opt = OptLevel::O1,
+            "-O0" => opt = OptLevel::O0,
+            "-O2" => opt = OptLevel::O2,
+            _ if arg.starts_with("--target=") => {
+                target = Some(target_named(&arg["--target=".len()..]));
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with("-isystem") => {
+                pp.system_include_path(value("-isystem"))
+            }
+            _ if arg.starts_with("-D") => {
+                let def = value("-D");
+                defines.push(match def.split_once('=') {
+                    Some((name, val)) => (name.to_string(), Some(val.into())),
+                    None => (def, Some("1".to_string())),
+                });
+            }
+            _ if arg.starts_with("-U") => defines.push((value("-U"), None)),
+            _ if arg.starts_with("-o") => output = Some(value("-o")),
+            _ if arg.starts_with("-I") => pp.include_path(value("-I")),
+            _ if arg.starts_with('-') && arg != "-" => {
+                eprintln!("ccom: unknown option `{}`\n\n{}", arg, USAGE);
+                exit(1);
+            }
+            _ if file.is_some() => {
+                eprintln!("ccom: more than one input file\n\n{}", USAGE);
+                exit(1);
+            }
+            _ => file = Some(arg),
+        }
+    }
 
-    let node = minicc_parser::parse(&src);
+    let target = match target {
+        Some(t) if jit && t.name() != minicc_jit::TARGET => {
+            eprintln!("ccom: `--jit` only runs `{}` code", minicc_jit::TARGET);
+            exit(1);
+        }
+        Some(t) => t,
+        None if jit => target_named(minicc_jit::TARGET),
+        None => minicc_gen::TARGETS[0],
+    };
+    let arch = minicc_as::Arch::for_target(target.name());
+    if matches!(emit, Emit::Obj) && arch.is_none() {
+        eprintln!("ccom: `-c` is not supported for `{}`", target.name());
+        exit(1);
+    }
+    let mut f: Box<dyn Write> = match &output {
+        Some(path) => Box::new(File::create(path).unwrap_or_else(|e| {
+            eprintln!("ccom: cannot create `{}`: {}", path, e);
+            exit(1);
+        })),
+        None => Box::new(std::io::stdout()),
+    };
+
+    // Assembly is only assembled.
+    if let (Emit::Obj, Some(path)) = (&emit, &file) {
+        if path.ends_with(".s") {
+            let src = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                exit(1);
+            });
+            match minicc_as::assemble(&src, arch.unwrap()) {
+                Ok(obj) => f.write_all(&obj).unwrap(),
+                Err(e) => {
+                    eprintln!("{}:{}", path, e);
+                    exit(1);
+                }
+            }
+            return;
+        }
+    }
+
+    let model = target.data_model();
+    for (name, val) in target.macros() {
+        pp.define(name, val);
+    }
+    for (name, val) in &defines {
+        match val {
+            Some(val) => pp.define(name, val),
+            None => pp.undef(name),
+        }
+    }
+
+    let out = match file.as_deref() {
+        Some(path) if path != "-" => pp.preprocess_file(Path::new(path)),
+        _ => {
+            let mut src = String::new();
+            std::io::stdin().read_to_string(&mut src).unwrap();
+            pp.preprocess_str("<stdin>", &src)
+        }
+    };
+
+    if only_pp {
+        f.write_all(out.with_line_markers().as_bytes()).unwrap();
+        return;
+    }
+
+    let node = minicc_parser::parse(&out.text, &out.map, model);
+    if run {
+        let res = on_big_stack(|| {
+            minicc_interp::run(&node, model, &mut std::io::stderr())
+        });
+        match res {
+            Ok(status) => exit(status),
+            Err(e) => {
+                match e.span {
+                    Some(span) => eprintln!(
+                        "{}: {}\n{}",
+                        out.map.span_loc(span),
+                        e,
+                        out.map.underline(span)
+                    ),
+                    None => eprintln!("ccom: {}", e),
+                }
+                exit(1);
+            }
+        }
+    }
+    if let Emit::C = emit {
+        write!(f, "{}", minicc_ast::c::print(&node)).unwrap();
+        return;
+    }
+
+    let mut module = minicc_ir::lower(&node, model);
+    PassManager::for_level(opt).run(&mut module);
+    if let Err(errs) = minicc_ir::verify(&module) {
+        for e in errs {
+            eprintln!("ccom: invalid IR: {}", e);
+        }
+        exit(1);
+    }
+
+    if jit {
+        let mut asm = Vec::new();
+        minicc_gen::gen(&mut asm, &module, target);
+        match minicc_jit::run(&String::from_utf8(asm).unwrap()) {
+            Ok(status) => exit(status),
+            Err(e) => {
+                eprintln!("ccom: {}", e);
+                exit(1);
+            }
+        }
+    }
+
+    match emit {
+        Emit::Asm => minicc_gen::gen(&mut f, &module, target),
+        Emit::Ir => write!(f, "{}", module).unwrap(),
+        Emit::Llvm => minicc_gen::llvm(&mut f, &module, target),
+        Emit::C => unreachable!(),
+        Emit::Obj => {
+            let mut asm = Vec::new();
+            minicc_gen::gen(&mut asm, &module, target);
+            let asm = String::from_utf8(asm).unwrap();
+            match minicc_as::assemble(&asm, arch.unwrap()) {
+                Ok(obj) => f.write_all(&obj).unwrap(),
+                Err(e) => {
+                    eprintln!("ccom: cannot assemble line {}", e);
+                    exit(1);
+                }
+            }
+        }
+    }
+}
+
+/// Runs `f` on a thread with a [`STACK`] sized stack.
+fn on_big_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|s| {
+        std::thread::Builder::new()
+            .stack_size(STACK)
+            .spawn_scoped(s, f)
+            .unwrap()
+            .join()
+            .unwrap()
+    })
+}
 
-    minicc_gen::gen(&mut std::io::stdout(), &node);
+fn target_named(name: &str) -> &'static dyn minicc_gen::Target {
+    minicc_gen::target(name).unwrap_or_else(|| {
+        eprintln!("ccom: unknown target `{}`\n\n{}", name, USAGE);
+        exit(1);
+    })
 }
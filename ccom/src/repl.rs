@@ -0,0 +1,424 @@
+//! `ccom repl`, which reads function definitions, statements and
+//! expressions one at a time and runs them with `minicc_interp`.
+//!
+//! Each input is checked together with declarations of what the session has
+//! defined so far: prototypes of its functions and, around statements, a
+//! function declaring its locals, so that the parser sees the same program
+//! as if it had been written in one file.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, IsTerminal, Stderr};
+use std::path::PathBuf;
+use std::process::exit;
+
+use minicc_ast::{Ast, AstKind, DataModel, SourceMap, Span, Symbol, Ty};
+use minicc_gen::Target;
+use minicc_interp::Session;
+use minicc_parser::scanner::TokenKind;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+const HELP: &str = "\
+Enter function definitions, statements or expressions, whose value and type
+are printed. A missing `;` after the last statement is added.
+
+  :ast      show the syntax tree of the last input
+  :asm      show the assembly generated for the last input
+  :tokens   show the tokens of the last input
+  :help     show this help
+  :quit     leave, as does end of input";
+
+/// Function whose body holds the statements of an input.
+const WRAPPER: &str = "__repl";
+
+pub fn main(target: &'static dyn Target) {
+    let mut repl = Repl {
+        target,
+        model: target.data_model(),
+        session: Session::new(target.data_model(), std::io::stderr()),
+        fns: Vec::new(),
+        locals: Vec::new(),
+        last: None,
+    };
+    let mut input = Input::new();
+    while let Some(text) = input.read() {
+        match text.trim() {
+            "" => {}
+            ":quit" | ":q" => break,
+            ":help" | ":h" => println!("{}", HELP),
+            cmd if cmd.starts_with(':') => repl.command(cmd),
+            _ => repl.eval(&text),
+        }
+    }
+    input.save();
+}
+
+struct Repl {
+    target: &'static dyn Target,
+    model: DataModel,
+    session: Session<Stderr>,
+    /// Functions defined or declared so far, with their types.
+    fns: Vec<(Symbol, Ty)>,
+    /// Locals declared at the top level so far.
+    locals: Vec<(Symbol, Ty)>,
+    /// The last input that was checked.
+    last: Option<Unit>,
+}
+
+/// An input checked with the declarations of the session before it.
+struct Unit {
+    input: String,
+    map: SourceMap,
+    nodes: Vec<Ast>,
+    /// Whether the input is statements in the body of [`WRAPPER`], after a
+    /// declaration of each local there was.
+    stmts: bool,
+    locals: usize,
+}
+
+impl Unit {
+    /// Nodes parsed from the input itself.
+    fn input_nodes(&self) -> Vec<&Ast> {
+        if !self.stmts {
+            return self.nodes.iter().collect();
+        }
+        let body = self.nodes.iter().find_map(|n| match &n.kind {
+            AstKind::FnDecl(f) if f.ident.as_str() == WRAPPER => {
+                f.body.as_ref()
+            }
+            _ => None,
+        });
+        match body.map(|b| &b.kind) {
+            Some(AstKind::CompoundStmt(b)) => {
+                b.items[self.locals..].iter().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn error(&self, span: Option<Span>, msg: &str) {
+        match span {
+            Some(span) => eprintln!(
+                "{}: {}\n{}",
+                self.map.span_loc(span),
+                msg,
+                self.map.underline(span)
+            ),
+            None => eprintln!("{}", msg),
+        }
+    }
+}
+
+impl Repl {
+    fn eval(&mut self, input: &str) {
+        // Functions are tried first, as a definition never parses as a
+        // statement while a statement may fail as a definition.
+        let unit = match self.check(input, false) {
+            Ok(unit) => unit,
+            Err(failed) => {
+                let (fn_err, fn_unit) = *failed;
+                let stmts = match input.trim_end().chars().last() {
+                    Some(';' | '}') => input.to_string(),
+                    _ => format!("{};", input.trim_end()),
+                };
+                match self.check(&stmts, true) {
+                    Ok(unit) => unit,
+                    // Report the error that got further into the input.
+                    Err(failed) => {
+                        let (err, unit) = *failed;
+                        let (err, unit) =
+                            if reach(&fn_err, &fn_unit) > reach(&err, &unit) {
+                                (fn_err, fn_unit)
+                            } else {
+                                (err, unit)
+                            };
+                        unit.error(Some(err.span), &err.msg);
+                        return;
+                    }
+                }
+            }
+        };
+
+        if unit.stmts {
+            self.exec(&unit);
+        } else {
+            self.session.define(&unit.nodes);
+            for node in &unit.nodes {
+                if let AstKind::FnDecl(f) = &node.kind {
+                    let ty = Ty::Fn(minicc_ast::FnTy {
+                        ret: Box::new(f.ret.clone()),
+                        params: f.prototyped.then(|| {
+                            f.params.iter().map(|p| p.ty.clone()).collect()
+                        }),
+                        variadic: f.variadic,
+                    });
+                    self.fns.retain(|(ident, _)| *ident != f.ident);
+                    self.fns.push((f.ident, ty));
+                }
+            }
+        }
+        self.last = Some(unit);
+    }
+
+    /// Runs the statements of `unit`, printing the value of expressions.
+    fn exec(&mut self, unit: &Unit) {
+        for node in unit.input_nodes() {
+            match self.session.exec(node) {
+                Ok(Some(val)) if node.ty != Ty::Void => {
+                    println!(
+                        "({}) {}",
+                        node.ty.declare(""),
+                        show(val, &node.ty)
+                    )
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    unit.error(e.span, &e.msg);
+                    return;
+                }
+            }
+            if let AstKind::VarDecl(v) = &node.kind {
+                self.locals.push((v.ident, v.ty.clone()));
+            }
+        }
+    }
+
+    /// Parses `input` after the declarations of the session, as function
+    /// declarations or as the statements of [`WRAPPER`].
+    fn check(
+        &self,
+        input: &str,
+        stmts: bool,
+    ) -> Result<Unit, Box<(minicc_parser::Error, Unit)>> {
+        let mut head = String::new();
+        for (ident, ty) in &self.fns {
+            writeln!(head, "{};", ty.declare(ident.as_str())).unwrap();
+        }
+        let mut tail = "\n";
+        if stmts {
+            writeln!(head, "void {}(void)\n{{", WRAPPER).unwrap();
+            for (ident, ty) in &self.locals {
+                writeln!(head, "    {};", ty.declare(ident.as_str())).unwrap();
+            }
+            tail = "\n}\n";
+        }
+
+        let mut map = SourceMap::new();
+        let session = map.add_file("<session>", &(head.clone() + tail));
+        let file = map.add_file("<input>", input);
+        map.push(0, session, 0);
+        map.push(head.len(), file, 0);
+        map.push(head.len() + input.len(), session, head.len());
+
+        let src = head + input + tail;
+        let mut unit = Unit {
+            input: input.to_string(),
+            map,
+            nodes: Vec::new(),
+            stmts,
+            locals: self.locals.len(),
+        };
+        match minicc_parser::try_parse(&src, &unit.map, self.model) {
+            Ok(nodes) => {
+                unit.nodes = nodes;
+                Ok(unit)
+            }
+            Err(e) => Err(Box::new((e, unit))),
+        }
+    }
+
+    fn command(&mut self, cmd: &str) {
+        if ![":ast", ":asm", ":tokens"].contains(&cmd) {
+            eprintln!("unknown command `{}`, see `:help`", cmd);
+            return;
+        }
+        let Some(unit) = &self.last else {
+            eprintln!("no input yet");
+            return;
+        };
+        match cmd {
+            ":ast" => {
+                let mut s = String::new();
+                for node in unit.input_nodes() {
+                    tree(&mut s, node, 0);
+                }
+                print!("{}", s);
+            }
+            ":asm" => {
+                let module = minicc_ir::lower(&unit.nodes, self.model);
+                minicc_gen::gen(&mut std::io::stdout(), &module, self.target);
+            }
+            ":tokens" => {
+                let map = SourceMap::from_src("<input>", &unit.input);
+                let tokens = match minicc_parser::tokens(
+                    &unit.input,
+                    &map,
+                    self.model,
+                ) {
+                    Ok(tokens) => tokens,
+                    Err(e) => return unit.error(Some(e.span), &e.msg),
+                };
+                for tok in tokens {
+                    let loc = map.span_loc(tok.span);
+                    let text = &unit.input[tok.span.start..tok.span.end];
+                    match &tok.kind {
+                        TokenKind::IntLit(_, ty) => {
+                            println!(
+                                "{}:{}\t{} ({})",
+                                loc.line, loc.col, text, ty
+                            )
+                        }
+                        TokenKind::Ident(_) => {
+                            println!(
+                                "{}:{}\t{} (identifier)",
+                                loc.line, loc.col, text
+                            )
+                        }
+                        _ => println!("{}:{}\t{}", loc.line, loc.col, text),
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// How far into the input an error is, the end for errors after it.
+fn reach(err: &minicc_parser::Error, unit: &Unit) -> usize {
+    match unit.map.file(err.span.file).name == "<input>" {
+        true => err.span.start,
+        false => unit.input.len(),
+    }
+}
+
+/// `val` of type `ty`, pointers in hexadecimal.
+fn show(val: i64, ty: &Ty) -> String {
+    match ty {
+        Ty::Ptr(_) | Ty::VaList => format!("{:#x}", val),
+        _ => val.to_string(),
+    }
+}
+
+/// Appends `node` and its children to `s`, a line each, indented by depth.
+fn tree(s: &mut String, node: &Ast, depth: usize) {
+    let label = match &node.kind {
+        AstKind::FnDecl(f) => format!("FnDecl {}", f.ident),
+        AstKind::CompoundStmt(_) => "CompoundStmt".to_string(),
+        AstKind::If(_) => "If".to_string(),
+        AstKind::For(_) => "For".to_string(),
+        AstKind::VarDecl(v) => format!("VarDecl {}: {}", v.ident, v.ty),
+        AstKind::Return(_) => "Return".to_string(),
+        AstKind::Call(_) => "Call".to_string(),
+        AstKind::Ref(r) => format!("Ref {}", r.ident),
+        AstKind::IntLit(n) => format!("IntLit {}", n.val),
+        AstKind::UnOp(n) => format!("UnOp {:?}", n.op),
+        AstKind::BinOp(n) => format!("BinOp {:?}", n.op),
+        AstKind::Cond(_) => "Cond".to_string(),
+        AstKind::Comma(_) => "Comma".to_string(),
+        AstKind::Cast(_) => "Cast".to_string(),
+        AstKind::VaStart(n) => format!("VaStart {}", n.last),
+        AstKind::VaArg(_) => "VaArg".to_string(),
+        AstKind::VaEnd(_) => "VaEnd".to_string(),
+    };
+    match &node.ty {
+        Ty::Void => writeln!(s, "{}{}", "  ".repeat(depth), label),
+        ty => writeln!(s, "{}{}: {}", "  ".repeat(depth), label, ty),
+    }
+    .unwrap();
+    for child in node.children() {
+        tree(s, child, depth + 1);
+    }
+}
+
+/// Where inputs are read from: a line editor with history on a terminal,
+/// plain lines otherwise, as from a script.
+enum Input {
+    Editor(Box<DefaultEditor>, Option<PathBuf>),
+    Plain(std::io::StdinLock<'static>),
+}
+
+impl Input {
+    fn new() -> Self {
+        if !std::io::stdin().is_terminal() {
+            return Input::Plain(std::io::stdin().lock());
+        }
+        let mut editor = DefaultEditor::new().unwrap_or_else(|e| {
+            eprintln!("ccom: cannot start the line editor: {}", e);
+            exit(1);
+        });
+        let history = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".ccom_history"));
+        if let Some(path) = &history {
+            // There is none the first time.
+            let _ = editor.load_history(path);
+        }
+        Input::Editor(Box::new(editor), history)
+    }
+
+    /// The next input, continued over lines while brackets are open, or
+    /// `None` at the end.
+    fn read(&mut self) -> Option<String> {
+        let mut text = String::new();
+        loop {
+            let prompt = if text.is_empty() { "> " } else { "... " };
+            let line = match self {
+                Input::Editor(editor, _) => match editor.readline(prompt) {
+                    Ok(line) => line,
+                    // Ctrl-C drops what was typed.
+                    Err(ReadlineError::Interrupted) => {
+                        text.clear();
+                        continue;
+                    }
+                    Err(ReadlineError::Eof) if text.is_empty() => return None,
+                    Err(ReadlineError::Eof) => return Some(text),
+                    Err(e) => {
+                        eprintln!("ccom: {}", e);
+                        return None;
+                    }
+                },
+                Input::Plain(stdin) => {
+                    let mut line = String::new();
+                    match stdin.read_line(&mut line) {
+                        Ok(0) | Err(_) if text.is_empty() => return None,
+                        Ok(0) | Err(_) => return Some(text),
+                        Ok(_) => line.trim_end_matches('\n').to_string(),
+                    }
+                }
+            };
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text += &line;
+            if open(&text) <= 0 {
+                break;
+            }
+        }
+        if let Input::Editor(editor, _) = self {
+            let _ = editor.add_history_entry(text.as_str());
+        }
+        Some(text)
+    }
+
+    fn save(&mut self) {
+        if let Input::Editor(editor, Some(path)) = self {
+            if let Err(e) = editor.save_history(path) {
+                eprintln!(
+                    "ccom: cannot save history to `{}`: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Brackets left open in `text`.
+fn open(text: &str) -> i32 {
+    text.chars()
+        .map(|c| match c {
+            '(' | '{' => 1,
+            ')' | '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
@@ -0,0 +1,613 @@
+//! Driver of the end-to-end tests in `test/`: each `NAME.c` is compiled
+//! and run by a [`Runner`] at each optimisation level asked for, and what it
+//! prints to standard output and error together is compared with
+//! `NAME.expect`, a unified diff shown when they differ.
+//!
+//! Comments in a test change what is expected of it:
+//!
+//! - `// EXPECT-ERROR: MSG` makes it a negative test, which `ccom` must
+//!   reject with an error containing `MSG`. It has no `.expect` file.
+//! - `// EXPECT-WARNING: MSG` checks that `ccom` prints `MSG` while
+//!   compiling it.
+//! - `// EXPECT-EXIT: N` checks that it exits with status `N`, which is
+//!   not checked otherwise.
+//!
+//! Tests run in parallel, each in a directory of its own, which is removed
+//! when all pass. Runners that need tools which are missing, or a host
+//! which is not x86 Linux, fail the test unless `MINICC_SKIP_MISSING_TOOLS=1`
+//! is set, which skips it.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use super::{installed, program_output, root, skip};
+
+/// Lines of context around the changes of a diff.
+const CONTEXT: usize = 3;
+
+struct Case {
+    name: String,
+    path: PathBuf,
+    /// Contents of the `.expect` file.
+    expect: Option<String>,
+    /// Message of `EXPECT-ERROR`.
+    error: Option<String>,
+    /// Message of `EXPECT-WARNING`.
+    warning: Option<String>,
+    /// Status of `EXPECT-EXIT`.
+    exit: Option<i32>,
+}
+
+/// How programs are built and run.
+pub enum Runner {
+    /// Running with `ccom --jit`.
+    Jit,
+    /// Linking with the runtime object for `target` with the `ld`
+    /// emulation.
+    Ld { target: String, emulation: &'static str, rt: PathBuf },
+    /// Linking an object of `ccom -c` with `lib/dbg.c` by the system `cc`
+    /// given the flags.
+    Cc { target: String, flags: Vec<String> },
+    /// Linking with the cross compiler and running with the emulator
+    /// command.
+    Cross { target: String, cc: String, run: Vec<String> },
+    /// Promoting the slots of the LLVM IR for x86-64 with `opt`, linking
+    /// `lib/dbg.ll` with `llvm-link` and running with `lli`, given the flags
+    /// of all three.
+    Llvm { flags: Vec<String> },
+    /// Printing back as C with `--emit=c` for `target`, the host, and
+    /// compiling that with `ccom`, or the system `cc` if `cc` is set.
+    EmitC { target: String, cc: bool },
+}
+
+fn ccom() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_ccom"))
+}
+
+/// Output of `cmd`, or why it failed with what it printed.
+fn output(cmd: &mut Command) -> Result<Output, String> {
+    let out = cmd.output().map_err(|e| format!("{:?}: {}", cmd, e))?;
+    match out.status.success() {
+        true => Ok(out),
+        false => Err(format!(
+            "{:?}: {}\n{}",
+            cmd,
+            out.status,
+            String::from_utf8_lossy(&[&out.stdout[..], &out.stderr].concat())
+        )),
+    }
+}
+
+/// Whether the host runs x86 Linux executables.
+fn x86_host() -> bool {
+    std::env::consts::ARCH == "x86_64" && std::env::consts::OS == "linux"
+}
+
+/// Value of the annotation `key` in `src`.
+fn annotation<'a>(src: &'a str, key: &str) -> Option<&'a str> {
+    let key = format!("// {}:", key);
+    src.lines().find_map(|l| Some(l.split_once(key.as_str())?.1.trim()))
+}
+
+fn cases() -> Vec<Result<Case, String>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(root().join("test"))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let src = fs::read_to_string(&path).unwrap();
+            let expect = fs::read_to_string(path.with_extension("expect")).ok();
+            let error = annotation(&src, "EXPECT-ERROR").map(str::to_string);
+            let warning =
+                annotation(&src, "EXPECT-WARNING").map(str::to_string);
+            let exit = match annotation(&src, "EXPECT-EXIT") {
+                Some(n) => Some(n.parse().map_err(|_| {
+                    format!("{}: invalid `EXPECT-EXIT: {}`", name, n)
+                })?),
+                None => None,
+            };
+            match (&expect, &error) {
+                (None, None) => Err(format!(
+                    "{}: no `.expect` file or `EXPECT-ERROR`",
+                    name
+                )),
+                (Some(_), Some(_)) => Err(format!(
+                    "{}: `EXPECT-ERROR` but also an `.expect` file",
+                    name
+                )),
+                _ => Ok(Case { name, path, expect, error, warning, exit }),
+            }
+        })
+        .collect()
+}
+
+impl Runner {
+    /// Running with `ccom --jit`, if the host can.
+    pub fn jit() -> Result<Runner, String> {
+        match x86_host() {
+            true => Ok(Runner::Jit),
+            false => Err("the host cannot run `--jit` code".to_string()),
+        }
+    }
+
+    /// Linking with the runtime in `lib/rt` for the x86 `target`, assembled
+    /// into `tmp`.
+    pub fn ld(target: &str, tmp: &Path) -> Result<Runner, String> {
+        let emulation = match target {
+            "i386-linux" => "elf_i386",
+            "x86_64-linux" => "elf_x86_64",
+            _ => return Err(format!("{} has no runtime", target)),
+        };
+        if !x86_host() || !installed("ld") {
+            return Err("ld is not installed or the host is not x86".into());
+        }
+        // Failing to assemble the runtime is a failure, not a reason to
+        // skip.
+        let rt = tmp.join("rt.o");
+        if let Err(e) = output(
+            ccom()
+                .arg(format!("--target={}", target))
+                .args(["-c", "-o"])
+                .arg(&rt)
+                .arg(root().join(format!("lib/rt/{}.s", target))),
+        ) {
+            panic!("cannot assemble the runtime: {}", e);
+        }
+        Ok(Runner::Ld { target: target.to_string(), emulation, rt })
+    }
+
+    /// Linking by the system `cc` with `flags`, if it can link and run an
+    /// empty program in `tmp`, which needs a C library for `target`.
+    pub fn cc(
+        target: &str,
+        flags: &[&str],
+        tmp: &Path,
+    ) -> Result<Runner, String> {
+        let exe = tmp.join("empty");
+        let linked = installed("cc")
+            && Command::new("cc")
+                .args(flags)
+                .arg("-o")
+                .arg(&exe)
+                .arg(root().join("test/empty.c"))
+                .arg(root().join("lib/dbg.c"))
+                .output()
+                .is_ok_and(|out| out.status.success())
+            && Command::new(&exe).status().is_ok_and(|s| s.success());
+        match linked {
+            true => Ok(Runner::Cc {
+                target: target.to_string(),
+                flags: flags.iter().map(|f| f.to_string()).collect(),
+            }),
+            false => {
+                Err(format!("cc cannot link and run programs for {}", target))
+            }
+        }
+    }
+
+    /// Linking with the cross compiler `cc` and running under `run`, if
+    /// both are installed.
+    pub fn cross(target: &str, cc: &str, run: &str) -> Result<Runner, String> {
+        let run: Vec<String> =
+            run.split_whitespace().map(str::to_string).collect();
+        match run.first() {
+            Some(emulator) if installed(cc) && installed(emulator) => {
+                Ok(Runner::Cross {
+                    target: target.to_string(),
+                    cc: cc.to_string(),
+                    run,
+                })
+            }
+            _ => Err(format!("{} or {} is not installed", cc, run.join(" "))),
+        }
+    }
+
+    /// Running LLVM IR with `lli`, if the LLVM tools are installed.
+    pub fn llvm() -> Result<Runner, String> {
+        if !["opt", "llvm-link", "lli"].iter().all(|cmd| installed(cmd)) {
+            return Err("opt, llvm-link or lli is not installed".to_string());
+        }
+        // Before LLVM 15 `ptr` needs a flag, which later versions dropped.
+        let opaque = Command::new("opt")
+            .args(["-opaque-pointers", "-version"])
+            .output()
+            .is_ok_and(|out| out.status.success());
+        let flags = match opaque {
+            true => vec!["-opaque-pointers".to_string()],
+            false => Vec::new(),
+        };
+        Ok(Runner::Llvm { flags })
+    }
+
+    /// Printing back as C and compiling that for the host, with `ccom`, or
+    /// the system `cc` if `cc` is set.
+    pub fn emit_c(cc: bool) -> Result<Runner, String> {
+        let target = format!("{}-linux", std::env::consts::ARCH);
+        if !installed("cc") || minicc_gen::target(&target).is_none() {
+            return Err(format!(
+                "cc is not installed or {} is not a target",
+                target
+            ));
+        }
+        Ok(Runner::EmitC { target, cc })
+    }
+
+    /// As the environment asks: `JIT=1` runs with `ccom --jit`, otherwise
+    /// programs are compiled for `TARGET`, by default `i386-linux`. x86
+    /// programs are linked with the runtime in `lib/rt` by `ld` alone,
+    /// other targets with `lib/dbg.c` by the cross compiler `CC`,
+    /// `ARCH-linux-gnu-gcc` by default, and run under `RUN`, by default
+    /// `qemu-ARCH -L /usr/ARCH-linux-gnu`.
+    pub fn from_env(tmp: &Path) -> Result<Runner, String> {
+        if std::env::var_os("JIT").is_some_and(|v| !v.is_empty()) {
+            return Runner::jit();
+        }
+
+        let target = std::env::var("TARGET")
+            .unwrap_or_else(|_| "i386-linux".to_string());
+        let arch = target.split('-').next().unwrap().to_string();
+        if let "i386" | "x86_64" = arch.as_str() {
+            return Runner::ld(&target, tmp);
+        }
+
+        let cc = std::env::var("CC")
+            .unwrap_or_else(|_| format!("{}-linux-gnu-gcc", arch));
+        let run = std::env::var("RUN").unwrap_or_else(|_| {
+            format!("qemu-{} -L /usr/{}-linux-gnu", arch, arch)
+        });
+        Runner::cross(&target, &cc, &run)
+    }
+
+    /// Commands compiling `case` at `level` in `dir` and the command
+    /// running it. `ccom` comes first, the only one expected to fail.
+    fn commands(
+        &self,
+        case: &Case,
+        level: &str,
+        dir: &Path,
+    ) -> (Vec<Command>, Command) {
+        let exe = dir.join("test");
+        match self {
+            // Compiling first tells errors apart from what programs print.
+            Runner::Jit => {
+                let mut check = ccom();
+                check
+                    .args(["--target=x86_64-linux", level, "-o"])
+                    .arg(dir.join("test.s"))
+                    .arg(&case.path);
+                let mut run = ccom();
+                run.args(["--jit", level]).arg(&case.path);
+                (vec![check], run)
+            }
+            Runner::Ld { target, emulation, rt } => {
+                let obj = dir.join("test.o");
+                let mut compile = ccom();
+                compile
+                    .arg(format!("--target={}", target))
+                    .args([level, "-c", "-o"])
+                    .arg(&obj)
+                    .arg(&case.path);
+                let mut link = Command::new("ld");
+                link.args(["-static", "-m", emulation, "-o"])
+                    .arg(&exe)
+                    .arg(&obj)
+                    .arg(rt);
+                (vec![compile, link], Command::new(&exe))
+            }
+            Runner::Cc { target, flags } => {
+                let obj = dir.join("test.o");
+                let mut compile = ccom();
+                compile
+                    .arg(format!("--target={}", target))
+                    .args([level, "-c", "-o"])
+                    .arg(&obj)
+                    .arg(&case.path);
+                let mut link = Command::new("cc");
+                link.args(flags)
+                    .arg("-o")
+                    .arg(&exe)
+                    .arg(&obj)
+                    .arg(root().join("lib/dbg.c"));
+                (vec![compile, link], Command::new(&exe))
+            }
+            Runner::Cross { target, cc, run } => {
+                let asm = dir.join("test.s");
+                let mut compile = ccom();
+                compile
+                    .arg(format!("--target={}", target))
+                    .args([level, "-o"])
+                    .arg(&asm)
+                    .arg(&case.path);
+                let mut link = Command::new(cc);
+                link.arg("-o")
+                    .arg(&exe)
+                    .arg(&asm)
+                    .arg(root().join("lib/dbg.c"));
+                let mut cmd = Command::new(&run[0]);
+                cmd.args(&run[1..]).arg(&exe);
+                (vec![compile, link], cmd)
+            }
+            Runner::Llvm { flags } => {
+                let (ll, opt, linked) = (
+                    dir.join("test.ll"),
+                    dir.join("opt.ll"),
+                    dir.join("linked.ll"),
+                );
+                let mut compile = ccom();
+                compile
+                    .args(["--target=x86_64-linux", "--emit=llvm", level])
+                    .arg("-o")
+                    .arg(&ll)
+                    .arg(&case.path);
+                let mut mem2reg = Command::new("opt");
+                mem2reg
+                    .args(flags)
+                    .args(["-passes=mem2reg", "-S", "-o"])
+                    .arg(&opt)
+                    .arg(&ll);
+                let mut link = Command::new("llvm-link");
+                link.args(flags)
+                    .args(["-S", "-o"])
+                    .arg(&linked)
+                    .arg(&opt)
+                    .arg(root().join("lib/dbg.ll"));
+                let mut run = Command::new("lli");
+                run.args(flags).arg(&linked);
+                (vec![compile, mem2reg, link], run)
+            }
+            Runner::EmitC { target, cc } => {
+                let c = dir.join("test.c");
+                let mut emit = ccom();
+                emit.arg(format!("--target={}", target))
+                    .args([level, "--emit=c", "-o"])
+                    .arg(&c)
+                    .arg(&case.path);
+                let mut link = Command::new("cc");
+                link.arg("-o").arg(&exe);
+                let mut compile = vec![emit];
+                if *cc {
+                    link.arg(&c);
+                } else {
+                    let asm = dir.join("test.s");
+                    let mut cmd = ccom();
+                    cmd.arg(format!("--target={}", target))
+                        .args([level, "-o"])
+                        .arg(&asm)
+                        .arg(&c);
+                    compile.push(cmd);
+                    link.arg(&asm);
+                }
+                link.arg(root().join("lib/dbg.c"));
+                compile.push(link);
+                (compile, Command::new(&exe))
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        case: &Case,
+        level: &str,
+        dir: &Path,
+    ) -> Result<(), String> {
+        fs::create_dir_all(dir).unwrap();
+        let (compile, mut run) = self.commands(case, level, dir);
+        for (i, mut cmd) in compile.into_iter().enumerate() {
+            let out = cmd.output().map_err(|e| format!("{:?}: {}", cmd, e))?;
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            match &case.error {
+                // Only `ccom` is expected to fail.
+                Some(msg) if i == 0 && out.status.success() => {
+                    return Err(format!("compiled, expected error `{}`", msg));
+                }
+                Some(msg) if i == 0 && !stderr.contains(msg.as_str()) => {
+                    return Err(format!(
+                        "expected error `{}`, got:\n{}",
+                        msg, stderr
+                    ));
+                }
+                Some(_) => return Ok(()),
+                None if !out.status.success() => {
+                    return Err(format!(
+                        "{:?}: {}\n{}",
+                        cmd, out.status, stderr
+                    ));
+                }
+                None => match &case.warning {
+                    Some(msg) if i == 0 && !stderr.contains(msg.as_str()) => {
+                        return Err(format!(
+                            "expected warning `{}`, got:\n{}",
+                            msg, stderr
+                        ));
+                    }
+                    _ => {}
+                },
+            }
+        }
+
+        // Both streams go to one file, interleaved as they are written.
+        let log = dir.join("output");
+        let file = File::create(&log).unwrap();
+        let status = run
+            .stdout(file.try_clone().unwrap())
+            .stderr(file)
+            .status()
+            .map_err(|e| format!("{:?}: {}", run, e))?;
+        let mut out =
+            String::from_utf8_lossy(&fs::read(&log).unwrap()).into_owned();
+        if let Runner::Jit = self {
+            out = program_output(&out).to_string();
+        }
+
+        let expect = case.expect.as_deref().unwrap();
+        let mut errs = Vec::new();
+        if out != expect {
+            let name = format!("test/{}.expect", case.name);
+            errs.push(diff(&name, expect, &out));
+        }
+        match (status.code(), case.exit) {
+            (None, _) => errs.push(format!("terminated by {}", status)),
+            (Some(code), Some(n)) if code != n => {
+                errs.push(format!("exited with {}, expected {}", code, n))
+            }
+            _ => {}
+        }
+        match errs.is_empty() {
+            true => Ok(()),
+            false => Err(errs.join("\n")),
+        }
+    }
+}
+
+/// Unified diff turning `old`, from the file `name`, into `new`.
+fn diff(name: &str, old: &str, new: &str) -> String {
+    let (a, b): (Vec<&str>, Vec<&str>) =
+        (old.lines().collect(), new.lines().collect());
+
+    // Lengths of the longest common subsequences of the suffixes.
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = match a[i] == b[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            ops.push((' ', a[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < a.len()
+            && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1])
+        {
+            ops.push(('-', a[i]));
+            i += 1;
+        } else {
+            ops.push(('+', b[j]));
+            j += 1;
+        }
+    }
+
+    let mut s = format!("--- {}\n+++ output\n", name);
+    let changes: Vec<usize> =
+        (0..ops.len()).filter(|&k| ops[k].0 != ' ').collect();
+    let mut k = 0;
+    while k < changes.len() {
+        // Changes closer than twice the context share a hunk.
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut last = changes[k];
+        while k + 1 < changes.len() && changes[k + 1] - last <= 2 * CONTEXT {
+            k += 1;
+            last = changes[k];
+        }
+        let end = (last + CONTEXT + 1).min(ops.len());
+        k += 1;
+
+        let count = |ops: &[(char, &str)], skip: char| {
+            ops.iter().filter(|(op, _)| *op != skip).count()
+        };
+        let (old_at, new_at) =
+            (count(&ops[..start], '+'), count(&ops[..start], '-'));
+        let (old_len, new_len) =
+            (count(&ops[start..end], '+'), count(&ops[start..end], '-'));
+        s += &format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_at + (old_len > 0) as usize,
+            old_len,
+            new_at + (new_len > 0) as usize,
+            new_len
+        );
+        for (op, line) in &ops[start..end] {
+            s += &format!("{}{}\n", op, line);
+        }
+    }
+    s
+}
+
+/// Runs the end-to-end tests at each of `levels` with the runner `new`
+/// makes, given a temporary directory named after `name`, or skips them if
+/// it cannot.
+pub fn programs(
+    name: &str,
+    levels: &[&str],
+    new: impl FnOnce(&Path) -> Result<Runner, String>,
+) {
+    let tmp = std::env::temp_dir().join(format!(
+        "minicc_{}_{}",
+        name,
+        std::process::id()
+    ));
+    fs::create_dir_all(&tmp).unwrap();
+    let runner = match new(&tmp) {
+        Ok(runner) => runner,
+        Err(why) => {
+            skip(&why);
+            fs::remove_dir_all(&tmp).unwrap();
+            return;
+        }
+    };
+
+    let all = self::cases();
+    let total = all.len() * levels.len();
+    let mut failed = Vec::new();
+    let mut cases = Vec::new();
+    for case in all {
+        match case {
+            Ok(case) => cases.push(case),
+            Err(e) => failed.push(e),
+        }
+    }
+    // Each case at each level is a job of its own.
+    let jobs: Vec<(&Case, &str)> = cases
+        .iter()
+        .flat_map(|case| levels.iter().map(move |&level| (case, level)))
+        .collect();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+    let workers = thread::available_parallelism().map_or(4, |n| n.get());
+    thread::scope(|s| {
+        for _ in 0..workers {
+            s.spawn(|| {
+                while let Some(&(case, level)) =
+                    jobs.get(next.fetch_add(1, Ordering::Relaxed))
+                {
+                    let dir = tmp.join(format!("{}{}", case.name, level));
+                    let res = runner.check(case, level, &dir);
+                    let name = format!("{} at {}", case.name, level);
+                    results.lock().unwrap().push((name, res));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|(a, _), (b, _)| a.cmp(b));
+    for (name, res) in results {
+        if let Err(e) = res {
+            eprintln!("{} => FAILED\n{}", name, e);
+            failed.push(name);
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "{} of {} end-to-end tests failed, files in {}: {:?}",
+        failed.len(),
+        total,
+        tmp.display(),
+        failed
+    );
+    fs::remove_dir_all(&tmp).unwrap();
+}
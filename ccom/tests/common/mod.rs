@@ -0,0 +1,73 @@
+//! Helpers for the tests that build and run programs with system tools.
+
+// Each test uses only some of these.
+#![allow(dead_code)]
+
+pub mod end_to_end;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The root of the workspace.
+pub fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+/// Whether `cmd` is in a directory of `PATH`.
+pub fn installed(cmd: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| dir.join(cmd).is_file())
+    })
+}
+
+/// What `cmd` prints to standard output, asserting it succeeds. Standard
+/// error, e.g. warnings, is left out.
+pub fn run(cmd: &mut Command) -> Vec<u8> {
+    let out = cmd.output().unwrap();
+    assert!(
+        out.status.success(),
+        "{:?}: {}\n{}",
+        cmd,
+        out.status,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    out.stdout
+}
+
+/// What `ccom --jit` or `--run` printed without the warnings of compiling,
+/// which come before anything the program prints.
+pub fn program_output(out: &str) -> &str {
+    let mut rest = out;
+    while let Some((line, next)) = rest.split_once('\n') {
+        if !line.contains(": warning: ") {
+            break;
+        }
+        rest = next;
+    }
+    rest
+}
+
+/// The end-to-end tests in `test/` that have an `.expect` file, sorted.
+pub fn tests() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(root().join("test"))
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter(|p| p.with_extension("expect").exists())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Reports that a test cannot run because of `why`, e.g. a tool that is not
+/// installed. Unless `MINICC_SKIP_MISSING_TOOLS=1` is set this fails the
+/// test, so that a passing test is one that ran; otherwise the caller is to
+/// return.
+#[track_caller]
+pub fn skip(why: &str) {
+    match std::env::var("MINICC_SKIP_MISSING_TOOLS") {
+        Ok(v) if v == "1" => eprintln!("skipping: {}", why),
+        _ => panic!("{}; set MINICC_SKIP_MISSING_TOOLS=1 to skip", why),
+    }
+}
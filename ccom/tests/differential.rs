@@ -0,0 +1,611 @@
+//! Differential testing against the system `cc`: random programs within
+//! the subset minicc accepts are compiled by both, for `x86_64-linux` and
+//! the host, and what they print through `dbg` must agree. A program they
+//! disagree on is minimised before it is reported.
+//!
+//! Programs are made of functions over the integer types with `if`, `for`,
+//! calls and expressions of the binary and unary operators, conditionals
+//! and casts. Each is run by `minicc_interp` first and dropped if it has
+//! undefined behaviour, such as signed overflow or division by zero, as the
+//! compilers may then rightly differ. For the order of evaluation not to
+//! matter, an expression makes at most one call.
+//!
+//! `DIFF_CASES` sets the number of programs, 40 by default, and
+//! `DIFF_SEED` the seed of the first, the next ones counting up from it.
+//! The host must be x86-64 Linux with `cc`.
+
+mod common;
+
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use minicc_ast::{DataModel, SourceMap};
+
+use common::{installed, skip};
+
+/// Integer types, all of which `dbg` takes after conversion to `int`.
+const TYPES: [&str; 10] = [
+    "char",
+    "unsigned char",
+    "short",
+    "unsigned short",
+    "int",
+    "unsigned",
+    "long",
+    "unsigned long",
+    "long long",
+    "unsigned long long",
+];
+
+const BIN_OPS: [&str; 11] =
+    ["+", "-", "*", "/", "%", "<", ">", "<=", ">=", "==", "!="];
+
+const LITERALS: [u64; 12] =
+    [0, 1, 2, 3, 7, 10, 100, 127, 255, 65535, 2147483647, 4294967295];
+
+const SUFFIXES: [&str; 5] = ["", "u", "l", "ul", "ll"];
+
+/// xorshift64*, so that a seed always makes the same program.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// True `percent` times in a hundred.
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())].clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Lit(u64, &'static str),
+    Var(String),
+    Bin(&'static str, Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    /// `*&x`, through a pointer.
+    Deref(String),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    Cast(&'static str, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    Assign(String, Expr),
+    Dbg(Expr),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    /// `for (i = 0; i < n; i = i + 1)`, `i` being assigned nowhere else.
+    For(String, u64, Vec<Stmt>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Func {
+    name: String,
+    ret: &'static str,
+    params: Vec<(&'static str, String)>,
+    locals: Vec<(&'static str, String)>,
+    body: Vec<Stmt>,
+    result: Expr,
+}
+
+/// A program, `main` being its last function.
+#[derive(Debug, Clone, PartialEq)]
+struct Prog {
+    funcs: Vec<Func>,
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Lit(n, suffix) => write!(f, "{}{}", n, suffix),
+            Expr::Var(v) => write!(f, "{}", v),
+            Expr::Bin(op, l, r) => write!(f, "({} {} {})", l, op, r),
+            Expr::Neg(e) => write!(f, "-({})", e),
+            Expr::Not(e) => write!(f, "!({})", e),
+            Expr::Deref(v) => write!(f, "*&{}", v),
+            Expr::Cond(c, t, e) => write!(f, "({} ? {} : {})", c, t, e),
+            Expr::Cast(ty, e) => write!(f, "({})({})", ty, e),
+            Expr::Call(name, args) => {
+                let args: Vec<String> =
+                    args.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", name, args.join(", "))
+            }
+        }
+    }
+}
+
+fn write_stmts(f: &mut String, stmts: &[Stmt], depth: usize) {
+    let pad = "    ".repeat(depth);
+    for s in stmts {
+        match s {
+            Stmt::Assign(v, e) => *f += &format!("{}{} = {};\n", pad, v, e),
+            Stmt::Dbg(e) => *f += &format!("{}dbg({});\n", pad, e),
+            Stmt::If(c, t, e) => {
+                *f += &format!("{}if ({}) {{\n", pad, c);
+                write_stmts(f, t, depth + 1);
+                if e.is_empty() {
+                    *f += &format!("{}}}\n", pad);
+                } else {
+                    *f += &format!("{}}} else {{\n", pad);
+                    write_stmts(f, e, depth + 1);
+                    *f += &format!("{}}}\n", pad);
+                }
+            }
+            Stmt::For(i, n, body) => {
+                *f += &format!(
+                    "{}for ({i} = 0; {i} < {}; {i} = {i} + 1) {{\n",
+                    pad,
+                    n,
+                    i = i
+                );
+                write_stmts(f, body, depth + 1);
+                *f += &format!("{}}}\n", pad);
+            }
+        }
+    }
+}
+
+impl Display for Prog {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut s = "void dbg(int v);\n".to_string();
+        for func in &self.funcs {
+            let params: Vec<String> = func
+                .params
+                .iter()
+                .map(|(ty, name)| format!("{} {}", ty, name))
+                .collect();
+            let params = match params.is_empty() {
+                true => "void".to_string(),
+                false => params.join(", "),
+            };
+            s += &format!("\n{} {}({})\n{{\n", func.ret, func.name, params);
+            for (ty, name) in &func.locals {
+                s += &format!("    {} {};\n", ty, name);
+            }
+            write_stmts(&mut s, &func.body, 1);
+            s += &format!("    return {};\n}}\n", func.result);
+        }
+        write!(f, "{}", s)
+    }
+}
+
+/// Generates programs, keeping track of what is in scope.
+struct Gen {
+    rng: Rng,
+    /// Functions defined so far, with their numbers of parameters.
+    funcs: Vec<(String, usize)>,
+    /// Variables that may be read, and of those the ones that may be
+    /// assigned.
+    vars: Vec<String>,
+    writable: Vec<String>,
+    /// Locals of the function being generated.
+    locals: Vec<(&'static str, String)>,
+}
+
+impl Gen {
+    fn prog(seed: u64) -> Prog {
+        let mut gen = Gen {
+            rng: Rng::new(seed),
+            funcs: Vec::new(),
+            vars: Vec::new(),
+            writable: Vec::new(),
+            locals: Vec::new(),
+        };
+        let mut funcs = Vec::new();
+        for n in 0..1 + gen.rng.below(4) {
+            funcs.push(gen.func(format!("f{}", n), false));
+        }
+        funcs.push(gen.func("main".to_string(), true));
+        Prog { funcs }
+    }
+
+    fn func(&mut self, name: String, main: bool) -> Func {
+        let params: Vec<(&'static str, String)> = match main {
+            true => Vec::new(),
+            false => (0..self.rng.below(4))
+                .map(|n| (self.rng.pick(&TYPES), format!("p{}", n)))
+                .collect(),
+        };
+        self.locals = (0..1 + self.rng.below(4))
+            .map(|n| (self.rng.pick(&TYPES), format!("x{}", n)))
+            .collect();
+        self.writable = params
+            .iter()
+            .chain(&self.locals)
+            .map(|(_, name)| name.clone())
+            .collect();
+        self.vars = params.iter().map(|(_, name)| name.clone()).collect();
+
+        // Locals are initialized in turn, from the ones before them.
+        let mut body: Vec<Stmt> = Vec::new();
+        for (_, name) in self.locals.clone() {
+            let e = self.expr(1, &mut 0);
+            body.push(Stmt::Assign(name.clone(), e));
+            self.vars.push(name);
+        }
+        body.extend(self.stmts(2));
+        if main {
+            for (f, arity) in self.funcs.clone() {
+                let args = (0..arity).map(|_| self.expr(2, &mut 0)).collect();
+                body.push(Stmt::Dbg(Expr::Call(f, args)));
+            }
+        }
+        let result = match main {
+            true => Expr::Lit(0, ""),
+            false => self.expr(3, &mut 1),
+        };
+
+        self.funcs.push((name.clone(), params.len()));
+        Func {
+            name,
+            ret: if main { "int" } else { self.rng.pick(&TYPES) },
+            params,
+            locals: std::mem::take(&mut self.locals),
+            body,
+            result,
+        }
+    }
+
+    fn stmts(&mut self, depth: usize) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        for _ in 0..1 + self.rng.below(4) {
+            let s = match self.rng.below(100) {
+                0..=39 => {
+                    let v = self.rng.pick(&self.writable);
+                    Stmt::Assign(v, self.expr(3, &mut 1))
+                }
+                40..=64 => Stmt::Dbg(self.expr(3, &mut 1)),
+                65..=82 if depth > 0 => {
+                    let c = self.expr(2, &mut 1);
+                    let t = self.stmts(depth - 1);
+                    let e = match self.rng.chance(50) {
+                        true => self.stmts(depth - 1),
+                        false => Vec::new(),
+                    };
+                    Stmt::If(c, t, e)
+                }
+                83..=99 if depth > 0 => {
+                    let i = format!("i{}", self.locals.len());
+                    self.locals.push(("int", i.clone()));
+                    // The counter is only read within the loop, as the
+                    // loop may not be reached.
+                    self.vars.push(i.clone());
+                    let n = self.rng.below(4) as u64;
+                    let body = self.stmts(depth - 1);
+                    self.vars.pop();
+                    Stmt::For(i, n, body)
+                }
+                _ => Stmt::Dbg(self.expr(2, &mut 1)),
+            };
+            stmts.push(s);
+        }
+        stmts
+    }
+
+    /// An expression at most `depth` deep with at most `calls` calls.
+    fn expr(&mut self, depth: usize, calls: &mut usize) -> Expr {
+        if depth == 0 || self.rng.chance(25) {
+            return match !self.vars.is_empty() && self.rng.chance(60) {
+                true => Expr::Var(self.rng.pick(&self.vars)),
+                false => self.lit(),
+            };
+        }
+        let sub = |gen: &mut Self, calls: &mut usize| {
+            Box::new(gen.expr(depth - 1, calls))
+        };
+        match self.rng.below(100) {
+            0..=49 => {
+                let op = self.rng.pick(&BIN_OPS);
+                let l = sub(self, calls);
+                // Mostly divide by a literal, which is rarely zero.
+                let r = match op {
+                    "/" | "%" if self.rng.chance(85) => {
+                        Box::new(Expr::Lit(1 + self.rng.below(9) as u64, ""))
+                    }
+                    _ => sub(self, calls),
+                };
+                Expr::Bin(op, l, r)
+            }
+            50..=57 => Expr::Neg(sub(self, calls)),
+            58..=63 => Expr::Not(sub(self, calls)),
+            64..=67 if !self.vars.is_empty() => {
+                Expr::Deref(self.rng.pick(&self.vars))
+            }
+            68..=77 => {
+                Expr::Cond(sub(self, calls), sub(self, calls), sub(self, calls))
+            }
+            78..=87 => Expr::Cast(self.rng.pick(&TYPES), sub(self, calls)),
+            _ if *calls > 0 && !self.funcs.is_empty() => {
+                *calls -= 1;
+                let (f, arity) = self.rng.pick(&self.funcs);
+                let args =
+                    (0..arity).map(|_| self.expr(depth - 1, &mut 0)).collect();
+                Expr::Call(f, args)
+            }
+            _ => self.lit(),
+        }
+    }
+
+    fn lit(&mut self) -> Expr {
+        Expr::Lit(self.rng.pick(&LITERALS), self.rng.pick(&SUFFIXES))
+    }
+}
+
+/// Expressions one step smaller than `e`, the bigger steps first.
+fn shrink_expr(e: &Expr) -> Vec<Expr> {
+    let zero = Expr::Lit(0, "");
+    let mut out = Vec::new();
+    if *e != zero {
+        out.push(zero);
+    }
+    match e {
+        Expr::Lit(..) | Expr::Var(_) => {}
+        Expr::Deref(v) => out.push(Expr::Var(v.clone())),
+        Expr::Bin(op, l, r) => {
+            out.extend([(**l).clone(), (**r).clone()]);
+            for l in shrink_expr(l) {
+                out.push(Expr::Bin(op, Box::new(l), r.clone()));
+            }
+            for r in shrink_expr(r) {
+                out.push(Expr::Bin(op, l.clone(), Box::new(r)));
+            }
+        }
+        Expr::Neg(x) | Expr::Not(x) | Expr::Cast(_, x) => {
+            out.push((**x).clone());
+            for x in shrink_expr(x) {
+                out.push(match e {
+                    Expr::Neg(_) => Expr::Neg(Box::new(x)),
+                    Expr::Not(_) => Expr::Not(Box::new(x)),
+                    Expr::Cast(ty, _) => Expr::Cast(ty, Box::new(x)),
+                    _ => unreachable!(),
+                });
+            }
+        }
+        Expr::Cond(c, t, f) => {
+            out.extend([(**c).clone(), (**t).clone(), (**f).clone()]);
+            for c in shrink_expr(c) {
+                out.push(Expr::Cond(Box::new(c), t.clone(), f.clone()));
+            }
+            for t in shrink_expr(t) {
+                out.push(Expr::Cond(c.clone(), Box::new(t), f.clone()));
+            }
+            for f in shrink_expr(f) {
+                out.push(Expr::Cond(c.clone(), t.clone(), Box::new(f)));
+            }
+        }
+        Expr::Call(name, args) => {
+            for (i, arg) in args.iter().enumerate() {
+                for a in shrink_expr(arg) {
+                    let mut args = args.clone();
+                    args[i] = a;
+                    out.push(Expr::Call(name.clone(), args));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Statements replacing `s` that are one step smaller.
+fn shrink_stmt(s: &Stmt) -> Vec<Vec<Stmt>> {
+    let mut out = Vec::new();
+    match s {
+        Stmt::Assign(v, e) => {
+            for e in shrink_expr(e) {
+                out.push(vec![Stmt::Assign(v.clone(), e)]);
+            }
+        }
+        Stmt::Dbg(e) => {
+            for e in shrink_expr(e) {
+                out.push(vec![Stmt::Dbg(e)]);
+            }
+        }
+        Stmt::If(c, t, e) => {
+            out.extend([t.clone(), e.clone()]);
+            for c in shrink_expr(c) {
+                out.push(vec![Stmt::If(c, t.clone(), e.clone())]);
+            }
+            for t in shrink_stmts(t) {
+                out.push(vec![Stmt::If(c.clone(), t, e.clone())]);
+            }
+            for e in shrink_stmts(e) {
+                out.push(vec![Stmt::If(c.clone(), t.clone(), e)]);
+            }
+        }
+        Stmt::For(i, n, body) => {
+            out.push(body.clone());
+            if *n > 0 {
+                out.push(vec![Stmt::For(i.clone(), n - 1, body.clone())]);
+            }
+            for body in shrink_stmts(body) {
+                out.push(vec![Stmt::For(i.clone(), *n, body)]);
+            }
+        }
+    }
+    out
+}
+
+fn shrink_stmts(stmts: &[Stmt]) -> Vec<Vec<Stmt>> {
+    let mut out = Vec::new();
+    for i in 0..stmts.len() {
+        out.push([&stmts[..i], &stmts[i + 1..]].concat());
+    }
+    for (i, s) in stmts.iter().enumerate() {
+        for s in shrink_stmt(s) {
+            out.push([&stmts[..i], &s, &stmts[i + 1..]].concat());
+        }
+    }
+    out
+}
+
+/// Programs one step smaller than `prog`, the bigger steps first.
+fn shrink(prog: &Prog) -> Vec<Prog> {
+    let mut out = Vec::new();
+    let n = prog.funcs.len();
+    for i in 0..n - 1 {
+        let mut p = prog.clone();
+        p.funcs.remove(i);
+        out.push(p);
+    }
+    for (i, f) in prog.funcs.iter().enumerate() {
+        let with = |f: Func| {
+            let mut p = prog.clone();
+            p.funcs[i] = f;
+            p
+        };
+        for j in 0..f.locals.len() {
+            let mut f = f.clone();
+            f.locals.remove(j);
+            out.push(with(f));
+        }
+        for body in shrink_stmts(&f.body) {
+            out.push(with(Func { body, ..f.clone() }));
+        }
+        for result in shrink_expr(&f.result) {
+            out.push(with(Func { result, ..f.clone() }));
+        }
+    }
+    out
+}
+
+/// What a program printed and the status it exited with.
+type Run = (String, Option<i32>);
+
+struct Tester {
+    model: DataModel,
+    dir: PathBuf,
+}
+
+impl Tester {
+    /// Whether minicc accepts `src` and runs it without undefined
+    /// behaviour.
+    fn defined(&self, src: &str) -> bool {
+        let map = SourceMap::from_src("diff.c", src);
+        match minicc_parser::try_parse(src, &map, self.model) {
+            Ok(nodes) => {
+                minicc_interp::run(&nodes, self.model, &mut Vec::new()).is_ok()
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Runs of `src` compiled by `cc` and then by minicc at each
+    /// optimisation level, or `None` if `cc` rejects it.
+    fn runs(&self, src: &str) -> Option<Vec<Run>> {
+        let (c, exe) = (self.dir.join("diff.c"), self.dir.join("diff"));
+        fs::write(&c, src).unwrap();
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let cc = Command::new("cc")
+            .args(["-w", "-o"])
+            .arg(&exe)
+            .arg(&c)
+            .arg(root.join("lib/dbg.c"))
+            .output()
+            .unwrap();
+        if !cc.status.success() {
+            return None;
+        }
+        let run = |cmd: &mut Command| {
+            let out = cmd.output().unwrap();
+            let out_text = [out.stdout, out.stderr].concat();
+            (String::from_utf8_lossy(&out_text).into_owned(), out.status.code())
+        };
+        let mut runs = vec![run(&mut Command::new(&exe))];
+        for level in ["-O0", "-O2"] {
+            runs.push(run(Command::new(env!("CARGO_BIN_EXE_ccom"))
+                .args(["--jit", level])
+                .arg(&c)));
+        }
+        Some(runs)
+    }
+
+    /// Whether `prog` is well defined and the compilers disagree on it.
+    fn mismatch(&self, prog: &Prog) -> Option<Vec<Run>> {
+        let src = prog.to_string();
+        if !self.defined(&src) {
+            return None;
+        }
+        let runs = self.runs(&src)?;
+        runs.iter().any(|r| *r != runs[0]).then_some(runs)
+    }
+
+    /// Shrinks `prog` while the compilers still disagree on it.
+    fn minimise(&self, mut prog: Prog) -> Prog {
+        'outer: loop {
+            for smaller in shrink(&prog) {
+                if self.mismatch(&smaller).is_some() {
+                    prog = smaller;
+                    continue 'outer;
+                }
+            }
+            return prog;
+        }
+    }
+}
+
+#[test]
+fn against_cc() {
+    let host =
+        std::env::consts::ARCH == "x86_64" && std::env::consts::OS == "linux";
+    if !host || !installed("cc") {
+        skip("cc is not installed or the host is not x86-64");
+        return;
+    }
+
+    let var = |name: &str, default: u64| {
+        std::env::var(name).map_or(default, |v| {
+            v.parse().unwrap_or_else(|_| panic!("invalid {}: {}", name, v))
+        })
+    };
+    let (cases, seed) = (var("DIFF_CASES", 40), var("DIFF_SEED", 1));
+    let dir = std::env::temp_dir()
+        .join(format!("minicc_differential_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let tester = Tester {
+        model: minicc_gen::target("x86_64-linux").unwrap().data_model(),
+        dir,
+    };
+
+    let mut ran = 0;
+    for seed in seed..seed + cases {
+        let prog = Gen::prog(seed);
+        if !tester.defined(&prog.to_string()) {
+            continue;
+        }
+        ran += 1;
+        if tester.mismatch(&prog).is_none() {
+            continue;
+        }
+        let small = tester.minimise(prog);
+        let runs = tester.mismatch(&small).unwrap();
+        panic!(
+            "cc and minicc disagree on seed {}, minimised to:\n{}\n\
+             cc: {:?}\nminicc -O0: {:?}\nminicc -O2: {:?}",
+            seed, small, runs[0], runs[1], runs[2]
+        );
+    }
+    eprintln!("{} of {} programs were well defined", ran, cases);
+    assert!(ran > 0, "no program was well defined");
+    fs::remove_dir_all(&tester.dir).unwrap();
+}
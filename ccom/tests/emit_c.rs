@@ -0,0 +1,57 @@
+//! Prints each of the end-to-end tests back as C with `--emit=c`, checks
+//! that printing the output again gives the same, and runs the output
+//! compiled both by minicc and by the system `cc` for the host, which must
+//! be a target.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::end_to_end::{self, Runner};
+use common::{run, skip, tests};
+
+#[test]
+fn print_back() {
+    let target = match Runner::emit_c(false) {
+        Ok(Runner::EmitC { target, .. }) => format!("--target={}", target),
+        Ok(_) => unreachable!(),
+        Err(why) => return skip(&why),
+    };
+    let tmp = std::env::temp_dir()
+        .join(format!("minicc_emit_c_{}", std::process::id()));
+    fs::create_dir_all(&tmp).unwrap();
+    let emit = |path: &Path| {
+        run(Command::new(env!("CARGO_BIN_EXE_ccom"))
+            .args([&target, "--emit=c"])
+            .arg(path))
+    };
+
+    let mut failed = Vec::new();
+    for path in tests() {
+        let c = tmp.join("test.c");
+        let src = emit(&path);
+        fs::write(&c, &src).unwrap();
+        if emit(&c) != src {
+            eprintln!(
+                "{} does not print back the same:\n{}",
+                path.display(),
+                String::from_utf8_lossy(&src)
+            );
+            failed.push(path);
+        }
+    }
+    assert!(failed.is_empty(), "failed: {:?}", failed);
+    fs::remove_dir_all(&tmp).unwrap();
+}
+
+#[test]
+fn ccom() {
+    end_to_end::programs("emit_c_ccom", &["-O0"], |_| Runner::emit_c(false));
+}
+
+#[test]
+fn cc() {
+    end_to_end::programs("emit_c_cc", &["-O0"], |_| Runner::emit_c(true));
+}
@@ -0,0 +1,19 @@
+//! The end-to-end tests in `test/`, run as the environment asks, which the
+//! tests of each backend and target do too, with the driver in
+//! `common/end_to_end.rs`.
+//!
+//! As the shell script this replaces did, programs are compiled for
+//! `TARGET`, by default `i386-linux`: x86 programs are linked with the
+//! runtime in `lib/rt` by `ld` alone, other targets with `lib/dbg.c` by the
+//! cross compiler `CC`, `ARCH-linux-gnu-gcc` by default, and run under
+//! `RUN`, by default `qemu-ARCH -L /usr/ARCH-linux-gnu`. With `JIT=1` they
+//! are run by `ccom --jit` instead.
+
+mod common;
+
+use common::end_to_end::{self, Runner};
+
+#[test]
+fn programs() {
+    end_to_end::programs("end_to_end", &["-O0"], Runner::from_env);
+}
@@ -0,0 +1,44 @@
+//! Runs the end-to-end tests with `ccom --jit` at each optimisation level,
+//! comparing what they print with the `.expect` files, on hosts that can.
+
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+mod common;
+
+use std::process::Command;
+
+use common::end_to_end::{self, Runner};
+
+#[test]
+fn programs() {
+    end_to_end::programs("jit", &["-O0", "-O2"], |_| Runner::jit());
+}
+
+#[test]
+fn status() {
+    let src = "int putchar(int c);
+int main() { putchar(111); putchar(107); putchar(10); return 3; }";
+    let out = Command::new(env!("CARGO_BIN_EXE_ccom"))
+        .args(["--jit", "-"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            use std::io::Write;
+            child.stdin.take().unwrap().write_all(src.as_bytes())?;
+            child.wait_with_output()
+        })
+        .unwrap();
+    assert_eq!(out.status.code(), Some(3));
+    assert_eq!(out.stdout, b"ok\n");
+
+    let out = Command::new(env!("CARGO_BIN_EXE_ccom"))
+        .args(["--jit", "--target=i386-linux", "-"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        "ccom: `--jit` only runs `x86_64-linux` code\n"
+    );
+}
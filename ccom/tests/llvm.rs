@@ -0,0 +1,40 @@
+//! Compiles the end-to-end tests to LLVM IR for x86-64 at each optimisation
+//! level, promotes the slots with `opt -passes=mem2reg`, links `lib/dbg.ll`
+//! with `llvm-link` and runs the result with `lli`.
+
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::end_to_end::{self, Runner};
+use common::{root, run, skip};
+
+#[test]
+fn programs() {
+    end_to_end::programs("llvm", &["-O0", "-O1", "-O2"], |_| Runner::llvm());
+}
+
+/// Locals whose address is not taken become registers.
+#[test]
+fn mem2reg() {
+    let flags = match Runner::llvm() {
+        Ok(Runner::Llvm { flags }) => flags,
+        Ok(_) => unreachable!(),
+        Err(why) => return skip(&why),
+    };
+    let ll = run(Command::new(env!("CARGO_BIN_EXE_ccom"))
+        .args(["--target=x86_64-linux", "--emit=llvm"])
+        .arg(root().join("test/fib.c")));
+    let mut opt = Command::new("opt")
+        .args(&flags)
+        .args(["-passes=mem2reg", "-S"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    opt.stdin.take().unwrap().write_all(&ll).unwrap();
+    let out = opt.wait_with_output().unwrap();
+    assert!(out.status.success(), "opt: {}", out.status);
+    assert!(!String::from_utf8(out.stdout).unwrap().contains("alloca"));
+}
@@ -0,0 +1,40 @@
+//! Checks that `-D` and `-U` override the macros the target predefines.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The text `ccom -E` gives for `src` with `args`, line markers dropped.
+fn preprocess(args: &[&str], src: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ccom"))
+        .arg("-E")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(src.as_bytes()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    let text = String::from_utf8(out.stdout).unwrap();
+    text.lines().filter(|l| !l.starts_with('#')).collect()
+}
+
+#[test]
+fn target() {
+    let src = "__linux__ __x86_64__\n";
+    let args = ["--target=x86_64-linux"];
+    assert_eq!(preprocess(&args, src), "1 1");
+}
+
+#[test]
+fn override_target() {
+    let src = "__linux__ __x86_64__\n";
+    let args = ["--target=x86_64-linux", "-U__linux__", "-D__x86_64__=5"];
+    assert_eq!(preprocess(&args, src), "__linux__ 5");
+}
+
+#[test]
+fn in_order() {
+    let src = "A B\n";
+    assert_eq!(preprocess(&["-DA", "-UA", "-UB", "-DB=2"], src), "A 2");
+}
@@ -0,0 +1,82 @@
+//! Compiles the end-to-end tests to objects with `ccom -c`, links them with
+//! `lib/dbg.c` by the system `cc` and compares what they print, for the x86
+//! targets. Also checks that the objects disassemble the same, with the
+//! same relocations, as those GNU `as` makes from the assembly, with
+//! `objdump`.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::end_to_end::{programs, Runner};
+use common::{installed, run, skip, tests};
+
+#[test]
+fn i386() {
+    programs("object_i386", &["-O0", "-O2"], |tmp| {
+        Runner::cc("i386-linux", &["-m32"], tmp)
+    });
+}
+
+#[test]
+fn x86_64() {
+    programs("object_x86_64", &["-O0", "-O2"], |tmp| {
+        Runner::cc("x86_64-linux", &["-m64"], tmp)
+    });
+}
+
+#[test]
+fn gnu_as() {
+    if !installed("as") || !installed("objdump") {
+        skip("as or objdump is not installed");
+        return;
+    }
+    let tmp = std::env::temp_dir()
+        .join(format!("minicc_object_as_{}", std::process::id()));
+    fs::create_dir_all(&tmp).unwrap();
+    let (asm, ours, theirs) =
+        (tmp.join("test.s"), tmp.join("ours.o"), tmp.join("theirs.o"));
+    // Disassembly and relocations, without the line naming the file.
+    let dump = |obj: &Path| {
+        let out = run(Command::new("objdump").arg("-dr").arg(obj));
+        let out = String::from_utf8(out).unwrap();
+        out.lines().skip(2).collect::<Vec<_>>().join("\n")
+    };
+
+    let mut failed = Vec::new();
+    for (target, flag) in [("i386-linux", "--32"), ("x86_64-linux", "--64")] {
+        for path in tests() {
+            for level in ["-O0", "-O2"] {
+                let target = format!("--target={}", target);
+                let out = run(Command::new(env!("CARGO_BIN_EXE_ccom"))
+                    .args([&target, level])
+                    .arg(&path));
+                fs::write(&asm, out).unwrap();
+                run(Command::new("as")
+                    .args([flag, "-o"])
+                    .arg(&theirs)
+                    .arg(&asm));
+                run(Command::new(env!("CARGO_BIN_EXE_ccom"))
+                    .args([&target, level, "-c", "-o"])
+                    .arg(&ours)
+                    .arg(&path));
+                let (ours, theirs) = (dump(&ours), dump(&theirs));
+                if ours != theirs {
+                    eprintln!(
+                        "{} for {} at {} differs:\n{}\n{}",
+                        path.display(),
+                        target,
+                        level,
+                        ours,
+                        theirs
+                    );
+                    failed.push((path.clone(), target, level));
+                }
+            }
+        }
+    }
+    assert!(failed.is_empty(), "failed: {:?}", failed);
+    fs::remove_dir_all(&tmp).unwrap();
+}
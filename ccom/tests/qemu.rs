@@ -0,0 +1,28 @@
+//! Runs the end-to-end tests compiled for the targets other than the host
+//! under `qemu-user`, with the emulator and cross compiler, used to
+//! assemble and link, installed as `qemu-ARCH` and `ARCH-linux-gnu-gcc`.
+
+mod common;
+
+use common::end_to_end::{programs, Runner};
+
+fn check(arch: &str) {
+    let (target, cc, run) = (
+        format!("{}-linux", arch),
+        format!("{}-linux-gnu-gcc", arch),
+        format!("qemu-{} -L /usr/{}-linux-gnu", arch, arch),
+    );
+    programs(&format!("qemu_{}", arch), &["-O0", "-O2"], |_| {
+        Runner::cross(&target, &cc, &run)
+    });
+}
+
+#[test]
+fn aarch64() {
+    check("aarch64");
+}
+
+#[test]
+fn riscv64() {
+    check("riscv64");
+}
@@ -0,0 +1,106 @@
+//! Feeds scripts to `ccom repl`, checking that state is kept between inputs,
+//! that errors leave the session usable and what the commands print.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Standard output and error of the session.
+fn repl(script: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ccom"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    (
+        String::from_utf8(out.stdout).unwrap(),
+        String::from_utf8(out.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn state() {
+    let (out, err) = repl(
+        "int sq(int x) { return x * x; }
+int y;
+y = sq(4);
+y + 1
+&y
+int i; int s; s = 0;
+for (i = 0; i < 5; i = i + 1) {
+  s = s + i;
+}
+s
+int sq(int x) {
+  return -x;
+}
+sq(y)
+",
+    );
+    assert_eq!(
+        out,
+        "(int) 16\n(int) 17\n(int *) 0x100000\n(int) 0\n(int) 10\n(int) -16\n"
+    );
+    assert_eq!(err, "");
+}
+
+#[test]
+fn errors() {
+    let (out, err) = repl("int x;\nx = 1 / 0\nx @\nx = 2\nx +\n:quit\nx\n");
+    assert_eq!(out, "(int) 2\n");
+    assert_eq!(
+        err,
+        "<input>:1:5: undefined behaviour: division by zero
+    x = 1 / 0;
+        ^~~~~
+<input>:1:3: unknown token `@`
+    x @;
+      ^
+<input>:1:4: expected expression, found `;`
+    x +;
+       ^
+"
+    );
+
+    let (out, err) = repl(":ast\n:nope\ny\n");
+    assert_eq!(out, "");
+    assert_eq!(
+        err,
+        "no input yet
+unknown command `:nope`, see `:help`
+<input>:1:1: cannot find value `y`
+    y;
+    ^
+"
+    );
+}
+
+#[test]
+fn commands() {
+    let (out, _) = repl("int two(void) { return 2; }\ntwo() + 1\n:ast\n");
+    assert_eq!(
+        out,
+        "(int) 3
+BinOp Add: int
+  Call: int
+    UnOp Addr: int (*)(void)
+      Ref two: int (void)
+  IntLit 1: int
+"
+    );
+
+    let (out, _) = repl("int x;\nx = 42u\n:tokens\n");
+    assert_eq!(
+        out,
+        "(int) 42\n1:1\tx (identifier)\n1:3\t=\n\
+         1:5\t42u (unsigned int)\n1:8\t;\n"
+    );
+
+    let (out, _) = repl("int one(void) { return 1; }\n:asm\n");
+    assert!(out.contains("one:\n"), "{}", out);
+    assert!(!out.contains("__repl"), "{}", out);
+}
@@ -0,0 +1,40 @@
+//! Interprets programs with `--run`, checking the exit status, what `dbg`
+//! prints and how undefined behaviour is reported.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(src: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ccom"))
+        .arg("--run")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(src.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn status() {
+    let out = run("int main() { dbg(-1); dbg(2); return 3; }");
+    assert_eq!(out.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&out.stderr), "-1\n2\n");
+    assert!(out.stdout.is_empty());
+}
+
+#[test]
+fn undefined() {
+    let src = "int main() { int x; x = 0; return 1 / x; }";
+    let out = run(src);
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stderr),
+        format!(
+            "<stdin>:1:35: undefined behaviour: division by zero\n    {}\n{}\n",
+            src,
+            " ".repeat(4 + 34) + "^~~~~"
+        )
+    );
+}
@@ -0,0 +1,26 @@
+// Uses what the runtime in `lib/rt` provides besides `dbg`, which needs
+// strings minicc cannot compile yet.
+
+int printf(const char *fmt, ...);
+int puts(const char *s);
+int putchar(int c);
+
+int main(void)
+{
+    int n;
+
+    printf("%d %i %u %x %c %s %%\n", -42, 7, 4294967295u, 0xbeef, 'z', "s");
+    printf("%d %d %u\n", 0, -2147483647 - 1, 0u);
+    printf("%ld %lu %lx\n", -1L, 123456789UL, 0xffUL);
+    printf("%lld %llu %llx\n", -9223372036854775807LL - 1,
+           18446744073709551615ULL, 0x123456789abcdefULL);
+    printf("%d %d %d %d %d %d %d\n", 1, 2, 3, 4, 5, 6, 7);
+    printf("%q%");
+    printf("\n");
+    n = printf("%s\n", "printed");
+    printf("%d\n", n);
+    puts("puts");
+    putchar('!');
+    putchar('\n');
+    return 3;
+}
@@ -0,0 +1,104 @@
+//! Links the end-to-end tests with the runtime in `lib/rt` into static
+//! executables, both assembled by `ccom -c`, with nothing but the system
+//! `ld`, and compares what they print, for the x86 targets, which the host
+//! must run. Also checks the rest of the runtime with `runtime.c`, compiled by
+//! the system `cc` as minicc has no strings yet.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use common::end_to_end::{self, Runner};
+use common::{installed, root, run, skip};
+
+/// Targets with the emulation of `ld` and the flag of `cc` for them.
+const TARGETS: [(&str, &str, &str); 2] = [
+    ("i386-linux", "elf_i386", "-m32"),
+    ("x86_64-linux", "elf_x86_64", "-m64"),
+];
+
+const EXPECT: &str = "\
+-42 7 4294967295 beef z s %
+0 -2147483648 0
+-1 123456789 ff
+-9223372036854775808 18446744073709551615 123456789abcdef
+1 2 3 4 5 6 7
+q
+printed
+8
+puts
+!
+";
+
+/// Whether `ld` is installed and the host runs x86 Linux executables.
+fn runnable() -> bool {
+    let host =
+        std::env::consts::ARCH == "x86_64" && std::env::consts::OS == "linux";
+    if !host || !installed("ld") {
+        skip("ld is not installed or the host is not x86");
+        return false;
+    }
+    true
+}
+
+/// Directory for the files of `runtime.c` and the runtime for `target` in
+/// it.
+fn runtime(target: &str) -> (PathBuf, PathBuf) {
+    let tmp = std::env::temp_dir().join(format!(
+        "minicc_runtime_c_{}_{}",
+        target,
+        std::process::id()
+    ));
+    fs::create_dir_all(&tmp).unwrap();
+    let rt = tmp.join("rt.o");
+    run(Command::new(env!("CARGO_BIN_EXE_ccom"))
+        .arg(format!("--target={}", target))
+        .args(["-c", "-o"])
+        .arg(&rt)
+        .arg(root().join(format!("lib/rt/{}.s", target))));
+    (tmp, rt)
+}
+
+#[test]
+fn programs() {
+    for (target, _, _) in TARGETS {
+        end_to_end::programs(
+            &format!("runtime_{}", target),
+            &["-O0", "-O2"],
+            |tmp| Runner::ld(target, tmp),
+        );
+    }
+}
+
+#[test]
+fn printf() {
+    if !runnable() {
+        return;
+    }
+    if !installed("cc") {
+        skip("cc is not installed");
+        return;
+    }
+    for (target, emulation, flag) in TARGETS {
+        let (tmp, rt) = runtime(target);
+        let (obj, exe) = (tmp.join("runtime.o"), tmp.join("runtime"));
+        // Without `-fno-builtin` calls to `printf` may become `puts`, and
+        // the stack protector would need the C library.
+        run(Command::new("cc")
+            .args([flag, "-c", "-fno-pic", "-fno-builtin", "-w"])
+            .args(["-fno-stack-protector", "-o"])
+            .arg(&obj)
+            .arg(root().join("ccom/tests/runtime.c")));
+        run(Command::new("ld")
+            .args(["-static", "-m", emulation, "-o"])
+            .arg(&exe)
+            .arg(&obj)
+            .arg(&rt));
+        let out = Command::new(&exe).output().unwrap();
+        assert_eq!(out.status.code(), Some(3), "{}", target);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), EXPECT, "{}", target);
+        fs::remove_dir_all(&tmp).unwrap();
+    }
+}
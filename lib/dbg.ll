@@ -0,0 +1,10 @@
+@stderr = external global ptr
+@fmt = private constant [4 x i8] c"%d\0A\00"
+
+declare i32 @fprintf(ptr, ptr, ...)
+
+define void @dbg(i32 %v) {
+  %f = load ptr, ptr @stderr
+  call i32 (ptr, ptr, ...) @fprintf(ptr %f, ptr @fmt, i32 %v)
+  ret void
+}
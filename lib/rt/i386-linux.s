@@ -0,0 +1,416 @@
+# Runtime for programs compiled for `i386-linux`, so that they link into
+# static executables without a C library:
+#
+#     ccom -c -o rt.o lib/rt/i386-linux.s
+#     ld -static -m elf_i386 -o prog prog.o rt.o
+#
+# System calls are made with `int $0x80` and output is not buffered. `write`
+# returns `-errno` on failure. `printf` knows `%d`, `%i`, `%u`, `%x`, `%c`,
+# `%s` and `%%`, with `l` or `ll` before the integer conversions, and prints
+# the letter of any other conversion.
+
+	.text
+	.globl _start
+	.type _start, @function
+_start:
+	xor %ebp, %ebp
+	mov (%esp), %eax	# argc
+	lea 4(%esp), %ecx	# argv
+	and $-16, %esp
+	sub $8, %esp
+	push %ecx
+	push %eax
+	call main
+	mov %eax, (%esp)
+	call exit
+
+# void exit(int status)
+	.globl exit
+	.type exit, @function
+exit:
+	mov 4(%esp), %ebx
+	mov $252, %eax		# exit_group
+	int $0x80
+	hlt
+
+# long write(int fd, const void *buf, unsigned long n)
+	.globl write
+	.type write, @function
+write:
+	push %ebx
+	mov 8(%esp), %ebx
+	mov 12(%esp), %ecx
+	mov 16(%esp), %edx
+	mov $4, %eax		# write
+	int $0x80
+	pop %ebx
+	ret
+
+# int putchar(int c)
+	.globl putchar
+	.type putchar, @function
+putchar:
+	lea 4(%esp), %eax	# the low byte of `c`
+	push $1
+	push %eax
+	push $1
+	call write
+	add $12, %esp
+	cmp $1, %eax
+	jne .Leof
+	movzbl 4(%esp), %eax
+	ret
+.Leof:
+	mov $-1, %eax
+	ret
+
+# int puts(const char *s)
+	.globl puts
+	.type puts, @function
+puts:
+	push %esi
+	mov 8(%esp), %esi
+	call .Lstrlen
+	push %edx
+	push %esi
+	push $1
+	call write
+	add $12, %esp
+	pop %esi
+	test %eax, %eax
+	js .Leof
+	push $10
+	call putchar
+	add $4, %esp
+	ret
+
+# void dbg(int v), printing `v` and a newline to standard error.
+	.globl dbg
+	.type dbg, @function
+dbg:
+	push %ebp
+	mov %esp, %ebp
+	push %esi
+	push %edi
+	sub $32, %esp
+	lea -9(%ebp), %edi
+	movb $10, (%edi)
+	mov 8(%ebp), %eax
+	cltd
+	call .Ldecimal
+	lea -8(%ebp), %edx
+	sub %edi, %edx
+	push %edx
+	push %edi
+	push $2
+	call write
+	lea -8(%ebp), %esp
+	pop %edi
+	pop %esi
+	pop %ebp
+	ret
+
+# int printf(const char *fmt, ...)
+#
+# The format is walked by %ebx. -16(%ebp) holds the next argument,
+# -20(%ebp) the number of bytes printed, -24(%ebp) the number of `l` and
+# the digits are written before -24(%ebp).
+	.globl printf
+	.type printf, @function
+printf:
+	push %ebp
+	mov %esp, %ebp
+	push %ebx
+	push %esi
+	push %edi
+	sub $44, %esp
+	mov 8(%ebp), %ebx
+	lea 12(%ebp), %eax
+	mov %eax, -16(%ebp)
+	movl $0, -20(%ebp)
+.Lprintf.next:
+	mov %ebx, %esi
+.Lprintf.scan:
+	movzbl (%ebx), %eax
+	test %eax, %eax
+	je .Lprintf.text
+	cmp $37, %eax		# '%'
+	je .Lprintf.text
+	inc %ebx
+	jmp .Lprintf.scan
+.Lprintf.text:
+	mov %ebx, %edx
+	sub %esi, %edx
+	call .Lprintf.out
+	cmpb $0, (%ebx)
+	je .Lprintf.end
+	inc %ebx
+	movl $0, -24(%ebp)
+.Lprintf.len:
+	movzbl (%ebx), %eax
+	inc %ebx
+	cmp $108, %eax		# 'l'
+	jne .Lprintf.conv
+	incl -24(%ebp)
+	jmp .Lprintf.len
+.Lprintf.conv:
+	lea -24(%ebp), %edi
+	mov $10, %ecx
+	cmp $100, %eax		# 'd'
+	je .Lprintf.signed
+	cmp $105, %eax		# 'i'
+	je .Lprintf.signed
+	cmp $117, %eax		# 'u'
+	je .Lprintf.unsigned
+	mov $16, %ecx
+	cmp $120, %eax		# 'x'
+	je .Lprintf.unsigned
+	cmp $99, %eax		# 'c'
+	je .Lprintf.char
+	cmp $115, %eax		# 's'
+	je .Lprintf.str
+	test %eax, %eax
+	jne .Lprintf.letter
+	dec %ebx
+	jmp .Lprintf.next
+.Lprintf.letter:
+	lea -1(%ebx), %esi
+	mov $1, %edx
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.signed:
+	call .Lprintf.arg
+	call .Ldecimal
+	jmp .Lprintf.digits
+.Lprintf.unsigned:
+	call .Lprintf.arg
+	cmpl $2, -24(%ebp)
+	jge .Lprintf.wide
+	xor %edx, %edx
+.Lprintf.wide:
+	call .Ldigits
+.Lprintf.digits:
+	mov %edi, %esi
+	lea -24(%ebp), %edx
+	sub %edi, %edx
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.char:
+	call .Lprintf.arg
+	lea -25(%ebp), %esi
+	mov %al, (%esi)
+	mov $1, %edx
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.str:
+	call .Lprintf.arg
+	mov %eax, %esi
+	call .Lstrlen
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.end:
+	mov -20(%ebp), %eax
+	lea -12(%ebp), %esp
+	pop %edi
+	pop %esi
+	pop %ebx
+	pop %ebp
+	ret
+
+# Takes the next argument of `printf` into %edx:%eax, sign extended unless
+# it is a `long long`.
+.Lprintf.arg:
+	mov -16(%ebp), %esi
+	mov (%esi), %eax
+	add $4, %esi
+	cmpl $2, -24(%ebp)
+	jge .Lprintf.arg64
+	cltd
+	mov %esi, -16(%ebp)
+	ret
+.Lprintf.arg64:
+	mov (%esi), %edx
+	add $4, %esi
+	mov %esi, -16(%ebp)
+	ret
+
+# Prints %edx bytes from %esi for `printf`.
+.Lprintf.out:
+	add %edx, -20(%ebp)
+	push %edx
+	push %esi
+	push $1
+	call write
+	add $12, %esp
+	ret
+
+# Length of the string at %esi in %edx.
+.Lstrlen:
+	mov %esi, %edx
+.Lstrlen.loop:
+	cmpb $0, (%edx)
+	je .Lstrlen.end
+	inc %edx
+	jmp .Lstrlen.loop
+.Lstrlen.end:
+	sub %esi, %edx
+	ret
+
+# Writes the signed %edx:%eax in decimal before %edi, leaving %edi at the
+# first character. Clobbers %eax, %ecx, %edx and %esi.
+.Ldecimal:
+	mov $10, %ecx
+	test %edx, %edx
+	jns .Ldigits
+	neg %eax
+	adc $0, %edx
+	neg %edx
+	call .Ldigits
+	dec %edi
+	movb $45, (%edi)	# '-'
+	ret
+
+# Writes the unsigned %edx:%eax in base %ecx before %edi, leaving %edi at
+# the first digit. Clobbers %eax, %edx and %esi.
+.Ldigits:
+	push %ebx
+	mov %edx, %ebx
+.Ldigits.next:
+	mov %eax, %esi
+	mov %ebx, %eax
+	xor %edx, %edx
+	div %ecx
+	mov %eax, %ebx
+	mov %esi, %eax
+	div %ecx
+	cmp $10, %edx
+	jb .Ldigits.dec
+	add $39, %edx		# 'a' - '0' - 10
+.Ldigits.dec:
+	add $48, %edx		# '0'
+	dec %edi
+	mov %dl, (%edi)
+	mov %eax, %edx
+	or %ebx, %edx
+	jne .Ldigits.next
+	pop %ebx
+	ret
+
+# The `long long` division the code generator calls, as in libgcc.
+#
+# unsigned long long __udivdi3(unsigned long long a, unsigned long long b)
+	.globl __udivdi3
+	.type __udivdi3, @function
+__udivdi3:
+	call .Ldivargs
+	call .Ludivmod
+	jmp .Ldivret
+
+# unsigned long long __umoddi3(unsigned long long a, unsigned long long b)
+	.globl __umoddi3
+	.type __umoddi3, @function
+__umoddi3:
+	call .Ldivargs
+	call .Ludivmod
+	mov %ebx, %eax
+	mov %ecx, %edx
+	jmp .Ldivret
+
+# long long __divdi3(long long a, long long b)
+	.globl __divdi3
+	.type __divdi3, @function
+__divdi3:
+	call .Ldivargs
+	mov %edx, %ecx
+	xor %edi, %ecx
+	push %ecx		# negative if the signs differ
+	call .Ldivabs
+	call .Ludivmod
+	pop %ecx
+	jmp .Ldivsign
+
+# long long __moddi3(long long a, long long b)
+	.globl __moddi3
+	.type __moddi3, @function
+__moddi3:
+	call .Ldivargs
+	push %edx		# negative if `a` is
+	call .Ldivabs
+	call .Ludivmod
+	mov %ebx, %eax
+	mov %ecx, %edx
+	pop %ecx
+	jmp .Ldivsign
+
+# Saves %ebp, %ebx, %esi and %edi and loads `a` into %edx:%eax and `b` into
+# %edi:%esi, for the entries above.
+.Ldivargs:
+	pop %ecx
+	push %ebp
+	push %ebx
+	push %esi
+	push %edi
+	mov 20(%esp), %eax
+	mov 24(%esp), %edx
+	mov 28(%esp), %esi
+	mov 32(%esp), %edi
+	jmp *%ecx
+
+# Negates %edx:%eax if %ecx is negative and returns from the entry.
+.Ldivsign:
+	test %ecx, %ecx
+	jns .Ldivret
+	neg %eax
+	adc $0, %edx
+	neg %edx
+.Ldivret:
+	pop %edi
+	pop %esi
+	pop %ebx
+	pop %ebp
+	ret
+
+# Makes %edx:%eax and %edi:%esi their absolute values.
+.Ldivabs:
+	test %edx, %edx
+	jns .Ldivabs.b
+	neg %eax
+	adc $0, %edx
+	neg %edx
+.Ldivabs.b:
+	test %edi, %edi
+	jns .Ldivabs.end
+	neg %esi
+	adc $0, %edi
+	neg %edi
+.Ldivabs.end:
+	ret
+
+# Divides %edx:%eax by %edi:%esi a bit at a time, leaving the quotient in
+# %edx:%eax and the remainder in %ecx:%ebx. Clobbers %ebp.
+.Ludivmod:
+	xor %ebx, %ebx
+	xor %ecx, %ecx
+	mov $64, %ebp
+.Ludivmod.next:
+	# Shifts the next bit of the dividend into the remainder.
+	add %eax, %eax
+	adc %edx, %edx
+	adc %ebx, %ebx
+	adc %ecx, %ecx
+	jc .Ludivmod.sub
+	cmp %edi, %ecx
+	jb .Ludivmod.skip
+	ja .Ludivmod.sub
+	cmp %esi, %ebx
+	jb .Ludivmod.skip
+.Ludivmod.sub:
+	sub %esi, %ebx
+	sbb %edi, %ecx
+	inc %eax
+.Ludivmod.skip:
+	dec %ebp
+	jne .Ludivmod.next
+	ret
+
+	.section .note.GNU-stack,"",@progbits
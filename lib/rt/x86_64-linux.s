@@ -0,0 +1,266 @@
+# Runtime for programs compiled for `x86_64-linux`, so that they link into
+# static executables without a C library:
+#
+#     ccom --target=x86_64-linux -c -o rt.o lib/rt/x86_64-linux.s
+#     ld -static -o prog prog.o rt.o
+#
+# System calls are made with `syscall` and output is not buffered. `write`
+# returns `-errno` on failure. `printf` knows `%d`, `%i`, `%u`, `%x`, `%c`,
+# `%s` and `%%`, with `l` or `ll` before the integer conversions, and prints
+# the letter of any other conversion.
+
+	.text
+	.globl _start
+	.type _start, @function
+_start:
+	xor %ebp, %ebp
+	mov (%rsp), %rdi	# argc
+	lea 8(%rsp), %rsi	# argv
+	and $-16, %rsp
+	call main
+	mov %eax, %edi
+	call exit
+
+# void exit(int status)
+	.globl exit
+	.type exit, @function
+exit:
+	mov $231, %eax		# exit_group
+	syscall
+	hlt
+
+# long write(int fd, const void *buf, unsigned long n)
+	.globl write
+	.type write, @function
+write:
+	mov $1, %eax		# write
+	syscall
+	ret
+
+# int putchar(int c)
+	.globl putchar
+	.type putchar, @function
+putchar:
+	push %rdi
+	mov %rsp, %rsi		# the low byte of `c`
+	mov $1, %edx
+	mov $1, %edi
+	call write
+	pop %rcx
+	cmp $1, %rax
+	jne .Leof
+	movzbl %cl, %eax
+	ret
+.Leof:
+	mov $-1, %eax
+	ret
+
+# int puts(const char *s)
+	.globl puts
+	.type puts, @function
+puts:
+	mov %rdi, %rsi
+	call .Lstrlen
+	mov $1, %edi
+	call write
+	test %rax, %rax
+	js .Leof
+	mov $10, %edi
+	jmp putchar
+
+# void dbg(int v), printing `v` and a newline to standard error.
+	.globl dbg
+	.type dbg, @function
+dbg:
+	push %rbp
+	mov %rsp, %rbp
+	sub $32, %rsp
+	mov %edi, %eax
+	cltq
+	lea -1(%rbp), %rdi
+	movb $10, (%rdi)
+	call .Ldecimal
+	mov %rdi, %rsi
+	mov %rbp, %rdx
+	sub %rdi, %rdx
+	mov $2, %edi
+	call write
+	leave
+	ret
+
+# int printf(const char *fmt, ...)
+#
+# The arguments in registers are pushed below the frame, so that they are
+# read from -40(%rbp) to -8(%rbp), then the others from 16(%rbp). The
+# format is walked by %rbx, %r12 points to the next argument, %r13 holds
+# the number of bytes printed, %r14 the number of `l` and the digits are
+# written before -72(%rbp).
+	.globl printf
+	.type printf, @function
+printf:
+	push %rbp
+	mov %rsp, %rbp
+	push %r9
+	push %r8
+	push %rcx
+	push %rdx
+	push %rsi
+	push %rbx
+	push %r12
+	push %r13
+	push %r14
+	sub $40, %rsp
+	mov %rdi, %rbx
+	lea -40(%rbp), %r12
+	xor %r13d, %r13d
+.Lprintf.next:
+	mov %rbx, %rsi
+.Lprintf.scan:
+	movzbl (%rbx), %eax
+	test %eax, %eax
+	je .Lprintf.text
+	cmp $37, %eax		# '%'
+	je .Lprintf.text
+	inc %rbx
+	jmp .Lprintf.scan
+.Lprintf.text:
+	mov %rbx, %rdx
+	sub %rsi, %rdx
+	call .Lprintf.out
+	cmpb $0, (%rbx)
+	je .Lprintf.end
+	inc %rbx
+	xor %r14d, %r14d
+.Lprintf.len:
+	movzbl (%rbx), %eax
+	inc %rbx
+	cmp $108, %eax		# 'l'
+	jne .Lprintf.conv
+	inc %r14d
+	jmp .Lprintf.len
+.Lprintf.conv:
+	lea -72(%rbp), %rdi
+	mov $10, %ecx
+	cmp $100, %eax		# 'd'
+	je .Lprintf.signed
+	cmp $105, %eax		# 'i'
+	je .Lprintf.signed
+	cmp $117, %eax		# 'u'
+	je .Lprintf.unsigned
+	mov $16, %ecx
+	cmp $120, %eax		# 'x'
+	je .Lprintf.unsigned
+	cmp $99, %eax		# 'c'
+	je .Lprintf.char
+	cmp $115, %eax		# 's'
+	je .Lprintf.str
+	test %eax, %eax
+	jne .Lprintf.letter
+	dec %rbx
+	jmp .Lprintf.next
+.Lprintf.letter:
+	lea -1(%rbx), %rsi
+	mov $1, %edx
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.signed:
+	call .Lprintf.arg
+	test %r14d, %r14d
+	jne .Lprintf.long
+	cltq
+.Lprintf.long:
+	call .Ldecimal
+	jmp .Lprintf.digits
+.Lprintf.unsigned:
+	call .Lprintf.arg
+	test %r14d, %r14d
+	jne .Lprintf.ulong
+	mov %eax, %eax
+.Lprintf.ulong:
+	call .Ldigits
+.Lprintf.digits:
+	mov %rdi, %rsi
+	lea -72(%rbp), %rdx
+	sub %rdi, %rdx
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.char:
+	call .Lprintf.arg
+	lea -73(%rbp), %rsi
+	mov %al, (%rsi)
+	mov $1, %edx
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.str:
+	call .Lprintf.arg
+	mov %rax, %rsi
+	call .Lstrlen
+	call .Lprintf.out
+	jmp .Lprintf.next
+.Lprintf.end:
+	mov %r13d, %eax
+	lea -72(%rbp), %rsp
+	pop %r14
+	pop %r13
+	pop %r12
+	pop %rbx
+	leave
+	ret
+
+# Takes the next argument of `printf` into %rax.
+.Lprintf.arg:
+	mov (%r12), %rax
+	add $8, %r12
+	cmp %rbp, %r12
+	jne .Lprintf.arg.end
+	lea 16(%rbp), %r12
+.Lprintf.arg.end:
+	ret
+
+# Prints %rdx bytes from %rsi for `printf`.
+.Lprintf.out:
+	add %rdx, %r13
+	mov $1, %edi
+	jmp write
+
+# Length of the string at %rsi in %rdx.
+.Lstrlen:
+	mov %rsi, %rdx
+.Lstrlen.loop:
+	cmpb $0, (%rdx)
+	je .Lstrlen.end
+	inc %rdx
+	jmp .Lstrlen.loop
+.Lstrlen.end:
+	sub %rsi, %rdx
+	ret
+
+# Writes the signed %rax in decimal before %rdi, leaving %rdi at the first
+# character. Clobbers %rax, %rcx and %rdx.
+.Ldecimal:
+	mov $10, %ecx
+	test %rax, %rax
+	jns .Ldigits
+	neg %rax
+	call .Ldigits
+	dec %rdi
+	movb $45, (%rdi)	# '-'
+	ret
+
+# Writes the unsigned %rax in base %rcx before %rdi, leaving %rdi at the
+# first digit. Clobbers %rax and %rdx.
+.Ldigits:
+	xor %edx, %edx
+	div %rcx
+	cmp $10, %edx
+	jb .Ldigits.dec
+	add $39, %edx		# 'a' - '0' - 10
+.Ldigits.dec:
+	add $48, %edx		# '0'
+	dec %rdi
+	mov %dl, (%rdi)
+	test %rax, %rax
+	jne .Ldigits
+	ret
+
+	.section .note.GNU-stack,"",@progbits
@@ -0,0 +1,277 @@
+//! ELF relocatable object files, ELF32 with `SHT_REL` relocations for i386
+//! and ELF64 with `SHT_RELA` ones for x86-64.
+//!
+//! The file holds the header, the contents of the sections, the symbol and
+//! string tables, the relocation sections and then the section headers.
+
+use crate::{Arch, Object, SectionKind};
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHF_INFO_LINK: u64 = 0x40;
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_FUNC: u8 = 2;
+
+/// Bytes of the file being written, with fields as wide as the class.
+struct Buf {
+    bytes: Vec<u8>,
+    wide: bool,
+}
+
+impl Buf {
+    fn u8(&mut self, v: u8) {
+        self.bytes.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.bytes.extend(v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.bytes.extend(v.to_le_bytes());
+    }
+
+    /// Address, offset or size, `Elf32_Word` or `Elf64_Xword`.
+    fn word(&mut self, v: u64) {
+        if self.wide {
+            self.bytes.extend(v.to_le_bytes());
+        } else {
+            self.u32(v as u32);
+        }
+    }
+
+    fn align(&mut self, n: usize) {
+        self.bytes.resize(self.bytes.len().next_multiple_of(n), 0);
+    }
+}
+
+/// String table, starting with the empty string.
+struct StrTab(Vec<u8>);
+
+impl StrTab {
+    fn new() -> Self {
+        Self(vec![0])
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        let off = self.0.len() as u32;
+        self.0.extend(s.as_bytes());
+        self.0.push(0);
+        off
+    }
+}
+
+struct Header {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+/// Lays out `obj` as an object file for `arch`.
+pub(crate) fn write(obj: &Object, arch: Arch) -> Vec<u8> {
+    let wide = arch == Arch::X86_64;
+    let rela = wide;
+    let word = if wide { 8 } else { 4 };
+    let mut buf = Buf { bytes: Vec::new(), wide };
+    let mut shstrtab = StrTab::new();
+    let mut headers = Vec::new();
+
+    // Locals come first in the symbol table, `sh_info` of which is the index
+    // of the first global.
+    let mut order: Vec<usize> = (0..obj.symbols.len()).collect();
+    order.sort_by_key(|&i| obj.symbols[i].global);
+    let mut index = vec![0; obj.symbols.len()];
+    for (n, &i) in order.iter().enumerate() {
+        index[i] = n as u32 + 1;
+    }
+    let first_global =
+        1 + order.iter().filter(|&&i| !obj.symbols[i].global).count() as u32;
+
+    buf.bytes.resize(if wide { 64 } else { 52 }, 0);
+    for sec in &obj.sections {
+        let mut data = sec.data.clone();
+        if !rela {
+            // `SHT_REL` keeps the addends in the places relocated.
+            for r in &sec.relocs {
+                let at = r.offset as usize..r.offset as usize + 4;
+                let v =
+                    i32::from_le_bytes(data[at.clone()].try_into().unwrap());
+                data[at].copy_from_slice(&(v + r.addend as i32).to_le_bytes());
+            }
+        }
+        let offset = buf.bytes.len() as u64;
+        if sec.kind == SectionKind::Progbits {
+            buf.bytes.extend(&data);
+        }
+        headers.push(Header {
+            name: shstrtab.add(&sec.name),
+            kind: match sec.kind {
+                SectionKind::Progbits => SHT_PROGBITS,
+                SectionKind::Nobits => SHT_NOBITS,
+            },
+            flags: sec.flags,
+            offset,
+            size: data.len() as u64,
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+        });
+    }
+
+    let mut strtab = StrTab::new();
+    buf.align(word);
+    let symtab_offset = buf.bytes.len() as u64;
+    let symtab = obj.sections.len() as u32 + 1;
+    let sym_size = if wide { 24 } else { 16 };
+    buf.bytes.resize(buf.bytes.len() + sym_size, 0);
+    for &i in &order {
+        let sym = &obj.symbols[i];
+        let name = strtab.add(&sym.name);
+        let bind = if sym.global { STB_GLOBAL } else { STB_LOCAL };
+        let kind = if sym.func { STT_FUNC } else { STT_NOTYPE };
+        let (shndx, value) = match sym.def {
+            Some((sec, off)) => (sec as u16 + 1, off),
+            None => (0, 0),
+        };
+        buf.u32(name);
+        if wide {
+            buf.u8(bind << 4 | kind);
+            buf.u8(0);
+            buf.u16(shndx);
+            buf.word(value);
+            buf.word(0);
+        } else {
+            buf.word(value);
+            buf.word(0);
+            buf.u8(bind << 4 | kind);
+            buf.u8(0);
+            buf.u16(shndx);
+        }
+    }
+    headers.push(Header {
+        name: shstrtab.add(".symtab"),
+        kind: SHT_SYMTAB,
+        flags: 0,
+        offset: symtab_offset,
+        size: buf.bytes.len() as u64 - symtab_offset,
+        link: symtab + 1,
+        info: first_global,
+        align: word as u64,
+        entsize: sym_size as u64,
+    });
+
+    let offset = buf.bytes.len() as u64;
+    buf.bytes.extend(&strtab.0);
+    headers.push(Header {
+        name: shstrtab.add(".strtab"),
+        kind: SHT_STRTAB,
+        flags: 0,
+        offset,
+        size: strtab.0.len() as u64,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+
+    for (s, sec) in obj.sections.iter().enumerate() {
+        if sec.relocs.is_empty() {
+            continue;
+        }
+        buf.align(word);
+        let offset = buf.bytes.len() as u64;
+        for r in &sec.relocs {
+            let sym = index[r.sym] as u64;
+            buf.word(r.offset);
+            if wide {
+                buf.word(sym << 32 | r.kind as u64);
+                buf.word(r.addend as u64);
+            } else {
+                buf.word(sym << 8 | r.kind as u64);
+            }
+        }
+        let prefix = if rela { ".rela" } else { ".rel" };
+        headers.push(Header {
+            name: shstrtab.add(&format!("{}{}", prefix, sec.name)),
+            kind: if rela { SHT_RELA } else { SHT_REL },
+            flags: SHF_INFO_LINK,
+            offset,
+            size: buf.bytes.len() as u64 - offset,
+            link: symtab,
+            info: s as u32 + 1,
+            align: word as u64,
+            entsize: if rela { 24 } else { 8 },
+        });
+    }
+
+    let offset = buf.bytes.len() as u64;
+    let name = shstrtab.add(".shstrtab");
+    buf.bytes.extend(&shstrtab.0);
+    headers.push(Header {
+        name,
+        kind: SHT_STRTAB,
+        flags: 0,
+        offset,
+        size: shstrtab.0.len() as u64,
+        link: 0,
+        info: 0,
+        align: 1,
+        entsize: 0,
+    });
+
+    buf.align(word);
+    let shoff = buf.bytes.len() as u64;
+    buf.bytes.resize(buf.bytes.len() + if wide { 64 } else { 40 }, 0);
+    for h in &headers {
+        buf.u32(h.name);
+        buf.u32(h.kind);
+        buf.word(h.flags);
+        buf.word(0);
+        buf.word(h.offset);
+        buf.word(h.size);
+        buf.u32(h.link);
+        buf.u32(h.info);
+        buf.word(h.align);
+        buf.word(h.entsize);
+    }
+
+    let shnum = headers.len() as u16 + 1;
+    let mut head = Buf { bytes: Vec::new(), wide };
+    head.bytes.extend(b"\x7fELF");
+    head.u8(if wide { 2 } else { 1 });
+    // Little endian, version 1, System V ABI.
+    head.bytes.extend([1, 1, 0]);
+    head.bytes.resize(16, 0);
+    // `ET_REL`
+    head.u16(1);
+    head.u16(match arch {
+        Arch::I386 => 3,
+        Arch::X86_64 => 62,
+    });
+    head.u32(1);
+    head.word(0);
+    head.word(0);
+    head.word(shoff);
+    head.u32(0);
+    head.u16(head.bytes.len() as u16 + 12);
+    head.u16(0);
+    head.u16(0);
+    head.u16(if wide { 64 } else { 40 });
+    head.u16(shnum);
+    head.u16(shnum - 1);
+    buf.bytes[..head.bytes.len()].copy_from_slice(&head.bytes);
+    buf.bytes
+}
@@ -0,0 +1,98 @@
+//! Assembler turning the x86 assembly `minicc_gen` writes into ELF
+//! relocatable object files, so that compiling to objects needs no
+//! binutils.
+//!
+//! It reads GNU assembler syntax, AT&T for the instructions, and encodes
+//! them as GNU `as` does without optimisation, jumps within a section
+//! taking the short form when the target is near enough. Calls to global
+//! symbols and references to symbols other than local labels become
+//! relocations.
+
+mod elf;
+mod x86;
+
+use std::fmt::{self, Display, Formatter};
+
+/// Instruction set, which also decides the ELF class and relocations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    /// ELF32 with `R_386_*` relocations.
+    I386,
+    /// ELF64 with `R_X86_64_*` relocations.
+    X86_64,
+}
+
+impl Arch {
+    /// Instruction set of the target of `minicc_gen` named `target`, if one
+    /// can be assembled.
+    pub fn for_target(target: &str) -> Option<Self> {
+        match target.split('-').next() {
+            Some("i386") => Some(Arch::I386),
+            Some("x86_64") => Some(Arch::X86_64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Assembles `src` for `arch` into the bytes of an ELF relocatable object.
+pub fn assemble(src: &str, arch: Arch) -> Result<Vec<u8>, AsmError> {
+    let obj = x86::assemble(src, arch)?;
+    Ok(elf::write(&obj, arch))
+}
+
+/// Assembled sections and symbols, before they are laid out in a file.
+#[derive(Debug, Default)]
+struct Object {
+    sections: Vec<Section>,
+    symbols: Vec<Sym>,
+}
+
+#[derive(Debug)]
+struct Section {
+    name: String,
+    kind: SectionKind,
+    /// `SHF_*` flags.
+    flags: u64,
+    data: Vec<u8>,
+    relocs: Vec<Reloc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SectionKind {
+    Progbits,
+    Nobits,
+}
+
+/// Defined or referenced symbol.
+#[derive(Debug)]
+struct Sym {
+    name: String,
+    /// Section index and offset, `None` if undefined.
+    def: Option<(usize, u64)>,
+    global: bool,
+    func: bool,
+}
+
+#[derive(Debug)]
+struct Reloc {
+    offset: u64,
+    /// Index into [`Object::symbols`].
+    sym: usize,
+    /// `R_*` type of the machine.
+    kind: u32,
+    addend: i64,
+}
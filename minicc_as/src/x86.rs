@@ -0,0 +1,1296 @@
+//! x86 instructions in AT&T syntax and the directives `minicc_gen` uses.
+//!
+//! Each statement becomes an item of its section: the bytes of an
+//! instruction, a label, or a branch whose size is left open. Jumps to
+//! labels of their section start short and are made long while their target
+//! is out of reach, then the sections are laid out and the references to
+//! other symbols become relocations.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{Arch, AsmError, Object, Reloc, Section, SectionKind, Sym};
+
+const R_386_32: u32 = 1;
+const R_386_PC32: u32 = 2;
+const R_386_PLT32: u32 = 4;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
+const R_X86_64_GOTPCRELX: u32 = 41;
+const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+const SHF_WRITE: u64 = 1;
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+
+/// Operand size, named by the suffix of mnemonics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Size {
+    B,
+    W,
+    L,
+    Q,
+}
+
+impl Size {
+    fn from_suffix(c: char) -> Option<Self> {
+        match c {
+            'b' => Some(Size::B),
+            'w' => Some(Size::W),
+            'l' => Some(Size::L),
+            'q' => Some(Size::Q),
+            _ => None,
+        }
+    }
+
+    fn bytes(self) -> usize {
+        match self {
+            Size::B => 1,
+            Size::W => 2,
+            Size::L => 4,
+            Size::Q => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Reg {
+    num: u8,
+    size: Size,
+}
+
+impl Reg {
+    /// Whether the register needs a REX prefix, `%spl` to `%dil` among the
+    /// byte registers.
+    fn needs_rex(self) -> bool {
+        self.num >= 8 || self.size == Size::B && self.num >= 4
+    }
+}
+
+/// Symbol with its `@` modifier, like `dbg@PLT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SymRef {
+    name: String,
+    modifier: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Imm {
+    Num(i64),
+    Sym(SymRef),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Base {
+    None,
+    Reg(Reg),
+    Rip,
+}
+
+/// `disp(base)`, the displacement a number or a symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Mem {
+    disp: Imm,
+    base: Base,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Opnd {
+    Reg(Reg),
+    Imm(Imm),
+    Mem(Mem),
+    /// Target of a direct branch.
+    Sym(SymRef),
+    /// Target of an indirect branch, `*%reg` or `*mem`.
+    Indirect(Box<Opnd>),
+}
+
+/// Register or memory operand, encoded in the ModRM byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rm {
+    Reg(Reg),
+    Mem(Mem),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Branch {
+    Jmp,
+    Jcc(u8),
+    Call,
+}
+
+/// Relocation of the bytes of an instruction.
+#[derive(Debug)]
+struct Fixup {
+    offset: usize,
+    sym: String,
+    kind: u32,
+    addend: i64,
+}
+
+#[derive(Debug)]
+enum Item {
+    Bytes { data: Vec<u8>, fixups: Vec<Fixup> },
+    Label(String),
+    Branch { op: Branch, target: SymRef, line: usize },
+}
+
+#[derive(Debug)]
+struct Sec {
+    name: String,
+    kind: SectionKind,
+    flags: u64,
+    items: Vec<Item>,
+}
+
+struct Asm {
+    arch: Arch,
+    line: usize,
+    secs: Vec<Sec>,
+    cur: usize,
+    /// Section of each label.
+    labels: HashMap<String, usize>,
+    globals: Vec<String>,
+    funcs: HashSet<String>,
+}
+
+pub(crate) fn assemble(src: &str, arch: Arch) -> Result<Object, AsmError> {
+    let mut a = Asm {
+        arch,
+        line: 0,
+        secs: Vec::new(),
+        cur: 0,
+        labels: HashMap::new(),
+        globals: Vec::new(),
+        funcs: HashSet::new(),
+    };
+    a.section(".text", None, None)?;
+    for (n, line) in src.lines().enumerate() {
+        a.line = n + 1;
+        a.statement(line)?;
+    }
+    a.finish()
+}
+
+impl Asm {
+    fn err<T>(&self, msg: impl Into<String>) -> Result<T, AsmError> {
+        Err(AsmError { line: self.line, msg: msg.into() })
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), AsmError> {
+        let mut s = strip_comment(line).trim();
+        while let Some((label, rest)) = s.split_once(':') {
+            if !is_symbol(label.trim()) {
+                break;
+            }
+            self.label(label.trim())?;
+            s = rest.trim();
+        }
+        if s.is_empty() {
+            return Ok(());
+        }
+
+        let (op, args) = match s.split_once(char::is_whitespace) {
+            Some((op, args)) => (op, split_args(args.trim())),
+            None => (s, Vec::new()),
+        };
+        if op.starts_with('.') {
+            self.directive(op, &args)
+        } else {
+            let opnds = args
+                .iter()
+                .map(|a| self.opnd(a))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.inst(op, &opnds)
+        }
+    }
+
+    fn label(&mut self, name: &str) -> Result<(), AsmError> {
+        if self.labels.insert(name.to_string(), self.cur).is_some() {
+            return self.err(format!("symbol `{}` is already defined", name));
+        }
+        self.secs[self.cur].items.push(Item::Label(name.to_string()));
+        Ok(())
+    }
+
+    fn directive(&mut self, op: &str, args: &[&str]) -> Result<(), AsmError> {
+        match (op, args) {
+            (".text" | ".data" | ".bss", []) => self.section(op, None, None),
+            (".section", [name, rest @ ..]) => {
+                let flags = rest.first().map(|f| f.trim_matches('"'));
+                self.section(name, flags, rest.get(1).copied())
+            }
+            (".globl" | ".global", [name]) if is_symbol(name) => {
+                self.globals.push(name.to_string());
+                Ok(())
+            }
+            (".type", [name, ty]) if is_symbol(name) => {
+                match ty.get(1..).unwrap_or_default() {
+                    "function" => {
+                        self.funcs.insert(name.to_string());
+                    }
+                    "object" | "notype" => {}
+                    _ => return self.err(format!("unknown type `{}`", ty)),
+                }
+                Ok(())
+            }
+            _ => self.err(format!("invalid directive `{}`", op)),
+        }
+    }
+
+    /// Switches to section `name`, with `flags` and `ty` as in `.section`,
+    /// defaulting for the usual sections.
+    fn section(
+        &mut self,
+        name: &str,
+        flags: Option<&str>,
+        ty: Option<&str>,
+    ) -> Result<(), AsmError> {
+        let flags = match flags {
+            Some(flags) => {
+                let mut f = 0;
+                for c in flags.chars() {
+                    f |= match c {
+                        'a' => SHF_ALLOC,
+                        'w' => SHF_WRITE,
+                        'x' => SHF_EXECINSTR,
+                        _ => return self.err(format!("unknown flag `{}`", c)),
+                    };
+                }
+                f
+            }
+            None if name == ".text" || name.starts_with(".text.") => {
+                SHF_ALLOC | SHF_EXECINSTR
+            }
+            None if name.starts_with(".rodata") => SHF_ALLOC,
+            None if name.starts_with(".data") || name.starts_with(".bss") => {
+                SHF_ALLOC | SHF_WRITE
+            }
+            None => 0,
+        };
+        let kind = match ty.map(|t| t.get(1..).unwrap_or_default()) {
+            Some("progbits") => SectionKind::Progbits,
+            Some("nobits") => SectionKind::Nobits,
+            None if name.starts_with(".bss") => SectionKind::Nobits,
+            None => SectionKind::Progbits,
+            Some(_) => {
+                return self.err(format!("unknown section type `{:?}`", ty))
+            }
+        };
+
+        self.cur = match self.secs.iter().position(|s| s.name == name) {
+            Some(i) => i,
+            None => {
+                self.secs.push(Sec {
+                    name: name.to_string(),
+                    kind,
+                    flags,
+                    items: Vec::new(),
+                });
+                self.secs.len() - 1
+            }
+        };
+        Ok(())
+    }
+
+    fn opnd(&self, s: &str) -> Result<Opnd, AsmError> {
+        if let Some(r) = s.strip_prefix('*') {
+            return Ok(Opnd::Indirect(Box::new(self.opnd(r)?)));
+        }
+        if let Some(r) = s.strip_prefix('%') {
+            return Ok(Opnd::Reg(self.reg(r)?));
+        }
+        if let Some(imm) = s.strip_prefix('$') {
+            return Ok(Opnd::Imm(self.imm(imm)?));
+        }
+        if let Some((disp, base)) = s.split_once('(') {
+            let Some(base) = base.strip_suffix(')') else {
+                return self.err(format!("invalid operand `{}`", s));
+            };
+            let base = match base.trim() {
+                "%rip" if self.arch == Arch::X86_64 => Base::Rip,
+                b if b.contains(',') => {
+                    return self.err("index registers are not supported");
+                }
+                b => match b.strip_prefix('%') {
+                    Some(r) => Base::Reg(self.reg(r)?),
+                    None => return self.err(format!("invalid base `{}`", b)),
+                },
+            };
+            if matches!(base, Base::Reg(r) if r.size != self.word()) {
+                return self.err(format!("invalid base in `{}`", s));
+            }
+            let disp =
+                if disp.is_empty() { Imm::Num(0) } else { self.imm(disp)? };
+            return Ok(Opnd::Mem(Mem { disp, base }));
+        }
+        match self.imm(s)? {
+            Imm::Sym(sym) => Ok(Opnd::Sym(sym)),
+            disp => Ok(Opnd::Mem(Mem { disp, base: Base::None })),
+        }
+    }
+
+    fn imm(&self, s: &str) -> Result<Imm, AsmError> {
+        if let Some(n) = parse_num(s) {
+            return Ok(Imm::Num(n));
+        }
+        let (name, modifier) = match s.split_once('@') {
+            Some((name, m)) => (name, Some(m.to_string())),
+            None => (s, None),
+        };
+        if !is_symbol(name) {
+            return self.err(format!("invalid expression `{}`", s));
+        }
+        Ok(Imm::Sym(SymRef { name: name.to_string(), modifier }))
+    }
+
+    fn reg(&self, name: &str) -> Result<Reg, AsmError> {
+        let Some(r) = reg(name) else {
+            return self.err(format!("unknown register `%{}`", name));
+        };
+        if self.arch == Arch::I386 && (r.needs_rex() || r.size == Size::Q) {
+            return self.err(format!("`%{}` is not available on i386", name));
+        }
+        Ok(r)
+    }
+
+    fn word(&self) -> Size {
+        match self.arch {
+            Arch::I386 => Size::L,
+            Arch::X86_64 => Size::Q,
+        }
+    }
+
+    fn inst(&mut self, op: &str, opnds: &[Opnd]) -> Result<(), AsmError> {
+        let (op, suffix) = split_mnemonic(op);
+        let mut e = Enc::default();
+
+        if let Some((op, cc)) = cc_op(op) {
+            match (op, opnds) {
+                ("j", [Opnd::Sym(target)]) => {
+                    return self.branch(Branch::Jcc(cc), target.clone());
+                }
+                ("set", [rm]) => {
+                    let rm = self.rm(rm)?;
+                    self.sized(Some(Size::B), &[&rm])?;
+                    self.modrm(&mut e, Size::L, &[0x0f, 0x90 | cc], 0, &rm, 0)?;
+                }
+                ("cmov", [src, Opnd::Reg(dst)]) => {
+                    let src = self.rm(src)?;
+                    let size = self.sized(suffix, &[&src, &Rm::Reg(*dst)])?;
+                    self.modrm(
+                        &mut e,
+                        size,
+                        &[0x0f, 0x40 | cc],
+                        dst.num,
+                        &src,
+                        0,
+                    )?;
+                }
+                _ => return self.invalid(op, opnds),
+            }
+            return self.push(e);
+        }
+
+        match (op, opnds) {
+            ("mov", [Opnd::Imm(imm), Opnd::Reg(dst)]) => {
+                let size = self.sized(suffix, &[&Rm::Reg(*dst)])?;
+                match (size, imm) {
+                    (Size::Q, Imm::Num(n)) if *n != *n as i32 as i64 => {
+                        self.rex(&mut e, true, 0, dst.num, false)?;
+                        e.data.push(0xb8 | dst.num & 7);
+                        e.data.extend(n.to_le_bytes());
+                    }
+                    (Size::Q, _) => {
+                        let rm = Rm::Reg(*dst);
+                        self.modrm(&mut e, size, &[0xc7], 0, &rm, 4)?;
+                        self.imm_bytes(&mut e, imm, size, 4)?;
+                    }
+                    _ => {
+                        if size == Size::W {
+                            e.data.push(0x66);
+                        }
+                        self.rex(&mut e, false, 0, dst.num, dst.needs_rex())?;
+                        let op = if size == Size::B { 0xb0 } else { 0xb8 };
+                        e.data.push(op | dst.num & 7);
+                        let n = size.bytes();
+                        self.imm_bytes(&mut e, imm, size, n)?;
+                    }
+                }
+            }
+            ("mov", [Opnd::Imm(imm), Opnd::Mem(dst)]) => {
+                let Some(size) = suffix else {
+                    return self.err("no size for `mov`");
+                };
+                let n = size.bytes().min(4);
+                let rm = Rm::Mem(dst.clone());
+                let op = if size == Size::B { 0xc6 } else { 0xc7 };
+                self.modrm(&mut e, size, &[op], 0, &rm, n)?;
+                self.imm_bytes(&mut e, imm, size, n)?;
+            }
+            ("mov", [src, dst]) => {
+                self.rm_reg(&mut e, suffix, 0x88, src, dst)?
+            }
+            ("movabs", [Opnd::Imm(Imm::Num(n)), Opnd::Reg(dst)])
+                if dst.size == Size::Q =>
+            {
+                self.rex(&mut e, true, 0, dst.num, false)?;
+                e.data.push(0xb8 | dst.num & 7);
+                e.data.extend(n.to_le_bytes());
+            }
+            ("lea", [Opnd::Mem(src), Opnd::Reg(dst)]) => {
+                let size = self.sized(suffix, &[&Rm::Reg(*dst)])?;
+                let rm = Rm::Mem(src.clone());
+                self.modrm(&mut e, size, &[0x8d], dst.num, &rm, 0)?;
+            }
+            (op, [src, Opnd::Reg(dst)]) if op.starts_with("movz") => {
+                let from = match op {
+                    "movzb" => Size::B,
+                    "movzw" => Size::W,
+                    _ => return self.invalid(op, opnds),
+                };
+                let src = self.rm(src)?;
+                self.sized(Some(from), &[&src])?;
+                let size = self.sized(suffix, &[&Rm::Reg(*dst)])?;
+                let op = if from == Size::B { 0xb6 } else { 0xb7 };
+                self.modrm(&mut e, size, &[0x0f, op], dst.num, &src, 0)?;
+            }
+            (op, [Opnd::Imm(imm), dst]) if alu(op).is_some() => {
+                let ext = alu(op).unwrap();
+                let dst = self.rm(dst)?;
+                let size = self.sized(suffix, &[&dst])?;
+                let acc = matches!(dst, Rm::Reg(Reg { num: 0, .. }));
+                match imm {
+                    Imm::Num(n) if size == Size::B && acc => {
+                        e.data.push(ext << 3 | 4);
+                        e.data.push(*n as u8);
+                    }
+                    _ if size == Size::B => {
+                        self.modrm(&mut e, size, &[0x80], ext, &dst, 1)?;
+                        self.imm_bytes(&mut e, imm, size, 1)?;
+                    }
+                    Imm::Num(n) if *n == *n as i8 as i64 => {
+                        self.modrm(&mut e, size, &[0x83], ext, &dst, 1)?;
+                        e.data.push(*n as u8);
+                    }
+                    _ => {
+                        let n = size.bytes().min(4);
+                        if acc {
+                            self.prefixes(&mut e, size, 0, 0, false)?;
+                            e.data.push(ext << 3 | 5);
+                        } else {
+                            self.modrm(&mut e, size, &[0x81], ext, &dst, n)?;
+                        }
+                        self.imm_bytes(&mut e, imm, size, n)?;
+                    }
+                }
+            }
+            (op, [src, dst]) if alu(op).is_some() => {
+                let base = alu(op).unwrap() << 3;
+                self.rm_reg(&mut e, suffix, base, src, dst)?;
+            }
+            ("test", [Opnd::Imm(imm), dst]) => {
+                let dst = self.rm(dst)?;
+                let size = self.sized(suffix, &[&dst])?;
+                let n = size.bytes().min(4);
+                if matches!(dst, Rm::Reg(Reg { num: 0, .. })) {
+                    self.prefixes(&mut e, size, 0, 0, false)?;
+                    e.data.push(if size == Size::B { 0xa8 } else { 0xa9 });
+                } else {
+                    let op = if size == Size::B { 0xf6 } else { 0xf7 };
+                    self.modrm(&mut e, size, &[op], 0, &dst, n)?;
+                }
+                self.imm_bytes(&mut e, imm, size, n)?;
+            }
+            ("test", [src @ Opnd::Reg(_), dst]) => {
+                self.rm_reg(&mut e, suffix, 0x84, src, dst)?
+            }
+            ("imul", [Opnd::Imm(imm), Opnd::Reg(dst)]) => {
+                let rm = Rm::Reg(*dst);
+                let size = self.sized(suffix, &[&rm])?;
+                match imm {
+                    Imm::Num(n) if *n == *n as i8 as i64 => {
+                        self.modrm(&mut e, size, &[0x6b], dst.num, &rm, 1)?;
+                        e.data.push(*n as u8);
+                    }
+                    _ => {
+                        let n = size.bytes().min(4);
+                        self.modrm(&mut e, size, &[0x69], dst.num, &rm, n)?;
+                        self.imm_bytes(&mut e, imm, size, n)?;
+                    }
+                }
+            }
+            ("imul", [src, Opnd::Reg(dst)]) => {
+                let src = self.rm(src)?;
+                let size = self.sized(suffix, &[&src, &Rm::Reg(*dst)])?;
+                self.modrm(&mut e, size, &[0x0f, 0xaf], dst.num, &src, 0)?;
+            }
+            (op, [Opnd::Imm(Imm::Num(n)), dst]) if shift(op).is_some() => {
+                let ext = shift(op).unwrap();
+                let dst = self.rm(dst)?;
+                let size = self.sized(suffix, &[&dst])?;
+                let byte = size == Size::B;
+                if *n == 1 {
+                    let op = if byte { 0xd0 } else { 0xd1 };
+                    self.modrm(&mut e, size, &[op], ext, &dst, 0)?;
+                } else {
+                    let op = if byte { 0xc0 } else { 0xc1 };
+                    self.modrm(&mut e, size, &[op], ext, &dst, 1)?;
+                    e.data.push(*n as u8);
+                }
+            }
+            (op, [Opnd::Reg(Reg { num: 1, size: Size::B }), dst])
+                if shift(op).is_some() =>
+            {
+                let ext = shift(op).unwrap();
+                let dst = self.rm(dst)?;
+                let size = self.sized(suffix, &[&dst])?;
+                let op = if size == Size::B { 0xd2 } else { 0xd3 };
+                self.modrm(&mut e, size, &[op], ext, &dst, 0)?;
+            }
+            (op @ ("inc" | "dec"), [Opnd::Reg(r)])
+                if self.arch == Arch::I386 && r.size != Size::B =>
+            {
+                // The one-byte forms, which are REX prefixes on x86-64.
+                if self.sized(suffix, &[&Rm::Reg(*r)])? == Size::W {
+                    e.data.push(0x66);
+                }
+                e.data.push(if op == "inc" { 0x40 } else { 0x48 } | r.num);
+            }
+            (op, [src]) if unary(op).is_some() => {
+                let (op, ext) = unary(op).unwrap();
+                let src = self.rm(src)?;
+                let size = self.sized(suffix, &[&src])?;
+                let op = if size == Size::B { op } else { op | 1 };
+                self.modrm(&mut e, size, &[op], ext, &src, 0)?;
+            }
+            ("push", [Opnd::Reg(r)]) => {
+                self.sized(suffix.or(Some(self.word())), &[&Rm::Reg(*r)])?;
+                self.rex(&mut e, false, 0, r.num, false)?;
+                e.data.push(0x50 | r.num & 7);
+            }
+            ("push", [Opnd::Imm(Imm::Num(n))]) if *n == *n as i8 as i64 => {
+                e.data.extend([0x6a, *n as u8]);
+            }
+            ("push", [Opnd::Imm(imm)]) => {
+                e.data.push(0x68);
+                self.imm_bytes(&mut e, imm, self.word(), 4)?;
+            }
+            ("push", [Opnd::Mem(m)]) => {
+                let rm = Rm::Mem(m.clone());
+                self.modrm(&mut e, Size::L, &[0xff], 6, &rm, 0)?;
+            }
+            ("pop", [Opnd::Reg(r)]) => {
+                self.sized(suffix.or(Some(self.word())), &[&Rm::Reg(*r)])?;
+                self.rex(&mut e, false, 0, r.num, false)?;
+                e.data.push(0x58 | r.num & 7);
+            }
+            ("pop", [Opnd::Mem(m)]) => {
+                let rm = Rm::Mem(m.clone());
+                self.modrm(&mut e, Size::L, &[0x8f], 0, &rm, 0)?;
+            }
+            ("call" | "jmp", [Opnd::Sym(target)]) => {
+                let op = if op == "call" { Branch::Call } else { Branch::Jmp };
+                return self.branch(op, target.clone());
+            }
+            ("call" | "jmp", [Opnd::Indirect(target)]) => {
+                let rm = self.rm(target)?;
+                if let Rm::Reg(r) = rm {
+                    self.sized(Some(self.word()), &[&Rm::Reg(r)])?;
+                }
+                let ext = if op == "call" { 2 } else { 4 };
+                self.modrm(&mut e, Size::L, &[0xff], ext, &rm, 0)?;
+            }
+            ("int", [Opnd::Imm(Imm::Num(n))]) => {
+                e.data.extend([0xcd, *n as u8])
+            }
+            ("cltd" | "cdq", []) => e.data.push(0x99),
+            ("cqto" | "cqo", []) => e.data.extend([0x48, 0x99]),
+            ("cltq" | "cdqe", []) => e.data.extend([0x48, 0x98]),
+            ("cwtl" | "cwde", []) => e.data.push(0x98),
+            ("ret", []) => e.data.push(0xc3),
+            ("leave", []) => e.data.push(0xc9),
+            ("hlt", []) => e.data.push(0xf4),
+            ("nop", []) => e.data.push(0x90),
+            ("ud2", []) => e.data.extend([0x0f, 0x0b]),
+            ("syscall", []) => e.data.extend([0x0f, 0x05]),
+            _ => return self.invalid(op, opnds),
+        }
+        if self.arch == Arch::I386 && matches!(op, "cqto" | "cqo" | "cltq") {
+            return self.err(format!("`{}` is not available on i386", op));
+        }
+        self.push(e)
+    }
+
+    fn invalid<T>(&self, op: &str, opnds: &[Opnd]) -> Result<T, AsmError> {
+        match opnds.is_empty() {
+            true => self.err(format!("invalid instruction `{}`", op)),
+            false => self.err(format!("invalid operands for `{}`", op)),
+        }
+    }
+
+    fn push(&mut self, e: Enc) -> Result<(), AsmError> {
+        let item = Item::Bytes { data: e.data, fixups: e.fixups };
+        self.secs[self.cur].items.push(item);
+        Ok(())
+    }
+
+    fn branch(&mut self, op: Branch, target: SymRef) -> Result<(), AsmError> {
+        if let Some(m) = target.modifier.as_deref().filter(|m| *m != "PLT") {
+            return self.err(format!("invalid modifier `@{}`", m));
+        }
+        let line = self.line;
+        let item = Item::Branch { op, target, line };
+        self.secs[self.cur].items.push(item);
+        Ok(())
+    }
+
+    fn rm(&self, opnd: &Opnd) -> Result<Rm, AsmError> {
+        match opnd {
+            Opnd::Reg(r) => Ok(Rm::Reg(*r)),
+            Opnd::Mem(m) => Ok(Rm::Mem(m.clone())),
+            // An absolute address.
+            Opnd::Sym(s) => {
+                Ok(Rm::Mem(Mem { disp: Imm::Sym(s.clone()), base: Base::None }))
+            }
+            _ => self.err("expected a register or memory operand"),
+        }
+    }
+
+    /// Size of an operation on `opnds`, from `suffix` or from the registers
+    /// among them, which must agree.
+    fn sized(
+        &self,
+        suffix: Option<Size>,
+        opnds: &[&Rm],
+    ) -> Result<Size, AsmError> {
+        let mut size = suffix;
+        for opnd in opnds {
+            if let Rm::Reg(r) = opnd {
+                match size {
+                    Some(s) if s != r.size => {
+                        return self.err("operand size mismatch");
+                    }
+                    _ => size = Some(r.size),
+                }
+            }
+        }
+        match size {
+            Some(Size::Q) if self.arch == Arch::I386 => {
+                self.err("64-bit operations are not available on i386")
+            }
+            Some(size) => Ok(size),
+            None => self.err("no size for the operation"),
+        }
+    }
+
+    /// `op src, dst` between a register and a register or memory, `op` being
+    /// the opcode of the byte form storing to the register or memory.
+    fn rm_reg(
+        &self,
+        e: &mut Enc,
+        suffix: Option<Size>,
+        op: u8,
+        src: &Opnd,
+        dst: &Opnd,
+    ) -> Result<(), AsmError> {
+        let (reg, rm, op) = match (src, dst) {
+            (Opnd::Reg(src), dst) => (*src, self.rm(dst)?, op),
+            (src, Opnd::Reg(dst)) => (*dst, self.rm(src)?, op | 2),
+            _ => return self.err("invalid operands"),
+        };
+        let size = self.sized(suffix, &[&Rm::Reg(reg), &rm])?;
+        let op = if size == Size::B { op } else { op | 1 };
+        e.force_rex = reg.size == Size::B && reg.needs_rex();
+        self.modrm(e, size, &[op], reg.num, &rm, 0)
+    }
+
+    /// Operand size and REX prefixes for `size` with `reg` in the reg field
+    /// and `b` in the base or r/m field. Returns whether there is a REX.
+    fn prefixes(
+        &self,
+        e: &mut Enc,
+        size: Size,
+        reg: u8,
+        b: u8,
+        force: bool,
+    ) -> Result<bool, AsmError> {
+        if size == Size::W {
+            e.data.push(0x66);
+        }
+        self.rex(e, size == Size::Q, reg, b, force)
+    }
+
+    /// REX prefix, if `w`, an extended register or `force` needs one.
+    fn rex(
+        &self,
+        e: &mut Enc,
+        w: bool,
+        reg: u8,
+        b: u8,
+        force: bool,
+    ) -> Result<bool, AsmError> {
+        let rex = (w as u8) << 3 | (reg >> 3) << 2 | b >> 3;
+        if rex == 0 && !force && !std::mem::take(&mut e.force_rex) {
+            return Ok(false);
+        }
+        if self.arch == Arch::I386 {
+            return self.err("instruction is not available on i386");
+        }
+        e.data.push(0x40 | rex);
+        Ok(true)
+    }
+
+    /// Prefixes, `op` and the ModRM, SIB and displacement of `reg` and `rm`,
+    /// followed by `imm` bytes of immediate.
+    fn modrm(
+        &self,
+        e: &mut Enc,
+        size: Size,
+        op: &[u8],
+        reg: u8,
+        rm: &Rm,
+        imm: usize,
+    ) -> Result<(), AsmError> {
+        let b = match rm {
+            Rm::Reg(r) => r.num,
+            Rm::Mem(Mem { base: Base::Reg(r), .. }) => r.num,
+            Rm::Mem(_) => 0,
+        };
+        let force =
+            matches!(rm, Rm::Reg(r) if r.size == Size::B && r.needs_rex());
+        let rex = self.prefixes(e, size, reg, b, force)?;
+        e.data.extend(op);
+
+        let reg = (reg & 7) << 3;
+        let mem = match rm {
+            Rm::Reg(r) => {
+                e.data.push(0xc0 | reg | r.num & 7);
+                return Ok(());
+            }
+            Rm::Mem(m) => m,
+        };
+        match (&mem.base, &mem.disp) {
+            (Base::Rip, disp) => {
+                e.data.push(reg | 5);
+                let kind = match disp {
+                    Imm::Sym(s)
+                        if s.modifier.as_deref() == Some("GOTPCREL") =>
+                    {
+                        match rex {
+                            true => R_X86_64_REX_GOTPCRELX,
+                            false => R_X86_64_GOTPCRELX,
+                        }
+                    }
+                    _ => R_X86_64_PC32,
+                };
+                self.disp32(e, disp, kind, -4 - imm as i64)?;
+            }
+            (Base::None, disp) => {
+                match self.arch {
+                    Arch::I386 => e.data.push(reg | 5),
+                    Arch::X86_64 => e.data.extend([reg | 4, 0x25]),
+                }
+                self.disp32(e, disp, self.abs32(Size::Q), 0)?;
+            }
+            (Base::Reg(base), disp) => {
+                let low = base.num & 7;
+                let md = match disp {
+                    Imm::Num(0) if low != 5 => 0x00,
+                    Imm::Num(n) if *n == *n as i8 as i64 => 0x40,
+                    _ => 0x80,
+                };
+                e.data.push(md | reg | low);
+                if low == 4 {
+                    e.data.push(0x24);
+                }
+                match (md, disp) {
+                    (0x00, _) => {}
+                    (0x40, Imm::Num(n)) => e.data.push(*n as u8),
+                    _ => self.disp32(e, disp, self.abs32(Size::Q), 0)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn disp32(
+        &self,
+        e: &mut Enc,
+        disp: &Imm,
+        kind: u32,
+        addend: i64,
+    ) -> Result<(), AsmError> {
+        match disp {
+            Imm::Num(n) if *n == *n as i32 as i64 => {
+                e.data.extend((*n as i32).to_le_bytes());
+                Ok(())
+            }
+            Imm::Num(n) => self.err(format!("displacement {} out of range", n)),
+            Imm::Sym(s) => {
+                let got = [R_X86_64_GOTPCRELX, R_X86_64_REX_GOTPCRELX];
+                if s.modifier.is_some() && !got.contains(&kind) {
+                    return self
+                        .err(format!("invalid modifier in `{}`", s.name));
+                }
+                self.fixup(e, s, kind, addend)
+            }
+        }
+    }
+
+    /// Four bytes relocated by `kind` against `sym`.
+    fn fixup(
+        &self,
+        e: &mut Enc,
+        sym: &SymRef,
+        kind: u32,
+        addend: i64,
+    ) -> Result<(), AsmError> {
+        if sym.name.starts_with(".L") {
+            return self.err(format!("cannot relocate against `{}`", sym.name));
+        }
+        e.fixups.push(Fixup {
+            offset: e.data.len(),
+            sym: sym.name.clone(),
+            kind,
+            addend,
+        });
+        e.data.extend([0; 4]);
+        Ok(())
+    }
+
+    /// Relocation of an absolute 32-bit field of an operation of `size`.
+    fn abs32(&self, size: Size) -> u32 {
+        match self.arch {
+            Arch::I386 => R_386_32,
+            Arch::X86_64 if size == Size::Q => R_X86_64_32S,
+            Arch::X86_64 => R_X86_64_32,
+        }
+    }
+
+    /// `n` bytes of immediate `imm` of an operation of `size`.
+    fn imm_bytes(
+        &self,
+        e: &mut Enc,
+        imm: &Imm,
+        size: Size,
+        n: usize,
+    ) -> Result<(), AsmError> {
+        match imm {
+            Imm::Num(v) => {
+                let bits = 8 * n as u32;
+                let fits = bits == 64
+                    || (*v >> (bits - 1) == 0 || *v >> (bits - 1) == -1)
+                    || size.bytes() == n && (*v as u64) >> bits == 0;
+                if !fits {
+                    return self.err(format!("immediate {} out of range", v));
+                }
+                e.data.extend(&v.to_le_bytes()[..n]);
+                Ok(())
+            }
+            Imm::Sym(s) if n == 4 && s.modifier.is_none() => {
+                self.fixup(e, s, self.abs32(size), 0)
+            }
+            Imm::Sym(s) => self.err(format!("invalid use of `{}`", s.name)),
+        }
+    }
+
+    /// Whether the branch `op` to `target` is resolved within section `sec`.
+    /// As with GNU `as`, calls to global symbols are relocated but jumps are
+    /// not.
+    fn local(&self, op: Branch, target: &SymRef, sec: usize) -> bool {
+        target.modifier.is_none()
+            && self.labels.get(&target.name) == Some(&sec)
+            && (op != Branch::Call || !self.globals.contains(&target.name))
+    }
+
+    fn finish(self) -> Result<Object, AsmError> {
+        let mut obj = Object::default();
+        let mut sym_index: HashMap<String, usize> = HashMap::new();
+        let mut offsets: HashMap<&str, u64> = HashMap::new();
+        let mut layouts = Vec::new();
+
+        for (s, sec) in self.secs.iter().enumerate() {
+            // Lengths of the items, grown until every branch reaches.
+            let mut long: Vec<bool> = sec
+                .items
+                .iter()
+                .map(|item| match item {
+                    Item::Branch { op: Branch::Call, .. } => true,
+                    Item::Branch { op, target, .. } => {
+                        !self.local(*op, target, s)
+                    }
+                    _ => false,
+                })
+                .collect();
+            let offs = loop {
+                let mut offs = Vec::new();
+                let mut off = 0;
+                let mut at = HashMap::new();
+                for (item, &long) in sec.items.iter().zip(&long) {
+                    offs.push(off);
+                    off += match item {
+                        Item::Bytes { data, .. } => data.len() as u64,
+                        Item::Label(name) => {
+                            at.insert(name.as_str(), off);
+                            0
+                        }
+                        Item::Branch { op, .. } => branch_len(*op, long),
+                    };
+                }
+                let mut grown = false;
+                for (i, item) in sec.items.iter().enumerate() {
+                    if let Item::Branch { op, target, .. } = item {
+                        if long[i] {
+                            continue;
+                        }
+                        let end = offs[i] + branch_len(*op, false);
+                        let disp = at[target.name.as_str()] as i64 - end as i64;
+                        if disp != disp as i8 as i64 {
+                            long[i] = true;
+                            grown = true;
+                        }
+                    }
+                }
+                if !grown {
+                    for (name, off) in at {
+                        offsets.insert(name, off);
+                    }
+                    break offs;
+                }
+            };
+            layouts.push((offs, long));
+        }
+
+        // Symbols in order of definition, then the undefined ones in order
+        // of reference. Local labels have none.
+        let mut defined: Vec<&String> =
+            self.labels.keys().filter(|name| !name.starts_with(".L")).collect();
+        defined.sort_by_key(|name| {
+            (self.labels[*name], offsets[name.as_str()], *name)
+        });
+        for name in defined {
+            sym_index.insert(name.clone(), obj.symbols.len());
+            obj.symbols.push(Sym {
+                name: name.clone(),
+                def: Some((self.labels[name], offsets[name.as_str()])),
+                global: self.globals.contains(name),
+                func: self.funcs.contains(name),
+            });
+        }
+        let mut index = |obj: &mut Object, name: &str| -> usize {
+            *sym_index.entry(name.to_string()).or_insert_with(|| {
+                obj.symbols.push(Sym {
+                    name: name.to_string(),
+                    def: None,
+                    global: true,
+                    func: false,
+                });
+                obj.symbols.len() - 1
+            })
+        };
+
+        for (s, sec) in self.secs.iter().enumerate() {
+            let (offs, long) = &layouts[s];
+            let mut data = Vec::new();
+            let mut relocs = Vec::new();
+            for (i, item) in sec.items.iter().enumerate() {
+                match item {
+                    Item::Label(_) => {}
+                    Item::Bytes { data: bytes, fixups } => {
+                        for f in fixups {
+                            relocs.push(Reloc {
+                                offset: offs[i] + f.offset as u64,
+                                sym: index(&mut obj, &f.sym),
+                                kind: f.kind,
+                                addend: f.addend,
+                            });
+                        }
+                        data.extend(bytes);
+                    }
+                    Item::Branch { op, target, line, .. } => {
+                        let long = long[i];
+                        match (op, long) {
+                            (Branch::Jmp, false) => data.push(0xeb),
+                            (Branch::Jmp, true) => data.push(0xe9),
+                            (Branch::Jcc(cc), false) => data.push(0x70 | cc),
+                            (Branch::Jcc(cc), true) => {
+                                data.extend([0x0f, 0x80 | cc])
+                            }
+                            (Branch::Call, _) => data.push(0xe8),
+                        }
+                        let end = offs[i] + branch_len(*op, long);
+                        if self.local(*op, target, s) {
+                            let disp = offsets[target.name.as_str()] as i64
+                                - end as i64;
+                            match long {
+                                true => {
+                                    data.extend((disp as i32).to_le_bytes())
+                                }
+                                false => data.push(disp as u8),
+                            }
+                            continue;
+                        }
+                        if target.name.starts_with(".L") {
+                            return Err(AsmError {
+                                line: *line,
+                                msg: format!(
+                                    "undefined label `{}`",
+                                    target.name
+                                ),
+                            });
+                        }
+                        let plt = target.modifier.is_some();
+                        let kind = match self.arch {
+                            Arch::I386 if plt => R_386_PLT32,
+                            Arch::I386 => R_386_PC32,
+                            Arch::X86_64 => R_X86_64_PLT32,
+                        };
+                        relocs.push(Reloc {
+                            offset: data.len() as u64,
+                            sym: index(&mut obj, &target.name),
+                            kind,
+                            addend: -4,
+                        });
+                        data.extend([0; 4]);
+                    }
+                }
+            }
+            obj.sections.push(Section {
+                name: sec.name.clone(),
+                kind: sec.kind,
+                flags: sec.flags,
+                data,
+                relocs,
+            });
+        }
+        for name in &self.globals {
+            index(&mut obj, name);
+        }
+        Ok(obj)
+    }
+}
+
+/// Bytes of an instruction being encoded.
+#[derive(Default)]
+struct Enc {
+    data: Vec<u8>,
+    fixups: Vec<Fixup>,
+    /// Whether a byte register in the reg field needs an empty REX prefix.
+    force_rex: bool,
+}
+
+fn branch_len(op: Branch, long: bool) -> u64 {
+    match (op, long) {
+        (Branch::Call, _) | (Branch::Jmp, true) => 5,
+        (Branch::Jcc(_), true) => 6,
+        (_, false) => 2,
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find('#') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn is_symbol(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || "_.$".contains(c))
+}
+
+/// Splits operands at the commas outside parentheses.
+fn split_args(s: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(s[start..].trim());
+    args
+}
+
+fn parse_num(s: &str) -> Option<i64> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let v = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None if s.starts_with(|c: char| c.is_ascii_digit()) => {
+            s.parse().ok()?
+        }
+        None => return None,
+    } as i64;
+    Some(if neg { v.wrapping_neg() } else { v })
+}
+
+fn reg(name: &str) -> Option<Reg> {
+    const NAMES: [&str; 8] = ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di"];
+    if let Some(n) = name.strip_prefix('r') {
+        let (num, size) = match n.find(|c: char| !c.is_ascii_digit()) {
+            Some(0) => {
+                let num = NAMES.iter().position(|r| *r == n)?;
+                return Some(Reg { num: num as u8, size: Size::Q });
+            }
+            Some(i) => {
+                let size = match &n[i..] {
+                    "b" => Size::B,
+                    "w" => Size::W,
+                    "d" => Size::L,
+                    _ => return None,
+                };
+                (&n[..i], size)
+            }
+            None => (n, Size::Q),
+        };
+        let num: u8 = num.parse().ok()?;
+        return (8..16).contains(&num).then_some(Reg { num, size });
+    }
+    if let Some(n) = name.strip_prefix('e') {
+        let num = NAMES.iter().position(|r| *r == n)?;
+        return Some(Reg { num: num as u8, size: Size::L });
+    }
+    if let Some(num) = NAMES.iter().position(|r| *r == name) {
+        return Some(Reg { num: num as u8, size: Size::W });
+    }
+    let num = match name {
+        "al" => 0,
+        "cl" => 1,
+        "dl" => 2,
+        "bl" => 3,
+        "spl" => 4,
+        "bpl" => 5,
+        "sil" => 6,
+        "dil" => 7,
+        _ => return None,
+    };
+    Some(Reg { num, size: Size::B })
+}
+
+/// Mnemonic without its size suffix, if it has one.
+fn split_mnemonic(op: &str) -> (&str, Option<Size>) {
+    const PLAIN: &[&str] = &[
+        "mov", "movabs", "lea", "add", "or", "adc", "sbb", "and", "sub", "xor",
+        "cmp", "test", "imul", "mul", "div", "idiv", "neg", "not", "inc",
+        "dec", "shl", "sal", "shr", "sar", "push", "pop", "call", "jmp", "ret",
+        "leave", "cltd", "cdq", "cqto", "cqo", "cltq", "cdqe", "cwtl", "cwde",
+        "ud2", "nop", "hlt", "int", "syscall",
+    ];
+    if PLAIN.contains(&op) || cc_op(op).is_some() {
+        return (op, None);
+    }
+    // `movzbl` is `movzb` to a 32-bit register.
+    if let Some(rest) = op.strip_prefix("movz") {
+        if rest.len() == 2 {
+            return (&op[..5], Size::from_suffix(rest.chars().nth(1).unwrap()));
+        }
+    }
+    match op.char_indices().last() {
+        Some((i, c)) if PLAIN.contains(&&op[..i]) => {
+            match Size::from_suffix(c) {
+                Some(size) => (&op[..i], Some(size)),
+                None => (op, None),
+            }
+        }
+        _ => (op, None),
+    }
+}
+
+/// `j`, `set` or `cmov` with the condition code of `op`.
+fn cc_op(op: &str) -> Option<(&str, u8)> {
+    const CCS: &[(&str, u8)] = &[
+        ("o", 0),
+        ("no", 1),
+        ("b", 2),
+        ("c", 2),
+        ("nae", 2),
+        ("ae", 3),
+        ("nb", 3),
+        ("nc", 3),
+        ("e", 4),
+        ("z", 4),
+        ("ne", 5),
+        ("nz", 5),
+        ("be", 6),
+        ("na", 6),
+        ("a", 7),
+        ("nbe", 7),
+        ("s", 8),
+        ("ns", 9),
+        ("p", 10),
+        ("pe", 10),
+        ("np", 11),
+        ("po", 11),
+        ("l", 12),
+        ("nge", 12),
+        ("ge", 13),
+        ("nl", 13),
+        ("le", 14),
+        ("ng", 14),
+        ("g", 15),
+        ("nle", 15),
+    ];
+    for prefix in ["j", "set", "cmov"] {
+        if let Some(cc) = op.strip_prefix(prefix) {
+            if let Some((_, n)) = CCS.iter().find(|(name, _)| *name == cc) {
+                return Some((prefix, *n));
+            }
+        }
+    }
+    None
+}
+
+/// Extension of the group 1 arithmetic instruction `op`, which is also its
+/// opcode divided by 8.
+fn alu(op: &str) -> Option<u8> {
+    let ext = match op {
+        "add" => 0,
+        "or" => 1,
+        "adc" => 2,
+        "sbb" => 3,
+        "and" => 4,
+        "sub" => 5,
+        "xor" => 6,
+        "cmp" => 7,
+        _ => return None,
+    };
+    Some(ext)
+}
+
+/// Extension of the group 2 shift `op`.
+fn shift(op: &str) -> Option<u8> {
+    match op {
+        "shl" | "sal" => Some(4),
+        "shr" => Some(5),
+        "sar" => Some(7),
+        _ => None,
+    }
+}
+
+/// Opcode of the byte form and extension of the group 3 and 4 instruction
+/// `op` on one operand.
+fn unary(op: &str) -> Option<(u8, u8)> {
+    match op {
+        "not" => Some((0xf6, 2)),
+        "neg" => Some((0xf6, 3)),
+        "mul" => Some((0xf6, 4)),
+        "imul" => Some((0xf6, 5)),
+        "div" => Some((0xf6, 6)),
+        "idiv" => Some((0xf6, 7)),
+        "inc" => Some((0xfe, 0)),
+        "dec" => Some((0xfe, 1)),
+        _ => None,
+    }
+}
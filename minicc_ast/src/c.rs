@@ -0,0 +1,296 @@
+//! Printing a checked program back as C, to compare what minicc thinks a
+//! program means with its source.
+//!
+//! Every subexpression is parenthesised, conversions are explicit casts,
+//! function designators are decayed with `&`, functions called without a
+//! declaration are declared and the bodies of `if` and `for` are always
+//! blocks. The output is preprocessed and specific to the data model the
+//! program was checked for.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::{Ast, AstKind, FnDecl, If, OpBin, OpUn, Symbol, Ty};
+
+/// Prints the function declarations `nodes` of a translation unit as C.
+pub fn print(nodes: &[Ast]) -> String {
+    let mut p = Printer::default();
+    for node in nodes {
+        p.fn_decl(node);
+    }
+
+    let mut s = String::new();
+    if p.va_list {
+        s += "#include <stdarg.h>\n\n";
+    }
+    for (ident, ty) in &p.implicit {
+        writeln!(s, "{};", ty.declare(ident.as_str())).unwrap();
+    }
+    if !p.implicit.is_empty() {
+        s.push('\n');
+    }
+    s + &p.out
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    /// Functions declared so far.
+    declared: HashSet<Symbol>,
+    /// Functions called before any declaration, with their implicit type.
+    implicit: Vec<(Symbol, Ty)>,
+    /// Whether `va_list` is used, which needs `<stdarg.h>`.
+    va_list: bool,
+    /// Whether the last declaration printed was a definition.
+    def: bool,
+}
+
+impl Printer {
+    fn fn_decl(&mut self, node: &Ast) {
+        let AstKind::FnDecl(FnDecl {
+            ident,
+            ret,
+            params,
+            variadic,
+            prototyped,
+            body,
+        }) = &node.kind
+        else {
+            unreachable!("expected a function declaration");
+        };
+
+        if !self.out.is_empty() && (self.def || body.is_some()) {
+            self.out.push('\n');
+        }
+        self.def = body.is_some();
+        self.declared.insert(*ident);
+
+        let mut list: Vec<_> = params
+            .iter()
+            .map(|p| self.ty(&p.ty, p.ident.map_or("", Symbol::as_str)))
+            .collect();
+        if *variadic {
+            list.push("...".to_string());
+        }
+        if *prototyped && list.is_empty() {
+            list.push("void".to_string());
+        }
+        let decl = self.ty(ret, &format!("{}({})", ident, list.join(", ")));
+
+        match body {
+            None => writeln!(self.out, "{};", decl).unwrap(),
+            Some(body) => {
+                writeln!(self.out, "{}", decl).unwrap();
+                self.block(body, 0);
+                self.out.push('\n');
+            }
+        }
+    }
+
+    fn stmt(&mut self, node: &Ast, depth: usize) {
+        let pad = "    ".repeat(depth);
+        match &node.kind {
+            AstKind::CompoundStmt(_) => {
+                self.out += &pad;
+                self.block(node, depth);
+                self.out.push('\n');
+            }
+            AstKind::If(n) => {
+                self.out += &pad;
+                self.if_(n, depth);
+                self.out.push('\n');
+            }
+            AstKind::For(n) => {
+                let init =
+                    n.init.as_ref().map_or(String::new(), |e| self.expr(e));
+                let cond =
+                    n.cond.as_ref().map_or(String::new(), |e| self.expr(e));
+                let inc =
+                    n.inc.as_ref().map_or(String::new(), |e| self.expr(e));
+                let cond =
+                    if cond.is_empty() { cond } else { format!(" {}", cond) };
+                let inc =
+                    if inc.is_empty() { inc } else { format!(" {}", inc) };
+                write!(self.out, "{}for ({};{};{}) ", pad, init, cond, inc)
+                    .unwrap();
+                self.block(&n.body, depth);
+                self.out.push('\n');
+            }
+            AstKind::VarDecl(n) => {
+                let decl = self.ty(&n.ty, n.ident.as_str());
+                writeln!(self.out, "{}{};", pad, decl).unwrap();
+            }
+            AstKind::Return(n) => match &n.expr {
+                Some(expr) => {
+                    let expr = self.expr(expr);
+                    writeln!(self.out, "{}return {};", pad, expr).unwrap();
+                }
+                None => writeln!(self.out, "{}return;", pad).unwrap(),
+            },
+            _ => {
+                let expr = self.expr(node);
+                writeln!(self.out, "{}{};", pad, expr).unwrap();
+            }
+        }
+    }
+
+    /// Prints `node` as a block, its items if a compound statement, from the
+    /// current column and without a newline after the `}`.
+    fn block(&mut self, node: &Ast, depth: usize) {
+        self.out += "{\n";
+        match &node.kind {
+            AstKind::CompoundStmt(n) => {
+                for item in &n.items {
+                    self.stmt(item, depth + 1);
+                }
+            }
+            _ => self.stmt(node, depth + 1),
+        }
+        self.out += &"    ".repeat(depth);
+        self.out.push('}');
+    }
+
+    /// Prints an `if` statement, with `else if` for an `if` in `else`.
+    fn if_(&mut self, n: &If, depth: usize) {
+        let cond = self.expr(&n.cond);
+        write!(self.out, "if ({}) ", cond).unwrap();
+        self.block(&n.then, depth);
+        match n.else_.as_deref() {
+            Some(Ast { kind: AstKind::If(n), .. }) => {
+                self.out += " else ";
+                self.if_(n, depth);
+            }
+            Some(else_) => {
+                self.out += " else ";
+                self.block(else_, depth);
+            }
+            None => {}
+        }
+    }
+
+    /// Expression `node`, parenthesised only where it is an operand.
+    fn expr(&mut self, node: &Ast) -> String {
+        match &node.kind {
+            AstKind::Ref(n) => {
+                if matches!(node.ty, Ty::Fn(_)) && self.declared.insert(n.ident)
+                {
+                    self.implicit.push((n.ident, node.ty.clone()));
+                }
+                n.ident.to_string()
+            }
+            AstKind::IntLit(n) => int_lit(n.val, &node.ty),
+            AstKind::Call(n) => {
+                let callee = self.operand(&n.callee);
+                let args: Vec<_> = n.args.iter().map(|a| self.arg(a)).collect();
+                format!("{}({})", callee, args.join(", "))
+            }
+            AstKind::UnOp(n) => {
+                let op = match n.op {
+                    OpUn::Neg => "-",
+                    OpUn::LogNot => "!",
+                    OpUn::Addr => "&",
+                    OpUn::Deref => "*",
+                };
+                format!("{}{}", op, self.operand(&n.expr))
+            }
+            AstKind::BinOp(n) => {
+                let op = match n.op {
+                    OpBin::Add => "+",
+                    OpBin::Sub => "-",
+                    OpBin::Mul => "*",
+                    OpBin::Div => "/",
+                    OpBin::Mod => "%",
+                    OpBin::Lt => "<",
+                    OpBin::Gt => ">",
+                    OpBin::Le => "<=",
+                    OpBin::Ge => ">=",
+                    OpBin::Eq => "==",
+                    OpBin::Ne => "!=",
+                    OpBin::Asign => "=",
+                };
+                let lhs = self.operand(&n.lhs);
+                format!("{} {} {}", lhs, op, self.operand(&n.rhs))
+            }
+            AstKind::Cond(n) => {
+                let cond = self.operand(&n.cond);
+                let then = self.operand(&n.then);
+                format!("{} ? {} : {}", cond, then, self.operand(&n.else_))
+            }
+            AstKind::Comma(n) => {
+                let lhs = self.operand(&n.lhs);
+                format!("{}, {}", lhs, self.operand(&n.rhs))
+            }
+            AstKind::Cast(n) => {
+                let ty = self.ty(&node.ty, "");
+                format!("({}){}", ty, self.operand(&n.expr))
+            }
+            AstKind::VaStart(n) => {
+                format!("va_start({}, {})", self.arg(&n.ap), n.last)
+            }
+            AstKind::VaArg(n) => {
+                let ap = self.arg(&n.ap);
+                format!("va_arg({}, {})", ap, self.ty(&node.ty, ""))
+            }
+            AstKind::VaEnd(n) => format!("va_end({})", self.arg(&n.ap)),
+            AstKind::FnDecl(_)
+            | AstKind::CompoundStmt(_)
+            | AstKind::If(_)
+            | AstKind::For(_)
+            | AstKind::VarDecl(_)
+            | AstKind::Return(_) => unreachable!("statement in an expression"),
+        }
+    }
+
+    /// Operand of an operator, parenthesised unless a primary or postfix
+    /// expression.
+    fn operand(&mut self, node: &Ast) -> String {
+        let expr = self.expr(node);
+        match node.kind {
+            AstKind::Ref(_)
+            | AstKind::IntLit(_)
+            | AstKind::Call(_)
+            | AstKind::VaStart(_)
+            | AstKind::VaArg(_)
+            | AstKind::VaEnd(_) => expr,
+            _ => format!("({})", expr),
+        }
+    }
+
+    /// Argument of a call, parenthesised only if a comma expression.
+    fn arg(&mut self, node: &Ast) -> String {
+        match node.kind {
+            AstKind::Comma(_) => self.operand(node),
+            _ => self.expr(node),
+        }
+    }
+
+    /// Declaration of `ident` with type `ty`.
+    fn ty(&mut self, ty: &Ty, ident: &str) -> String {
+        self.va_list |= uses_va_list(ty);
+        ty.declare(ident)
+    }
+}
+
+/// Literal of value `val` and type `ty`, suffixed to keep its type.
+fn int_lit(val: i64, ty: &Ty) -> String {
+    match ty {
+        Ty::Int => val.to_string(),
+        Ty::Long => format!("{}l", val),
+        Ty::LongLong => format!("{}ll", val),
+        Ty::UInt => format!("{}u", val as u64),
+        Ty::ULong => format!("{}ul", val as u64),
+        Ty::ULongLong => format!("{}ull", val as u64),
+        ty => format!("(({}){})", ty, val),
+    }
+}
+
+fn uses_va_list(ty: &Ty) -> bool {
+    match ty {
+        Ty::VaList => true,
+        Ty::Ptr(ty) => uses_va_list(ty),
+        Ty::Fn(f) => {
+            uses_va_list(&f.ret) || f.params.iter().flatten().any(uses_va_list)
+        }
+        _ => false,
+    }
+}
@@ -1,7 +1,66 @@
+pub mod c;
+pub mod source;
+pub mod symbol;
+
+pub use source::{SourceMap, Span};
+pub use symbol::Symbol;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Ast {
     pub kind: AstKind,
-    pub loc: usize,
+    /// Source text the node was parsed from.
+    pub span: Span,
+    /// Type of the expression. Statements are `void`.
+    pub ty: Ty,
+}
+
+impl Ast {
+    /// Direct child nodes in source order.
+    pub fn children(&self) -> Vec<&Ast> {
+        use AstKind::*;
+        match &self.kind {
+            FnDecl(n) => n.body.iter().map(|b| &**b).collect(),
+            CompoundStmt(n) => n.items.iter().collect(),
+            If(n) => [Some(&n.cond), Some(&n.then), n.else_.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(|b| &**b)
+                .collect(),
+            For(n) => [
+                n.init.as_ref(),
+                n.cond.as_ref(),
+                n.inc.as_ref(),
+                Some(&n.body),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|b| &**b)
+            .collect(),
+            Return(n) => n.expr.iter().map(|b| &**b).collect(),
+            Call(n) => std::iter::once(&*n.callee).chain(&n.args).collect(),
+            UnOp(n) => vec![&n.expr],
+            BinOp(n) => vec![&n.lhs, &n.rhs],
+            Cond(n) => vec![&n.cond, &n.then, &n.else_],
+            Comma(n) => vec![&n.lhs, &n.rhs],
+            Cast(n) => vec![&n.expr],
+            VaStart(n) => vec![&n.ap],
+            VaArg(n) => vec![&n.ap],
+            VaEnd(n) => vec![&n.ap],
+            VarDecl(_) | Ref(_) | IntLit(_) => Vec::new(),
+        }
+    }
+
+    /// Innermost node whose span contains `offset` in `file`, e.g. the node
+    /// under a cursor.
+    pub fn node_at(&self, file: usize, offset: usize) -> Option<&Ast> {
+        if !self.span.contains(file, offset) {
+            return None;
+        }
+        self.children()
+            .into_iter()
+            .find_map(|c| c.node_at(file, offset))
+            .or(Some(self))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,13 +76,32 @@ pub enum AstKind {
     IntLit(IntLit),
     UnOp(UnOp),
     BinOp(BinOp),
+    Cond(Cond),
+    Comma(Comma),
+    Cast(Cast),
+    VaStart(VaStart),
+    VaArg(VaArg),
+    VaEnd(VaEnd),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FnDecl {
-    pub ident: String,
-    pub params: Vec<String>,
-    pub body: Box<Ast>,
+    pub ident: Symbol,
+    pub ret: Ty,
+    pub params: Vec<Param>,
+    /// Whether the parameter list ends with `...`.
+    pub variadic: bool,
+    /// Whether the parameters are declared, unlike in `int f()`.
+    pub prototyped: bool,
+    /// `None` for a prototype.
+    pub body: Option<Box<Ast>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    /// May be omitted in a prototype.
+    pub ident: Option<Symbol>,
+    pub ty: Ty,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -48,23 +126,25 @@ pub struct For {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VarDecl {
-    pub ident: String,
+    pub ident: Symbol,
+    pub ty: Ty,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Return {
-    pub expr: Box<Ast>,
+    pub expr: Option<Box<Ast>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Call {
-    pub ident: String,
+    /// Pointer to the called function.
+    pub callee: Box<Ast>,
     pub args: Vec<Ast>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Ref {
-    pub ident: String,
+    pub ident: Symbol,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -82,6 +162,8 @@ pub struct UnOp {
 pub enum OpUn {
     Neg,    // `-`
     LogNot, // `!`
+    Addr,   // `&`
+    Deref,  // `*`
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -106,3 +188,277 @@ pub enum OpBin {
     Ne,    // `!=`
     Asign, // `=`
 }
+
+/// `cond ? then : else_`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cond {
+    pub cond: Box<Ast>,
+    pub then: Box<Ast>,
+    pub else_: Box<Ast>,
+}
+
+/// `lhs, rhs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comma {
+    pub lhs: Box<Ast>,
+    pub rhs: Box<Ast>,
+}
+
+/// Conversion of `expr` to the type of the enclosing node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cast {
+    pub expr: Box<Ast>,
+}
+
+/// `va_start(ap, last)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaStart {
+    pub ap: Box<Ast>,
+    pub last: Symbol,
+}
+
+/// `va_arg(ap, T)`, where `T` is the type of the enclosing node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaArg {
+    pub ap: Box<Ast>,
+}
+
+/// `va_end(ap)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VaEnd {
+    pub ap: Box<Ast>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ty {
+    Void,
+    Char, // `char`, signed on i386
+    SChar,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Long,
+    ULong,
+    LongLong,
+    ULongLong,
+    /// Pointer to the next variadic argument, or the structure of the ABI
+    /// if [`DataModel::va_list`] is larger.
+    VaList,
+    Ptr(Box<Ty>),
+    Fn(FnTy),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FnTy {
+    pub ret: Box<Ty>,
+    /// `None` if declared without a prototype, e.g. `int f()`.
+    pub params: Option<Vec<Ty>>,
+    pub variadic: bool,
+}
+
+/// Sizes of the types that depend on the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataModel {
+    /// Size of `long` and of pointers.
+    pub long: usize,
+    /// Size of `va_list`.
+    pub va_list: usize,
+}
+
+impl DataModel {
+    /// 32-bit `long` and pointers, as on i386.
+    pub const ILP32: Self = Self { long: 4, va_list: 4 };
+    /// 64-bit `long` and pointers, with a `va_list` that is a pointer.
+    pub const LP64: Self = Self { long: 8, va_list: 8 };
+
+    /// Whether `va_list` is the structure of the ABI rather than a pointer.
+    pub fn va_list_is_struct(self) -> bool {
+        self.va_list > self.long
+    }
+}
+
+impl Default for DataModel {
+    fn default() -> Self {
+        Self::ILP32
+    }
+}
+
+impl Ty {
+    /// Size in bytes. `void` has none (C99 6.2.5p19): the parser rejects
+    /// the objects, dereferences and arithmetic that would need it, and this
+    /// panics for it.
+    pub fn size(&self, model: DataModel) -> usize {
+        use Ty::*;
+        match self {
+            Void => panic!("`void` has no size"),
+            Char | SChar | UChar => 1,
+            Short | UShort => 2,
+            Int | UInt => 4,
+            Long | ULong | Ptr(_) => model.long,
+            VaList => model.va_list,
+            LongLong | ULongLong => 8,
+            Fn(_) => 1,
+        }
+    }
+
+    pub fn align(&self, model: DataModel) -> usize {
+        match self {
+            Ty::VaList => model.va_list.min(model.long),
+            ty => ty.size(model),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        !matches!(self, Ty::Void | Ty::VaList | Ty::Ptr(_) | Ty::Fn(_))
+    }
+
+    /// Type pointed to by a pointer.
+    pub fn pointee(&self) -> Ty {
+        match self {
+            Ty::Ptr(ty) => (**ty).clone(),
+            ty => panic!("`{}` is not a pointer", ty),
+        }
+    }
+
+    /// Whether values of the type can be compared with zero.
+    pub fn is_scalar(&self) -> bool {
+        self.is_integer() || matches!(self, Ty::VaList | Ty::Ptr(_))
+    }
+
+    /// Pointer to a function, the type of a decayed function designator.
+    pub fn is_fn_ptr(&self) -> bool {
+        matches!(self, Ty::Ptr(ty) if matches!(**ty, Ty::Fn(_)))
+    }
+
+    /// Compatible types (C99 6.2.7), where a function declared without a
+    /// prototype is compatible with any parameter list.
+    pub fn is_compatible(&self, other: &Ty) -> bool {
+        match (self, other) {
+            (Ty::Ptr(a), Ty::Ptr(b)) => a.is_compatible(b),
+            (Ty::Fn(a), Ty::Fn(b)) => {
+                a.ret.is_compatible(&b.ret)
+                    && match (&a.params, &b.params) {
+                        (Some(x), Some(y)) => {
+                            a.variadic == b.variadic
+                                && x.len() == y.len()
+                                && x.iter()
+                                    .zip(y)
+                                    .all(|(x, y)| x.is_compatible(y))
+                        }
+                        _ => true,
+                    }
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Declaration of `ident` with this type in C syntax, e.g.
+    /// `int (*ident)(int)`. An empty `ident` gives the type name.
+    pub fn declare(&self, ident: &str) -> String {
+        match self {
+            Ty::Ptr(ty) if matches!(**ty, Ty::Fn(_)) => {
+                ty.declare(&format!("(*{})", ident))
+            }
+            Ty::Ptr(ty) => ty.declare(&format!("*{}", ident)),
+            Ty::Fn(f) => {
+                let mut params: Vec<_> = match &f.params {
+                    Some(p) if p.is_empty() && !f.variadic => {
+                        vec!["void".to_string()]
+                    }
+                    Some(p) => p.iter().map(|ty| ty.declare("")).collect(),
+                    None => Vec::new(),
+                };
+                if f.variadic {
+                    params.push("...".to_string());
+                }
+                f.ret.declare(&format!("{}({})", ident, params.join(", ")))
+            }
+            ty if ident.is_empty() => ty.to_string(),
+            ty => format!("{} {}", ty, ident),
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        use Ty::*;
+        matches!(self, Char | SChar | Short | Int | Long | LongLong)
+    }
+
+    /// Integer conversion rank.
+    fn rank(&self) -> u8 {
+        use Ty::*;
+        match self {
+            Void | VaList | Ptr(_) | Fn(_) => 0,
+            Char | SChar | UChar => 1,
+            Short | UShort => 2,
+            Int | UInt => 3,
+            Long | ULong => 4,
+            LongLong | ULongLong => 5,
+        }
+    }
+
+    fn to_unsigned(&self) -> Ty {
+        use Ty::*;
+        match self {
+            Char | SChar => UChar,
+            Short => UShort,
+            Int => UInt,
+            Long => ULong,
+            LongLong => ULongLong,
+            ty => ty.clone(),
+        }
+    }
+
+    /// Integer promotions (C99 6.3.1.1p2).
+    pub fn promoted(&self) -> Ty {
+        use Ty::*;
+        match self {
+            Char | SChar | UChar | Short | UShort => Int,
+            ty => ty.clone(),
+        }
+    }
+
+    /// Usual arithmetic conversions (C99 6.3.1.8), which depend on the sizes
+    /// of `model`.
+    pub fn common(&self, other: &Ty, model: DataModel) -> Ty {
+        let (a, b) = (self.promoted(), other.promoted());
+        if a == b {
+            return a;
+        }
+        if a.is_signed() == b.is_signed() {
+            return if a.rank() > b.rank() { a } else { b };
+        }
+
+        let (s, u) = if a.is_signed() { (a, b) } else { (b, a) };
+        if u.rank() >= s.rank() {
+            u
+        } else if s.size(model) > u.size(model) {
+            s
+        } else {
+            s.to_unsigned()
+        }
+    }
+}
+
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Ty::*;
+        match self {
+            Void => write!(f, "void"),
+            Char => write!(f, "char"),
+            SChar => write!(f, "signed char"),
+            UChar => write!(f, "unsigned char"),
+            Short => write!(f, "short"),
+            UShort => write!(f, "unsigned short"),
+            Int => write!(f, "int"),
+            UInt => write!(f, "unsigned int"),
+            Long => write!(f, "long"),
+            ULong => write!(f, "unsigned long"),
+            LongLong => write!(f, "long long"),
+            ULongLong => write!(f, "unsigned long long"),
+            VaList => write!(f, "va_list"),
+            Ptr(_) | Fn(_) => write!(f, "{}", self.declare("")),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+/// Maps offsets in the text given to the scanner back to the files the text
+/// was read from.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<File>,
+    /// Sorted by `out`.
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct File {
+    pub name: String,
+    pub src: String,
+}
+
+/// Text starting at `out` was copied from `offset` in `file`.
+#[derive(Debug, Clone)]
+struct Segment {
+    out: usize,
+    file: usize,
+    offset: usize,
+}
+
+/// Range of bytes `start..end` in a file of a `SourceMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Span covering both `self` and `other`. Spans in different files, e.g.
+    /// where an expression starts in a header, cover only `self`.
+    pub fn to(self, other: Span) -> Span {
+        if self.file != other.file {
+            return self;
+        }
+        Span {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+
+    pub fn contains(&self, file: usize, offset: usize) -> bool {
+        self.file == file && (self.start..self.end).contains(&offset)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loc<'a> {
+    pub file: &'a str,
+    /// 1-based.
+    pub line: usize,
+    /// 1-based, in bytes.
+    pub col: usize,
+}
+
+impl std::fmt::Display for Loc<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map of text that was not preprocessed.
+    pub fn from_src(name: &str, src: &str) -> Self {
+        let mut map = Self::new();
+        let file = map.add_file(name, src);
+        map.push(0, file, 0);
+        map
+    }
+
+    pub fn add_file(&mut self, name: &str, src: &str) -> usize {
+        self.files.push(File { name: name.to_string(), src: src.to_string() });
+        self.files.len() - 1
+    }
+
+    pub fn file(&self, file: usize) -> &File {
+        &self.files[file]
+    }
+
+    /// Records that text from `out` onwards comes from `offset` in `file`.
+    pub fn push(&mut self, out: usize, file: usize, offset: usize) {
+        if let Some(last) = self.segments.last() {
+            debug_assert!(last.out <= out);
+            if last.file == file && last.offset + (out - last.out) == offset {
+                return;
+            }
+        }
+        self.segments.push(Segment { out, file, offset });
+    }
+
+    /// Location of an offset in the mapped text.
+    pub fn resolve(&self, out: usize) -> Loc<'_> {
+        let (file, offset) = self.origin(out);
+        self.loc(file, offset)
+    }
+
+    /// Location of an offset in `file`.
+    pub fn loc(&self, file: usize, offset: usize) -> Loc<'_> {
+        let Some(f) = self.files.get(file) else {
+            return Loc { file: "<unknown>", line: 1, col: offset + 1 };
+        };
+        let offset = offset.min(f.src.len());
+        let before = &f.src.as_bytes()[..offset];
+        let line = before.iter().filter(|c| **c == b'\n').count() + 1;
+        let col = offset
+            - before.iter().rposition(|c| *c == b'\n').map_or(0, |i| i + 1)
+            + 1;
+        Loc { file: &f.name, line, col }
+    }
+
+    /// Span of the text `start..end` in the mapped text, which must not
+    /// cross files.
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        let (file, offset) = self.origin(start);
+        let end = match end.checked_sub(1).filter(|e| *e > start) {
+            Some(last) => match self.origin(last) {
+                (f, o) if f == file && o >= offset => o + 1,
+                _ => offset + (end - start),
+            },
+            None => offset + (end - start),
+        };
+        Span { file, start: offset, end }
+    }
+
+    /// Location of the start of `span`.
+    pub fn span_loc(&self, span: Span) -> Loc<'_> {
+        self.loc(span.file, span.start)
+    }
+
+    /// The first line of `span` with the spanned part underlined, e.g.
+    ///
+    /// ```text
+    ///     a = b + 1;
+    ///         ^~~~~
+    /// ```
+    pub fn underline(&self, span: Span) -> String {
+        let Some(f) = self.files.get(span.file) else {
+            return String::new();
+        };
+        let start = span.start.min(f.src.len());
+        let line_start = f.src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end =
+            f.src[start..].find('\n').map_or(f.src.len(), |i| start + i);
+        let end = span.end.clamp(start + 1, line_end.max(start + 1));
+
+        let line = &f.src[line_start..line_end];
+        let indent: String = f.src[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!("    {}\n    {}^{}", line, indent, "~".repeat(end - start - 1))
+    }
+
+    /// File and offset an offset in the mapped text was copied from.
+    fn origin(&self, out: usize) -> (usize, usize) {
+        let i = self.segments.partition_point(|s| s.out <= out);
+        match i.checked_sub(1).map(|i| &self.segments[i]) {
+            Some(s) => (s.file, s.offset + (out - s.out)),
+            None => (usize::MAX, out),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+/// Interned string, compared and hashed as an integer.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// Symbols in the first chunk of [`STRS`], each next chunk twice as large.
+const FIRST_CHUNK: usize = 256;
+
+/// String of each symbol, in chunks that never move once allocated so that
+/// [`Symbol::as_str`] reads them without locking.
+static STRS: [OnceLock<Box<[OnceLock<&'static str>]>>; 24] =
+    [const { OnceLock::new() }; 24];
+
+/// Symbol of each string, only locked to write when interning a new one.
+fn ids() -> &'static RwLock<HashMap<&'static str, Symbol>> {
+    static IDS: OnceLock<RwLock<HashMap<&'static str, Symbol>>> =
+        OnceLock::new();
+    IDS.get_or_init(Default::default)
+}
+
+/// Chunk of [`STRS`] holding symbol `n` and its index in it.
+fn slot(n: usize) -> (usize, usize) {
+    let chunk = (n / FIRST_CHUNK + 1).ilog2() as usize;
+    (chunk, n - FIRST_CHUNK * ((1 << chunk) - 1))
+}
+
+impl Symbol {
+    pub fn intern(s: &str) -> Self {
+        if let Some(sym) = ids().read().unwrap().get(s) {
+            return *sym;
+        }
+        let mut ids = ids().write().unwrap();
+        if let Some(sym) = ids.get(s) {
+            return *sym;
+        }
+
+        // Strings are kept for the rest of the process, each once however
+        // often it is interned, so that a REPL or JIT session reusing its
+        // names does not grow.
+        let s: &'static str = Box::leak(s.into());
+        let (chunk, i) = slot(ids.len());
+        let strs = STRS[chunk].get_or_init(|| {
+            (0..FIRST_CHUNK << chunk).map(|_| OnceLock::new()).collect()
+        });
+        strs[i].set(s).unwrap();
+        let sym = Symbol(ids.len() as u32);
+        ids.insert(s, sym);
+        sym
+    }
+
+    pub fn as_str(self) -> &'static str {
+        let (chunk, i) = slot(self.0 as usize);
+        STRS[chunk].get().and_then(|strs| strs[i].get()).unwrap()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Self::intern(s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
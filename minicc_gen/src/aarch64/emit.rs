@@ -0,0 +1,281 @@
+use super::*;
+use crate::regalloc::MFunc;
+
+/// Writes allocated `mf`, with the prologue saving the callee-saved registers
+/// in `used` and laying out `slots`.
+pub(super) fn emit(
+    f: &mut dyn Write,
+    name: Symbol,
+    mf: &MFunc<MInst>,
+    slots: &[ir::Slot],
+    used: &[PReg],
+) {
+    let saved: Vec<PReg> =
+        ABI.callee_saved.iter().copied().filter(|r| used.contains(r)).collect();
+
+    // `x29` points to the saved `x29` and `x30`, with the callee-saved
+    // registers right below, then slots and spill slots.
+    let mut size = 8 * saved.len() as u32;
+    let mut offsets = Vec::new();
+    for slot in slots {
+        size = (size + slot.size).next_multiple_of(slot.align.max(1));
+        offsets.push(-(size as i32));
+    }
+    let spill_base = size;
+    size += 8 * mf.spill_slots;
+    let frame = size.next_multiple_of(16);
+
+    let mut e = Emitter { f, name, saved: &saved, slots: offsets, spill_base };
+
+    o!(e.f, "	stp	x29, x30, [sp, #-16]!");
+    o!(e.f, "	mov	x29, sp");
+    e.sp("sub", frame);
+    for (i, r) in saved.iter().enumerate() {
+        let addr = Addr::new(Base::Frame(-8 * (i as i32 + 1)));
+        e.mem("st", Reg::P(*r), addr, Size::X);
+    }
+
+    for (b, block) in mf.blocks.iter().enumerate() {
+        if b > 0 {
+            o!(e.f, ".L{}.{}:", name, b);
+        }
+        let mut insts = block.insts.iter().peekable();
+        while let Some(inst) = insts.next() {
+            // Branch on the inverse condition if the taken target follows.
+            if let MInst::BCond { cc, target } = *inst {
+                if let Some(MInst::B { target: other }) = insts.peek() {
+                    if target == b + 1 {
+                        e.inst(&MInst::BCond {
+                            cc: cc.inverse(),
+                            target: *other,
+                        });
+                        insts.next();
+                        continue;
+                    }
+                }
+            }
+            if matches!(*inst, MInst::B { target } if target == b + 1) {
+                continue;
+            }
+            e.inst(inst);
+        }
+    }
+}
+
+struct Emitter<'a> {
+    f: &'a mut dyn Write,
+    name: Symbol,
+    saved: &'a [PReg],
+    /// Offset of each slot from `x29`.
+    slots: Vec<i32>,
+    /// Offset below `x29` where the spill slots start.
+    spill_base: u32,
+}
+
+impl Emitter<'_> {
+    fn inst(&mut self, inst: &MInst) {
+        match *inst {
+            MInst::Mov { dst, src } if dst == src => {}
+            MInst::Mov { dst, src } => {
+                o!(self.f, "	mov	{}, {}", x(dst), x(src))
+            }
+            MInst::MovImm { dst, imm } => self.mov_imm(&x(dst), imm),
+            MInst::Adr { dst, sym } => {
+                o!(self.f, "	adrp	{}, :got:{}", x(dst), sym);
+                o!(self.f, "	ldr	{}, [{}, :got_lo12:{}]", x(dst), x(dst), sym);
+            }
+            MInst::Load { dst, addr, size } => self.mem("ld", dst, addr, size),
+            MInst::Store { src, addr, size } => self.mem("st", src, addr, size),
+            MInst::Lea { dst, addr } => {
+                let (base, disp) = self.base(addr);
+                self.add_imm(&x(dst), &base, disp as i64);
+            }
+            MInst::Alu { op, size, dst, lhs, rhs } => {
+                let reg = |r| sized(r, size);
+                let rhs = match rhs {
+                    Opnd::Reg(r) => reg(r),
+                    Opnd::Imm(i) => format!("#{}", i),
+                };
+                o!(
+                    self.f,
+                    "	{}	{}, {}, {}",
+                    op.name(),
+                    reg(dst),
+                    reg(lhs),
+                    rhs
+                );
+            }
+            MInst::Msub { dst, lhs, rhs, minuend } => o!(
+                self.f,
+                "	msub	{}, {}, {}, {}",
+                x(dst),
+                x(lhs),
+                x(rhs),
+                x(minuend)
+            ),
+            MInst::Ext { dst, src, from, signed } => {
+                let (dst, src) = (preg(dst).0, preg(src).0);
+                match (from, signed) {
+                    (Size::B, true) => o!(self.f, "	sxtb	x{}, w{}", dst, src),
+                    (Size::H, true) => o!(self.f, "	sxth	x{}, w{}", dst, src),
+                    (Size::W, true) => o!(self.f, "	sxtw	x{}, w{}", dst, src),
+                    // Writing a `w` register clears the upper half.
+                    (Size::B, false) => o!(self.f, "	uxtb	w{}, w{}", dst, src),
+                    (Size::H, false) => o!(self.f, "	uxth	w{}, w{}", dst, src),
+                    (Size::W, false) => o!(self.f, "	mov	w{}, w{}", dst, src),
+                    (Size::X, _) => o!(self.f, "	mov	x{}, x{}", dst, src),
+                }
+            }
+            MInst::Cmp { lhs, rhs, size } => {
+                let rhs = match rhs {
+                    Opnd::Reg(r) => sized(r, size),
+                    Opnd::Imm(i) => format!("#{}", i),
+                };
+                o!(self.f, "	cmp	{}, {}", sized(lhs, size), rhs);
+            }
+            MInst::Cset { cc, dst } => {
+                o!(self.f, "	cset	{}, {}", sized(dst, Size::W), cc.name())
+            }
+            MInst::Csel { cc, dst, lhs, rhs } => o!(
+                self.f,
+                "	csel	{}, {}, {}, {}",
+                x(dst),
+                x(lhs),
+                x(rhs),
+                cc.name()
+            ),
+            MInst::SubSp { n } => self.sp("sub", n),
+            MInst::AddSp { n } => self.sp("add", n),
+            MInst::StoreArg { src, offset } => {
+                o!(self.f, "	str	{}, [sp, #{}]", x(src), offset)
+            }
+            MInst::Call { target: Callee::Sym(s), .. } => {
+                o!(self.f, "	bl	{}", s)
+            }
+            MInst::Call { target: Callee::Reg(r), .. } => {
+                o!(self.f, "	blr	{}", x(r))
+            }
+            MInst::B { target } => {
+                o!(self.f, "	b	.L{}.{}", self.name, target)
+            }
+            MInst::BCond { cc, target } => {
+                o!(self.f, "	b.{}	.L{}.{}", cc.name(), self.name, target)
+            }
+            MInst::Ret { .. } => {
+                for (i, r) in self.saved.iter().enumerate() {
+                    let addr = Addr::new(Base::Frame(-8 * (i as i32 + 1)));
+                    self.mem("ld", Reg::P(*r), addr, Size::X);
+                }
+                o!(self.f, "	mov	sp, x29");
+                o!(self.f, "	ldp	x29, x30, [sp], #16");
+                o!(self.f, "	ret");
+            }
+            MInst::Brk => o!(self.f, "	brk	#1000"),
+            MInst::Reload { dst, slot } => {
+                let addr = self.spill(slot);
+                self.mem("ld", dst, addr, Size::X);
+            }
+            MInst::Spill { src, slot } => {
+                let addr = self.spill(slot);
+                self.mem("st", src, addr, Size::X);
+            }
+        }
+    }
+
+    /// Writes a load (`op` of `ld`) or store (`st`) of `reg` at `addr`,
+    /// through `x16` if the offset does not fit in the instruction.
+    fn mem(&mut self, op: &str, reg: Reg, addr: Addr, size: Size) {
+        let (suffix, reg) = match size {
+            Size::B => ("b", sized(reg, Size::W)),
+            Size::H => ("h", sized(reg, Size::W)),
+            size => ("", sized(reg, size)),
+        };
+        let (base, disp) = self.base(addr);
+        let n = size.bytes();
+        if disp >= 0 && disp % n == 0 && disp / n < 4096 {
+            o!(self.f, "	{}r{}	{}, [{}, #{}]", op, suffix, reg, base, disp);
+        } else if (-256..256).contains(&disp) {
+            o!(self.f, "	{}ur{}	{}, [{}, #{}]", op, suffix, reg, base, disp);
+        } else {
+            let ip = x(Reg::P(IP0));
+            self.mov_imm(&ip, disp as i64);
+            o!(self.f, "	add	{}, {}, {}", ip, base, ip);
+            o!(self.f, "	{}r{}	{}, [{}]", op, suffix, reg, ip);
+        }
+    }
+
+    /// Base register and offset of `addr`.
+    fn base(&self, addr: Addr) -> (String, i32) {
+        let fp = x(Reg::P(FP));
+        match addr.base {
+            Base::Reg(r) => (x(r), addr.disp),
+            Base::Slot(s) => (fp, self.slots[s as usize] + addr.disp),
+            Base::Frame(off) => (fp, off + addr.disp),
+        }
+    }
+
+    fn spill(&self, slot: u32) -> Addr {
+        let off = self.spill_base + 8 * (slot + 1);
+        Addr::new(Base::Frame(-(off as i32)))
+    }
+
+    fn mov_imm(&mut self, dst: &str, imm: i64) {
+        // A single `movz` or `movn`.
+        if (-0x10000..0x10000).contains(&imm) {
+            o!(self.f, "	mov	{}, #{}", dst, imm);
+            return;
+        }
+        o!(self.f, "	movz	{}, #{}", dst, imm as u64 & 0xffff);
+        for shift in [16, 32, 48] {
+            let part = (imm as u64 >> shift) & 0xffff;
+            if part != 0 {
+                o!(self.f, "	movk	{}, #{}, lsl #{}", dst, part, shift);
+            }
+        }
+    }
+
+    /// `dst = base + imm`
+    fn add_imm(&mut self, dst: &str, base: &str, imm: i64) {
+        match imm {
+            0 => o!(self.f, "	mov	{}, {}", dst, base),
+            1..4096 => o!(self.f, "	add	{}, {}, #{}", dst, base, imm),
+            -4095..0 => o!(self.f, "	sub	{}, {}, #{}", dst, base, -imm),
+            _ => {
+                let ip = x(Reg::P(IP0));
+                self.mov_imm(&ip, imm);
+                o!(self.f, "	add	{}, {}, {}", dst, base, ip);
+            }
+        }
+    }
+
+    /// Moves `sp` by `n` bytes with `op` of `add` or `sub`.
+    fn sp(&mut self, op: &str, n: u32) {
+        match n {
+            0 => {}
+            1..4096 => o!(self.f, "	{}	sp, sp, #{}", op, n),
+            _ => {
+                let ip = x(Reg::P(IP0));
+                self.mov_imm(&ip, n as i64);
+                o!(self.f, "	{}	sp, sp, {}", op, ip);
+            }
+        }
+    }
+}
+
+fn preg(r: Reg) -> PReg {
+    match r {
+        Reg::P(p) => p,
+        Reg::V(v) => panic!("unallocated register v{}", v),
+    }
+}
+
+fn x(r: Reg) -> String {
+    sized(r, Size::X)
+}
+
+fn sized(r: Reg, size: Size) -> String {
+    match size {
+        Size::X => format!("x{}", preg(r).0),
+        _ => format!("w{}", preg(r).0),
+    }
+}
@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+
+use ir::{BinOp, BlockId, CmpOp, ConvOp, Inst, Term, Ty, Value};
+
+use super::*;
+use crate::regalloc::{MBlock, MFunc};
+
+/// Machine form of an IR register.
+#[derive(Debug, Clone, Copy)]
+enum Val {
+    /// Values narrower than 64 bits leave the upper bits undefined.
+    R(Reg),
+    /// Address of a slot, folded into addressing modes.
+    Slot(u32),
+    /// Comparison only used by the branch ending its block, which sets the
+    /// flags itself.
+    Flags,
+}
+
+/// Selects instructions for `func`, one machine block per IR block followed
+/// by the blocks copying phi operands on branch edges. Returns them with the
+/// slots of the function, which may have more than its IR ones.
+pub(super) fn select(func: &ir::Function) -> (MFunc<MInst>, Vec<ir::Slot>) {
+    let mut s = Isel {
+        func,
+        mf: MFunc::new(),
+        vals: Vec::new(),
+        insts: Vec::new(),
+        fused: HashMap::new(),
+        slots: func.slots.clone(),
+        reg_save: None,
+    };
+    s.assign_vals();
+
+    let n = func.blocks.len();
+    for _ in 0..n {
+        s.mf.blocks.push(MBlock { insts: Vec::new(), succs: Vec::new() });
+    }
+    let cfg = ir::Cfg::new(func);
+    for &b in &cfg.rpo {
+        if b == BlockId(0) {
+            s.params();
+        }
+        for inst in &func.block(b).insts {
+            s.inst(inst);
+        }
+        let succs = s.term(b);
+        let insts = std::mem::take(&mut s.insts);
+        s.mf.blocks[b.0 as usize] = MBlock { insts, succs };
+    }
+    (s.mf, s.slots)
+}
+
+struct Isel<'a> {
+    func: &'a ir::Function,
+    mf: MFunc<MInst>,
+    vals: Vec<Val>,
+    /// Instructions of the block being selected.
+    insts: Vec<MInst>,
+    /// Comparison the branch ending each block is fused with.
+    fused: HashMap<BlockId, (CmpOp, Ty, Value, Value)>,
+    slots: Vec<ir::Slot>,
+    /// Slot the argument registers of a variadic function are saved to.
+    reg_save: Option<u32>,
+}
+
+impl Isel<'_> {
+    fn assign_vals(&mut self) {
+        let mut uses = vec![0; self.func.regs.len()];
+        for block in &self.func.blocks {
+            let term = block.term.iter().flat_map(|t| t.uses());
+            for v in block.insts.iter().flat_map(|i| i.uses()).chain(term) {
+                if let Value::Reg(r) = v {
+                    uses[r.0 as usize] += 1;
+                }
+            }
+        }
+
+        for _ in &self.func.regs {
+            let val = Val::R(self.vreg());
+            self.vals.push(val);
+        }
+
+        for b in self.func.block_ids() {
+            let block = self.func.block(b);
+            for inst in &block.insts {
+                if let Inst::SlotAddr { dst, slot } = *inst {
+                    self.vals[dst.0 as usize] = Val::Slot(slot.0);
+                }
+            }
+            if let (
+                Some(&Inst::Cmp { op, ty, dst, lhs, rhs }),
+                Some(Term::Br { cond: Value::Reg(cond), .. }),
+            ) = (block.insts.last(), &block.term)
+            {
+                if dst == *cond && uses[dst.0 as usize] == 1 {
+                    self.vals[dst.0 as usize] = Val::Flags;
+                    self.fused.insert(b, (op, ty, lhs, rhs));
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, inst: MInst) {
+        self.insts.push(inst);
+    }
+
+    fn vreg(&mut self) -> Reg {
+        self.mf.new_vreg(ABI.gpr)
+    }
+
+    fn val(&self, r: ir::Reg) -> Val {
+        self.vals[r.0 as usize]
+    }
+
+    fn dst(&self, r: ir::Reg) -> Reg {
+        match self.val(r) {
+            Val::R(r) => r,
+            v => unreachable!("{:?} is not a register", v),
+        }
+    }
+
+    fn reg(&mut self, v: Value) -> Reg {
+        match v {
+            Value::Imm(imm) => {
+                let dst = self.vreg();
+                self.emit(MInst::MovImm { dst, imm });
+                dst
+            }
+            Value::Reg(r) => match self.val(r) {
+                Val::R(r) => r,
+                Val::Slot(s) => {
+                    let dst = self.vreg();
+                    let addr = Addr::new(Base::Slot(s));
+                    self.emit(MInst::Lea { dst, addr });
+                    dst
+                }
+                Val::Flags => unreachable!("fused comparison used"),
+            },
+        }
+    }
+
+    /// Operand of `add`, `sub` or `cmp`.
+    fn opnd(&mut self, v: Value) -> Opnd {
+        match v {
+            Value::Imm(i) if (0..4096).contains(&i) => Opnd::Imm(i),
+            v => Opnd::Reg(self.reg(v)),
+        }
+    }
+
+    fn addr(&mut self, v: Value) -> Addr {
+        match v {
+            Value::Reg(r) => match self.val(r) {
+                Val::Slot(s) => Addr::new(Base::Slot(s)),
+                _ => Addr::new(Base::Reg(self.reg(v))),
+            },
+            Value::Imm(_) => Addr::new(Base::Reg(self.reg(v))),
+        }
+    }
+
+    fn mov(&mut self, dst: Reg, v: Value) {
+        match v {
+            Value::Imm(imm) => self.emit(MInst::MovImm { dst, imm }),
+            v => {
+                let src = self.reg(v);
+                self.emit(MInst::Mov { dst, src });
+            }
+        }
+    }
+
+    /// Moves `v` of type `from` to `dst`, sign or zero extended to 64 bits.
+    fn extend(&mut self, dst: Reg, v: Value, from: Ty, signed: bool) {
+        match from {
+            Ty::I8 | Ty::I16 | Ty::I32 => {
+                let src = self.reg(v);
+                let from = size(from);
+                self.emit(MInst::Ext { dst, src, from, signed });
+            }
+            _ => self.mov(dst, v),
+        }
+    }
+
+    /// Register holding `v` extended to at least 32 bits.
+    fn extended(&mut self, v: Value, ty: Ty, signed: bool) -> Reg {
+        match (v, ty) {
+            (Value::Imm(i), Ty::I8 | Ty::I16) if !signed => {
+                let bits = ty.bits().unwrap();
+                self.reg(Value::Imm(i & ((1 << bits) - 1)))
+            }
+            (Value::Reg(_), Ty::I8 | Ty::I16) => {
+                let dst = self.vreg();
+                self.extend(dst, v, ty, signed);
+                dst
+            }
+            _ => self.reg(v),
+        }
+    }
+
+    /// Moves the parameters from where the caller passed them.
+    fn params(&mut self) {
+        // `va_arg` reads the unnamed arguments passed in registers from where
+        // they are saved.
+        if self.func.sig.variadic {
+            let slot = self.slots.len() as u32;
+            self.slots.push(ir::Slot { size: 8 * 8, align: 8 });
+            self.reg_save = Some(slot);
+            for (i, r) in ARG_REGS.into_iter().enumerate() {
+                self.emit(MInst::Store {
+                    src: Reg::P(r),
+                    addr: Addr::new(Base::Slot(slot)).offset(8 * i as i32),
+                    size: Size::X,
+                });
+            }
+        }
+        for (i, &p) in self.func.params.iter().enumerate() {
+            let dst = self.dst(p);
+            match ARG_REGS.get(i) {
+                Some(&r) => self.emit(MInst::Mov { dst, src: Reg::P(r) }),
+                None => {
+                    let offset = 16 + 8 * (i - ARG_REGS.len()) as i32;
+                    let addr = Addr::new(Base::Frame(offset));
+                    self.emit(MInst::Load { dst, addr, size: Size::X });
+                }
+            }
+        }
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Copy { dst, val } => self.mov(self.dst(*dst), *val),
+            Inst::Bin { op, dst, lhs, rhs } => {
+                let ty = self.func.reg_ty(*dst);
+                self.bin(*op, ty, *dst, *lhs, *rhs)
+            }
+            Inst::Cmp { op, ty, dst, lhs, rhs } => {
+                if matches!(self.val(*dst), Val::Flags) {
+                    return;
+                }
+                let cc = self.cond(*op, *ty, *lhs, *rhs);
+                self.emit(MInst::Cset { cc, dst: self.dst(*dst) });
+            }
+            Inst::Conv { op, dst, from, val } => {
+                let dst = self.dst(*dst);
+                match op {
+                    ConvOp::SExt => self.extend(dst, *val, *from, true),
+                    ConvOp::ZExt => self.extend(dst, *val, *from, false),
+                    // The upper bits of narrow values are undefined anyway.
+                    _ => self.mov(dst, *val),
+                }
+            }
+            Inst::SlotAddr { .. } => {}
+            Inst::FuncAddr { dst, name } => {
+                self.emit(MInst::Adr { dst: self.dst(*dst), sym: *name })
+            }
+            Inst::Load { dst, addr } => {
+                let addr = self.addr(*addr);
+                let size = size(self.func.reg_ty(*dst));
+                self.emit(MInst::Load { dst: self.dst(*dst), addr, size });
+            }
+            Inst::Store { ty, val, addr } => {
+                let addr = self.addr(*addr);
+                let src = self.reg(*val);
+                self.emit(MInst::Store { src, addr, size: size(*ty) });
+            }
+            Inst::Call { dst, callee, sig: _, args } => {
+                self.call(*dst, callee, args)
+            }
+            Inst::VaStart { ap } => self.va_start(*ap),
+            Inst::VaArg { dst, ap } => self.va_arg(*dst, *ap),
+            Inst::VaEnd { .. } => {}
+            // Copied on the edges into the block.
+            Inst::Phi { .. } => {}
+        }
+    }
+
+    fn bin(&mut self, op: BinOp, ty: Ty, dst: ir::Reg, lhs: Value, rhs: Value) {
+        let dst = self.dst(dst);
+        let op = match op {
+            BinOp::Add => AluOp::Add,
+            BinOp::Sub => AluOp::Sub,
+            BinOp::Mul => AluOp::Mul,
+            BinOp::SDiv | BinOp::SRem | BinOp::UDiv | BinOp::URem => {
+                let signed = matches!(op, BinOp::SDiv | BinOp::SRem);
+                let a = self.extended(lhs, ty, signed);
+                let b = self.extended(rhs, ty, signed);
+                let div = if signed { AluOp::SDiv } else { AluOp::UDiv };
+                let size = op_size(ty);
+                if matches!(op, BinOp::SDiv | BinOp::UDiv) {
+                    let rhs = Opnd::Reg(b);
+                    self.emit(MInst::Alu { op: div, size, dst, lhs: a, rhs });
+                } else {
+                    let q = self.vreg();
+                    let rhs = Opnd::Reg(b);
+                    self.emit(MInst::Alu {
+                        op: div,
+                        size,
+                        dst: q,
+                        lhs: a,
+                        rhs,
+                    });
+                    let (lhs, rhs, minuend) = (q, b, a);
+                    self.emit(MInst::Msub { dst, lhs, rhs, minuend });
+                }
+                return;
+            }
+        };
+        let lhs = self.reg(lhs);
+        let rhs = match op {
+            AluOp::Add | AluOp::Sub => self.opnd(rhs),
+            _ => Opnd::Reg(self.reg(rhs)),
+        };
+        self.emit(MInst::Alu { op, size: Size::X, dst, lhs, rhs });
+    }
+
+    /// Sets the flags for comparison `op`, returning the condition that holds
+    /// if it is true.
+    fn cond(&mut self, op: CmpOp, ty: Ty, lhs: Value, rhs: Value) -> Cc {
+        let signed =
+            matches!(op, CmpOp::SLt | CmpOp::SLe | CmpOp::SGt | CmpOp::SGe);
+        let cc = match op {
+            CmpOp::Eq => Cc::Eq,
+            CmpOp::Ne => Cc::Ne,
+            CmpOp::SLt => Cc::Lt,
+            CmpOp::SLe => Cc::Le,
+            CmpOp::SGt => Cc::Gt,
+            CmpOp::SGe => Cc::Ge,
+            CmpOp::ULt => Cc::Lo,
+            CmpOp::ULe => Cc::Ls,
+            CmpOp::UGt => Cc::Hi,
+            CmpOp::UGe => Cc::Hs,
+        };
+        let lhs = self.extended(lhs, ty, signed);
+        let rhs = match ty {
+            Ty::I8 | Ty::I16 => Opnd::Reg(self.extended(rhs, ty, signed)),
+            _ => self.opnd(rhs),
+        };
+        self.emit(MInst::Cmp { lhs, rhs, size: op_size(ty) });
+        cc
+    }
+
+    fn call(
+        &mut self,
+        dst: Option<ir::Reg>,
+        callee: &ir::Callee,
+        args: &[(Ty, Value)],
+    ) {
+        let target = match callee {
+            ir::Callee::Direct(name) => Callee::Sym(*name),
+            ir::Callee::Indirect(v) => Callee::Reg(self.reg(*v)),
+        };
+        let (regs, stack) = args.split_at(args.len().min(ARG_REGS.len()));
+        let n = (8 * stack.len() as u32).next_multiple_of(16);
+        self.emit(MInst::SubSp { n });
+        for (i, &(_, v)) in stack.iter().enumerate() {
+            let src = self.reg(v);
+            self.emit(MInst::StoreArg { src, offset: 8 * i as u32 });
+        }
+        let srcs: Vec<Reg> = regs.iter().map(|&(_, v)| self.reg(v)).collect();
+        let mut used = Vec::new();
+        for (src, r) in srcs.into_iter().zip(ARG_REGS) {
+            self.emit(MInst::Mov { dst: Reg::P(r), src });
+            used.push(r);
+        }
+        self.emit(MInst::Call { target, args: used });
+        self.emit(MInst::AddSp { n });
+        if let Some(dst) = dst {
+            let dst = self.dst(dst);
+            self.emit(MInst::Mov { dst, src: Reg::P(X0) });
+        }
+    }
+
+    /// Initialises the `va_list` at `ap`: the address of the next argument
+    /// on the stack, the end of the saved argument registers, that of the
+    /// vector registers, which are not saved, and the negative offsets from
+    /// the ends to the next saved register of each.
+    fn va_start(&mut self, ap: Value) {
+        let n = self.func.params.len();
+        let addr = self.addr(ap);
+
+        let overflow = 16 + 8 * n.saturating_sub(ARG_REGS.len()) as i32;
+        let stack = self.vreg();
+        let frame = Addr::new(Base::Frame(overflow));
+        self.emit(MInst::Lea { dst: stack, addr: frame });
+        self.emit(MInst::Store { src: stack, addr, size: Size::X });
+
+        let top = self.vreg();
+        let save = Addr::new(Base::Slot(self.reg_save.unwrap())).offset(64);
+        self.emit(MInst::Lea { dst: top, addr: save });
+        self.emit(MInst::Store {
+            src: top,
+            addr: addr.offset(8),
+            size: Size::X,
+        });
+
+        let zero = self.reg(Value::Imm(0));
+        let vr_top = addr.offset(16);
+        self.emit(MInst::Store { src: zero, addr: vr_top, size: Size::X });
+        let offs = -8 * (ARG_REGS.len() - n.min(ARG_REGS.len())) as i64;
+        let offs = self.reg(Value::Imm(offs));
+        let gr_offs = addr.offset(24);
+        self.emit(MInst::Store { src: offs, addr: gr_offs, size: Size::W });
+        let vr_offs = addr.offset(28);
+        self.emit(MInst::Store { src: zero, addr: vr_offs, size: Size::W });
+    }
+
+    /// Reads the next argument of the `va_list` at `ap` from the saved
+    /// registers while the offset to them is negative, from the stack after.
+    fn va_arg(&mut self, dst: ir::Reg, ap: Value) {
+        let ap = self.addr(ap);
+        let offs32 = self.vreg();
+        let gr_offs = ap.offset(24);
+        self.emit(MInst::Load { dst: offs32, addr: gr_offs, size: Size::W });
+        let offs = self.vreg();
+        let (src, from) = (offs32, Size::W);
+        self.emit(MInst::Ext { dst: offs, src, from, signed: true });
+        let stack = self.vreg();
+        self.emit(MInst::Load { dst: stack, addr: ap, size: Size::X });
+        let top = self.vreg();
+        self.emit(MInst::Load { dst: top, addr: ap.offset(8), size: Size::X });
+
+        let add = |dst, lhs, rhs| MInst::Alu {
+            op: AluOp::Add,
+            size: Size::X,
+            dst,
+            lhs,
+            rhs,
+        };
+        let in_reg = self.vreg();
+        self.emit(add(in_reg, top, Opnd::Reg(offs)));
+        let next_offs = self.vreg();
+        self.emit(add(next_offs, offs, Opnd::Imm(8)));
+        let next_stack = self.vreg();
+        self.emit(add(next_stack, stack, Opnd::Imm(8)));
+
+        self.emit(MInst::Cmp { lhs: offs, rhs: Opnd::Imm(0), size: Size::X });
+        let addr = self.vreg();
+        let (lhs, rhs) = (in_reg, stack);
+        self.emit(MInst::Csel { cc: Cc::Lt, dst: addr, lhs, rhs });
+        let offs_out = self.vreg();
+        let (lhs, rhs) = (next_offs, offs);
+        self.emit(MInst::Csel { cc: Cc::Lt, dst: offs_out, lhs, rhs });
+        let stack_out = self.vreg();
+        let (lhs, rhs) = (stack, next_stack);
+        self.emit(MInst::Csel { cc: Cc::Lt, dst: stack_out, lhs, rhs });
+
+        self.emit(MInst::Store { src: offs_out, addr: gr_offs, size: Size::W });
+        self.emit(MInst::Store { src: stack_out, addr: ap, size: Size::X });
+        let size = size(self.func.reg_ty(dst));
+        let addr = Addr::new(Base::Reg(addr));
+        self.emit(MInst::Load { dst: self.dst(dst), addr, size });
+    }
+
+    /// Selects the terminator of `b`, returning the successors.
+    fn term(&mut self, b: BlockId) -> Vec<usize> {
+        match self.func.block(b).term.as_ref().unwrap() {
+            Term::Ret(None) => {
+                self.emit(MInst::Ret { regs: &[] });
+                Vec::new()
+            }
+            Term::Ret(Some((_, v))) => {
+                self.mov(Reg::P(X0), *v);
+                self.emit(MInst::Ret { regs: &[X0] });
+                Vec::new()
+            }
+            Term::Jmp(target) => {
+                let insts = self.phi_copies(b, *target);
+                self.insts.extend(insts);
+                let target = target.0 as usize;
+                self.emit(MInst::B { target });
+                vec![target]
+            }
+            &Term::Br { ty, cond, then, else_ } => {
+                let cc = match self.fused.get(&b) {
+                    Some(&(op, ty, lhs, rhs)) => self.cond(op, ty, lhs, rhs),
+                    None => {
+                        let lhs = self.extended(cond, ty, false);
+                        let (rhs, size) = (Opnd::Imm(0), op_size(ty));
+                        self.emit(MInst::Cmp { lhs, rhs, size });
+                        Cc::Ne
+                    }
+                };
+                let then = self.edge(b, then);
+                let else_ = self.edge(b, else_);
+                self.emit(MInst::BCond { cc, target: then });
+                self.emit(MInst::B { target: else_ });
+                vec![then, else_]
+            }
+            Term::Unreachable => {
+                self.emit(MInst::Brk);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Machine block to branch to for the edge from `from` to `to`, a new one
+    /// if `to` has phis to copy.
+    fn edge(&mut self, from: BlockId, to: BlockId) -> usize {
+        let to_phis =
+            matches!(self.func.block(to).insts.first(), Some(Inst::Phi { .. }));
+        if !to_phis {
+            return to.0 as usize;
+        }
+        let mut insts = self.phi_copies(from, to);
+        let target = to.0 as usize;
+        insts.push(MInst::B { target });
+        self.mf.blocks.push(MBlock { insts, succs: vec![target] });
+        self.mf.blocks.len() - 1
+    }
+
+    /// Copies the values the phis of `to` take from `from`, all at once as
+    /// one phi may read another.
+    fn phi_copies(&mut self, from: BlockId, to: BlockId) -> Vec<MInst> {
+        let saved = std::mem::take(&mut self.insts);
+        let mut moves = Vec::new();
+        for inst in &self.func.block(to).insts {
+            let Inst::Phi { dst, args } = inst else {
+                break;
+            };
+            let (_, v) = args.iter().find(|(b, _)| *b == from).unwrap();
+            let dst = self.dst(*dst);
+            match *v {
+                Value::Imm(imm) => moves.push(MInst::MovImm { dst, imm }),
+                v => {
+                    let t = self.vreg();
+                    self.mov(t, v);
+                    moves.push(MInst::Mov { dst, src: t });
+                }
+            }
+        }
+        self.insts.extend(moves);
+        std::mem::replace(&mut self.insts, saved)
+    }
+}
+
+/// Size of a value of type `ty` in memory.
+fn size(ty: Ty) -> Size {
+    match ty {
+        Ty::I8 => Size::B,
+        Ty::I16 => Size::H,
+        Ty::I32 => Size::W,
+        Ty::I64 | Ty::Ptr => Size::X,
+    }
+}
+
+/// Size to compare and divide values of type `ty` at, the narrow ones being
+/// extended first.
+fn op_size(ty: Ty) -> Size {
+    match ty {
+        Ty::I64 | Ty::Ptr => Size::X,
+        _ => Size::W,
+    }
+}
@@ -0,0 +1,479 @@
+//! AArch64 code generation: instruction selection into [`MInst`]s over
+//! virtual registers, register allocation, then output in GNU assembler
+//! syntax.
+//!
+//! Functions follow the AAPCS64: the first eight arguments are passed in
+//! `x0` to `x7` and the rest in 8 byte stack slots, `sp` is 16-byte aligned,
+//! results come back in `x0`, and `x19` to `x28` are preserved. Variadic
+//! arguments are passed like named ones and read through the `va_list`
+//! structure of the ABI.
+
+mod emit;
+mod isel;
+
+use std::io::Write;
+
+use minicc_ast::{DataModel, Symbol};
+use minicc_ir as ir;
+
+use crate::regalloc::{self, Access, MachInst, PReg, Reg};
+use crate::{Abi, Dialect, Target};
+
+/// Registers of the first integer arguments, in order.
+const ARG_REGS: [PReg; 8] =
+    [PReg(0), PReg(1), PReg(2), PReg(3), PReg(4), PReg(5), PReg(6), PReg(7)];
+pub const X0: PReg = PReg(0);
+/// Scratch register of the emitter, for offsets too large for an
+/// instruction.
+const IP0: PReg = PReg(16);
+const FP: PReg = PReg(29);
+
+/// Operand of `add`, `sub` and `cmp`, whose immediates are 12 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opnd {
+    Reg(Reg),
+    Imm(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    Reg(Reg),
+    /// Slot of the function.
+    Slot(u32),
+    /// Offset from `x29`, where the arguments are.
+    Frame(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Addr {
+    pub base: Base,
+    pub disp: i32,
+}
+
+impl Addr {
+    pub fn new(base: Base) -> Self {
+        Self { base, disp: 0 }
+    }
+
+    /// The address `n` bytes further.
+    pub fn offset(self, n: i32) -> Self {
+        Self { disp: self.disp + n, ..self }
+    }
+}
+
+/// Width of an operation, loads of less than 8 bytes zero extend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    B,
+    H,
+    W,
+    X,
+}
+
+impl Size {
+    fn bytes(self) -> i32 {
+        match self {
+            Size::B => 1,
+            Size::H => 2,
+            Size::W => 4,
+            Size::X => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cc {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Lo,
+    Ls,
+    Hi,
+    Hs,
+}
+
+impl Cc {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cc::Eq => "eq",
+            Cc::Ne => "ne",
+            Cc::Lt => "lt",
+            Cc::Le => "le",
+            Cc::Gt => "gt",
+            Cc::Ge => "ge",
+            Cc::Lo => "lo",
+            Cc::Ls => "ls",
+            Cc::Hi => "hi",
+            Cc::Hs => "hs",
+        }
+    }
+
+    pub fn inverse(self) -> Self {
+        match self {
+            Cc::Eq => Cc::Ne,
+            Cc::Ne => Cc::Eq,
+            Cc::Lt => Cc::Ge,
+            Cc::Le => Cc::Gt,
+            Cc::Gt => Cc::Le,
+            Cc::Ge => Cc::Lt,
+            Cc::Lo => Cc::Hs,
+            Cc::Ls => Cc::Hi,
+            Cc::Hi => Cc::Ls,
+            Cc::Hs => Cc::Lo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+}
+
+impl AluOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            AluOp::Add => "add",
+            AluOp::Sub => "sub",
+            AluOp::Mul => "mul",
+            AluOp::SDiv => "sdiv",
+            AluOp::UDiv => "udiv",
+        }
+    }
+}
+
+/// Machine instruction, on 64-bit registers unless it has a size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MInst {
+    Mov {
+        dst: Reg,
+        src: Reg,
+    },
+    MovImm {
+        dst: Reg,
+        imm: i64,
+    },
+    /// Loads the address of function `sym` from the GOT.
+    Adr {
+        dst: Reg,
+        sym: Symbol,
+    },
+    Load {
+        dst: Reg,
+        addr: Addr,
+        size: Size,
+    },
+    Store {
+        src: Reg,
+        addr: Addr,
+        size: Size,
+    },
+    Lea {
+        dst: Reg,
+        addr: Addr,
+    },
+    /// `dst = lhs op rhs`, only `add` and `sub` taking an immediate.
+    Alu {
+        op: AluOp,
+        size: Size,
+        dst: Reg,
+        lhs: Reg,
+        rhs: Opnd,
+    },
+    /// `dst = minuend - lhs * rhs`
+    Msub {
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+        minuend: Reg,
+    },
+    /// `dst` is the low `from` of `src`, sign or zero extended.
+    Ext {
+        dst: Reg,
+        src: Reg,
+        from: Size,
+        signed: bool,
+    },
+    /// Sets the flags from `lhs - rhs`.
+    Cmp {
+        lhs: Reg,
+        rhs: Opnd,
+        size: Size,
+    },
+    /// `dst = cc ? 1 : 0`
+    Cset {
+        cc: Cc,
+        dst: Reg,
+    },
+    /// `dst = cc ? lhs : rhs`
+    Csel {
+        cc: Cc,
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+    /// Reserves `n` bytes of stack for arguments.
+    SubSp {
+        n: u32,
+    },
+    /// Stores an argument `offset` bytes above `sp`.
+    StoreArg {
+        src: Reg,
+        offset: u32,
+    },
+    /// Frees `n` bytes of arguments.
+    AddSp {
+        n: u32,
+    },
+    Call {
+        target: Callee,
+        /// Registers passing arguments.
+        args: Vec<PReg>,
+    },
+    B {
+        target: usize,
+    },
+    BCond {
+        cc: Cc,
+        target: usize,
+    },
+    /// Returns, with the result in `regs`.
+    Ret {
+        regs: &'static [PReg],
+    },
+    Brk,
+    /// Loads `dst` from spill slot `slot`.
+    Reload {
+        dst: Reg,
+        slot: u32,
+    },
+    /// Stores `src` to spill slot `slot`.
+    Spill {
+        src: Reg,
+        slot: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Callee {
+    Sym(Symbol),
+    Reg(Reg),
+}
+
+fn visit_opnd(opnd: &mut Opnd, f: &mut dyn FnMut(&mut Reg, Access)) {
+    if let Opnd::Reg(r) = opnd {
+        f(r, Access::Use);
+    }
+}
+
+fn visit_addr(addr: &mut Addr, f: &mut dyn FnMut(&mut Reg, Access)) {
+    if let Base::Reg(r) = &mut addr.base {
+        f(r, Access::Use);
+    }
+}
+
+impl MachInst for MInst {
+    fn visit_regs(&mut self, f: &mut dyn FnMut(&mut Reg, Access)) {
+        match self {
+            MInst::Mov { dst, src } => {
+                f(src, Access::Use);
+                f(dst, Access::Def);
+            }
+            MInst::MovImm { dst, .. }
+            | MInst::Adr { dst, .. }
+            | MInst::Cset { dst, .. }
+            | MInst::Reload { dst, .. } => f(dst, Access::Def),
+            MInst::Load { dst, addr, .. } | MInst::Lea { dst, addr } => {
+                visit_addr(addr, f);
+                f(dst, Access::Def);
+            }
+            MInst::Store { src, addr, .. } => {
+                f(src, Access::Use);
+                visit_addr(addr, f);
+            }
+            MInst::Alu { dst, lhs, rhs, .. } => {
+                f(lhs, Access::Use);
+                visit_opnd(rhs, f);
+                f(dst, Access::Def);
+            }
+            MInst::Msub { dst, lhs, rhs, minuend } => {
+                f(lhs, Access::Use);
+                f(rhs, Access::Use);
+                f(minuend, Access::Use);
+                f(dst, Access::Def);
+            }
+            MInst::Ext { dst, src, .. } => {
+                f(src, Access::Use);
+                f(dst, Access::Def);
+            }
+            MInst::Cmp { lhs, rhs, .. } => {
+                f(lhs, Access::Use);
+                visit_opnd(rhs, f);
+            }
+            MInst::Csel { dst, lhs, rhs, .. } => {
+                f(lhs, Access::Use);
+                f(rhs, Access::Use);
+                f(dst, Access::Def);
+            }
+            MInst::StoreArg { src, .. } | MInst::Spill { src, .. } => {
+                f(src, Access::Use)
+            }
+            MInst::Call { target, args } => {
+                if let Callee::Reg(r) = target {
+                    f(r, Access::Use);
+                }
+                for p in args {
+                    f(&mut Reg::P(*p), Access::Use);
+                }
+            }
+            MInst::Ret { regs } => {
+                for p in *regs {
+                    f(&mut Reg::P(*p), Access::Use);
+                }
+            }
+            MInst::SubSp { .. }
+            | MInst::AddSp { .. }
+            | MInst::B { .. }
+            | MInst::BCond { .. }
+            | MInst::Brk => {}
+        }
+    }
+
+    fn clobbers(&self) -> &'static [PReg] {
+        match self {
+            MInst::Call { .. } => ABI.call_clobbers,
+            _ => &[],
+        }
+    }
+
+    fn as_move(&self) -> Option<(Reg, Reg)> {
+        match *self {
+            MInst::Mov { dst, src } => Some((dst, src)),
+            _ => None,
+        }
+    }
+
+    fn load_spill(reg: Reg, slot: u32) -> Self {
+        MInst::Reload { dst: reg, slot }
+    }
+
+    fn store_spill(reg: Reg, slot: u32) -> Self {
+        MInst::Spill { src: reg, slot }
+    }
+}
+
+static ABI: Abi = Abi {
+    // Caller-saved first. `x16` to `x18` are reserved.
+    gpr: &[
+        PReg(9),
+        PReg(10),
+        PReg(11),
+        PReg(12),
+        PReg(13),
+        PReg(14),
+        PReg(15),
+        PReg(0),
+        PReg(1),
+        PReg(2),
+        PReg(3),
+        PReg(4),
+        PReg(5),
+        PReg(6),
+        PReg(7),
+        PReg(8),
+        PReg(19),
+        PReg(20),
+        PReg(21),
+        PReg(22),
+        PReg(23),
+        PReg(24),
+        PReg(25),
+        PReg(26),
+        PReg(27),
+        PReg(28),
+    ],
+    arg_regs: &ARG_REGS,
+    ret_regs: &[X0],
+    callee_saved: &[
+        PReg(19),
+        PReg(20),
+        PReg(21),
+        PReg(22),
+        PReg(23),
+        PReg(24),
+        PReg(25),
+        PReg(26),
+        PReg(27),
+        PReg(28),
+    ],
+    call_clobbers: &[
+        PReg(0),
+        PReg(1),
+        PReg(2),
+        PReg(3),
+        PReg(4),
+        PReg(5),
+        PReg(6),
+        PReg(7),
+        PReg(8),
+        PReg(9),
+        PReg(10),
+        PReg(11),
+        PReg(12),
+        PReg(13),
+        PReg(14),
+        PReg(15),
+    ],
+    stack_slot: 8,
+    stack_align: 16,
+};
+
+/// AArch64 Linux.
+pub struct Aarch64;
+
+impl Target for Aarch64 {
+    fn name(&self) -> &'static str {
+        "aarch64-linux"
+    }
+
+    fn data_model(&self) -> DataModel {
+        DataModel { va_list: 32, ..DataModel::LP64 }
+    }
+
+    fn macros(&self) -> &'static [(&'static str, &'static str)] {
+        &[("__aarch64__", "1"), ("__LP64__", "1"), ("__linux__", "1")]
+    }
+
+    fn abi(&self) -> &'static Abi {
+        &ABI
+    }
+
+    fn llvm_triple(&self) -> &'static str {
+        "aarch64-unknown-linux-gnu"
+    }
+
+    fn llvm_data_layout(&self) -> &'static str {
+        "e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128"
+    }
+
+    fn dialect(&self) -> &'static Dialect {
+        &Dialect { type_prefix: '%' }
+    }
+
+    fn gen_func(
+        &self,
+        f: &mut dyn Write,
+        _module: &ir::Module,
+        func: &ir::Function,
+    ) {
+        let (mut mf, slots) = isel::select(func);
+        let used = regalloc::allocate(&mut mf);
+        emit::emit(f, func.name, &mf, &slots, &used);
+    }
+}
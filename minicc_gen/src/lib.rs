@@ -1,9 +1,8 @@
-use std::collections::HashMap;
+//! Assembly generation from the IR.
+
 use std::io::Write;
-use std::ops::RangeFrom;
-use std::process::exit;
 
-use minicc_ast as ast;
+use minicc_ir as ir;
 
 macro_rules! o {
     ($dst:expr) => {
@@ -14,232 +13,117 @@ macro_rules! o {
     };
 }
 
-pub fn gen(f: &mut dyn Write, nodes: &[ast::Ast]) {
-    let mut g = Gen { f, label_cnt: 0.., curr_fn: Fn::new("".to_string()) };
-    for i in nodes {
-        g.gen(i);
+mod aarch64;
+mod llvm;
+mod regalloc;
+mod riscv64;
+mod wasm32;
+mod x86;
+
+pub use llvm::llvm;
+use minicc_ast::{DataModel, Symbol};
+pub use regalloc::PReg;
+
+/// Machine, ABI and assembler dialect to generate code for. Backends
+/// implement it and are listed in [`TARGETS`].
+pub trait Target: Sync {
+    /// Triple naming the target, like `x86_64-linux`.
+    fn name(&self) -> &'static str;
+
+    /// Sizes and alignments of the C types.
+    fn data_model(&self) -> DataModel;
+
+    /// Macros predefined for the target besides the common ones.
+    fn macros(&self) -> &'static [(&'static str, &'static str)];
+
+    /// Register file and integer calling convention.
+    fn abi(&self) -> &'static Abi;
+
+    /// Target triple of LLVM.
+    fn llvm_triple(&self) -> &'static str;
+
+    /// Data layout string of LLVM for the triple.
+    fn llvm_data_layout(&self) -> &'static str;
+
+    /// Assembler syntax of the default [`Target::gen`].
+    fn dialect(&self) -> &'static Dialect {
+        &Dialect { type_prefix: '@' }
+    }
+
+    /// Writes function definition `func` of `module`, which the default
+    /// [`Target::gen`] has started with its label.
+    fn gen_func(
+        &self,
+        f: &mut dyn Write,
+        module: &ir::Module,
+        func: &ir::Function,
+    );
+
+    /// Writes `module`, by default as GNU assembler source with the
+    /// definitions in order.
+    fn gen(&self, f: &mut dyn Write, module: &ir::Module) {
+        let p = self.dialect().type_prefix;
+        for func in module.funcs.iter().filter(|func| !func.is_decl()) {
+            begin_func(f, func.name, p);
+            self.gen_func(f, module, func);
+            o!(f);
+        }
+        // The stack need not be executable.
+        o!(f, "	.section	.note.GNU-stack,\"\",{}progbits", p);
     }
 }
 
-struct Gen<'a> {
-    pub f: &'a mut dyn Write,
-    pub label_cnt: RangeFrom<usize>,
-    pub curr_fn: Fn,
+/// Registers of a target, numbered as [`PReg`]s, and how functions pass
+/// integers in them. Targets without registers have none.
+#[derive(Debug)]
+pub struct Abi {
+    /// Registers the allocator may use, caller-saved first.
+    pub gpr: &'static [PReg],
+    /// Registers of the first integer arguments, in order.
+    pub arg_regs: &'static [PReg],
+    /// Registers of an integer result, the low part first if it needs more
+    /// than one.
+    pub ret_regs: &'static [PReg],
+    /// Registers a function must preserve.
+    pub callee_saved: &'static [PReg],
+    /// Allocatable registers a call may change.
+    pub call_clobbers: &'static [PReg],
+    /// Size of each argument on the stack.
+    pub stack_slot: u32,
+    /// Alignment of the stack pointer at calls.
+    pub stack_align: u32,
 }
 
-struct Fn {
-    pub ident: String,
-    pub offset: isize,
-    pub vars: HashMap<String, isize>,
+/// Syntax of the GNU assembler of a target.
+#[derive(Debug)]
+pub struct Dialect {
+    /// Prefix of symbol and section types, `%` if `@` starts a comment.
+    pub type_prefix: char,
 }
 
-impl Fn {
-    fn new(ident: String) -> Self {
-        Self { ident, offset: 0, vars: HashMap::new() }
-    }
+/// Targets code can be generated for, the first being the default.
+pub static TARGETS: [&dyn Target; 5] = [
+    &x86::Mode::I386,
+    &x86::Mode::X86_64,
+    &aarch64::Aarch64,
+    &riscv64::Riscv64,
+    &wasm32::Wasm32,
+];
+
+/// Target named by triple `name`.
+pub fn target(name: &str) -> Option<&'static dyn Target> {
+    TARGETS.into_iter().find(|t| t.name() == name)
 }
 
-impl<'a> Gen<'a> {
-    fn gen(&mut self, node: &ast::Ast) {
-        use ast::AstKind::*;
-        match &node.kind {
-            FnDecl(n) => self.fn_decl(n, node.loc),
-            CompoundStmt(n) => self.compound_stmt(n, node.loc),
-            If(n) => self.if_(n, node.loc),
-            For(n) => self.for_(n, node.loc),
-            Call(n) => self.call(n, node.loc),
-            VarDecl(n) => self.var_decl(n, node.loc),
-            Return(n) => self.return_(n, node.loc),
-            Ref(n) => self.ref_(n, node.loc),
-            IntLit(n) => self.int_lit(n, node.loc),
-            UnOp(n) => self.un_op(n, node.loc),
-            BinOp(n) => self.bin_op(n, node.loc),
-        }
-    }
-
-    fn fn_decl(&mut self, node: &ast::FnDecl, _loc: usize) {
-        self.curr_fn = Fn::new(node.ident.clone());
-
-        for (i, ident) in node.params.iter().enumerate() {
-            self.curr_fn.vars.insert(ident.clone(), 4 * (i as isize + 2));
-        }
-
-        o!(self.f, "	.text");
-        o!(self.f, ".L{}:", self.curr_fn.ident);
-        self.gen(&node.body);
-
-        o!(self.f, ".Lret{}:", self.curr_fn.ident);
-        o!(self.f, "	mov	%ebp, %esp");
-        o!(self.f, "	pop	%ebp");
-        o!(self.f, "	ret");
-        o!(self.f);
-        o!(self.f, "	.globl	{}", self.curr_fn.ident);
-        o!(self.f, "	.type	{},@function", self.curr_fn.ident);
-        o!(self.f, "{}:", self.curr_fn.ident);
-        o!(self.f, "	push	%ebp");
-        o!(self.f, "	mov	%esp, %ebp");
-        o!(self.f, "	add	${}, %esp", self.curr_fn.offset);
-        o!(self.f, "	jmp	.L{}", self.curr_fn.ident);
-    }
-
-    fn compound_stmt(&mut self, node: &ast::CompoundStmt, _loc: usize) {
-        for i in &node.items {
-            self.gen(i);
-        }
-    }
-
-    fn if_(&mut self, node: &ast::If, _loc: usize) {
-        let elsel = self.next_label();
-        let endl = self.next_label();
-
-        self.gen(&node.cond);
-        o!(self.f, "	cmp	$0, %eax");
-        o!(self.f, "	je	.Lelse{elsel}");
-
-        self.gen(&node.then);
-        o!(self.f, "	jmp	.Lend{endl}");
-
-        o!(self.f, ".Lelse{elsel}:");
-        if let Some(else_) = &node.else_ {
-            self.gen(else_);
-        }
-        o!(self.f, ".Lend{endl}:");
-    }
-
-    fn for_(&mut self, node: &ast::For, _loc: usize) {
-        let beginl = self.next_label();
-        let endl = self.next_label();
-
-        if let Some(init) = &node.init {
-            self.gen(init);
-        }
-        o!(self.f, ".Lbegin{beginl}:");
-        if let Some(cond) = &node.cond {
-            self.gen(cond);
-            o!(self.f, "	cmp	$0, %eax");
-            o!(self.f, "	je	.Lend{endl}");
-        }
-        self.gen(&node.body);
-        if let Some(inc) = &node.inc {
-            self.gen(inc);
-        }
-        o!(self.f, "	jmp	.Lbegin{beginl}");
-        o!(self.f, ".Lend{endl}:");
-    }
-
-    fn call(&mut self, node: &ast::Call, _loc: usize) {
-        for i in node.args.iter().rev() {
-            self.gen(i);
-            o!(self.f, "	push	%eax");
-        }
-        o!(self.f, "	call	{}", node.ident);
-        o!(self.f, "	add	${}, %esp", node.args.len() * 4);
-    }
-
-    fn var_decl(&mut self, node: &ast::VarDecl, _loc: usize) {
-        self.curr_fn.offset += -4;
-        self.curr_fn.vars.insert(node.ident.clone(), self.curr_fn.offset);
-    }
-
-    fn return_(&mut self, node: &ast::Return, _loc: usize) {
-        self.gen(&node.expr);
-        o!(self.f, "	jmp	.Lret{}", self.curr_fn.ident);
-    }
-
-    fn ref_(&mut self, node: &ast::Ref, loc: usize) {
-        if let Some(offset) = self.curr_fn.vars.get(&node.ident) {
-            o!(self.f, "	mov	{}(%ebp), %eax", offset);
-        } else {
-            self.err(loc, &format!("cannot find value `{}`", node.ident));
-        }
-    }
-
-    fn int_lit(&mut self, node: &ast::IntLit, _loc: usize) {
-        o!(self.f, "	mov	${}, %eax", node.val);
-    }
-
-    fn un_op(&mut self, node: &ast::UnOp, _loc: usize) {
-        self.gen(&node.expr);
-
-        match node.op {
-            ast::OpUn::Neg => {
-                o!(self.f, "	neg	%eax");
-            }
-            ast::OpUn::LogNot => {
-                o!(self.f, "	cmp	$0, %eax");
-                o!(self.f, "	sete	%al");
-                o!(self.f, "	movzb	%al, %eax");
-            }
-        }
-    }
-
-    fn bin_op(&mut self, node: &ast::BinOp, loc: usize) {
-        if node.op == ast::OpBin::Asign {
-            self.gen(&node.rhs);
-
-            if let ast::AstKind::Ref(l) = &node.lhs.kind {
-                if let Some(offset) = self.curr_fn.vars.get(&l.ident) {
-                    o!(self.f, "	mov	%eax, {}(%ebp)", offset)
-                } else {
-                    self.err(loc, &format!("cannot find value `{}`", l.ident))
-                }
-            } else {
-                self.err(loc, "expression is not assignable");
-            }
-            return;
-        }
-
-        self.gen(&node.rhs);
-        o!(self.f, "	push	%eax");
-        self.gen(&node.lhs);
-
-        o!(self.f, "	pop	%ecx");
-        match node.op {
-            ast::OpBin::Add => {
-                o!(self.f, "	add	%ecx, %eax");
-                return;
-            }
-            ast::OpBin::Sub => {
-                o!(self.f, "	sub	%ecx, %eax");
-                return;
-            }
-            ast::OpBin::Mul => {
-                o!(self.f, "	imul	%ecx, %eax");
-                return;
-            }
-            ast::OpBin::Div => {
-                o!(self.f, "	cltd");
-                o!(self.f, "	idiv	%ecx");
-                return;
-            }
-            ast::OpBin::Mod => {
-                o!(self.f, "	cltd");
-                o!(self.f, "	idiv	%ecx");
-                o!(self.f, "	mov	%edx, %eax");
-                return;
-            }
-            _ => {}
-        }
-
-        o!(self.f, "	cmp	%ecx, %eax");
-        match node.op {
-            ast::OpBin::Lt => o!(self.f, "	setl	%al"),
-            ast::OpBin::Gt => o!(self.f, "	setg	%al"),
-            ast::OpBin::Le => o!(self.f, "	setle	%al"),
-            ast::OpBin::Ge => o!(self.f, "	setge	%al"),
-            ast::OpBin::Eq => o!(self.f, "	sete	%al"),
-            ast::OpBin::Ne => o!(self.f, "	setne	%al"),
-            _ => unreachable!("{:?}", node.op),
-        }
-        o!(self.f, "	movzb	%al, %eax");
-    }
-
-    fn next_label(&mut self) -> usize {
-        self.label_cnt.next().unwrap()
-    }
+/// Writes code for `target` of the function definitions of `module`, which
+/// must have been lowered for its data model.
+pub fn gen(f: &mut dyn Write, module: &ir::Module, target: &dyn Target) {
+    target.gen(f, module);
+}
 
-    fn err(&self, loc: usize, msg: &str) -> ! {
-        eprintln!("{}: {}", loc, msg);
-        exit(1);
-    }
+fn begin_func(f: &mut dyn Write, name: Symbol, type_prefix: char) {
+    o!(f, "	.text");
+    o!(f, "	.globl	{}", name);
+    o!(f, "	.type	{}, {}function", name, type_prefix);
+    o!(f, "{}:", name);
 }
@@ -0,0 +1,421 @@
+//! LLVM IR in the textual form, for comparison with LLVM's optimiser.
+//!
+//! It is written from `minicc_ir` rather than from the checked AST, so that
+//! the conversions, short-circuit evaluation and `va_list` ABI are lowered
+//! once, as for the other backends, and so that at each optimisation level
+//! LLVM is given what minicc's own passes made of the program. At `-O0` every
+//! local is in a slot, giving the `alloca`, `load` and `store` form that
+//! `opt -passes=mem2reg` expects; from `-O1` minicc's `mem2reg` has already
+//! promoted them.
+//!
+//! Slots become `alloca`s typed like their loads and stores if those agree,
+//! so that `opt -passes=mem2reg` promotes them, and byte arrays otherwise.
+//! Comparisons are `icmp` zero extended to `i32`, and pointers are opaque
+//! `ptr`s.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+
+use minicc_ast::Symbol;
+use minicc_ir::{
+    BinOp, BlockId, Callee, CmpOp, ConvOp, Function, Inst, Module, Reg,
+    Signature, SlotId, Term, Ty, Value,
+};
+
+use crate::Target;
+
+/// Writes `module`, lowered for `target`, as LLVM IR.
+pub fn llvm(f: &mut dyn Write, module: &Module, target: &dyn Target) {
+    o!(f, "target datalayout = \"{}\"", target.llvm_data_layout());
+    o!(f, "target triple = \"{}\"", target.llvm_triple());
+    let ptr_size = target.data_model().long as u32;
+    let mut va = false;
+    for func in &module.funcs {
+        o!(f);
+        if func.is_decl() {
+            o!(
+                f,
+                "declare {} @{}{}",
+                RetTy(func.sig.ret),
+                func.name,
+                Params(&func.sig)
+            );
+        } else {
+            Func::new(f, func, ptr_size).emit();
+        }
+        va |=
+            func.blocks.iter().flat_map(|b| &b.insts).any(|i| {
+                matches!(i, Inst::VaStart { .. } | Inst::VaEnd { .. })
+            });
+    }
+    if va {
+        o!(f);
+        o!(f, "declare void @llvm.va_start(ptr)");
+        o!(f, "declare void @llvm.va_end(ptr)");
+    }
+}
+
+/// What a register stands for where LLVM has no instruction defining it.
+#[derive(Debug, Clone, Copy)]
+enum Operand {
+    Value(Value),
+    Slot(SlotId),
+    Func(Symbol),
+}
+
+/// Operand `.1` of type `.0`.
+struct Typed(Ty, Operand);
+
+impl Display for Typed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            Operand::Value(Value::Reg(r)) => write!(f, "%r{}", r.0),
+            Operand::Value(Value::Imm(0)) if self.0 == Ty::Ptr => {
+                f.write_str("null")
+            }
+            Operand::Value(Value::Imm(i)) if self.0 == Ty::Ptr => {
+                write!(f, "inttoptr (i64 {} to ptr)", i)
+            }
+            Operand::Value(Value::Imm(i)) => write!(f, "{}", i),
+            Operand::Slot(s) => write!(f, "%s{}", s.0),
+            Operand::Func(name) => write!(f, "@{}", name),
+        }
+    }
+}
+
+struct RetTy(Option<Ty>);
+
+impl Display for RetTy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(ty) => f.write_str(ty_name(ty)),
+            None => f.write_str("void"),
+        }
+    }
+}
+
+/// Parameter types of a signature, in parentheses.
+struct Params<'a>(&'a Signature);
+
+impl Display for Params<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut params: Vec<_> =
+            self.0.params.iter().map(|ty| ty_name(*ty)).collect();
+        if self.0.variadic {
+            params.push("...");
+        }
+        write!(f, "({})", params.join(", "))
+    }
+}
+
+fn ty_name(ty: Ty) -> &'static str {
+    match ty {
+        Ty::I8 => "i8",
+        Ty::I16 => "i16",
+        Ty::I32 => "i32",
+        Ty::I64 => "i64",
+        Ty::Ptr => "ptr",
+    }
+}
+
+struct Func<'a> {
+    f: &'a mut dyn Write,
+    func: &'a Function,
+    /// Copies, slot and function addresses, which uses refer to directly.
+    aliases: HashMap<Reg, Operand>,
+    /// Type of each slot, `None` for an array of bytes.
+    slot_tys: Vec<Option<Ty>>,
+}
+
+impl<'a> Func<'a> {
+    fn new(f: &'a mut dyn Write, func: &'a Function, ptr_size: u32) -> Self {
+        let mut aliases = HashMap::new();
+        for inst in func.blocks.iter().flat_map(|b| &b.insts) {
+            let (dst, op) = match *inst {
+                Inst::Copy { dst, val } => (dst, Operand::Value(val)),
+                Inst::SlotAddr { dst, slot } => (dst, Operand::Slot(slot)),
+                Inst::FuncAddr { dst, name } => (dst, Operand::Func(name)),
+                _ => continue,
+            };
+            aliases.insert(dst, op);
+        }
+        let mut func = Self { f, func, aliases, slot_tys: Vec::new() };
+
+        let size = |ty| match ty {
+            Ty::I8 => 1,
+            Ty::I16 => 2,
+            Ty::I32 => 4,
+            Ty::I64 => 8,
+            Ty::Ptr => ptr_size,
+        };
+        let mut tys: Vec<Option<Option<Ty>>> =
+            vec![None; func.func.slots.len()];
+        for inst in func.func.blocks.iter().flat_map(|b| &b.insts) {
+            let (ty, addr) = match *inst {
+                Inst::Load { dst, addr } => (func.func.reg_ty(dst), addr),
+                Inst::Store { ty, addr, .. } => (ty, addr),
+                _ => continue,
+            };
+            if let Operand::Slot(s) = func.resolve(addr) {
+                let slot = &mut tys[s.0 as usize];
+                *slot = match *slot {
+                    None => Some(Some(ty)),
+                    Some(Some(t)) if t == ty => Some(Some(ty)),
+                    _ => Some(None),
+                };
+            }
+        }
+        func.slot_tys = (tys.into_iter().enumerate())
+            .map(|(i, ty)| {
+                ty.flatten().filter(|ty| size(*ty) == func.func.slots[i].size)
+            })
+            .collect();
+        func
+    }
+
+    /// What `val` stands for, following copies.
+    fn resolve(&self, mut val: Value) -> Operand {
+        while let Value::Reg(r) = val {
+            match self.aliases.get(&r) {
+                Some(Operand::Value(v)) => val = *v,
+                Some(op) => return *op,
+                None => break,
+            }
+        }
+        Operand::Value(val)
+    }
+
+    fn typed(&self, ty: Ty, val: Value) -> Typed {
+        Typed(ty, self.resolve(val))
+    }
+
+    fn emit(&mut self) {
+        let func = self.func;
+        let params: Vec<_> = (func.params.iter())
+            .map(|p| format!("{} %r{}", ty_name(func.reg_ty(*p)), p.0))
+            .chain(func.sig.variadic.then(|| "...".to_string()))
+            .collect();
+        o!(
+            self.f,
+            "define {} @{}({}) {{",
+            RetTy(func.sig.ret),
+            func.name,
+            params.join(", ")
+        );
+
+        // The entry block must have no predecessors.
+        let entry_is_target = (func.blocks.iter())
+            .filter_map(|b| b.term.as_ref())
+            .any(|t| t.succs().contains(&BlockId(0)));
+        if entry_is_target {
+            o!(self.f, "entry:");
+        } else {
+            o!(self.f, "bb0:");
+        }
+        for (i, slot) in func.slots.iter().enumerate() {
+            let ty = match self.slot_tys[i] {
+                Some(ty) => ty_name(ty).to_string(),
+                None => format!("[{} x i8]", slot.size),
+            };
+            o!(
+                self.f,
+                "  %s{} = alloca {}, align {}",
+                i,
+                ty,
+                slot.align.max(1)
+            );
+        }
+        if entry_is_target {
+            o!(self.f, "  br label %bb0");
+        }
+
+        for (i, block) in func.blocks.iter().enumerate() {
+            if i > 0 || entry_is_target {
+                o!(self.f, "bb{}:", i);
+            }
+            for inst in &block.insts {
+                self.inst(inst);
+            }
+            self.term(BlockId(i as u32), block.term.as_ref().unwrap());
+        }
+        o!(self.f, "}}");
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        let func = self.func;
+        match *inst {
+            Inst::Copy { .. }
+            | Inst::SlotAddr { .. }
+            | Inst::FuncAddr { .. } => {}
+            Inst::Bin { op, dst, lhs, rhs } => {
+                let ty = func.reg_ty(dst);
+                let op = match op {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    BinOp::Mul => "mul",
+                    BinOp::SDiv => "sdiv",
+                    BinOp::UDiv => "udiv",
+                    BinOp::SRem => "srem",
+                    BinOp::URem => "urem",
+                };
+                o!(
+                    self.f,
+                    "  %r{} = {} {} {}, {}",
+                    dst.0,
+                    op,
+                    ty_name(ty),
+                    self.typed(ty, lhs),
+                    self.typed(ty, rhs)
+                );
+            }
+            Inst::Cmp { op, ty, dst, lhs, rhs } => {
+                let op = match op {
+                    CmpOp::Eq => "eq",
+                    CmpOp::Ne => "ne",
+                    CmpOp::SLt => "slt",
+                    CmpOp::SLe => "sle",
+                    CmpOp::SGt => "sgt",
+                    CmpOp::SGe => "sge",
+                    CmpOp::ULt => "ult",
+                    CmpOp::ULe => "ule",
+                    CmpOp::UGt => "ugt",
+                    CmpOp::UGe => "uge",
+                };
+                o!(
+                    self.f,
+                    "  %r{}.b = icmp {} {} {}, {}",
+                    dst.0,
+                    op,
+                    ty_name(ty),
+                    self.typed(ty, lhs),
+                    self.typed(ty, rhs)
+                );
+                o!(self.f, "  %r{} = zext i1 %r{}.b to i32", dst.0, dst.0);
+            }
+            Inst::Conv { op, dst, from, val } => {
+                let op = match op {
+                    ConvOp::SExt => "sext",
+                    ConvOp::ZExt => "zext",
+                    ConvOp::Trunc => "trunc",
+                    ConvOp::PtrToInt => "ptrtoint",
+                    ConvOp::IntToPtr => "inttoptr",
+                };
+                o!(
+                    self.f,
+                    "  %r{} = {} {} {} to {}",
+                    dst.0,
+                    op,
+                    ty_name(from),
+                    self.typed(from, val),
+                    ty_name(func.reg_ty(dst))
+                );
+            }
+            Inst::Load { dst, addr } => {
+                let ty = func.reg_ty(dst);
+                o!(
+                    self.f,
+                    "  %r{} = load {}, ptr {}",
+                    dst.0,
+                    ty_name(ty),
+                    self.typed(Ty::Ptr, addr)
+                );
+            }
+            Inst::Store { ty, val, addr } => o!(
+                self.f,
+                "  store {} {}, ptr {}",
+                ty_name(ty),
+                self.typed(ty, val),
+                self.typed(Ty::Ptr, addr)
+            ),
+            Inst::Call { dst, ref callee, ref sig, ref args } => {
+                let args: Vec<_> = (args.iter())
+                    .map(|&(ty, v)| {
+                        format!("{} {}", ty_name(ty), self.typed(ty, v))
+                    })
+                    .collect();
+                let callee = match *callee {
+                    Callee::Direct(name) => format!("@{}", name),
+                    Callee::Indirect(v) => self.typed(Ty::Ptr, v).to_string(),
+                };
+                // Variadic calls need the type of the callee.
+                let ty = if sig.variadic {
+                    format!("{} {}", RetTy(sig.ret), Params(sig))
+                } else {
+                    RetTy(sig.ret).to_string()
+                };
+                let dst =
+                    dst.map_or(String::new(), |d| format!("%r{} = ", d.0));
+                o!(
+                    self.f,
+                    "  {}call {} {}({})",
+                    dst,
+                    ty,
+                    callee,
+                    args.join(", ")
+                );
+            }
+            Inst::VaStart { ap } => o!(
+                self.f,
+                "  call void @llvm.va_start(ptr {})",
+                self.typed(Ty::Ptr, ap)
+            ),
+            Inst::VaArg { dst, ap } => o!(
+                self.f,
+                "  %r{} = va_arg ptr {}, {}",
+                dst.0,
+                self.typed(Ty::Ptr, ap),
+                ty_name(func.reg_ty(dst))
+            ),
+            Inst::VaEnd { ap } => o!(
+                self.f,
+                "  call void @llvm.va_end(ptr {})",
+                self.typed(Ty::Ptr, ap)
+            ),
+            Inst::Phi { dst, ref args } => {
+                let ty = func.reg_ty(dst);
+                let args: Vec<_> = (args.iter())
+                    .map(|&(b, v)| {
+                        format!("[ {}, %bb{} ]", self.typed(ty, v), b.0)
+                    })
+                    .collect();
+                o!(
+                    self.f,
+                    "  %r{} = phi {} {}",
+                    dst.0,
+                    ty_name(ty),
+                    args.join(", ")
+                );
+            }
+        }
+    }
+
+    fn term(&mut self, b: BlockId, term: &Term) {
+        match *term {
+            Term::Ret(None) => o!(self.f, "  ret void"),
+            Term::Ret(Some((ty, val))) => {
+                o!(self.f, "  ret {} {}", ty_name(ty), self.typed(ty, val))
+            }
+            Term::Jmp(to) => o!(self.f, "  br label %bb{}", to.0),
+            Term::Br { ty, cond, then, else_ } => {
+                let zero = if ty == Ty::Ptr { "null" } else { "0" };
+                o!(
+                    self.f,
+                    "  %bb{}.c = icmp ne {} {}, {}",
+                    b.0,
+                    ty_name(ty),
+                    self.typed(ty, cond),
+                    zero
+                );
+                o!(
+                    self.f,
+                    "  br i1 %bb{}.c, label %bb{}, label %bb{}",
+                    b.0,
+                    then.0,
+                    else_.0
+                );
+            }
+            Term::Unreachable => o!(self.f, "  unreachable"),
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! Register allocation by linear scan over live intervals (Poletto and
+//! Sarkar, "Linear Scan Register Allocation").
+//!
+//! Each virtual register gets a single interval from its first to its last
+//! live position, and physical registers written or read by instructions,
+//! e.g. the operands of `idiv` or registers clobbered by a call, are live in
+//! short fixed ranges that a virtual register may not overlap. A virtual
+//! register that does not fit is spilled to a frame slot: every access then
+//! goes through a new short-lived register and the allocation is redone.
+
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reg {
+    /// Virtual register.
+    V(u32),
+    /// Physical register, numbered by the target.
+    P(PReg),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PReg(pub u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Use,
+    Def,
+    /// Read, then written, e.g. the destination of a two-address `add`.
+    UseDef,
+}
+
+/// Instruction of a target.
+pub trait MachInst: Sized {
+    /// Calls `f` with each register operand and how it is accessed.
+    fn visit_regs(&mut self, f: &mut dyn FnMut(&mut Reg, Access));
+
+    /// Physical registers written besides the operands, e.g. by a call.
+    fn clobbers(&self) -> &'static [PReg] {
+        &[]
+    }
+
+    /// `(dst, src)` of a register copy, which is free if both get the same
+    /// register.
+    fn as_move(&self) -> Option<(Reg, Reg)> {
+        None
+    }
+
+    /// Loads `reg` from spill slot `slot`.
+    fn load_spill(reg: Reg, slot: u32) -> Self;
+
+    /// Stores `reg` to spill slot `slot`.
+    fn store_spill(reg: Reg, slot: u32) -> Self;
+}
+
+pub struct MFunc<I> {
+    pub blocks: Vec<MBlock<I>>,
+    /// Registers each virtual register may be assigned.
+    pub classes: Vec<&'static [PReg]>,
+    pub spill_slots: u32,
+}
+
+pub struct MBlock<I> {
+    pub insts: Vec<I>,
+    pub succs: Vec<usize>,
+}
+
+impl<I> MFunc<I> {
+    pub fn new() -> Self {
+        Self { blocks: Vec::new(), classes: Vec::new(), spill_slots: 0 }
+    }
+
+    pub fn new_vreg(&mut self, class: &'static [PReg]) -> Reg {
+        self.classes.push(class);
+        Reg::V(self.classes.len() as u32 - 1)
+    }
+}
+
+impl<I> Default for MFunc<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Replaces every virtual register with a physical one from its class,
+/// preferring registers earlier in the class, and returns the physical
+/// registers used.
+pub fn allocate<I: MachInst>(func: &mut MFunc<I>) -> Vec<PReg> {
+    // Registers loading or storing a spilled value live for one instruction
+    // and must not be spilled again.
+    let mut unspillable = HashSet::new();
+    loop {
+        let live = Liveness::new(func);
+        match scan(func, &live, &unspillable) {
+            Ok(assigned) => return rewrite(func, &assigned),
+            Err(spilled) => spill(func, &spilled, &mut unspillable),
+        }
+    }
+}
+
+/// Variable of the liveness analysis: virtual registers, then physical ones.
+fn var(func_vregs: usize, reg: Reg) -> usize {
+    match reg {
+        Reg::V(v) => v as usize,
+        Reg::P(p) => func_vregs + p.0 as usize,
+    }
+}
+
+struct Liveness {
+    /// `[start, end]` of each virtual register, in positions where
+    /// instruction `i` reads at `2 * i` and writes at `2 * i + 1`.
+    intervals: Vec<Option<(usize, usize)>>,
+    /// Ranges where each physical register is live.
+    fixed: Vec<Vec<(usize, usize)>>,
+    /// Register each virtual register is copied from or to.
+    hints: Vec<Option<Reg>>,
+}
+
+impl Liveness {
+    fn new<I: MachInst>(func: &mut MFunc<I>) -> Self {
+        let nv = func.classes.len();
+        let nvars = nv + 256;
+        let n = func.blocks.len();
+
+        // Registers read before written, and written, in each block.
+        let mut gen = vec![HashSet::new(); n];
+        let mut kill = vec![HashSet::new(); n];
+        let mut hints = vec![None; nv];
+        for (b, block) in func.blocks.iter_mut().enumerate() {
+            for inst in &mut block.insts {
+                inst.visit_regs(&mut |r, access| {
+                    let v = var(nv, *r);
+                    if access != Access::Def && !kill[b].contains(&v) {
+                        gen[b].insert(v);
+                    }
+                    if access != Access::Use {
+                        kill[b].insert(v);
+                    }
+                });
+                for p in inst.clobbers() {
+                    kill[b].insert(var(nv, Reg::P(*p)));
+                }
+                if let Some((dst, src)) = inst.as_move() {
+                    for (a, b) in [(dst, src), (src, dst)] {
+                        if let Reg::V(v) = a {
+                            hints[v as usize].get_or_insert(b);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut live_in: Vec<HashSet<usize>> = gen.clone();
+        let mut live_out: Vec<HashSet<usize>> = vec![HashSet::new(); n];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for b in (0..n).rev() {
+                let out: HashSet<usize> = func.blocks[b]
+                    .succs
+                    .iter()
+                    .flat_map(|s| live_in[*s].iter().copied())
+                    .collect();
+                let mut inn = gen[b].clone();
+                inn.extend(out.iter().filter(|v| !kill[b].contains(v)));
+                if inn.len() != live_in[b].len() {
+                    live_in[b] = inn;
+                    changed = true;
+                }
+                live_out[b] = out;
+            }
+        }
+
+        let mut intervals: Vec<Option<(usize, usize)>> = vec![None; nv];
+        let mut fixed = vec![Vec::new(); nvars - nv];
+        let mut touch = |v: usize, pos: usize| {
+            if v < nv {
+                let iv = &mut intervals[v];
+                *iv = Some(match *iv {
+                    Some((s, e)) => (s.min(pos), e.max(pos)),
+                    None => (pos, pos),
+                });
+            }
+        };
+
+        let mut k = 0;
+        for (b, block) in func.blocks.iter_mut().enumerate() {
+            if block.insts.is_empty() {
+                continue;
+            }
+            let (start, end) = (2 * k, 2 * (k + block.insts.len()) - 1);
+            for v in &live_in[b] {
+                touch(*v, start);
+            }
+            for v in &live_out[b] {
+                touch(*v, end);
+            }
+
+            // Physical registers precisely, walking backwards from the end
+            // of the block, where `live` holds the end of each live range.
+            let mut live: Vec<Option<usize>> = vec![None; nvars - nv];
+            for v in live_out[b].iter().filter(|v| **v >= nv) {
+                live[v - nv] = Some(end);
+            }
+            for (i, inst) in block.insts.iter_mut().enumerate().rev() {
+                let pos = 2 * (k + i);
+                let mut defs = Vec::new();
+                let mut uses = Vec::new();
+                inst.visit_regs(&mut |r, access| {
+                    let v = var(nv, *r);
+                    if access != Access::Use {
+                        defs.push(v);
+                    }
+                    if access != Access::Def {
+                        uses.push(v);
+                    }
+                });
+                defs.extend(
+                    inst.clobbers().iter().map(|p| var(nv, Reg::P(*p))),
+                );
+
+                for &v in &defs {
+                    touch(v, pos + 1);
+                    if v >= nv {
+                        let end = live[v - nv].take().unwrap_or(pos + 1);
+                        fixed[v - nv].push((pos + 1, end));
+                    }
+                }
+                for &v in &uses {
+                    touch(v, pos);
+                    if v >= nv {
+                        live[v - nv].get_or_insert(pos);
+                    }
+                }
+            }
+            for (p, end) in live.iter().enumerate() {
+                if let Some(end) = end {
+                    fixed[p].push((start, *end));
+                }
+            }
+            k += block.insts.len();
+        }
+
+        Self { intervals, fixed, hints }
+    }
+
+    /// Whether physical register `p` is live anywhere in `[s, e]`.
+    fn fixed_overlaps(&self, p: PReg, s: usize, e: usize) -> bool {
+        self.fixed[p.0 as usize].iter().any(|&(fs, fe)| fs <= e && s <= fe)
+    }
+}
+
+/// Assigns registers to the intervals in order of their start, or returns
+/// the virtual registers to spill.
+fn scan<I>(
+    func: &MFunc<I>,
+    live: &Liveness,
+    unspillable: &HashSet<u32>,
+) -> Result<Vec<PReg>, Vec<u32>> {
+    let mut order: Vec<(usize, usize, u32)> = live
+        .intervals
+        .iter()
+        .enumerate()
+        .filter_map(|(v, iv)| iv.map(|(s, e)| (s, e, v as u32)))
+        .collect();
+    order.sort();
+
+    let mut assigned: Vec<Option<PReg>> = vec![None; func.classes.len()];
+    // `(end, vreg)` of the intervals holding a register.
+    let mut active: Vec<(usize, u32)> = Vec::new();
+    let mut spilled = Vec::new();
+
+    for (s, e, v) in order {
+        active.retain(|&(end, _)| end >= s);
+        let class = func.classes[v as usize];
+        let free = |p: PReg, active: &[(usize, u32)]| {
+            !active.iter().any(|(_, a)| assigned[*a as usize] == Some(p))
+                && !live.fixed_overlaps(p, s, e)
+        };
+
+        let hint = match live.hints[v as usize] {
+            Some(Reg::P(p)) => Some(p),
+            Some(Reg::V(h)) => assigned[h as usize],
+            None => None,
+        };
+        let pick = hint
+            .filter(|p| class.contains(p) && free(*p, &active))
+            .or_else(|| class.iter().copied().find(|p| free(*p, &active)));
+        if let Some(p) = pick {
+            assigned[v as usize] = Some(p);
+            active.push((e, v));
+            continue;
+        }
+
+        // Take the register of the interval ending last, if it ends after
+        // this one and its register suits this one.
+        let victim = active
+            .iter()
+            .filter(|(_, a)| {
+                let p = assigned[*a as usize].unwrap();
+                !unspillable.contains(a)
+                    && class.contains(&p)
+                    && !live.fixed_overlaps(p, s, e)
+            })
+            .max()
+            .copied();
+        match victim {
+            Some((end, a)) if end > e || unspillable.contains(&v) => {
+                assigned[v as usize] = assigned[a as usize].take();
+                active.retain(|x| x.1 != a);
+                active.push((e, v));
+                spilled.push(a);
+            }
+            _ if !unspillable.contains(&v) => spilled.push(v),
+            _ => panic!("no register for v{} in [{}, {}]", v, s, e),
+        }
+    }
+
+    if spilled.is_empty() {
+        Ok(assigned.into_iter().map(|p| p.unwrap_or(PReg(0))).collect())
+    } else {
+        Err(spilled)
+    }
+}
+
+/// Gives each spilled register a slot, loaded before and stored after each
+/// instruction accessing it through a new register.
+fn spill<I: MachInst>(
+    func: &mut MFunc<I>,
+    spilled: &[u32],
+    unspillable: &mut HashSet<u32>,
+) {
+    let mut slot = vec![None; func.classes.len()];
+    for &v in spilled {
+        slot[v as usize] = Some(func.spill_slots);
+        func.spill_slots += 1;
+    }
+
+    let classes = &mut func.classes;
+    for block in &mut func.blocks {
+        let insts = std::mem::take(&mut block.insts);
+        for mut inst in insts {
+            let mut loads = Vec::new();
+            let mut stores = Vec::new();
+            inst.visit_regs(&mut |r, access| {
+                let Reg::V(v) = *r else {
+                    return;
+                };
+                let Some(s) = slot[v as usize] else {
+                    return;
+                };
+                classes.push(classes[v as usize]);
+                let tmp = classes.len() as u32 - 1;
+                unspillable.insert(tmp);
+                *r = Reg::V(tmp);
+                if access != Access::Def {
+                    loads.push(I::load_spill(*r, s));
+                }
+                if access != Access::Use {
+                    stores.push(I::store_spill(*r, s));
+                }
+            });
+            block.insts.extend(loads);
+            block.insts.push(inst);
+            block.insts.extend(stores);
+        }
+    }
+}
+
+fn rewrite<I: MachInst>(func: &mut MFunc<I>, assigned: &[PReg]) -> Vec<PReg> {
+    let mut used = Vec::new();
+    for inst in func.blocks.iter_mut().flat_map(|b| &mut b.insts) {
+        inst.visit_regs(&mut |r, _| {
+            if let Reg::V(v) = *r {
+                *r = Reg::P(assigned[v as usize]);
+            }
+            if let Reg::P(p) = *r {
+                if !used.contains(&p) {
+                    used.push(p);
+                }
+            }
+        });
+    }
+    used.sort();
+    used
+}
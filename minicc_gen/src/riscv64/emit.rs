@@ -0,0 +1,275 @@
+use super::*;
+use crate::regalloc::MFunc;
+
+/// Writes allocated `mf`, with the prologue saving the callee-saved registers
+/// in `used` and laying out `slots`.
+pub(super) fn emit(
+    f: &mut dyn Write,
+    name: Symbol,
+    variadic: bool,
+    mf: &MFunc<MInst>,
+    slots: &[ir::Slot],
+    used: &[PReg],
+) {
+    let saved: Vec<PReg> =
+        ABI.callee_saved.iter().copied().filter(|r| used.contains(r)).collect();
+
+    // `s0` points to the arguments on the stack, preceded by the argument
+    // registers of a variadic function so that all are contiguous. Below it
+    // are the saved `ra` and `s0`, the callee-saved registers, then slots and
+    // spill slots.
+    let mut size = 16 + 8 * saved.len() as u32;
+    let mut offsets = Vec::new();
+    for slot in slots {
+        size = (size + slot.size).next_multiple_of(slot.align.max(1));
+        offsets.push(-(size as i32));
+    }
+    let spill_base = size;
+    size += 8 * mf.spill_slots;
+    let frame = size.next_multiple_of(16) - 16;
+
+    let mut e = Emitter {
+        f,
+        name,
+        variadic,
+        saved: &saved,
+        slots: offsets,
+        spill_base,
+    };
+
+    if variadic {
+        o!(e.f, "	addi	sp, sp, -64");
+        for (i, r) in ARG_REGS.iter().enumerate() {
+            o!(e.f, "	sd	{}, {}(sp)", reg(Reg::P(*r)), 8 * i);
+        }
+    }
+    o!(e.f, "	addi	sp, sp, -16");
+    o!(e.f, "	sd	ra, 8(sp)");
+    o!(e.f, "	sd	s0, 0(sp)");
+    o!(e.f, "	addi	s0, sp, 16");
+    e.sp(-(frame as i64));
+    for (i, r) in saved.iter().enumerate() {
+        let addr = Addr::new(Base::Frame(-16 - 8 * (i as i32 + 1)));
+        e.mem("sd", Reg::P(*r), addr);
+    }
+
+    for (b, block) in mf.blocks.iter().enumerate() {
+        if b > 0 {
+            o!(e.f, ".L{}.{}:", name, b);
+        }
+        let mut insts = block.insts.iter().peekable();
+        while let Some(inst) = insts.next() {
+            // Branch on the inverse condition if the taken target follows.
+            if let MInst::B { cc, lhs, rhs, target } = *inst {
+                if let Some(MInst::J { target: other }) = insts.peek() {
+                    if target == b + 1 {
+                        e.inst(&MInst::B {
+                            cc: cc.inverse(),
+                            lhs,
+                            rhs,
+                            target: *other,
+                        });
+                        insts.next();
+                        continue;
+                    }
+                }
+            }
+            if matches!(*inst, MInst::J { target } if target == b + 1) {
+                continue;
+            }
+            e.inst(inst);
+        }
+    }
+}
+
+struct Emitter<'a> {
+    f: &'a mut dyn Write,
+    name: Symbol,
+    variadic: bool,
+    saved: &'a [PReg],
+    /// Offset of each slot from `s0`.
+    slots: Vec<i32>,
+    /// Offset below `s0` where the spill slots start.
+    spill_base: u32,
+}
+
+impl Emitter<'_> {
+    fn inst(&mut self, inst: &MInst) {
+        match *inst {
+            MInst::Mv { dst, src } if dst == src => {}
+            MInst::Mv { dst, src } => {
+                o!(self.f, "	mv	{}, {}", reg(dst), reg(src))
+            }
+            MInst::Li { dst, imm } => o!(self.f, "	li	{}, {}", reg(dst), imm),
+            MInst::La { dst, sym } => o!(self.f, "	la	{}, {}", reg(dst), sym),
+            MInst::Load { dst, addr, size } => {
+                let op = match size {
+                    Size::B => "lbu",
+                    Size::H => "lhu",
+                    Size::W => "lwu",
+                    Size::D => "ld",
+                };
+                self.mem(op, dst, addr);
+            }
+            MInst::Store { src, addr, size } => {
+                let op = match size {
+                    Size::B => "sb",
+                    Size::H => "sh",
+                    Size::W => "sw",
+                    Size::D => "sd",
+                };
+                self.mem(op, src, addr);
+            }
+            MInst::Lea { dst, addr } => {
+                let (base, disp) = self.base(addr);
+                self.add_imm(&reg(dst), &base, disp as i64);
+            }
+            MInst::Alu { op, dst, lhs, rhs: Opnd::Reg(rhs) } => o!(
+                self.f,
+                "	{}	{}, {}, {}",
+                op.name(),
+                reg(dst),
+                reg(lhs),
+                reg(rhs)
+            ),
+            MInst::Alu { op, dst, lhs, rhs: Opnd::Imm(i) } => {
+                let op = match op {
+                    AluOp::Add => "addi",
+                    AluOp::Xor => "xori",
+                    AluOp::Slt => "slti",
+                    AluOp::Sltu => "sltiu",
+                    op => panic!("{} takes no immediate", op.name()),
+                };
+                o!(self.f, "	{}	{}, {}, {}", op, reg(dst), reg(lhs), i);
+            }
+            MInst::Ext { dst, src, from, signed } => {
+                let (d, s) = (reg(dst), reg(src));
+                let shift = |f: &mut dyn Write, right, n| {
+                    o!(f, "	slli	{}, {}, {}", d, s, n);
+                    o!(f, "	{}	{}, {}, {}", right, d, d, n);
+                };
+                match (from, signed) {
+                    (Size::B, true) => shift(self.f, "srai", 56),
+                    (Size::H, true) => shift(self.f, "srai", 48),
+                    (Size::W, true) => o!(self.f, "	sext.w	{}, {}", d, s),
+                    (Size::B, false) => o!(self.f, "	andi	{}, {}, 255", d, s),
+                    (Size::H, false) => shift(self.f, "srli", 48),
+                    (Size::W, false) => shift(self.f, "srli", 32),
+                    (Size::D, _) => o!(self.f, "	mv	{}, {}", d, s),
+                }
+            }
+            MInst::SetZ { zero, dst, src } => {
+                let op = if zero { "seqz" } else { "snez" };
+                o!(self.f, "	{}	{}, {}", op, reg(dst), reg(src));
+            }
+            MInst::SubSp { n } => self.sp(-(n as i64)),
+            MInst::AddSp { n } => self.sp(n as i64),
+            MInst::StoreArg { src, offset } => {
+                self.mem_at("sd", src, "sp", offset as i32)
+            }
+            MInst::Call { target: Callee::Sym(s), .. } => {
+                o!(self.f, "	call	{}", s)
+            }
+            MInst::Call { target: Callee::Reg(r), .. } => {
+                o!(self.f, "	jalr	{}", reg(r))
+            }
+            MInst::J { target } => {
+                o!(self.f, "	j	.L{}.{}", self.name, target)
+            }
+            MInst::B { cc, lhs, rhs, target } => o!(
+                self.f,
+                "	b{}	{}, {}, .L{}.{}",
+                cc.name(),
+                reg(lhs),
+                reg(rhs),
+                self.name,
+                target
+            ),
+            MInst::Ret { .. } => {
+                for (i, r) in self.saved.iter().enumerate() {
+                    let addr = Addr::new(Base::Frame(-16 - 8 * (i as i32 + 1)));
+                    self.mem("ld", Reg::P(*r), addr);
+                }
+                o!(self.f, "	addi	sp, s0, -16");
+                o!(self.f, "	ld	ra, 8(sp)");
+                o!(self.f, "	ld	s0, 0(sp)");
+                let n = if self.variadic { 16 + 64 } else { 16 };
+                o!(self.f, "	addi	sp, sp, {}", n);
+                o!(self.f, "	ret");
+            }
+            MInst::Unimp => o!(self.f, "	unimp"),
+            MInst::Reload { dst, slot } => {
+                let addr = self.spill(slot);
+                self.mem("ld", dst, addr);
+            }
+            MInst::Spill { src, slot } => {
+                let addr = self.spill(slot);
+                self.mem("sd", src, addr);
+            }
+        }
+    }
+
+    /// Writes load or store `op` of `r` at `addr`.
+    fn mem(&mut self, op: &str, r: Reg, addr: Addr) {
+        let (base, disp) = self.base(addr);
+        self.mem_at(op, r, &base, disp);
+    }
+
+    /// Writes load or store `op` of `r` at `disp` from `base`, through `t6`
+    /// if the offset does not fit in the instruction.
+    fn mem_at(&mut self, op: &str, r: Reg, base: &str, disp: i32) {
+        if (-2048..2048).contains(&disp) {
+            o!(self.f, "	{}	{}, {}({})", op, reg(r), disp, base);
+        } else {
+            let t = reg(Reg::P(SCRATCH));
+            o!(self.f, "	li	{}, {}", t, disp);
+            o!(self.f, "	add	{}, {}, {}", t, t, base);
+            o!(self.f, "	{}	{}, 0({})", op, reg(r), t);
+        }
+    }
+
+    /// Base register and offset of `addr`.
+    fn base(&self, addr: Addr) -> (String, i32) {
+        let fp = reg(Reg::P(FP));
+        match addr.base {
+            Base::Reg(r) => (reg(r), addr.disp),
+            Base::Slot(s) => (fp, self.slots[s as usize] + addr.disp),
+            Base::Frame(off) => (fp, off + addr.disp),
+        }
+    }
+
+    fn spill(&self, slot: u32) -> Addr {
+        let off = self.spill_base + 8 * (slot + 1);
+        Addr::new(Base::Frame(-(off as i32)))
+    }
+
+    /// `dst = base + imm`
+    fn add_imm(&mut self, dst: &str, base: &str, imm: i64) {
+        if (-2048..2048).contains(&imm) {
+            o!(self.f, "	addi	{}, {}, {}", dst, base, imm);
+        } else {
+            let t = reg(Reg::P(SCRATCH));
+            o!(self.f, "	li	{}, {}", t, imm);
+            o!(self.f, "	add	{}, {}, {}", dst, base, t);
+        }
+    }
+
+    /// Moves `sp` by `n` bytes.
+    fn sp(&mut self, n: i64) {
+        if n != 0 {
+            self.add_imm("sp", "sp", n);
+        }
+    }
+}
+
+fn reg(r: Reg) -> String {
+    const NAMES: [&str; 32] = [
+        "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0",
+        "a1", "a2", "a3", "a4", "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6",
+        "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+    ];
+    match r {
+        Reg::P(p) => NAMES[p.0 as usize].to_owned(),
+        Reg::V(v) => panic!("unallocated register v{}", v),
+    }
+}
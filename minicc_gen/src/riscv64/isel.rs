@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+
+use ir::{BinOp, BlockId, CmpOp, ConvOp, Inst, Term, Ty, Value};
+
+use super::*;
+use crate::regalloc::{MBlock, MFunc};
+
+/// Machine form of an IR register.
+#[derive(Debug, Clone, Copy)]
+enum Val {
+    /// Values narrower than 64 bits leave the upper bits undefined.
+    R(Reg),
+    /// Address of a slot, folded into addressing modes.
+    Slot(u32),
+    /// Comparison only used by the branch ending its block, which compares
+    /// itself.
+    Fused,
+}
+
+/// Selects instructions for `func`, one machine block per IR block followed
+/// by the blocks copying phi operands on branch edges. Returns them with the
+/// slots of the function.
+pub(super) fn select(func: &ir::Function) -> (MFunc<MInst>, Vec<ir::Slot>) {
+    let mut s = Isel {
+        func,
+        mf: MFunc::new(),
+        vals: Vec::new(),
+        insts: Vec::new(),
+        fused: HashMap::new(),
+    };
+    s.assign_vals();
+
+    let n = func.blocks.len();
+    for _ in 0..n {
+        s.mf.blocks.push(MBlock { insts: Vec::new(), succs: Vec::new() });
+    }
+    let cfg = ir::Cfg::new(func);
+    for &b in &cfg.rpo {
+        if b == BlockId(0) {
+            s.params();
+        }
+        for inst in &func.block(b).insts {
+            s.inst(inst);
+        }
+        let succs = s.term(b);
+        let insts = std::mem::take(&mut s.insts);
+        s.mf.blocks[b.0 as usize] = MBlock { insts, succs };
+    }
+    (s.mf, func.slots.clone())
+}
+
+struct Isel<'a> {
+    func: &'a ir::Function,
+    mf: MFunc<MInst>,
+    vals: Vec<Val>,
+    /// Instructions of the block being selected.
+    insts: Vec<MInst>,
+    /// Comparison the branch ending each block is fused with.
+    fused: HashMap<BlockId, (CmpOp, Ty, Value, Value)>,
+}
+
+impl Isel<'_> {
+    fn assign_vals(&mut self) {
+        let mut uses = vec![0; self.func.regs.len()];
+        for block in &self.func.blocks {
+            let term = block.term.iter().flat_map(|t| t.uses());
+            for v in block.insts.iter().flat_map(|i| i.uses()).chain(term) {
+                if let Value::Reg(r) = v {
+                    uses[r.0 as usize] += 1;
+                }
+            }
+        }
+
+        for _ in &self.func.regs {
+            let val = Val::R(self.vreg());
+            self.vals.push(val);
+        }
+
+        for b in self.func.block_ids() {
+            let block = self.func.block(b);
+            for inst in &block.insts {
+                if let Inst::SlotAddr { dst, slot } = *inst {
+                    self.vals[dst.0 as usize] = Val::Slot(slot.0);
+                }
+            }
+            if let (
+                Some(&Inst::Cmp { op, ty, dst, lhs, rhs }),
+                Some(Term::Br { cond: Value::Reg(cond), .. }),
+            ) = (block.insts.last(), &block.term)
+            {
+                if dst == *cond && uses[dst.0 as usize] == 1 {
+                    self.vals[dst.0 as usize] = Val::Fused;
+                    self.fused.insert(b, (op, ty, lhs, rhs));
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, inst: MInst) {
+        self.insts.push(inst);
+    }
+
+    fn vreg(&mut self) -> Reg {
+        self.mf.new_vreg(ABI.gpr)
+    }
+
+    fn val(&self, r: ir::Reg) -> Val {
+        self.vals[r.0 as usize]
+    }
+
+    fn dst(&self, r: ir::Reg) -> Reg {
+        match self.val(r) {
+            Val::R(r) => r,
+            v => unreachable!("{:?} is not a register", v),
+        }
+    }
+
+    /// Register holding `v`, `zero` for 0.
+    fn reg(&mut self, v: Value) -> Reg {
+        match v {
+            Value::Imm(0) => Reg::P(ZERO),
+            Value::Imm(imm) => {
+                let dst = self.vreg();
+                self.emit(MInst::Li { dst, imm });
+                dst
+            }
+            Value::Reg(r) => match self.val(r) {
+                Val::R(r) => r,
+                Val::Slot(s) => {
+                    let dst = self.vreg();
+                    let addr = Addr::new(Base::Slot(s));
+                    self.emit(MInst::Lea { dst, addr });
+                    dst
+                }
+                Val::Fused => unreachable!("fused comparison used"),
+            },
+        }
+    }
+
+    /// Operand of an instruction with an immediate form.
+    fn opnd(&mut self, v: Value) -> Opnd {
+        match v {
+            Value::Imm(i) if (-2048..2048).contains(&i) => Opnd::Imm(i),
+            v => Opnd::Reg(self.reg(v)),
+        }
+    }
+
+    fn addr(&mut self, v: Value) -> Addr {
+        match v {
+            Value::Reg(r) => match self.val(r) {
+                Val::Slot(s) => Addr::new(Base::Slot(s)),
+                _ => Addr::new(Base::Reg(self.reg(v))),
+            },
+            Value::Imm(_) => Addr::new(Base::Reg(self.reg(v))),
+        }
+    }
+
+    fn mov(&mut self, dst: Reg, v: Value) {
+        match v {
+            Value::Imm(imm) => self.emit(MInst::Li { dst, imm }),
+            v => {
+                let src = self.reg(v);
+                self.emit(MInst::Mv { dst, src });
+            }
+        }
+    }
+
+    /// Moves `v` of type `from` to `dst`, sign or zero extended to 64 bits.
+    fn extend(&mut self, dst: Reg, v: Value, from: Ty, signed: bool) {
+        match from {
+            Ty::I8 | Ty::I16 | Ty::I32 => {
+                let src = self.reg(v);
+                let from = size(from);
+                self.emit(MInst::Ext { dst, src, from, signed });
+            }
+            _ => self.mov(dst, v),
+        }
+    }
+
+    /// Register holding `v` extended to 64 bits, which is always by sign for
+    /// `i32` as that keeps the order of unsigned values too.
+    fn extended(&mut self, v: Value, ty: Ty, signed: bool) -> Reg {
+        match (v, ty) {
+            (Value::Imm(i), Ty::I8 | Ty::I16) if !signed => {
+                let bits = ty.bits().unwrap();
+                self.reg(Value::Imm(i & ((1 << bits) - 1)))
+            }
+            (Value::Reg(_), Ty::I8 | Ty::I16) => {
+                let dst = self.vreg();
+                self.extend(dst, v, ty, signed);
+                dst
+            }
+            (Value::Reg(_), Ty::I32) => {
+                let dst = self.vreg();
+                self.extend(dst, v, ty, true);
+                dst
+            }
+            _ => self.reg(v),
+        }
+    }
+
+    /// Moves the parameters from where the caller passed them.
+    fn params(&mut self) {
+        // The prologue of a variadic function stores the argument registers
+        // right below the arguments on the stack.
+        let stack = if self.func.sig.variadic { 64 } else { 0 };
+        for (i, &p) in self.func.params.iter().enumerate() {
+            let dst = self.dst(p);
+            match ARG_REGS.get(i) {
+                Some(&r) => self.emit(MInst::Mv { dst, src: Reg::P(r) }),
+                None => {
+                    let offset = stack + 8 * (i - ARG_REGS.len()) as i32;
+                    let addr = Addr::new(Base::Frame(offset));
+                    self.emit(MInst::Load { dst, addr, size: Size::D });
+                }
+            }
+        }
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Copy { dst, val } => self.mov(self.dst(*dst), *val),
+            Inst::Bin { op, dst, lhs, rhs } => {
+                let ty = self.func.reg_ty(*dst);
+                self.bin(*op, ty, *dst, *lhs, *rhs)
+            }
+            Inst::Cmp { op, ty, dst, lhs, rhs } => {
+                if matches!(self.val(*dst), Val::Fused) {
+                    return;
+                }
+                let dst = self.dst(*dst);
+                self.set(*op, *ty, dst, *lhs, *rhs);
+            }
+            Inst::Conv { op, dst, from, val } => {
+                let dst = self.dst(*dst);
+                match op {
+                    ConvOp::SExt => self.extend(dst, *val, *from, true),
+                    ConvOp::ZExt => self.extend(dst, *val, *from, false),
+                    // The upper bits of narrow values are undefined anyway.
+                    _ => self.mov(dst, *val),
+                }
+            }
+            Inst::SlotAddr { .. } => {}
+            Inst::FuncAddr { dst, name } => {
+                self.emit(MInst::La { dst: self.dst(*dst), sym: *name })
+            }
+            Inst::Load { dst, addr } => {
+                let addr = self.addr(*addr);
+                let size = size(self.func.reg_ty(*dst));
+                self.emit(MInst::Load { dst: self.dst(*dst), addr, size });
+            }
+            Inst::Store { ty, val, addr } => {
+                let addr = self.addr(*addr);
+                let src = self.reg(*val);
+                self.emit(MInst::Store { src, addr, size: size(*ty) });
+            }
+            Inst::Call { dst, callee, sig: _, args } => {
+                self.call(*dst, callee, args)
+            }
+            Inst::VaStart { ap } => {
+                // Past the named arguments, in registers or not.
+                let n = self.func.params.len() as i32;
+                let addr = self.addr(*ap);
+                let p = self.vreg();
+                let frame = Addr::new(Base::Frame(8 * n));
+                self.emit(MInst::Lea { dst: p, addr: frame });
+                self.emit(MInst::Store { src: p, addr, size: Size::D });
+            }
+            Inst::VaArg { dst, ap } => {
+                let addr = self.addr(*ap);
+                let p = self.vreg();
+                self.emit(MInst::Load { dst: p, addr, size: Size::D });
+                let size = size(self.func.reg_ty(*dst));
+                let arg = Addr::new(Base::Reg(p));
+                self.emit(MInst::Load { dst: self.dst(*dst), addr: arg, size });
+                let next = self.vreg();
+                self.emit(MInst::Alu {
+                    op: AluOp::Add,
+                    dst: next,
+                    lhs: p,
+                    rhs: Opnd::Imm(8),
+                });
+                self.emit(MInst::Store { src: next, addr, size: Size::D });
+            }
+            Inst::VaEnd { .. } => {}
+            // Copied on the edges into the block.
+            Inst::Phi { .. } => {}
+        }
+    }
+
+    fn bin(&mut self, op: BinOp, ty: Ty, dst: ir::Reg, lhs: Value, rhs: Value) {
+        let dst = self.dst(dst);
+        let op = match op {
+            BinOp::Add => AluOp::Add,
+            BinOp::Sub => match rhs {
+                Value::Imm(i) if (-2047..2049).contains(&i) => {
+                    let lhs = self.reg(lhs);
+                    let rhs = Opnd::Imm(-i);
+                    self.emit(MInst::Alu { op: AluOp::Add, dst, lhs, rhs });
+                    return;
+                }
+                _ => AluOp::Sub,
+            },
+            BinOp::Mul => AluOp::Mul,
+            BinOp::SDiv | BinOp::SRem | BinOp::UDiv | BinOp::URem => {
+                let signed = matches!(op, BinOp::SDiv | BinOp::SRem);
+                // The `w` forms only read the low 32 bits.
+                let op = match (op, ty) {
+                    (BinOp::SDiv, Ty::I32) => AluOp::Divw,
+                    (BinOp::UDiv, Ty::I32) => AluOp::Divuw,
+                    (BinOp::SRem, Ty::I32) => AluOp::Remw,
+                    (BinOp::URem, Ty::I32) => AluOp::Remuw,
+                    (BinOp::SDiv, _) => AluOp::Div,
+                    (BinOp::UDiv, _) => AluOp::Divu,
+                    (BinOp::SRem, _) => AluOp::Rem,
+                    _ => AluOp::Remu,
+                };
+                let (lhs, rhs) = match ty {
+                    Ty::I8 | Ty::I16 => (
+                        self.extended(lhs, ty, signed),
+                        self.extended(rhs, ty, signed),
+                    ),
+                    _ => (self.reg(lhs), self.reg(rhs)),
+                };
+                let rhs = Opnd::Reg(rhs);
+                self.emit(MInst::Alu { op, dst, lhs, rhs });
+                return;
+            }
+        };
+        let lhs = self.reg(lhs);
+        let rhs = match op {
+            AluOp::Add => self.opnd(rhs),
+            _ => Opnd::Reg(self.reg(rhs)),
+        };
+        self.emit(MInst::Alu { op, dst, lhs, rhs });
+    }
+
+    /// Operands of comparison `op`, extended to 64 bits.
+    fn operands(
+        &mut self,
+        op: CmpOp,
+        ty: Ty,
+        lhs: Value,
+        rhs: Value,
+    ) -> (Reg, Reg) {
+        let signed =
+            matches!(op, CmpOp::SLt | CmpOp::SLe | CmpOp::SGt | CmpOp::SGe);
+        let lhs = self.extended(lhs, ty, signed);
+        let rhs = self.extended(rhs, ty, signed);
+        (lhs, rhs)
+    }
+
+    /// Sets `dst` to whether comparison `op` is true.
+    fn set(&mut self, op: CmpOp, ty: Ty, dst: Reg, lhs: Value, rhs: Value) {
+        let (a, b) = self.operands(op, ty, lhs, rhs);
+        let (slt, swap, not) = match op {
+            CmpOp::Eq | CmpOp::Ne => {
+                let zero = op == CmpOp::Eq;
+                let src = if b == Reg::P(ZERO) {
+                    a
+                } else {
+                    let t = self.vreg();
+                    let rhs = Opnd::Reg(b);
+                    self.emit(MInst::Alu {
+                        op: AluOp::Xor,
+                        dst: t,
+                        lhs: a,
+                        rhs,
+                    });
+                    t
+                };
+                self.emit(MInst::SetZ { zero, dst, src });
+                return;
+            }
+            CmpOp::SLt => (AluOp::Slt, false, false),
+            CmpOp::SGt => (AluOp::Slt, true, false),
+            CmpOp::SLe => (AluOp::Slt, true, true),
+            CmpOp::SGe => (AluOp::Slt, false, true),
+            CmpOp::ULt => (AluOp::Sltu, false, false),
+            CmpOp::UGt => (AluOp::Sltu, true, false),
+            CmpOp::ULe => (AluOp::Sltu, true, true),
+            CmpOp::UGe => (AluOp::Sltu, false, true),
+        };
+        let (lhs, rhs) = if swap { (b, a) } else { (a, b) };
+        if !not {
+            let rhs = Opnd::Reg(rhs);
+            self.emit(MInst::Alu { op: slt, dst, lhs, rhs });
+            return;
+        }
+        let t = self.vreg();
+        self.emit(MInst::Alu { op: slt, dst: t, lhs, rhs: Opnd::Reg(rhs) });
+        let one = Opnd::Imm(1);
+        self.emit(MInst::Alu { op: AluOp::Xor, dst, lhs: t, rhs: one });
+    }
+
+    /// Condition and operands of a branch taken if comparison `op` is true.
+    fn cond(
+        &mut self,
+        op: CmpOp,
+        ty: Ty,
+        lhs: Value,
+        rhs: Value,
+    ) -> (Cc, Reg, Reg) {
+        let (a, b) = self.operands(op, ty, lhs, rhs);
+        match op {
+            CmpOp::Eq => (Cc::Eq, a, b),
+            CmpOp::Ne => (Cc::Ne, a, b),
+            CmpOp::SLt => (Cc::Lt, a, b),
+            CmpOp::SGe => (Cc::Ge, a, b),
+            CmpOp::SGt => (Cc::Lt, b, a),
+            CmpOp::SLe => (Cc::Ge, b, a),
+            CmpOp::ULt => (Cc::Ltu, a, b),
+            CmpOp::UGe => (Cc::Geu, a, b),
+            CmpOp::UGt => (Cc::Ltu, b, a),
+            CmpOp::ULe => (Cc::Geu, b, a),
+        }
+    }
+
+    /// Register holding `v` as passed or returned: integers narrower than 64
+    /// bits are sign extended, which is right for the signed types.
+    fn abi_reg(&mut self, ty: Ty, v: Value) -> Reg {
+        match (ty, v) {
+            (Ty::I8 | Ty::I16 | Ty::I32, Value::Reg(_)) => {
+                let dst = self.vreg();
+                self.extend(dst, v, ty, true);
+                dst
+            }
+            _ => self.reg(v),
+        }
+    }
+
+    fn call(
+        &mut self,
+        dst: Option<ir::Reg>,
+        callee: &ir::Callee,
+        args: &[(Ty, Value)],
+    ) {
+        let target = match callee {
+            ir::Callee::Direct(name) => Callee::Sym(*name),
+            ir::Callee::Indirect(v) => Callee::Reg(self.reg(*v)),
+        };
+        let (regs, stack) = args.split_at(args.len().min(ARG_REGS.len()));
+        let n = (8 * stack.len() as u32).next_multiple_of(16);
+        self.emit(MInst::SubSp { n });
+        for (i, &(ty, v)) in stack.iter().enumerate() {
+            let src = self.abi_reg(ty, v);
+            self.emit(MInst::StoreArg { src, offset: 8 * i as u32 });
+        }
+        let srcs: Vec<Reg> =
+            regs.iter().map(|&(ty, v)| self.abi_reg(ty, v)).collect();
+        let mut used = Vec::new();
+        for (src, r) in srcs.into_iter().zip(ARG_REGS) {
+            self.emit(MInst::Mv { dst: Reg::P(r), src });
+            used.push(r);
+        }
+        self.emit(MInst::Call { target, args: used });
+        self.emit(MInst::AddSp { n });
+        if let Some(dst) = dst {
+            let dst = self.dst(dst);
+            self.emit(MInst::Mv { dst, src: Reg::P(A0) });
+        }
+    }
+
+    /// Selects the terminator of `b`, returning the successors.
+    fn term(&mut self, b: BlockId) -> Vec<usize> {
+        match self.func.block(b).term.as_ref().unwrap() {
+            Term::Ret(None) => {
+                self.emit(MInst::Ret { regs: &[] });
+                Vec::new()
+            }
+            &Term::Ret(Some((ty, v))) => {
+                let src = self.abi_reg(ty, v);
+                self.emit(MInst::Mv { dst: Reg::P(A0), src });
+                self.emit(MInst::Ret { regs: &[A0] });
+                Vec::new()
+            }
+            Term::Jmp(target) => {
+                let insts = self.phi_copies(b, *target);
+                self.insts.extend(insts);
+                let target = target.0 as usize;
+                self.emit(MInst::J { target });
+                vec![target]
+            }
+            &Term::Br { ty, cond, then, else_ } => {
+                let (cc, lhs, rhs) = match self.fused.get(&b) {
+                    Some(&(op, ty, lhs, rhs)) => self.cond(op, ty, lhs, rhs),
+                    None => {
+                        let lhs = self.extended(cond, ty, false);
+                        (Cc::Ne, lhs, Reg::P(ZERO))
+                    }
+                };
+                let then = self.edge(b, then);
+                let else_ = self.edge(b, else_);
+                self.emit(MInst::B { cc, lhs, rhs, target: then });
+                self.emit(MInst::J { target: else_ });
+                vec![then, else_]
+            }
+            Term::Unreachable => {
+                self.emit(MInst::Unimp);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Machine block to branch to for the edge from `from` to `to`, a new one
+    /// if `to` has phis to copy.
+    fn edge(&mut self, from: BlockId, to: BlockId) -> usize {
+        let to_phis =
+            matches!(self.func.block(to).insts.first(), Some(Inst::Phi { .. }));
+        if !to_phis {
+            return to.0 as usize;
+        }
+        let mut insts = self.phi_copies(from, to);
+        let target = to.0 as usize;
+        insts.push(MInst::J { target });
+        self.mf.blocks.push(MBlock { insts, succs: vec![target] });
+        self.mf.blocks.len() - 1
+    }
+
+    /// Copies the values the phis of `to` take from `from`, all at once as
+    /// one phi may read another.
+    fn phi_copies(&mut self, from: BlockId, to: BlockId) -> Vec<MInst> {
+        let saved = std::mem::take(&mut self.insts);
+        let mut moves = Vec::new();
+        for inst in &self.func.block(to).insts {
+            let Inst::Phi { dst, args } = inst else {
+                break;
+            };
+            let (_, v) = args.iter().find(|(b, _)| *b == from).unwrap();
+            let dst = self.dst(*dst);
+            match *v {
+                Value::Imm(imm) => moves.push(MInst::Li { dst, imm }),
+                v => {
+                    let t = self.vreg();
+                    self.mov(t, v);
+                    moves.push(MInst::Mv { dst, src: t });
+                }
+            }
+        }
+        self.insts.extend(moves);
+        std::mem::replace(&mut self.insts, saved)
+    }
+}
+
+/// Size of a value of type `ty` in memory.
+fn size(ty: Ty) -> Size {
+    match ty {
+        Ty::I8 => Size::B,
+        Ty::I16 => Size::H,
+        Ty::I32 => Size::W,
+        Ty::I64 | Ty::Ptr => Size::D,
+    }
+}
@@ -0,0 +1,449 @@
+//! RISC-V 64 code generation: instruction selection into [`MInst`]s over
+//! virtual registers, register allocation, then output in GNU assembler
+//! syntax for RV64IM.
+//!
+//! Functions follow the LP64 integer calling convention: the first eight
+//! arguments are passed in `a0` to `a7`, sign extended to 64 bits, and the
+//! rest in 8 byte stack slots, `sp` is 16-byte aligned, results come back in
+//! `a0`, and `s0` to `s11` are preserved. Variadic arguments are passed like
+//! named ones and `va_list` is a pointer to the next.
+
+mod emit;
+mod isel;
+
+use std::io::Write;
+
+use minicc_ast::{DataModel, Symbol};
+use minicc_ir as ir;
+
+use crate::regalloc::{self, Access, MachInst, PReg, Reg};
+use crate::{Abi, Target};
+
+/// Registers of the first integer arguments, `a0` to `a7`, in order.
+const ARG_REGS: [PReg; 8] = [
+    PReg(10),
+    PReg(11),
+    PReg(12),
+    PReg(13),
+    PReg(14),
+    PReg(15),
+    PReg(16),
+    PReg(17),
+];
+const ZERO: PReg = PReg(0);
+const A0: PReg = PReg(10);
+const FP: PReg = PReg(8);
+/// Scratch register of the emitter, `t6`, for offsets too large for an
+/// instruction.
+const SCRATCH: PReg = PReg(31);
+
+/// Operand of the instructions that have an `i` form, whose immediates are
+/// 12-bit signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opnd {
+    Reg(Reg),
+    Imm(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    Reg(Reg),
+    /// Slot of the function.
+    Slot(u32),
+    /// Offset from `s0`, where the arguments are.
+    Frame(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Addr {
+    pub base: Base,
+    pub disp: i32,
+}
+
+impl Addr {
+    pub fn new(base: Base) -> Self {
+        Self { base, disp: 0 }
+    }
+}
+
+/// Width of a memory access, loads of less than 8 bytes zero extend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    B,
+    H,
+    W,
+    D,
+}
+
+/// Condition of a branch comparing two registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cc {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Ltu,
+    Geu,
+}
+
+impl Cc {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cc::Eq => "eq",
+            Cc::Ne => "ne",
+            Cc::Lt => "lt",
+            Cc::Ge => "ge",
+            Cc::Ltu => "ltu",
+            Cc::Geu => "geu",
+        }
+    }
+
+    pub fn inverse(self) -> Self {
+        match self {
+            Cc::Eq => Cc::Ne,
+            Cc::Ne => Cc::Eq,
+            Cc::Lt => Cc::Ge,
+            Cc::Ge => Cc::Lt,
+            Cc::Ltu => Cc::Geu,
+            Cc::Geu => Cc::Ltu,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Divu,
+    Rem,
+    Remu,
+    /// Division and remainder of the low 32 bits, sign extending the result.
+    Divw,
+    Divuw,
+    Remw,
+    Remuw,
+    Xor,
+    Slt,
+    Sltu,
+}
+
+impl AluOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            AluOp::Add => "add",
+            AluOp::Sub => "sub",
+            AluOp::Mul => "mul",
+            AluOp::Div => "div",
+            AluOp::Divu => "divu",
+            AluOp::Rem => "rem",
+            AluOp::Remu => "remu",
+            AluOp::Divw => "divw",
+            AluOp::Divuw => "divuw",
+            AluOp::Remw => "remw",
+            AluOp::Remuw => "remuw",
+            AluOp::Xor => "xor",
+            AluOp::Slt => "slt",
+            AluOp::Sltu => "sltu",
+        }
+    }
+}
+
+/// Machine instruction, on 64-bit registers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MInst {
+    Mv {
+        dst: Reg,
+        src: Reg,
+    },
+    Li {
+        dst: Reg,
+        imm: i64,
+    },
+    /// Loads the address of function `sym`.
+    La {
+        dst: Reg,
+        sym: Symbol,
+    },
+    Load {
+        dst: Reg,
+        addr: Addr,
+        size: Size,
+    },
+    Store {
+        src: Reg,
+        addr: Addr,
+        size: Size,
+    },
+    Lea {
+        dst: Reg,
+        addr: Addr,
+    },
+    /// `dst = lhs op rhs`, only `add`, `xor`, `slt` and `sltu` taking an
+    /// immediate.
+    Alu {
+        op: AluOp,
+        dst: Reg,
+        lhs: Reg,
+        rhs: Opnd,
+    },
+    /// `dst` is the low `from` of `src`, sign or zero extended.
+    Ext {
+        dst: Reg,
+        src: Reg,
+        from: Size,
+        signed: bool,
+    },
+    /// `dst = (src == 0) == zero`
+    SetZ {
+        zero: bool,
+        dst: Reg,
+        src: Reg,
+    },
+    /// Reserves `n` bytes of stack for arguments.
+    SubSp {
+        n: u32,
+    },
+    /// Stores an argument `offset` bytes above `sp`.
+    StoreArg {
+        src: Reg,
+        offset: u32,
+    },
+    /// Frees `n` bytes of arguments.
+    AddSp {
+        n: u32,
+    },
+    Call {
+        target: Callee,
+        /// Registers passing arguments.
+        args: Vec<PReg>,
+    },
+    J {
+        target: usize,
+    },
+    /// Branches if `lhs cc rhs`.
+    B {
+        cc: Cc,
+        lhs: Reg,
+        rhs: Reg,
+        target: usize,
+    },
+    /// Returns, with the result in `regs`.
+    Ret {
+        regs: &'static [PReg],
+    },
+    Unimp,
+    /// Loads `dst` from spill slot `slot`.
+    Reload {
+        dst: Reg,
+        slot: u32,
+    },
+    /// Stores `src` to spill slot `slot`.
+    Spill {
+        src: Reg,
+        slot: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Callee {
+    Sym(Symbol),
+    Reg(Reg),
+}
+
+fn visit_opnd(opnd: &mut Opnd, f: &mut dyn FnMut(&mut Reg, Access)) {
+    if let Opnd::Reg(r) = opnd {
+        f(r, Access::Use);
+    }
+}
+
+fn visit_addr(addr: &mut Addr, f: &mut dyn FnMut(&mut Reg, Access)) {
+    if let Base::Reg(r) = &mut addr.base {
+        f(r, Access::Use);
+    }
+}
+
+impl MachInst for MInst {
+    fn visit_regs(&mut self, f: &mut dyn FnMut(&mut Reg, Access)) {
+        match self {
+            MInst::Mv { dst, src }
+            | MInst::Ext { dst, src, .. }
+            | MInst::SetZ { dst, src, .. } => {
+                f(src, Access::Use);
+                f(dst, Access::Def);
+            }
+            MInst::Li { dst, .. }
+            | MInst::La { dst, .. }
+            | MInst::Reload { dst, .. } => f(dst, Access::Def),
+            MInst::Load { dst, addr, .. } | MInst::Lea { dst, addr } => {
+                visit_addr(addr, f);
+                f(dst, Access::Def);
+            }
+            MInst::Store { src, addr, .. } => {
+                f(src, Access::Use);
+                visit_addr(addr, f);
+            }
+            MInst::Alu { dst, lhs, rhs, .. } => {
+                f(lhs, Access::Use);
+                visit_opnd(rhs, f);
+                f(dst, Access::Def);
+            }
+            MInst::StoreArg { src, .. } | MInst::Spill { src, .. } => {
+                f(src, Access::Use)
+            }
+            MInst::Call { target, args } => {
+                if let Callee::Reg(r) = target {
+                    f(r, Access::Use);
+                }
+                for p in args {
+                    f(&mut Reg::P(*p), Access::Use);
+                }
+            }
+            MInst::B { lhs, rhs, .. } => {
+                f(lhs, Access::Use);
+                f(rhs, Access::Use);
+            }
+            MInst::Ret { regs } => {
+                for p in *regs {
+                    f(&mut Reg::P(*p), Access::Use);
+                }
+            }
+            MInst::SubSp { .. }
+            | MInst::AddSp { .. }
+            | MInst::J { .. }
+            | MInst::Unimp => {}
+        }
+    }
+
+    fn clobbers(&self) -> &'static [PReg] {
+        match self {
+            MInst::Call { .. } => ABI.call_clobbers,
+            _ => &[],
+        }
+    }
+
+    fn as_move(&self) -> Option<(Reg, Reg)> {
+        match *self {
+            MInst::Mv { dst, src } => Some((dst, src)),
+            _ => None,
+        }
+    }
+
+    fn load_spill(reg: Reg, slot: u32) -> Self {
+        MInst::Reload { dst: reg, slot }
+    }
+
+    fn store_spill(reg: Reg, slot: u32) -> Self {
+        MInst::Spill { src: reg, slot }
+    }
+}
+
+static ABI: Abi = Abi {
+    // Caller-saved first. `t6` is reserved.
+    gpr: &[
+        PReg(5),
+        PReg(6),
+        PReg(7),
+        PReg(28),
+        PReg(29),
+        PReg(30),
+        PReg(10),
+        PReg(11),
+        PReg(12),
+        PReg(13),
+        PReg(14),
+        PReg(15),
+        PReg(16),
+        PReg(17),
+        PReg(9),
+        PReg(18),
+        PReg(19),
+        PReg(20),
+        PReg(21),
+        PReg(22),
+        PReg(23),
+        PReg(24),
+        PReg(25),
+        PReg(26),
+        PReg(27),
+    ],
+    arg_regs: &ARG_REGS,
+    ret_regs: &[A0],
+    callee_saved: &[
+        PReg(9),
+        PReg(18),
+        PReg(19),
+        PReg(20),
+        PReg(21),
+        PReg(22),
+        PReg(23),
+        PReg(24),
+        PReg(25),
+        PReg(26),
+        PReg(27),
+    ],
+    call_clobbers: &[
+        PReg(5),
+        PReg(6),
+        PReg(7),
+        PReg(28),
+        PReg(29),
+        PReg(30),
+        PReg(10),
+        PReg(11),
+        PReg(12),
+        PReg(13),
+        PReg(14),
+        PReg(15),
+        PReg(16),
+        PReg(17),
+    ],
+    stack_slot: 8,
+    stack_align: 16,
+};
+
+/// RISC-V 64 Linux.
+pub struct Riscv64;
+
+impl Target for Riscv64 {
+    fn name(&self) -> &'static str {
+        "riscv64-linux"
+    }
+
+    fn data_model(&self) -> DataModel {
+        DataModel::LP64
+    }
+
+    fn macros(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("__riscv", "1"),
+            ("__riscv_xlen", "64"),
+            ("__LP64__", "1"),
+            ("__linux__", "1"),
+        ]
+    }
+
+    fn abi(&self) -> &'static Abi {
+        &ABI
+    }
+
+    fn llvm_triple(&self) -> &'static str {
+        "riscv64-unknown-linux-gnu"
+    }
+
+    fn llvm_data_layout(&self) -> &'static str {
+        "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128"
+    }
+
+    fn gen_func(
+        &self,
+        f: &mut dyn Write,
+        _module: &ir::Module,
+        func: &ir::Function,
+    ) {
+        let (mut mf, slots) = isel::select(func);
+        let used = regalloc::allocate(&mut mf);
+        emit::emit(f, func.name, func.sig.variadic, &mf, &slots, &used);
+    }
+}
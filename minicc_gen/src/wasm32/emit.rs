@@ -0,0 +1,571 @@
+use std::fmt::Display;
+
+use minicc_ir::{
+    BinOp, BlockId, Callee, Cfg, CmpOp, ConvOp, DomTree, Inst, Reg, Term, Value,
+};
+
+use super::*;
+
+/// Writes function definition `func` of `module`, in which function pointers
+/// index `table` from 1.
+pub(super) fn emit(
+    f: &mut dyn Write,
+    module: &ir::Module,
+    table: &[Symbol],
+    func: &ir::Function,
+) {
+    let cfg = Cfg::new(func);
+    let dom = DomTree::new(&cfg);
+    let mut order = vec![usize::MAX; func.blocks.len()];
+    for (i, b) in cfg.rpo.iter().enumerate() {
+        order[b.0 as usize] = i;
+    }
+    for &b in &cfg.rpo {
+        for &s in cfg.succs(b) {
+            if order[s.0 as usize] <= order[b.0 as usize]
+                && !dom.dominates(s, b)
+            {
+                panic!("irreducible control flow in {}", func.name);
+            }
+        }
+    }
+
+    // The variadic arguments of calls are stored at the bottom of the frame,
+    // then come the slots.
+    let mut e = Emitter {
+        f,
+        module,
+        table,
+        func,
+        cfg,
+        dom,
+        order,
+        slots: Vec::new(),
+        frame: 0,
+        depth: 0,
+    };
+    let mut size = func
+        .blocks
+        .iter()
+        .flat_map(|b| &b.insts)
+        .filter_map(|i| match i {
+            Inst::Call { callee, sig, args, .. } => {
+                let n = e.callee_sig(callee, sig).params.len();
+                Some(va_layout(&args[n.min(args.len())..]).1)
+            }
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    for slot in &func.slots {
+        size = size.next_multiple_of(slot.align.max(1));
+        e.slots.push(size);
+        size += slot.size;
+    }
+    e.frame = size.next_multiple_of(ABI.stack_align);
+
+    let mut header =
+        format!("  (func ${} (export \"{}\")", func.name, func.name);
+    for &p in &func.params {
+        header += &format!(" (param {} {})", reg(p), val_ty(func.reg_ty(p)));
+    }
+    if func.sig.variadic {
+        header += " (param $va i32)";
+    }
+    if let Some(ret) = func.sig.ret {
+        header += &format!(" (result {})", val_ty(ret));
+    }
+    o!(e.f, "{}", header);
+    for (r, ty) in func.regs.iter().enumerate() {
+        let r = Reg(r as u32);
+        if !func.params.contains(&r) {
+            o!(e.f, "    (local {} {})", reg(r), val_ty(*ty));
+        }
+    }
+    if e.frame > 0 {
+        o!(e.f, "    (local $fp i32)");
+    }
+    let mut insts = func.blocks.iter().flat_map(|b| &b.insts);
+    if insts.any(|i| matches!(i, Inst::VaArg { .. })) {
+        o!(e.f, "    (local $vp i32)");
+    }
+
+    if e.frame > 0 {
+        e.line("global.get $__stack_pointer");
+        e.line(format!("i32.const {}", e.frame));
+        e.line("i32.sub");
+        e.line("local.tee $fp");
+        e.line("global.set $__stack_pointer");
+    }
+    if let Some(&entry) = e.cfg.rpo.first() {
+        e.tree(entry);
+    }
+    // Every path has returned, but the validator only knows it of the last
+    // instruction.
+    if func.sig.ret.is_some() {
+        e.line("unreachable");
+    }
+    o!(e.f, "  )");
+}
+
+/// Offset of each of variadic arguments `args` and their size in all.
+fn va_layout(args: &[(Ty, Value)]) -> (Vec<u32>, u32) {
+    let mut offsets = Vec::new();
+    let mut size = 0;
+    for (ty, _) in args {
+        size = u32::next_multiple_of(size, va_size(*ty));
+        offsets.push(size);
+        size += va_size(*ty);
+    }
+    (offsets, size)
+}
+
+/// Size of a variadic argument of type `ty`, which is also its alignment.
+fn va_size(ty: Ty) -> u32 {
+    if ty == Ty::I64 {
+        8
+    } else {
+        4
+    }
+}
+
+struct Emitter<'a> {
+    f: &'a mut dyn Write,
+    module: &'a ir::Module,
+    table: &'a [Symbol],
+    func: &'a ir::Function,
+    cfg: Cfg,
+    dom: DomTree,
+    /// Reverse postorder number of each block.
+    order: Vec<usize>,
+    /// Offset of each slot from `$fp`.
+    slots: Vec<u32>,
+    frame: u32,
+    /// Nesting of the current instruction in the body.
+    depth: usize,
+}
+
+impl Emitter<'_> {
+    fn line(&mut self, text: impl Display) {
+        o!(self.f, "    {:2$}{}", "", text, 2 * self.depth);
+    }
+
+    fn order(&self, b: BlockId) -> usize {
+        self.order[b.0 as usize]
+    }
+
+    /// Whether `b` is reached by a back edge.
+    fn is_loop_header(&self, b: BlockId) -> bool {
+        self.cfg.preds(b).iter().any(|&p| {
+            self.dom.is_reachable(p) && self.order(p) >= self.order(b)
+        })
+    }
+
+    /// Whether `b` is reached by more than one forward edge, so that its code
+    /// follows a `block` its predecessors break out of.
+    fn is_merge(&self, b: BlockId) -> bool {
+        let forward = self.cfg.preds(b).iter().filter(|&&p| {
+            self.dom.is_reachable(p) && self.order(p) < self.order(b)
+        });
+        forward.count() > 1
+    }
+
+    /// Writes `b` and the blocks it dominates.
+    fn tree(&mut self, b: BlockId) {
+        // The merge blocks follow nested `block`s, the latest outermost.
+        let mut merges: Vec<_> = (self.dom.children(b).iter().copied())
+            .filter(|&c| self.is_merge(c))
+            .collect();
+        merges.sort_by_key(|&c| std::cmp::Reverse(self.order(c)));
+
+        if self.is_loop_header(b) {
+            self.line(format!("loop $loop{}", b.0));
+            self.depth += 1;
+            self.within(b, &merges);
+            self.depth -= 1;
+            self.line("end");
+        } else {
+            self.within(b, &merges);
+        }
+    }
+
+    fn within(&mut self, b: BlockId, merges: &[BlockId]) {
+        match merges.split_first() {
+            Some((&m, rest)) => {
+                self.line(format!("block $bb{}", m.0));
+                self.depth += 1;
+                self.within(b, rest);
+                self.depth -= 1;
+                self.line("end");
+                self.tree(m);
+            }
+            None => self.block(b),
+        }
+    }
+
+    fn block(&mut self, b: BlockId) {
+        for inst in &self.func.block(b).insts {
+            self.inst(inst);
+        }
+        match *self.func.block(b).term.as_ref().unwrap() {
+            Term::Ret(val) => {
+                if let Some((ty, val)) = val {
+                    self.value(ty, val);
+                    self.convert(ty, self.func.sig.ret.unwrap());
+                }
+                if self.frame > 0 {
+                    self.line("local.get $fp");
+                    self.line(format!("i32.const {}", self.frame));
+                    self.line("i32.add");
+                    self.line("global.set $__stack_pointer");
+                }
+                self.line("return");
+            }
+            Term::Jmp(to) => self.branch(b, to),
+            Term::Br { then, else_, .. } if then == else_ => {
+                self.branch(b, then)
+            }
+            Term::Br { ty, cond, then, else_ } => {
+                if let Some(label) = self.label(b, then) {
+                    self.cond(ty, cond, false);
+                    self.line(format!("br_if {}", label));
+                    self.branch(b, else_);
+                } else if let Some(label) = self.label(b, else_) {
+                    self.cond(ty, cond, true);
+                    self.line(format!("br_if {}", label));
+                    self.branch(b, then);
+                } else {
+                    self.cond(ty, cond, false);
+                    self.line("if");
+                    self.depth += 1;
+                    self.branch(b, then);
+                    self.depth -= 1;
+                    self.line("else");
+                    self.depth += 1;
+                    self.branch(b, else_);
+                    self.depth -= 1;
+                    self.line("end");
+                }
+            }
+            Term::Unreachable => self.line("unreachable"),
+        }
+    }
+
+    /// Label a branch from `from` to `to` breaks to, unless the phis of `to`
+    /// need copies or `to` follows inline.
+    fn label(&self, from: BlockId, to: BlockId) -> Option<String> {
+        let has_phi =
+            matches!(self.func.block(to).insts.first(), Some(Inst::Phi { .. }));
+        if has_phi {
+            None
+        } else if self.order(to) <= self.order(from) {
+            Some(format!("$loop{}", to.0))
+        } else if self.is_merge(to) {
+            Some(format!("$bb{}", to.0))
+        } else {
+            None
+        }
+    }
+
+    /// Writes the control transfer from `from` to `to`.
+    fn branch(&mut self, from: BlockId, to: BlockId) {
+        // Read all the incoming values before setting any phi.
+        let phis: Vec<_> = (self.func.block(to).insts.iter())
+            .filter_map(|inst| match inst {
+                Inst::Phi { dst, args } => {
+                    let (_, val) = args.iter().find(|(b, _)| *b == from)?;
+                    Some((*dst, *val))
+                }
+                _ => None,
+            })
+            .collect();
+        for &(dst, val) in &phis {
+            self.value(self.func.reg_ty(dst), val);
+        }
+        for &(dst, _) in phis.iter().rev() {
+            self.line(format!("local.set {}", reg(dst)));
+        }
+
+        if self.order(to) <= self.order(from) {
+            self.line(format!("br $loop{}", to.0));
+        } else if self.is_merge(to) {
+            self.line(format!("br $bb{}", to.0));
+        } else {
+            self.tree(to);
+        }
+    }
+
+    /// Pushes whether `cond` of type `ty` is nonzero, or zero if `negate`.
+    fn cond(&mut self, ty: Ty, cond: Value, negate: bool) {
+        self.value(ty, cond);
+        if ty == Ty::I64 {
+            self.line("i64.eqz");
+            if !negate {
+                self.line("i32.eqz");
+            }
+        } else if negate {
+            self.line("i32.eqz");
+        }
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match *inst {
+            Inst::Copy { dst, val } => {
+                self.value(self.func.reg_ty(dst), val);
+                self.set(dst);
+            }
+            Inst::Bin { op, dst, lhs, rhs } => {
+                let ty = self.func.reg_ty(dst);
+                let unsigned = matches!(op, BinOp::UDiv | BinOp::URem);
+                for val in [lhs, rhs] {
+                    self.value(ty, val);
+                    if unsigned {
+                        self.zero_extend(ty);
+                    }
+                }
+                let op = match op {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    BinOp::Mul => "mul",
+                    BinOp::SDiv => "div_s",
+                    BinOp::UDiv => "div_u",
+                    BinOp::SRem => "rem_s",
+                    BinOp::URem => "rem_u",
+                };
+                self.line(format!("{}.{}", val_ty(ty), op));
+                self.sign_extend(ty);
+                self.set(dst);
+            }
+            Inst::Cmp { op, ty, dst, lhs, rhs } => {
+                let unsigned = matches!(
+                    op,
+                    CmpOp::ULt | CmpOp::ULe | CmpOp::UGt | CmpOp::UGe
+                );
+                for val in [lhs, rhs] {
+                    self.value(ty, val);
+                    if unsigned {
+                        self.zero_extend(ty);
+                    }
+                }
+                let op = match op {
+                    CmpOp::Eq => "eq",
+                    CmpOp::Ne => "ne",
+                    CmpOp::SLt => "lt_s",
+                    CmpOp::SLe => "le_s",
+                    CmpOp::SGt => "gt_s",
+                    CmpOp::SGe => "ge_s",
+                    CmpOp::ULt => "lt_u",
+                    CmpOp::ULe => "le_u",
+                    CmpOp::UGt => "gt_u",
+                    CmpOp::UGe => "ge_u",
+                };
+                self.line(format!("{}.{}", val_ty(ty), op));
+                self.set(dst);
+            }
+            Inst::Conv { op, dst, from, val } => {
+                let to = self.func.reg_ty(dst);
+                self.value(from, val);
+                match op {
+                    ConvOp::SExt if to == Ty::I64 && from != Ty::I64 => {
+                        self.line("i64.extend_i32_s")
+                    }
+                    ConvOp::SExt => {}
+                    ConvOp::ZExt => {
+                        self.zero_extend(from);
+                        if to == Ty::I64 && from != Ty::I64 {
+                            self.line("i64.extend_i32_u");
+                        }
+                    }
+                    ConvOp::Trunc => {
+                        self.convert(from, to);
+                        self.sign_extend(to);
+                    }
+                    ConvOp::PtrToInt | ConvOp::IntToPtr => {
+                        self.convert(from, to)
+                    }
+                }
+                self.set(dst);
+            }
+            Inst::SlotAddr { dst, slot } => {
+                self.line("local.get $fp");
+                let offset = self.slots[slot.0 as usize];
+                if offset > 0 {
+                    self.line(format!("i32.const {}", offset));
+                    self.line("i32.add");
+                }
+                self.set(dst);
+            }
+            Inst::FuncAddr { dst, name } => {
+                let i = self.table.iter().position(|s| *s == name).unwrap();
+                self.line(format!("i32.const {}", i + 1));
+                self.set(dst);
+            }
+            Inst::Load { dst, addr } => {
+                self.value(Ty::Ptr, addr);
+                self.load(self.func.reg_ty(dst));
+                self.set(dst);
+            }
+            Inst::Store { ty, val, addr } => {
+                self.value(Ty::Ptr, addr);
+                self.value(ty, val);
+                self.store(ty, 0);
+            }
+            Inst::Call { dst, ref callee, ref sig, ref args } => {
+                self.call(dst, callee, sig, args)
+            }
+            Inst::VaStart { ap } => {
+                self.value(Ty::Ptr, ap);
+                self.line("local.get $va");
+                self.store(Ty::Ptr, 0);
+            }
+            Inst::VaArg { dst, ap } => {
+                let ty = self.func.reg_ty(dst);
+                self.value(Ty::Ptr, ap);
+                self.line("i32.load");
+                if ty == Ty::I64 {
+                    self.line("i32.const 7");
+                    self.line("i32.add");
+                    self.line("i32.const -8");
+                    self.line("i32.and");
+                }
+                self.line("local.tee $vp");
+                self.load(ty);
+                self.set(dst);
+                self.value(Ty::Ptr, ap);
+                self.line("local.get $vp");
+                self.line(format!("i32.const {}", va_size(ty)));
+                self.line("i32.add");
+                self.store(Ty::Ptr, 0);
+            }
+            Inst::VaEnd { .. } | Inst::Phi { .. } => {}
+        }
+    }
+
+    /// Signature of the function a call of signature `sig` calls: that of
+    /// its definition or declaration if direct, which the arguments are
+    /// converted to.
+    fn callee_sig(
+        &self,
+        callee: &Callee,
+        sig: &ir::Signature,
+    ) -> ir::Signature {
+        match callee {
+            Callee::Direct(name) => self.module.func(*name).map(|f| &f.sig),
+            Callee::Indirect(_) => None,
+        }
+        .unwrap_or(sig)
+        .clone()
+    }
+
+    fn call(
+        &mut self,
+        dst: Option<Reg>,
+        callee: &Callee,
+        sig: &ir::Signature,
+        args: &[(Ty, Value)],
+    ) {
+        let sig = &self.callee_sig(callee, sig);
+        let (fixed, va) = args.split_at(sig.params.len().min(args.len()));
+        let (offsets, _) = va_layout(va);
+        for (&(ty, val), offset) in va.iter().zip(offsets) {
+            self.line("local.get $fp");
+            self.value(ty, val);
+            self.store(ty, offset);
+        }
+        for (&(ty, val), &param) in fixed.iter().zip(&sig.params) {
+            self.value(ty, val);
+            self.convert(ty, param);
+        }
+        if sig.variadic {
+            self.line("local.get $fp");
+        }
+        match *callee {
+            Callee::Direct(name) => self.line(format!("call ${}", name)),
+            Callee::Indirect(val) => {
+                self.value(Ty::Ptr, val);
+                self.line(format!("call_indirect{}", func_type(sig)));
+            }
+        }
+        match (sig.ret, dst) {
+            (Some(ret), Some(dst)) => {
+                self.convert(ret, self.func.reg_ty(dst));
+                self.set(dst);
+            }
+            (Some(_), None) => self.line("drop"),
+            (None, Some(dst)) => {
+                self.value(self.func.reg_ty(dst), Value::Imm(0));
+                self.set(dst);
+            }
+            (None, None) => {}
+        }
+    }
+
+    /// Pushes `val` of type `ty`.
+    fn value(&mut self, ty: Ty, val: Value) {
+        match val {
+            Value::Reg(r) => self.line(format!("local.get {}", reg(r))),
+            Value::Imm(i) => self.line(format!("{}.const {}", val_ty(ty), i)),
+        }
+    }
+
+    fn set(&mut self, dst: Reg) {
+        self.line(format!("local.set {}", reg(dst)));
+    }
+
+    /// Converts the value on top from type `from` to `to` of another width.
+    fn convert(&mut self, from: Ty, to: Ty) {
+        match (val_ty(from), val_ty(to)) {
+            ("i32", "i64") => self.line("i64.extend_i32_s"),
+            ("i64", "i32") => self.line("i32.wrap_i64"),
+            _ => {}
+        }
+    }
+
+    /// Sign extends the value on top, if `ty` is narrow.
+    fn sign_extend(&mut self, ty: Ty) {
+        match ty {
+            Ty::I8 => self.line("i32.extend8_s"),
+            Ty::I16 => self.line("i32.extend16_s"),
+            _ => {}
+        }
+    }
+
+    /// Zero extends the value on top, if `ty` is narrow.
+    fn zero_extend(&mut self, ty: Ty) {
+        let mask = match ty {
+            Ty::I8 => 0xff,
+            Ty::I16 => 0xffff,
+            _ => return,
+        };
+        self.line(format!("i32.const {}", mask));
+        self.line("i32.and");
+    }
+
+    fn load(&mut self, ty: Ty) {
+        let op = match ty {
+            Ty::I8 => "i32.load8_s",
+            Ty::I16 => "i32.load16_s",
+            Ty::I32 | Ty::Ptr => "i32.load",
+            Ty::I64 => "i64.load",
+        };
+        self.line(op);
+    }
+
+    fn store(&mut self, ty: Ty, offset: u32) {
+        let op = match ty {
+            Ty::I8 => "i32.store8",
+            Ty::I16 => "i32.store16",
+            Ty::I32 | Ty::Ptr => "i32.store",
+            Ty::I64 => "i64.store",
+        };
+        if offset > 0 {
+            self.line(format!("{} offset={}", op, offset));
+        } else {
+            self.line(op);
+        }
+    }
+}
+
+fn reg(r: Reg) -> String {
+    format!("$r{}", r.0)
+}
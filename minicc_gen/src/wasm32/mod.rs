@@ -0,0 +1,148 @@
+//! WebAssembly code generation, writing a module in the text format.
+//!
+//! Registers become locals, `i64` ones of type `i64` and the others `i32`,
+//! narrow integers kept sign extended. Slots live in a frame on a stack in
+//! linear memory, below `$__stack_pointer`. The blocks of a function are
+//! nested into `block`s, `loop`s and `if`s following its dominator tree,
+//! which needs the control flow to be reducible, as that of C without `goto`
+//! is.
+//!
+//! Functions not defined are imported from module `env`. A variadic function
+//! takes the address of its variadic arguments as a last parameter, each
+//! stored at the next offset aligned to its size, and `va_list` points to the
+//! next. Function pointers index the table, in which 0 is null.
+
+mod emit;
+
+use std::io::Write;
+
+use minicc_ast::{DataModel, Symbol};
+use minicc_ir as ir;
+use minicc_ir::Ty;
+
+use crate::{Abi, Target};
+
+/// Pages of linear memory, all of it stack.
+const PAGES: u32 = 16;
+
+static ABI: Abi = Abi {
+    gpr: &[],
+    arg_regs: &[],
+    ret_regs: &[],
+    callee_saved: &[],
+    call_clobbers: &[],
+    stack_slot: 4,
+    stack_align: 16,
+};
+
+/// WebAssembly with 32-bit linear memory.
+pub struct Wasm32;
+
+impl Target for Wasm32 {
+    fn name(&self) -> &'static str {
+        "wasm32"
+    }
+
+    fn data_model(&self) -> DataModel {
+        DataModel::ILP32
+    }
+
+    fn macros(&self) -> &'static [(&'static str, &'static str)] {
+        &[("__wasm__", "1"), ("__wasm32__", "1")]
+    }
+
+    fn abi(&self) -> &'static Abi {
+        &ABI
+    }
+
+    fn llvm_triple(&self) -> &'static str {
+        "wasm32-unknown-unknown"
+    }
+
+    fn llvm_data_layout(&self) -> &'static str {
+        "e-m:e-p:32:32-i64:64-n32:64-S128"
+    }
+
+    fn gen_func(
+        &self,
+        f: &mut dyn Write,
+        module: &ir::Module,
+        func: &ir::Function,
+    ) {
+        emit::emit(f, module, &table(module), func);
+    }
+
+    fn gen(&self, f: &mut dyn Write, module: &ir::Module) {
+        o!(f, "(module");
+        for func in module.funcs.iter().filter(|func| func.is_decl()) {
+            o!(
+                f,
+                "  (import \"env\" \"{}\" (func ${}{}))",
+                func.name,
+                func.name,
+                func_type(&func.sig)
+            );
+        }
+        o!(f, "  (memory (export \"memory\") {})", PAGES);
+        o!(
+            f,
+            "  (global $__stack_pointer (mut i32) (i32.const {}))",
+            PAGES * 0x10000
+        );
+        let table = table(module);
+        o!(f, "  (table {} funcref)", table.len() + 1);
+        if !table.is_empty() {
+            let funcs: Vec<_> =
+                table.iter().map(|s| format!("${}", s)).collect();
+            o!(f, "  (elem (i32.const 1) func {})", funcs.join(" "));
+        }
+        for func in module.funcs.iter().filter(|func| !func.is_decl()) {
+            self.gen_func(f, module, func);
+        }
+        o!(f, ")");
+    }
+}
+
+/// Functions whose address is taken, in order of the table from index 1.
+fn table(module: &ir::Module) -> Vec<Symbol> {
+    let taken: Vec<_> = module
+        .funcs
+        .iter()
+        .flat_map(|func| &func.blocks)
+        .flat_map(|b| &b.insts)
+        .filter_map(|i| match i {
+            ir::Inst::FuncAddr { name, .. } => Some(*name),
+            _ => None,
+        })
+        .collect();
+    module
+        .funcs
+        .iter()
+        .map(|func| func.name)
+        .filter(|name| taken.contains(name))
+        .collect()
+}
+
+/// Type of a value of type `ty`.
+fn val_ty(ty: Ty) -> &'static str {
+    match ty {
+        Ty::I64 => "i64",
+        Ty::I8 | Ty::I16 | Ty::I32 | Ty::Ptr => "i32",
+    }
+}
+
+/// Parameters and result of a function of signature `sig`.
+fn func_type(sig: &ir::Signature) -> String {
+    let mut params: Vec<_> = sig.params.iter().map(|ty| val_ty(*ty)).collect();
+    if sig.variadic {
+        params.push("i32");
+    }
+    let mut s = String::new();
+    if !params.is_empty() {
+        s += &format!(" (param {})", params.join(" "));
+    }
+    if let Some(ret) = sig.ret {
+        s += &format!(" (result {})", val_ty(ret));
+    }
+    s
+}
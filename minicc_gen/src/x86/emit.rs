@@ -0,0 +1,304 @@
+use super::*;
+use crate::regalloc::MFunc;
+
+/// Writes allocated `mf`, with the prologue saving the callee-saved registers
+/// in `used` and laying out `slots`.
+pub(super) fn emit(
+    f: &mut dyn Write,
+    name: Symbol,
+    mode: Mode,
+    mf: &MFunc<MInst>,
+    slots: &[ir::Slot],
+    used: &[PReg],
+) {
+    let word = mode.word();
+    let saved: Vec<PReg> = mode
+        .abi()
+        .callee_saved
+        .iter()
+        .copied()
+        .filter(|r| used.contains(r))
+        .collect();
+
+    // Callee-saved registers go right below the saved frame pointer, then
+    // slots and spill slots.
+    let mut size = word * saved.len() as u32;
+    let mut offsets = Vec::new();
+    for slot in slots {
+        size = (size + slot.size).next_multiple_of(slot.align.max(1));
+        offsets.push(-(size as i32));
+    }
+    let spill_base = size;
+    size += word * mf.spill_slots;
+    let frame = match mode {
+        Mode::I386 => size.next_multiple_of(4),
+        // Calls need `%rsp` 16-byte aligned, which it is after pushing the
+        // return address and `%rbp`.
+        Mode::X86_64 => size.next_multiple_of(16),
+    } - word * saved.len() as u32;
+    let calls = mf
+        .blocks
+        .iter()
+        .flat_map(|b| &b.insts)
+        .any(|i| matches!(i, MInst::Call { .. }));
+    // Leaf functions need not move `%rsp` over up to 128 bytes below it.
+    let red_zone = mode == Mode::X86_64 && !calls && size <= 128;
+
+    let mut e =
+        Emitter { f, name, mode, saved: &saved, slots: offsets, spill_base };
+    let (sp, bp) = (e.reg(SP), e.reg(BP));
+
+    o!(e.f, "	push	{}", bp);
+    o!(e.f, "	mov	{}, {}", sp, bp);
+    for r in &saved {
+        o!(e.f, "	push	{}", e.reg(*r));
+    }
+    if frame > 0 && !red_zone {
+        o!(e.f, "	sub	${}, {}", frame, sp);
+    }
+
+    for (b, block) in mf.blocks.iter().enumerate() {
+        if b > 0 {
+            o!(e.f, ".L{}.{}:", name, b);
+        }
+        let mut insts = block.insts.iter().peekable();
+        while let Some(inst) = insts.next() {
+            // Branch on the inverse condition if the taken target follows.
+            if let MInst::Jcc { cc, target } = *inst {
+                if let Some(MInst::Jmp { target: other }) = insts.peek() {
+                    if target == b + 1 {
+                        e.inst(&MInst::Jcc {
+                            cc: cc.inverse(),
+                            target: *other,
+                        });
+                        insts.next();
+                        continue;
+                    }
+                }
+            }
+            if matches!(*inst, MInst::Jmp { target } if target == b + 1) {
+                continue;
+            }
+            e.inst(inst);
+        }
+    }
+}
+
+struct Emitter<'a> {
+    f: &'a mut dyn Write,
+    name: Symbol,
+    mode: Mode,
+    saved: &'a [PReg],
+    /// Offset of each slot from the frame pointer.
+    slots: Vec<i32>,
+    /// Offset below the frame pointer where the spill slots start.
+    spill_base: u32,
+}
+
+impl Emitter<'_> {
+    fn inst(&mut self, inst: &MInst) {
+        let word = self.mode.word_size();
+        match *inst {
+            MInst::Mov { dst, src: Opnd::Reg(src) } if dst == src => {}
+            MInst::Mov { dst, src: Opnd::Imm(i) }
+                if self.mode == Mode::X86_64 && i != i as i32 as i64 =>
+            {
+                o!(self.f, "	movabs	${}, {}", i, self.reg(dst))
+            }
+            MInst::Mov { dst, src: Opnd::Sym(s) }
+                if self.mode == Mode::X86_64 =>
+            {
+                o!(self.f, "	mov	{}@GOTPCREL(%rip), {}", s, self.reg(dst))
+            }
+            MInst::Mov { dst, src } => {
+                o!(self.f, "	mov	{}, {}", self.opnd(src), self.reg(dst))
+            }
+            MInst::Load { dst, addr, size } => {
+                let (op, size) = match size {
+                    Size::B => ("movzbl", Size::L),
+                    Size::W => ("movzwl", Size::L),
+                    size => ("mov", size),
+                };
+                let dst = self.sized(dst, size);
+                o!(self.f, "	{}	{}, {}", op, self.addr(addr), dst);
+            }
+            MInst::Store { src, addr, size } => {
+                let op = match size {
+                    Size::B => "movb",
+                    Size::W => "movw",
+                    Size::L => "movl",
+                    Size::Q => "movq",
+                };
+                let src = match src {
+                    Opnd::Reg(r) => self.sized(r, size),
+                    src => self.opnd(src),
+                };
+                o!(self.f, "	{}	{}, {}", op, src, self.addr(addr));
+            }
+            MInst::Lea { dst, addr } => {
+                o!(self.f, "	lea	{}, {}", self.addr(addr), self.reg(dst))
+            }
+            MInst::Alu { op, dst, src } => {
+                let (src, dst) = (self.opnd(src), self.reg(dst));
+                o!(self.f, "	{}	{}, {}", op.name(), src, dst);
+            }
+            MInst::Shift { op, dst, amount } => {
+                let op = match op {
+                    ShiftOp::Shl => "shl",
+                    ShiftOp::Shr => "shr",
+                    ShiftOp::Sar => "sar",
+                };
+                o!(self.f, "	{}	${}, {}", op, amount, self.reg(dst));
+            }
+            MInst::Cmp { lhs, rhs, size } => {
+                let rhs = match rhs {
+                    Opnd::Reg(r) => self.sized(r, size),
+                    rhs => self.opnd(rhs),
+                };
+                o!(self.f, "	cmp	{}, {}", rhs, self.sized(lhs, size));
+            }
+            MInst::Test { reg, size: Size::B } => {
+                o!(self.f, "	test	$255, {}", self.sized(reg, Size::L))
+            }
+            MInst::Test { reg, size: Size::W } => {
+                o!(self.f, "	test	$65535, {}", self.sized(reg, Size::L))
+            }
+            MInst::Test { reg, size } => {
+                let reg = self.sized(reg, size);
+                o!(self.f, "	test	{}, {}", reg, reg);
+            }
+            MInst::Set { cc, dst } => {
+                let (b, l) =
+                    (self.sized(dst, Size::B), self.sized(dst, Size::L));
+                o!(self.f, "	set{}	{}", cc.name(), b);
+                o!(self.f, "	movzbl	{}, {}", b, l);
+            }
+            MInst::Cmov { cc, dst, src } => {
+                let (src, dst) = (self.reg(src), self.reg(dst));
+                o!(self.f, "	cmov{}	{}, {}", cc.name(), src, dst);
+            }
+            MInst::Cltd { size: Size::Q } => o!(self.f, "	cqto"),
+            MInst::Cltd { .. } => o!(self.f, "	cltd"),
+            MInst::Div { signed, src, size } => {
+                let op = if signed { "idiv" } else { "div" };
+                o!(self.f, "	{}	{}", op, self.sized(src, size));
+            }
+            MInst::Mul { src } => o!(self.f, "	mull	{}", self.reg(src)),
+            MInst::Push { src } => o!(self.f, "	push	{}", self.opnd(src)),
+            MInst::Call { target: Opnd::Reg(r), .. } => {
+                o!(self.f, "	call	*{}", self.reg(r))
+            }
+            MInst::Call { target: Opnd::Sym(s), .. } => match self.mode {
+                Mode::I386 => o!(self.f, "	call	{}", s),
+                Mode::X86_64 => o!(self.f, "	call	{}@PLT", s),
+            },
+            MInst::Call { target: Opnd::Imm(i), .. } => {
+                o!(self.f, "	call	{}", i)
+            }
+            MInst::AddSp { n: 0 } | MInst::SubSp { n: 0 } => {}
+            MInst::AddSp { n } => o!(self.f, "	add	${}, {}", n, self.reg(SP)),
+            MInst::SubSp { n } => o!(self.f, "	sub	${}, {}", n, self.reg(SP)),
+            MInst::Jmp { target } => {
+                o!(self.f, "	jmp	.L{}.{}", self.name, target)
+            }
+            MInst::Jcc { cc, target } => {
+                o!(self.f, "	j{}	.L{}.{}", cc.name(), self.name, target)
+            }
+            MInst::Ret { .. } => {
+                let (sp, bp) = (self.reg(SP), self.reg(BP));
+                if self.saved.is_empty() {
+                    o!(self.f, "	mov	{}, {}", bp, sp);
+                } else {
+                    let n = self.mode.word() as usize * self.saved.len();
+                    o!(self.f, "	lea	-{}({}), {}", n, bp, sp);
+                }
+                for r in self.saved.iter().rev() {
+                    o!(self.f, "	pop	{}", self.reg(*r));
+                }
+                o!(self.f, "	pop	{}", bp);
+                o!(self.f, "	ret");
+            }
+            MInst::Ud2 => o!(self.f, "	ud2"),
+            MInst::Reload { dst, slot } => {
+                let addr = self.spill(slot);
+                o!(self.f, "	mov	{}, {}", addr, self.sized(dst, word));
+            }
+            MInst::Spill { src, slot } => {
+                let addr = self.spill(slot);
+                o!(self.f, "	mov	{}, {}", self.sized(src, word), addr);
+            }
+        }
+    }
+
+    fn addr(&self, addr: Addr) -> String {
+        let bp = self.reg(BP);
+        let (base, disp) = match addr.base {
+            Base::Reg(r) => (self.reg(preg(r)), addr.disp),
+            Base::Slot(s) => (bp, self.slots[s as usize] + addr.disp),
+            Base::Frame(off) => (bp, off + addr.disp),
+        };
+        match disp {
+            0 => format!("({})", base),
+            _ => format!("{}({})", disp, base),
+        }
+    }
+
+    fn spill(&self, slot: u32) -> String {
+        let off = self.spill_base + self.mode.word() * (slot + 1);
+        format!("-{}({})", off, self.reg(BP))
+    }
+
+    fn opnd(&self, opnd: Opnd) -> String {
+        match opnd {
+            Opnd::Reg(r) => self.reg(preg(r)),
+            // i386 immediates are 32 bits, whatever their sign.
+            Opnd::Imm(i) if self.mode == Mode::I386 => format!("${}", i as i32),
+            Opnd::Imm(i) => format!("${}", i),
+            Opnd::Sym(s) => format!("${}", s),
+        }
+    }
+
+    /// Name of a register as wide as a pointer.
+    fn reg(&self, r: impl Into<Reg>) -> String {
+        self.sized(r.into(), self.mode.word_size())
+    }
+
+    fn sized(&self, r: Reg, size: Size) -> String {
+        const NAMES: [&str; 8] =
+            ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di"];
+        let p = preg(r);
+        let n = p.0 as usize;
+        if n >= 8 {
+            let suffix = match size {
+                Size::B => "b",
+                Size::W => "w",
+                Size::L => "d",
+                Size::Q => "",
+            };
+            return format!("%r{}{}", n, suffix);
+        }
+        match size {
+            Size::B if n < 4 => format!("%{}l", &NAMES[n][..1]),
+            Size::B if self.mode == Mode::I386 => {
+                panic!("%e{} has no low byte", NAMES[n])
+            }
+            Size::B => format!("%{}l", NAMES[n]),
+            Size::W => format!("%{}", NAMES[n]),
+            Size::L => format!("%e{}", NAMES[n]),
+            Size::Q => format!("%r{}", NAMES[n]),
+        }
+    }
+}
+
+impl From<PReg> for Reg {
+    fn from(p: PReg) -> Self {
+        Reg::P(p)
+    }
+}
+
+fn preg(r: Reg) -> PReg {
+    match r {
+        Reg::P(p) => p,
+        Reg::V(v) => panic!("unallocated register v{}", v),
+    }
+}
@@ -0,0 +1,931 @@
+use std::collections::HashMap;
+
+use ir::{BinOp, BlockId, Callee, CmpOp, ConvOp, Inst, Term, Ty, Value};
+
+use super::*;
+use crate::regalloc::{MBlock, MFunc};
+
+/// Machine form of an IR register.
+#[derive(Debug, Clone, Copy)]
+enum Val {
+    /// Values narrower than a register leave the upper bits undefined.
+    R(Reg),
+    /// Low and high halves of an `i64` on i386.
+    Pair(Reg, Reg),
+    /// Address of a slot, folded into addressing modes.
+    Slot(u32),
+    /// Comparison only used by the branch ending its block, which sets the
+    /// flags itself.
+    Flags,
+}
+
+/// Selects instructions for `func`, one machine block per IR block followed
+/// by the blocks copying phi operands on branch edges. Returns them with the
+/// slots of the function, which may have more than its IR ones.
+pub(super) fn select(
+    func: &ir::Function,
+    mode: Mode,
+) -> (MFunc<MInst>, Vec<ir::Slot>) {
+    let mut s = Isel {
+        func,
+        mode,
+        mf: MFunc::new(),
+        vals: Vec::new(),
+        insts: Vec::new(),
+        fused: HashMap::new(),
+        slots: func.slots.clone(),
+        reg_save: None,
+    };
+    s.assign_vals();
+
+    let n = func.blocks.len();
+    for _ in 0..n {
+        s.mf.blocks.push(MBlock { insts: Vec::new(), succs: Vec::new() });
+    }
+    let cfg = ir::Cfg::new(func);
+    for &b in &cfg.rpo {
+        if b == BlockId(0) {
+            s.params();
+        }
+        for inst in &func.block(b).insts {
+            s.inst(inst);
+        }
+        let succs = s.term(b);
+        let insts = std::mem::take(&mut s.insts);
+        s.mf.blocks[b.0 as usize] = MBlock { insts, succs };
+    }
+    (s.mf, s.slots)
+}
+
+struct Isel<'a> {
+    func: &'a ir::Function,
+    mode: Mode,
+    mf: MFunc<MInst>,
+    vals: Vec<Val>,
+    /// Instructions of the block being selected.
+    insts: Vec<MInst>,
+    /// Comparison the branch ending each block is fused with.
+    fused: HashMap<BlockId, (CmpOp, Ty, Value, Value)>,
+    slots: Vec<ir::Slot>,
+    /// Slot the argument registers of a variadic x86-64 function are saved
+    /// to.
+    reg_save: Option<u32>,
+}
+
+impl Isel<'_> {
+    fn assign_vals(&mut self) {
+        let mut uses = vec![0; self.func.regs.len()];
+        for block in &self.func.blocks {
+            let term = block.term.iter().flat_map(|t| t.uses());
+            for v in block.insts.iter().flat_map(|i| i.uses()).chain(term) {
+                if let Value::Reg(r) = v {
+                    uses[r.0 as usize] += 1;
+                }
+            }
+        }
+
+        for ty in &self.func.regs {
+            let val = match ty {
+                Ty::I64 if self.mode == Mode::I386 => {
+                    Val::Pair(self.vreg(), self.vreg())
+                }
+                _ => Val::R(self.vreg()),
+            };
+            self.vals.push(val);
+        }
+
+        for b in self.func.block_ids() {
+            let block = self.func.block(b);
+            for inst in &block.insts {
+                match *inst {
+                    Inst::SlotAddr { dst, slot } => {
+                        self.vals[dst.0 as usize] = Val::Slot(slot.0)
+                    }
+                    // `setcc` writes a byte register.
+                    Inst::Cmp { dst, .. } => {
+                        self.vals[dst.0 as usize] =
+                            Val::R(self.mf.new_vreg(self.mode.byte_regs()))
+                    }
+                    _ => {}
+                }
+            }
+            if let (
+                Some(&Inst::Cmp { op, ty, dst, lhs, rhs }),
+                Some(Term::Br { cond: Value::Reg(cond), .. }),
+            ) = (block.insts.last(), &block.term)
+            {
+                if dst == *cond && uses[dst.0 as usize] == 1 {
+                    self.vals[dst.0 as usize] = Val::Flags;
+                    self.fused.insert(b, (op, ty, lhs, rhs));
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, inst: MInst) {
+        self.insts.push(inst);
+    }
+
+    fn vreg(&mut self) -> Reg {
+        self.mf.new_vreg(self.mode.abi().gpr)
+    }
+
+    fn val(&self, r: ir::Reg) -> Val {
+        self.vals[r.0 as usize]
+    }
+
+    fn dst(&self, r: ir::Reg) -> Reg {
+        match self.val(r) {
+            Val::R(r) => r,
+            v => unreachable!("{:?} is not a register", v),
+        }
+    }
+
+    fn dst_pair(&self, r: ir::Reg) -> (Reg, Reg) {
+        match self.val(r) {
+            Val::Pair(lo, hi) => (lo, hi),
+            v => unreachable!("{:?} is not a pair", v),
+        }
+    }
+
+    /// Operand holding a value as wide as a register or narrower, or the low
+    /// half of an `i64` on i386.
+    fn opnd(&mut self, v: Value) -> Opnd {
+        match v {
+            Value::Imm(i) if self.mode == Mode::I386 => {
+                Opnd::Imm(i as i32 as i64)
+            }
+            Value::Imm(i) if i == i as i32 as i64 => Opnd::Imm(i),
+            // Only `mov` takes a 64-bit immediate.
+            Value::Imm(i) => {
+                let dst = self.vreg();
+                self.emit(MInst::Mov { dst, src: Opnd::Imm(i) });
+                Opnd::Reg(dst)
+            }
+            Value::Reg(r) => match self.val(r) {
+                Val::R(r) | Val::Pair(r, _) => Opnd::Reg(r),
+                Val::Slot(s) => {
+                    let dst = self.vreg();
+                    self.emit(MInst::Lea {
+                        dst,
+                        addr: Addr::new(Base::Slot(s)),
+                    });
+                    Opnd::Reg(dst)
+                }
+                Val::Flags => unreachable!("fused comparison used"),
+            },
+        }
+    }
+
+    fn reg(&mut self, v: Value) -> Reg {
+        match self.opnd(v) {
+            Opnd::Reg(r) => r,
+            src => {
+                let dst = self.vreg();
+                self.emit(MInst::Mov { dst, src });
+                dst
+            }
+        }
+    }
+
+    fn pair(&mut self, v: Value) -> (Opnd, Opnd) {
+        match v {
+            Value::Imm(i) => {
+                (Opnd::Imm(i as i32 as i64), Opnd::Imm((i >> 32) as i32 as i64))
+            }
+            Value::Reg(r) => {
+                let (lo, hi) = self.dst_pair(r);
+                (Opnd::Reg(lo), Opnd::Reg(hi))
+            }
+        }
+    }
+
+    fn addr(&mut self, v: Value) -> Addr {
+        match v {
+            Value::Reg(r) => match self.val(r) {
+                Val::Slot(s) => Addr::new(Base::Slot(s)),
+                _ => Addr::new(Base::Reg(self.reg(v))),
+            },
+            Value::Imm(_) => Addr::new(Base::Reg(self.reg(v))),
+        }
+    }
+
+    fn mov(&mut self, dst: Reg, v: Value) {
+        let src = self.opnd(v);
+        self.emit(MInst::Mov { dst, src });
+    }
+
+    /// Moves `v` of type `from` to `dst`, sign or zero extended to the width
+    /// of a register.
+    fn extend(&mut self, dst: Reg, v: Value, from: Ty, signed: bool) {
+        self.mov(dst, v);
+        let word_bits = 8 * self.mode.word();
+        let bits = from.bits().unwrap_or(word_bits);
+        if bits >= word_bits {
+            return;
+        }
+        if !signed && bits < 32 {
+            let mask = (1 << bits) - 1;
+            self.emit(MInst::Alu { op: AluOp::And, dst, src: Opnd::Imm(mask) });
+            return;
+        }
+        let op = if signed { ShiftOp::Sar } else { ShiftOp::Shr };
+        let amount = (word_bits - bits) as u8;
+        self.emit(MInst::Shift { op: ShiftOp::Shl, dst, amount });
+        self.emit(MInst::Shift { op, dst, amount });
+    }
+
+    /// Register holding `v` extended to the width of a register.
+    fn extended(&mut self, v: Value, ty: Ty, signed: bool) -> Reg {
+        if matches!(ty, Ty::I8 | Ty::I16) {
+            let dst = self.vreg();
+            self.extend(dst, v, ty, signed);
+            dst
+        } else {
+            self.reg(v)
+        }
+    }
+
+    /// Moves the parameters from where the caller passed them.
+    fn params(&mut self) {
+        if self.mode == Mode::X86_64 {
+            return self.params64();
+        }
+        let mut offset = 8;
+        for &p in &self.func.params {
+            match self.val(p) {
+                Val::R(dst) => {
+                    let addr = Addr::new(Base::Frame(offset));
+                    self.emit(MInst::Load { dst, addr, size: Size::L });
+                    offset += 4;
+                }
+                Val::Pair(lo, hi) => {
+                    let addr = Addr::new(Base::Frame(offset));
+                    self.emit(MInst::Load { dst: lo, addr, size: Size::L });
+                    let addr = addr.offset(4);
+                    self.emit(MInst::Load { dst: hi, addr, size: Size::L });
+                    offset += 8;
+                }
+                v => unreachable!("parameter is {:?}", v),
+            }
+        }
+    }
+
+    fn params64(&mut self) {
+        let arg_regs = self.mode.abi().arg_regs;
+        // `va_arg` reads the unnamed arguments passed in registers from where
+        // they are saved.
+        if self.func.sig.variadic {
+            let slot = self.slots.len() as u32;
+            self.slots.push(ir::Slot { size: 8 * 6, align: 8 });
+            self.reg_save = Some(slot);
+            for (i, r) in arg_regs.iter().copied().enumerate() {
+                self.emit(MInst::Store {
+                    src: Opnd::Reg(Reg::P(r)),
+                    addr: Addr::new(Base::Slot(slot)).offset(8 * i as i32),
+                    size: Size::Q,
+                });
+            }
+        }
+        for (i, &p) in self.func.params.iter().enumerate() {
+            let dst = self.dst(p);
+            match arg_regs.get(i) {
+                Some(&r) => {
+                    self.emit(MInst::Mov { dst, src: Opnd::Reg(Reg::P(r)) })
+                }
+                None => {
+                    let offset = 16 + 8 * (i - arg_regs.len()) as i32;
+                    let addr = Addr::new(Base::Frame(offset));
+                    self.emit(MInst::Load { dst, addr, size: Size::Q });
+                }
+            }
+        }
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Copy { dst, val } => match self.val(*dst) {
+                Val::Pair(lo, hi) => {
+                    let (l, h) = self.pair(*val);
+                    self.emit(MInst::Mov { dst: lo, src: l });
+                    self.emit(MInst::Mov { dst: hi, src: h });
+                }
+                _ => self.mov(self.dst(*dst), *val),
+            },
+            Inst::Bin { op, dst, lhs, rhs } => match self.func.reg_ty(*dst) {
+                Ty::I64 if self.mode == Mode::I386 => {
+                    self.bin64(*op, *dst, *lhs, *rhs)
+                }
+                ty => self.bin(*op, ty, *dst, *lhs, *rhs),
+            },
+            Inst::Cmp { op, ty, dst, lhs, rhs } => {
+                if matches!(self.val(*dst), Val::Flags) {
+                    return;
+                }
+                let cc = self.cond(*op, *ty, *lhs, *rhs);
+                self.emit(MInst::Set { cc, dst: self.dst(*dst) });
+            }
+            Inst::Conv { op, dst, from, val } => {
+                self.conv(*op, *dst, *from, *val)
+            }
+            Inst::SlotAddr { .. } => {}
+            Inst::FuncAddr { dst, name } => self.emit(MInst::Mov {
+                dst: self.dst(*dst),
+                src: Opnd::Sym(*name),
+            }),
+            Inst::Load { dst, addr } => {
+                let addr = self.addr(*addr);
+                self.load(*dst, addr);
+            }
+            Inst::Store { ty, val, addr } => {
+                let addr = self.addr(*addr);
+                match ty {
+                    Ty::I64 if self.mode == Mode::I386 => {
+                        let (lo, hi) = self.pair(*val);
+                        self.emit(MInst::Store {
+                            src: lo,
+                            addr,
+                            size: Size::L,
+                        });
+                        let addr = addr.offset(4);
+                        self.emit(MInst::Store {
+                            src: hi,
+                            addr,
+                            size: Size::L,
+                        });
+                    }
+                    Ty::I8 if self.mode == Mode::I386 => {
+                        // Only some registers have a low byte.
+                        let src = match self.opnd(*val) {
+                            Opnd::Reg(r) => {
+                                let dst =
+                                    self.mf.new_vreg(self.mode.byte_regs());
+                                self.emit(MInst::Mov {
+                                    dst,
+                                    src: Opnd::Reg(r),
+                                });
+                                Opnd::Reg(dst)
+                            }
+                            src => src,
+                        };
+                        self.emit(MInst::Store { src, addr, size: Size::B });
+                    }
+                    _ => {
+                        let src = self.opnd(*val);
+                        let size = self.size(*ty);
+                        self.emit(MInst::Store { src, addr, size });
+                    }
+                }
+            }
+            Inst::Call { dst, callee, sig, args } => match self.mode {
+                Mode::I386 => self.call(*dst, callee, args),
+                Mode::X86_64 => self.call64(*dst, callee, sig, args),
+            },
+            Inst::VaStart { ap } if self.mode == Mode::X86_64 => {
+                self.va_start64(*ap)
+            }
+            Inst::VaArg { dst, ap } if self.mode == Mode::X86_64 => {
+                self.va_arg64(*dst, *ap)
+            }
+            Inst::VaStart { ap } => {
+                // `ap` points right after the last named argument.
+                let offset: i32 = 8 + self
+                    .func
+                    .sig
+                    .params
+                    .iter()
+                    .map(|t| slot_size(*t))
+                    .sum::<i32>();
+                let addr = self.addr(*ap);
+                let p = self.vreg();
+                let frame = Addr::new(Base::Frame(offset));
+                self.emit(MInst::Lea { dst: p, addr: frame });
+                self.emit(MInst::Store {
+                    src: Opnd::Reg(p),
+                    addr,
+                    size: Size::L,
+                });
+            }
+            Inst::VaArg { dst, ap } => {
+                let addr = self.addr(*ap);
+                let p = self.vreg();
+                self.emit(MInst::Load { dst: p, addr, size: Size::L });
+                self.load(*dst, Addr::new(Base::Reg(p)));
+                let next = self.vreg();
+                let n = slot_size(self.func.reg_ty(*dst)) as i64;
+                self.emit(MInst::Mov { dst: next, src: Opnd::Reg(p) });
+                self.emit(MInst::Alu {
+                    op: AluOp::Add,
+                    dst: next,
+                    src: Opnd::Imm(n),
+                });
+                self.emit(MInst::Store {
+                    src: Opnd::Reg(next),
+                    addr,
+                    size: Size::L,
+                });
+            }
+            Inst::VaEnd { .. } => {}
+            // Copied on the edges into the block.
+            Inst::Phi { .. } => {}
+        }
+    }
+
+    fn load(&mut self, dst: ir::Reg, addr: Addr) {
+        match self.val(dst) {
+            Val::Pair(lo, hi) => {
+                self.emit(MInst::Load { dst: lo, addr, size: Size::L });
+                let addr = addr.offset(4);
+                self.emit(MInst::Load { dst: hi, addr, size: Size::L });
+            }
+            _ => {
+                let size = self.size(self.func.reg_ty(dst));
+                self.emit(MInst::Load { dst: self.dst(dst), addr, size });
+            }
+        }
+    }
+
+    fn bin(&mut self, op: BinOp, ty: Ty, dst: ir::Reg, lhs: Value, rhs: Value) {
+        let dst = self.dst(dst);
+        let alu = match op {
+            BinOp::Add => AluOp::Add,
+            BinOp::Sub => AluOp::Sub,
+            BinOp::Mul => AluOp::Imul,
+            BinOp::SDiv | BinOp::SRem | BinOp::UDiv | BinOp::URem => {
+                let signed = matches!(op, BinOp::SDiv | BinOp::SRem);
+                let src = self.extended(rhs, ty, signed);
+                let lhs = self.extended(lhs, ty, signed);
+                let size = self.cmp_size(ty);
+                self.emit(MInst::Mov { dst: Reg::P(AX), src: Opnd::Reg(lhs) });
+                if signed {
+                    self.emit(MInst::Cltd { size });
+                } else {
+                    self.emit(MInst::Mov {
+                        dst: Reg::P(DX),
+                        src: Opnd::Imm(0),
+                    });
+                }
+                self.emit(MInst::Div { signed, src, size });
+                let res = match op {
+                    BinOp::SDiv | BinOp::UDiv => AX,
+                    _ => DX,
+                };
+                self.emit(MInst::Mov { dst, src: Opnd::Reg(Reg::P(res)) });
+                return;
+            }
+        };
+        self.mov(dst, lhs);
+        let src = self.opnd(rhs);
+        self.emit(MInst::Alu { op: alu, dst, src });
+    }
+
+    fn bin64(&mut self, op: BinOp, dst: ir::Reg, lhs: Value, rhs: Value) {
+        let (dl, dh) = self.dst_pair(dst);
+        let (al, ah) = self.pair(lhs);
+        let (bl, bh) = self.pair(rhs);
+        let (lo_op, hi_op) = match op {
+            BinOp::Add => (AluOp::Add, AluOp::Adc),
+            BinOp::Sub => (AluOp::Sub, AluOp::Sbb),
+            BinOp::Mul => {
+                // The cross products only affect the high half.
+                let t = self.vreg();
+                self.emit(MInst::Mov { dst: t, src: ah });
+                self.emit(MInst::Alu { op: AluOp::Imul, dst: t, src: bl });
+                let u = self.vreg();
+                self.emit(MInst::Mov { dst: u, src: bh });
+                self.emit(MInst::Alu { op: AluOp::Imul, dst: u, src: al });
+                self.emit(MInst::Alu {
+                    op: AluOp::Add,
+                    dst: t,
+                    src: Opnd::Reg(u),
+                });
+
+                let src = self.vreg();
+                self.emit(MInst::Mov { dst: src, src: bl });
+                self.emit(MInst::Mov { dst: Reg::P(AX), src: al });
+                self.emit(MInst::Mul { src });
+                self.emit(MInst::Mov { dst: dl, src: Opnd::Reg(Reg::P(AX)) });
+                self.emit(MInst::Mov { dst: dh, src: Opnd::Reg(Reg::P(DX)) });
+                self.emit(MInst::Alu {
+                    op: AluOp::Add,
+                    dst: dh,
+                    src: Opnd::Reg(t),
+                });
+                return;
+            }
+            BinOp::SDiv | BinOp::UDiv | BinOp::SRem | BinOp::URem => {
+                let name = match op {
+                    BinOp::SDiv => "__divdi3",
+                    BinOp::UDiv => "__udivdi3",
+                    BinOp::SRem => "__moddi3",
+                    _ => "__umoddi3",
+                };
+                for src in [bh, bl, ah, al] {
+                    self.emit(MInst::Push { src });
+                }
+                self.emit(MInst::Call {
+                    target: Opnd::Sym(Symbol::intern(name)),
+                    args: Vec::new(),
+                    clobbers: self.mode.abi().call_clobbers,
+                });
+                self.emit(MInst::AddSp { n: 16 });
+                self.emit(MInst::Mov { dst: dl, src: Opnd::Reg(Reg::P(AX)) });
+                self.emit(MInst::Mov { dst: dh, src: Opnd::Reg(Reg::P(DX)) });
+                return;
+            }
+        };
+        self.emit(MInst::Mov { dst: dl, src: al });
+        self.emit(MInst::Mov { dst: dh, src: ah });
+        self.emit(MInst::Alu { op: lo_op, dst: dl, src: bl });
+        self.emit(MInst::Alu { op: hi_op, dst: dh, src: bh });
+    }
+
+    /// Sets the flags for comparison `op`, returning the condition that holds
+    /// if it is true.
+    fn cond(&mut self, op: CmpOp, ty: Ty, lhs: Value, rhs: Value) -> Cc {
+        let signed =
+            matches!(op, CmpOp::SLt | CmpOp::SLe | CmpOp::SGt | CmpOp::SGe);
+        let cc = match op {
+            CmpOp::Eq => Cc::E,
+            CmpOp::Ne => Cc::Ne,
+            CmpOp::SLt => Cc::L,
+            CmpOp::SLe => Cc::Le,
+            CmpOp::SGt => Cc::G,
+            CmpOp::SGe => Cc::Ge,
+            CmpOp::ULt => Cc::B,
+            CmpOp::ULe => Cc::Be,
+            CmpOp::UGt => Cc::A,
+            CmpOp::UGe => Cc::Ae,
+        };
+
+        if ty != Ty::I64 || self.mode == Mode::X86_64 {
+            let lhs = self.extended(lhs, ty, signed);
+            let rhs = match ty {
+                Ty::I8 | Ty::I16 => Opnd::Reg(self.extended(rhs, ty, signed)),
+                _ => self.opnd(rhs),
+            };
+            let size = self.cmp_size(ty);
+            self.emit(MInst::Cmp { lhs, rhs, size });
+            return cc;
+        }
+
+        let (al, ah) = self.pair(lhs);
+        let (bl, bh) = self.pair(rhs);
+        if matches!(op, CmpOp::Eq | CmpOp::Ne) {
+            let t = self.vreg();
+            self.emit(MInst::Mov { dst: t, src: al });
+            self.emit(MInst::Alu { op: AluOp::Xor, dst: t, src: bl });
+            let u = self.vreg();
+            self.emit(MInst::Mov { dst: u, src: ah });
+            self.emit(MInst::Alu { op: AluOp::Xor, dst: u, src: bh });
+            self.emit(MInst::Alu { op: AluOp::Or, dst: t, src: Opnd::Reg(u) });
+            return cc;
+        }
+
+        // The borrow of `x - y` only gives `<` and `>=`, so swap the operands
+        // of the others.
+        let (xl, xh, yl, yh, cc) = match cc {
+            Cc::L | Cc::Ge | Cc::B | Cc::Ae => (al, ah, bl, bh, cc),
+            Cc::G => (bl, bh, al, ah, Cc::L),
+            Cc::Le => (bl, bh, al, ah, Cc::Ge),
+            Cc::A => (bl, bh, al, ah, Cc::B),
+            _ => (bl, bh, al, ah, Cc::Ae),
+        };
+        let t = self.vreg();
+        self.emit(MInst::Mov { dst: t, src: xh });
+        let lhs = match xl {
+            Opnd::Reg(r) => r,
+            src => {
+                let dst = self.vreg();
+                self.emit(MInst::Mov { dst, src });
+                dst
+            }
+        };
+        self.emit(MInst::Cmp { lhs, rhs: yl, size: Size::L });
+        self.emit(MInst::Alu { op: AluOp::Sbb, dst: t, src: yh });
+        cc
+    }
+
+    fn conv(&mut self, op: ConvOp, dst: ir::Reg, from: Ty, val: Value) {
+        match self.val(dst) {
+            Val::Pair(lo, hi) => {
+                let signed = op == ConvOp::SExt;
+                match from {
+                    Ty::I8 | Ty::I16 => self.extend(lo, val, from, signed),
+                    _ => self.mov(lo, val),
+                }
+                if signed {
+                    self.emit(MInst::Mov { dst: hi, src: Opnd::Reg(lo) });
+                    let amount = 31;
+                    self.emit(MInst::Shift {
+                        op: ShiftOp::Sar,
+                        dst: hi,
+                        amount,
+                    });
+                } else {
+                    self.emit(MInst::Mov { dst: hi, src: Opnd::Imm(0) });
+                }
+            }
+            _ => {
+                let dst = self.dst(dst);
+                match op {
+                    ConvOp::SExt => self.extend(dst, val, from, true),
+                    ConvOp::ZExt => self.extend(dst, val, from, false),
+                    // The upper bits of narrow values are undefined anyway.
+                    _ => self.mov(dst, val),
+                }
+            }
+        }
+    }
+
+    fn call(
+        &mut self,
+        dst: Option<ir::Reg>,
+        callee: &Callee,
+        args: &[(Ty, Value)],
+    ) {
+        let target = match callee {
+            Callee::Direct(name) => Opnd::Sym(*name),
+            Callee::Indirect(v) => Opnd::Reg(self.reg(*v)),
+        };
+        let mut n = 0;
+        for &(ty, v) in args.iter().rev() {
+            if ty == Ty::I64 {
+                let (lo, hi) = self.pair(v);
+                self.emit(MInst::Push { src: hi });
+                self.emit(MInst::Push { src: lo });
+            } else {
+                let src = self.opnd(v);
+                self.emit(MInst::Push { src });
+            }
+            n += slot_size(ty) as u32;
+        }
+        let clobbers = self.mode.abi().call_clobbers;
+        self.emit(MInst::Call { target, args: Vec::new(), clobbers });
+        self.emit(MInst::AddSp { n });
+        self.result(dst);
+    }
+
+    fn call64(
+        &mut self,
+        dst: Option<ir::Reg>,
+        callee: &Callee,
+        sig: &ir::Signature,
+        args: &[(Ty, Value)],
+    ) {
+        let arg_regs = self.mode.abi().arg_regs;
+        let target = match callee {
+            Callee::Direct(name) => Opnd::Sym(*name),
+            Callee::Indirect(v) => Opnd::Reg(self.reg(*v)),
+        };
+        let (regs, stack) = args.split_at(args.len().min(arg_regs.len()));
+        // Keep `%rsp` 16-byte aligned at the call.
+        let pad = 8 * (stack.len() as u32 % 2);
+        self.emit(MInst::SubSp { n: pad });
+        for &(_, v) in stack.iter().rev() {
+            let src = self.opnd(v);
+            self.emit(MInst::Push { src });
+        }
+        let srcs: Vec<Opnd> = regs.iter().map(|&(_, v)| self.opnd(v)).collect();
+        let mut used = Vec::new();
+        for (src, r) in srcs.into_iter().zip(arg_regs.iter().copied()) {
+            self.emit(MInst::Mov { dst: Reg::P(r), src });
+            used.push(r);
+        }
+        // No arguments are passed in vector registers.
+        if sig.variadic {
+            self.emit(MInst::Mov { dst: Reg::P(AX), src: Opnd::Imm(0) });
+            used.push(AX);
+        }
+        let clobbers = self.mode.abi().call_clobbers;
+        self.emit(MInst::Call { target, args: used, clobbers });
+        self.emit(MInst::AddSp { n: pad + 8 * stack.len() as u32 });
+        self.result(dst);
+    }
+
+    /// Moves the result of a call to `dst`.
+    fn result(&mut self, dst: Option<ir::Reg>) {
+        match dst.map(|d| self.val(d)) {
+            Some(Val::Pair(lo, hi)) => {
+                self.emit(MInst::Mov { dst: lo, src: Opnd::Reg(Reg::P(AX)) });
+                self.emit(MInst::Mov { dst: hi, src: Opnd::Reg(Reg::P(DX)) });
+            }
+            Some(Val::R(dst)) => {
+                self.emit(MInst::Mov { dst, src: Opnd::Reg(Reg::P(AX)) })
+            }
+            _ => {}
+        }
+    }
+
+    /// Initialises the `va_list` at `ap`, with the offset of the next saved
+    /// argument register, that of the next vector register (past all of
+    /// them), the address of the next argument on the stack, and that of the
+    /// register save area.
+    fn va_start64(&mut self, ap: Value) {
+        let arg_regs = self.mode.abi().arg_regs;
+        let n = self.func.params.len();
+        let addr = self.addr(ap);
+        let gp = 8 * n.min(arg_regs.len()) as i64;
+        self.emit(MInst::Store { src: Opnd::Imm(gp), addr, size: Size::L });
+        let fp = Opnd::Imm(8 * 6 + 16 * 8);
+        self.emit(MInst::Store {
+            src: fp,
+            addr: addr.offset(4),
+            size: Size::L,
+        });
+
+        let overflow = 16 + 8 * n.saturating_sub(arg_regs.len()) as i32;
+        let p = self.vreg();
+        let frame = Addr::new(Base::Frame(overflow));
+        self.emit(MInst::Lea { dst: p, addr: frame });
+        let src = Opnd::Reg(p);
+        self.emit(MInst::Store { src, addr: addr.offset(8), size: Size::Q });
+
+        let p = self.vreg();
+        let save = Addr::new(Base::Slot(self.reg_save.unwrap()));
+        self.emit(MInst::Lea { dst: p, addr: save });
+        let src = Opnd::Reg(p);
+        self.emit(MInst::Store { src, addr: addr.offset(16), size: Size::Q });
+    }
+
+    /// Reads the next argument of the `va_list` at `ap` from the register
+    /// save area while there is one there, from the stack after.
+    fn va_arg64(&mut self, dst: ir::Reg, ap: Value) {
+        let arg_regs = self.mode.abi().arg_regs;
+        let ap = self.addr(ap);
+        let gp = self.vreg();
+        self.emit(MInst::Load { dst: gp, addr: ap, size: Size::L });
+        let overflow = self.vreg();
+        let addr = ap.offset(8);
+        self.emit(MInst::Load { dst: overflow, addr, size: Size::Q });
+        let addr = self.vreg();
+        self.emit(MInst::Load {
+            dst: addr,
+            addr: ap.offset(16),
+            size: Size::Q,
+        });
+        self.emit(MInst::Alu { op: AluOp::Add, dst: addr, src: Opnd::Reg(gp) });
+
+        let next_gp = self.vreg();
+        self.emit(MInst::Mov { dst: next_gp, src: Opnd::Reg(gp) });
+        let eight = Opnd::Imm(8);
+        self.emit(MInst::Alu { op: AluOp::Add, dst: next_gp, src: eight });
+        let next_ov = self.vreg();
+        self.emit(MInst::Mov { dst: next_ov, src: Opnd::Reg(overflow) });
+        self.emit(MInst::Alu { op: AluOp::Add, dst: next_ov, src: eight });
+
+        let lhs = gp;
+        let rhs = Opnd::Imm(8 * arg_regs.len() as i64);
+        self.emit(MInst::Cmp { lhs, rhs, size: Size::L });
+        // Past the registers: read from the stack and leave `gp_offset`.
+        self.emit(MInst::Cmov { cc: Cc::Ae, dst: addr, src: overflow });
+        self.emit(MInst::Cmov { cc: Cc::Ae, dst: next_gp, src: gp });
+        // Still in the registers: leave `overflow_arg_area`.
+        self.emit(MInst::Cmov { cc: Cc::B, dst: next_ov, src: overflow });
+
+        let src = Opnd::Reg(next_gp);
+        self.emit(MInst::Store { src, addr: ap, size: Size::L });
+        let src = Opnd::Reg(next_ov);
+        self.emit(MInst::Store { src, addr: ap.offset(8), size: Size::Q });
+        self.load(dst, Addr::new(Base::Reg(addr)));
+    }
+
+    /// Selects the terminator of `b`, returning the successors.
+    fn term(&mut self, b: BlockId) -> Vec<usize> {
+        match self.func.block(b).term.as_ref().unwrap() {
+            Term::Ret(None) => {
+                self.emit(MInst::Ret { regs: &[] });
+                Vec::new()
+            }
+            Term::Ret(Some((Ty::I64, v))) if self.mode == Mode::I386 => {
+                let (lo, hi) = self.pair(*v);
+                self.emit(MInst::Mov { dst: Reg::P(AX), src: lo });
+                self.emit(MInst::Mov { dst: Reg::P(DX), src: hi });
+                self.emit(MInst::Ret { regs: &[AX, DX] });
+                Vec::new()
+            }
+            Term::Ret(Some((_, v))) => {
+                self.mov(Reg::P(AX), *v);
+                self.emit(MInst::Ret { regs: &[AX] });
+                Vec::new()
+            }
+            Term::Jmp(target) => {
+                let insts = self.phi_copies(b, *target);
+                self.insts.extend(insts);
+                let target = target.0 as usize;
+                self.emit(MInst::Jmp { target });
+                vec![target]
+            }
+            &Term::Br { ty, cond, then, else_ } => {
+                let cc = match self.fused.get(&b) {
+                    Some(&(op, ty, lhs, rhs)) => self.cond(op, ty, lhs, rhs),
+                    None => self.test(ty, cond),
+                };
+                let then = self.edge(b, then);
+                let else_ = self.edge(b, else_);
+                self.emit(MInst::Jcc { cc, target: then });
+                self.emit(MInst::Jmp { target: else_ });
+                vec![then, else_]
+            }
+            Term::Unreachable => {
+                self.emit(MInst::Ud2);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Sets the flags so that `ne` holds if `cond` is not zero.
+    fn test(&mut self, ty: Ty, cond: Value) -> Cc {
+        match ty {
+            Ty::I64 if self.mode == Mode::I386 => {
+                let (lo, hi) = self.pair(cond);
+                let t = self.vreg();
+                self.emit(MInst::Mov { dst: t, src: lo });
+                self.emit(MInst::Alu { op: AluOp::Or, dst: t, src: hi });
+            }
+            _ => {
+                let reg = self.reg(cond);
+                let size = self.size(ty);
+                self.emit(MInst::Test { reg, size });
+            }
+        }
+        Cc::Ne
+    }
+
+    /// Machine block to branch to for the edge from `from` to `to`, a new one
+    /// if `to` has phis to copy.
+    fn edge(&mut self, from: BlockId, to: BlockId) -> usize {
+        let to_phis =
+            matches!(self.func.block(to).insts.first(), Some(Inst::Phi { .. }));
+        if !to_phis {
+            return to.0 as usize;
+        }
+        let mut insts = self.phi_copies(from, to);
+        let target = to.0 as usize;
+        insts.push(MInst::Jmp { target });
+        self.mf.blocks.push(MBlock { insts, succs: vec![target] });
+        self.mf.blocks.len() - 1
+    }
+
+    /// Copies the values the phis of `to` take from `from`, all at once as
+    /// one phi may read another.
+    fn phi_copies(&mut self, from: BlockId, to: BlockId) -> Vec<MInst> {
+        let saved = std::mem::take(&mut self.insts);
+        let mut moves = Vec::new();
+        for inst in &self.func.block(to).insts {
+            let Inst::Phi { dst, args } = inst else {
+                break;
+            };
+            let (_, v) = args.iter().find(|(b, _)| *b == from).unwrap();
+            let srcs = match self.val(*dst) {
+                Val::Pair(lo, hi) => {
+                    let (l, h) = self.pair(*v);
+                    vec![(lo, l), (hi, h)]
+                }
+                _ => vec![(self.dst(*dst), self.opnd(*v))],
+            };
+            for (dst, src) in srcs {
+                match src {
+                    Opnd::Reg(_) => {
+                        let t = self.vreg();
+                        self.emit(MInst::Mov { dst: t, src });
+                        moves.push((dst, Opnd::Reg(t)));
+                    }
+                    _ => moves.push((dst, src)),
+                }
+            }
+        }
+        for (dst, src) in moves {
+            self.emit(MInst::Mov { dst, src });
+        }
+        std::mem::replace(&mut self.insts, saved)
+    }
+
+    /// Size of a value of type `ty` in memory, or of the low half of an
+    /// `i64` on i386.
+    fn size(&self, ty: Ty) -> Size {
+        match ty {
+            Ty::I8 => Size::B,
+            Ty::I16 => Size::W,
+            Ty::I32 => Size::L,
+            Ty::I64 | Ty::Ptr => self.mode.word_size(),
+        }
+    }
+
+    /// Size to compare and divide values of type `ty` at, the narrow ones
+    /// being extended first.
+    fn cmp_size(&self, ty: Ty) -> Size {
+        match ty {
+            Ty::I64 | Ty::Ptr => self.mode.word_size(),
+            _ => Size::L,
+        }
+    }
+}
+
+/// Size of an argument on the stack.
+fn slot_size(ty: Ty) -> i32 {
+    match ty {
+        Ty::I64 => 8,
+        _ => 4,
+    }
+}
@@ -0,0 +1,492 @@
+//! x86 code generation: instruction selection into [`MInst`]s over virtual
+//! registers, register allocation, then output in AT&T syntax.
+//!
+//! On i386 functions follow the cdecl convention: arguments are pushed right
+//! to left in 4 byte slots, 8 bytes for `i64`, results come back in `%eax`,
+//! or `%edx:%eax` for `i64`, and `%ebx`, `%esi` and `%edi` are preserved.
+//!
+//! On x86-64 they follow the System V ABI: the first six arguments are passed
+//! in `%rdi`, `%rsi`, `%rdx`, `%rcx`, `%r8` and `%r9` and the rest pushed in 8
+//! byte slots, `%rsp` is 16-byte aligned at calls, `%al` bounds the vector
+//! registers a variadic callee saves, results come back in `%rax`, and `%rbx`
+//! and `%r12` to `%r15` are preserved. Functions calling nothing keep small
+//! frames in the red zone below `%rsp`.
+
+mod emit;
+mod isel;
+
+use std::io::Write;
+
+use minicc_ast::{DataModel, Symbol};
+use minicc_ir as ir;
+
+use crate::regalloc::{self, Access, MachInst, PReg, Reg};
+use crate::{Abi, Target};
+
+pub const AX: PReg = PReg(0);
+pub const CX: PReg = PReg(1);
+pub const DX: PReg = PReg(2);
+pub const BX: PReg = PReg(3);
+pub const SP: PReg = PReg(4);
+pub const BP: PReg = PReg(5);
+pub const SI: PReg = PReg(6);
+pub const DI: PReg = PReg(7);
+pub const R8: PReg = PReg(8);
+pub const R9: PReg = PReg(9);
+pub const R10: PReg = PReg(10);
+pub const R11: PReg = PReg(11);
+pub const R12: PReg = PReg(12);
+pub const R13: PReg = PReg(13);
+pub const R14: PReg = PReg(14);
+pub const R15: PReg = PReg(15);
+
+/// Instruction set, with the calling convention of its System V ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    I386,
+    X86_64,
+}
+
+impl Mode {
+    /// Size of a register and of a pointer in bytes.
+    pub fn word(self) -> u32 {
+        match self {
+            Mode::I386 => 4,
+            Mode::X86_64 => 8,
+        }
+    }
+
+    fn word_size(self) -> Size {
+        match self {
+            Mode::I386 => Size::L,
+            Mode::X86_64 => Size::Q,
+        }
+    }
+
+    /// Registers with an addressable low byte.
+    fn byte_regs(self) -> &'static [PReg] {
+        match self {
+            Mode::I386 => &[AX, CX, DX, BX],
+            Mode::X86_64 => self.abi().gpr,
+        }
+    }
+}
+
+static I386_ABI: Abi = Abi {
+    gpr: &[AX, CX, DX, BX, SI, DI],
+    arg_regs: &[],
+    ret_regs: &[AX, DX],
+    callee_saved: &[BX, SI, DI],
+    call_clobbers: &[AX, CX, DX],
+    stack_slot: 4,
+    stack_align: 4,
+};
+
+static X86_64_ABI: Abi = Abi {
+    gpr: &[AX, CX, DX, SI, DI, R8, R9, R10, R11, BX, R12, R13, R14, R15],
+    arg_regs: &[DI, SI, DX, CX, R8, R9],
+    ret_regs: &[AX],
+    callee_saved: &[BX, R12, R13, R14, R15],
+    call_clobbers: &[AX, CX, DX, SI, DI, R8, R9, R10, R11],
+    stack_slot: 8,
+    stack_align: 16,
+};
+
+impl Target for Mode {
+    fn name(&self) -> &'static str {
+        match self {
+            Mode::I386 => "i386-linux",
+            Mode::X86_64 => "x86_64-linux",
+        }
+    }
+
+    fn data_model(&self) -> DataModel {
+        match self {
+            Mode::I386 => DataModel::ILP32,
+            Mode::X86_64 => DataModel { va_list: 24, ..DataModel::LP64 },
+        }
+    }
+
+    fn macros(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Mode::I386 => &[("__i386__", "1"), ("__linux__", "1")],
+            Mode::X86_64 => {
+                &[("__x86_64__", "1"), ("__LP64__", "1"), ("__linux__", "1")]
+            }
+        }
+    }
+
+    fn abi(&self) -> &'static Abi {
+        match self {
+            Mode::I386 => &I386_ABI,
+            Mode::X86_64 => &X86_64_ABI,
+        }
+    }
+
+    fn llvm_triple(&self) -> &'static str {
+        match self {
+            Mode::I386 => "i386-pc-linux-gnu",
+            Mode::X86_64 => "x86_64-pc-linux-gnu",
+        }
+    }
+
+    fn llvm_data_layout(&self) -> &'static str {
+        match self {
+            Mode::I386 => {
+                "e-m:e-p:32:32-p270:32:32-p271:32:32-p272:64:64-f64:32:64-f80:32-n8:16:32-S128"
+            }
+            Mode::X86_64 => {
+                "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+            }
+        }
+    }
+
+    fn gen_func(
+        &self,
+        f: &mut dyn Write,
+        _module: &ir::Module,
+        func: &ir::Function,
+    ) {
+        let (mut mf, slots) = isel::select(func, *self);
+        let used = regalloc::allocate(&mut mf);
+        emit::emit(f, func.name, *self, &mf, &slots, &used);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opnd {
+    Reg(Reg),
+    Imm(i64),
+    /// Address of a function.
+    Sym(Symbol),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    Reg(Reg),
+    /// Slot of the function.
+    Slot(u32),
+    /// Offset from the frame pointer, where the arguments are.
+    Frame(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Addr {
+    pub base: Base,
+    pub disp: i32,
+}
+
+impl Addr {
+    pub fn new(base: Base) -> Self {
+        Self { base, disp: 0 }
+    }
+
+    /// The address `n` bytes further.
+    pub fn offset(self, n: i32) -> Self {
+        Self { disp: self.disp + n, ..self }
+    }
+}
+
+/// Width of an operation, loads of less than 4 bytes zero extend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    B,
+    W,
+    L,
+    Q,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cc {
+    E,
+    Ne,
+    L,
+    Le,
+    G,
+    Ge,
+    B,
+    Be,
+    A,
+    Ae,
+}
+
+impl Cc {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cc::E => "e",
+            Cc::Ne => "ne",
+            Cc::L => "l",
+            Cc::Le => "le",
+            Cc::G => "g",
+            Cc::Ge => "ge",
+            Cc::B => "b",
+            Cc::Be => "be",
+            Cc::A => "a",
+            Cc::Ae => "ae",
+        }
+    }
+
+    pub fn inverse(self) -> Self {
+        match self {
+            Cc::E => Cc::Ne,
+            Cc::Ne => Cc::E,
+            Cc::L => Cc::Ge,
+            Cc::Le => Cc::G,
+            Cc::G => Cc::Le,
+            Cc::Ge => Cc::L,
+            Cc::B => Cc::Ae,
+            Cc::Be => Cc::A,
+            Cc::A => Cc::Be,
+            Cc::Ae => Cc::B,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Adc,
+    Sub,
+    Sbb,
+    Imul,
+    And,
+    Or,
+    Xor,
+}
+
+impl AluOp {
+    pub fn name(self) -> &'static str {
+        match self {
+            AluOp::Add => "add",
+            AluOp::Adc => "adc",
+            AluOp::Sub => "sub",
+            AluOp::Sbb => "sbb",
+            AluOp::Imul => "imul",
+            AluOp::And => "and",
+            AluOp::Or => "or",
+            AluOp::Xor => "xor",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftOp {
+    Shl,
+    Shr,
+    Sar,
+}
+
+/// Machine instruction, on registers as wide as a pointer unless it has a
+/// size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MInst {
+    Mov {
+        dst: Reg,
+        src: Opnd,
+    },
+    Load {
+        dst: Reg,
+        addr: Addr,
+        size: Size,
+    },
+    Store {
+        src: Opnd,
+        addr: Addr,
+        size: Size,
+    },
+    Lea {
+        dst: Reg,
+        addr: Addr,
+    },
+    /// `dst = dst op src`
+    Alu {
+        op: AluOp,
+        dst: Reg,
+        src: Opnd,
+    },
+    Shift {
+        op: ShiftOp,
+        dst: Reg,
+        amount: u8,
+    },
+    /// Sets the flags from `lhs - rhs`.
+    Cmp {
+        lhs: Reg,
+        rhs: Opnd,
+        size: Size,
+    },
+    /// Sets the flags from the low `size` of `reg`.
+    Test {
+        reg: Reg,
+        size: Size,
+    },
+    /// `dst = cc ? 1 : 0`
+    Set {
+        cc: Cc,
+        dst: Reg,
+    },
+    /// `dst = cc ? src : dst`
+    Cmov {
+        cc: Cc,
+        dst: Reg,
+        src: Reg,
+    },
+    /// Sign extends `%eax` into `%edx`, or `%rax` into `%rdx`.
+    Cltd {
+        size: Size,
+    },
+    /// Divides `%edx:%eax`, or `%rdx:%rax`, by `src`, leaving the quotient in
+    /// `%eax` and the remainder in `%edx`.
+    Div {
+        signed: bool,
+        src: Reg,
+        size: Size,
+    },
+    /// `%edx:%eax = %eax * src`, unsigned.
+    Mul {
+        src: Reg,
+    },
+    Push {
+        src: Opnd,
+    },
+    Call {
+        target: Opnd,
+        /// Registers passing arguments.
+        args: Vec<PReg>,
+        clobbers: &'static [PReg],
+    },
+    /// Pops `n` bytes of arguments.
+    AddSp {
+        n: u32,
+    },
+    /// Pads the stack by `n` bytes before pushing arguments.
+    SubSp {
+        n: u32,
+    },
+    Jmp {
+        target: usize,
+    },
+    Jcc {
+        cc: Cc,
+        target: usize,
+    },
+    /// Returns, with the result in `regs`.
+    Ret {
+        regs: &'static [PReg],
+    },
+    Ud2,
+    /// Loads `dst` from spill slot `slot`.
+    Reload {
+        dst: Reg,
+        slot: u32,
+    },
+    /// Stores `src` to spill slot `slot`.
+    Spill {
+        src: Reg,
+        slot: u32,
+    },
+}
+
+fn visit_opnd(opnd: &mut Opnd, f: &mut dyn FnMut(&mut Reg, Access)) {
+    if let Opnd::Reg(r) = opnd {
+        f(r, Access::Use);
+    }
+}
+
+fn visit_addr(addr: &mut Addr, f: &mut dyn FnMut(&mut Reg, Access)) {
+    if let Base::Reg(r) = &mut addr.base {
+        f(r, Access::Use);
+    }
+}
+
+fn visit_fixed(
+    regs: &[PReg],
+    access: Access,
+    f: &mut dyn FnMut(&mut Reg, Access),
+) {
+    for p in regs {
+        f(&mut Reg::P(*p), access);
+    }
+}
+
+impl MachInst for MInst {
+    fn visit_regs(&mut self, f: &mut dyn FnMut(&mut Reg, Access)) {
+        match self {
+            MInst::Mov { dst, src } => {
+                visit_opnd(src, f);
+                f(dst, Access::Def);
+            }
+            MInst::Load { dst, addr, .. } | MInst::Lea { dst, addr } => {
+                visit_addr(addr, f);
+                f(dst, Access::Def);
+            }
+            MInst::Store { src, addr, .. } => {
+                visit_opnd(src, f);
+                visit_addr(addr, f);
+            }
+            MInst::Alu { dst, src, .. } => {
+                visit_opnd(src, f);
+                f(dst, Access::UseDef);
+            }
+            MInst::Shift { dst, .. } => f(dst, Access::UseDef),
+            MInst::Cmp { lhs, rhs, .. } => {
+                f(lhs, Access::Use);
+                visit_opnd(rhs, f);
+            }
+            MInst::Test { reg, .. } => f(reg, Access::Use),
+            MInst::Set { dst, .. } => f(dst, Access::Def),
+            MInst::Cmov { dst, src, .. } => {
+                f(src, Access::Use);
+                f(dst, Access::UseDef);
+            }
+            MInst::Cltd { .. } => {
+                visit_fixed(&[AX], Access::Use, f);
+                visit_fixed(&[DX], Access::Def, f);
+            }
+            MInst::Div { src, .. } => {
+                f(src, Access::Use);
+                visit_fixed(&[AX, DX], Access::UseDef, f);
+            }
+            MInst::Mul { src } => {
+                f(src, Access::Use);
+                visit_fixed(&[AX], Access::UseDef, f);
+                visit_fixed(&[DX], Access::Def, f);
+            }
+            MInst::Push { src } => visit_opnd(src, f),
+            MInst::Call { target, args, .. } => {
+                visit_opnd(target, f);
+                visit_fixed(args, Access::Use, f);
+            }
+            MInst::Ret { regs } => visit_fixed(regs, Access::Use, f),
+            MInst::Reload { dst, .. } => f(dst, Access::Def),
+            MInst::Spill { src, .. } => f(src, Access::Use),
+            MInst::AddSp { .. }
+            | MInst::SubSp { .. }
+            | MInst::Jmp { .. }
+            | MInst::Jcc { .. }
+            | MInst::Ud2 => {}
+        }
+    }
+
+    fn clobbers(&self) -> &'static [PReg] {
+        match self {
+            MInst::Call { clobbers, .. } => clobbers,
+            _ => &[],
+        }
+    }
+
+    fn as_move(&self) -> Option<(Reg, Reg)> {
+        match *self {
+            MInst::Mov { dst, src: Opnd::Reg(src) } => Some((dst, src)),
+            _ => None,
+        }
+    }
+
+    fn load_spill(reg: Reg, slot: u32) -> Self {
+        MInst::Reload { dst: reg, slot }
+    }
+
+    fn store_spill(reg: Reg, slot: u32) -> Self {
+        MInst::Spill { src: reg, slot }
+    }
+}
@@ -0,0 +1,73 @@
+//! Generates assembly for each target from each `golden/*.c` optimised at
+//! `-O2` and compares it with `golden/*.TARGET.s`, or `.wat` for WebAssembly.
+//! `UPDATE_GOLDEN=1` rewrites the expected output instead. None of it needs an
+//! assembler.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use minicc_gen::Target;
+use minicc_ir::{OptLevel, PassManager};
+use minicc_pp::Preprocessor;
+
+fn gen(path: &Path, target: &dyn Target, level: OptLevel) -> String {
+    let model = target.data_model();
+    let out = Preprocessor::new().preprocess_file(path);
+    let nodes = minicc_parser::parse(&out.text, &out.map, model);
+    let mut module = minicc_ir::lower(&nodes, model);
+    PassManager::for_level(level).run(&mut module);
+    let mut asm = Vec::new();
+    minicc_gen::gen(&mut asm, &module, target);
+    String::from_utf8(asm).unwrap()
+}
+
+fn sources(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failed = Vec::new();
+
+    for path in sources(&dir) {
+        for &target in &minicc_gen::TARGETS {
+            let text = gen(&path, target, OptLevel::O2);
+            let ext = if target.name() == "wasm32" { "wat" } else { "s" };
+            let expected =
+                path.with_extension(format!("{}.{}", target.name(), ext));
+            if update {
+                fs::write(&expected, &text).unwrap();
+            } else if fs::read_to_string(&expected).ok().as_deref()
+                != Some(&*text)
+            {
+                eprintln!("{} differs:\n{}", expected.display(), text);
+                failed.push(expected);
+            }
+        }
+    }
+    assert!(failed.is_empty(), "mismatched golden files: {:?}", failed);
+}
+
+/// Every program of the end-to-end tests compiles for every target at each
+/// optimisation level.
+#[test]
+fn gen_tests() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test");
+    // Negative tests, which have no `.expect` file, do not compile.
+    let programs = sources(&dir).into_iter();
+    for path in programs.filter(|p| p.with_extension("expect").exists()) {
+        for &target in &minicc_gen::TARGETS {
+            for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+                gen(&path, target, level);
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+	.text
+	.globl	f
+	.type	f, %function
+f:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	sxtw	x9, w0
+	mov	x10, #0
+	sub	x10, x10, x0
+	sxtb	x11, w1
+	mul	x10, x10, x11
+	mov	x11, #3
+	udiv	w11, w2, w11
+	mov	x12, #2
+	udiv	w13, w11, w12
+	msub	x11, x13, x12, x11
+	add	x10, x10, x11
+	sub	x0, x10, x9
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.text
+	.globl	big
+	.type	big, %function
+big:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	movz	x9, #1227
+	movk	x9, #29179, lsl #16
+	movk	x9, #287, lsl #32
+	mul	x9, x0, x9
+	mov	x10, #4096
+	add	x0, x9, x10
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.text
+	.globl	rel
+	.type	rel, %function
+rel:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	cmp	w0, #10
+	cset	w9, lt
+	mov	x10, #5000
+	cmp	w1, w10
+	cset	w10, hs
+	add	x9, x9, x10
+	sxth	x10, w2
+	mov	x11, #-1
+	cmp	w10, w11
+	cset	w10, ne
+	add	x0, x9, x10
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.section	.note.GNU-stack,"",%progbits
@@ -0,0 +1,16 @@
+int f(int a, char c, unsigned u)
+{
+    long long l;
+    l = a;
+    return -a * c + u / 3 % 2 - (int)l;
+}
+
+long long big(long long a)
+{
+    return a * 1234567890123 + 4096;
+}
+
+int rel(int a, unsigned b, short s)
+{
+    return (a < 10) + (b >= 5000) + (s != -1);
+}
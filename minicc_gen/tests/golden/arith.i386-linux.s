@@ -0,0 +1,83 @@
+	.text
+	.globl	f
+	.type	f, @function
+f:
+	push	%ebp
+	mov	%esp, %ebp
+	push	%ebx
+	push	%esi
+	mov	8(%ebp), %eax
+	mov	12(%ebp), %ecx
+	mov	16(%ebp), %edx
+	mov	%eax, %ebx
+	mov	%ebx, %esi
+	sar	$31, %esi
+	mov	$0, %esi
+	sub	%eax, %esi
+	shl	$24, %ecx
+	sar	$24, %ecx
+	imul	%ecx, %esi
+	mov	$3, %ecx
+	mov	%edx, %eax
+	mov	$0, %edx
+	div	%ecx
+	mov	$2, %ecx
+	mov	$0, %edx
+	div	%ecx
+	add	%edx, %esi
+	sub	%ebx, %esi
+	mov	%esi, %eax
+	lea	-8(%ebp), %esp
+	pop	%esi
+	pop	%ebx
+	pop	%ebp
+	ret
+
+	.text
+	.globl	big
+	.type	big, @function
+big:
+	push	%ebp
+	mov	%esp, %ebp
+	mov	8(%ebp), %eax
+	mov	12(%ebp), %ecx
+	imul	$1912276171, %ecx
+	mov	$287, %edx
+	imul	%eax, %edx
+	add	%edx, %ecx
+	mov	$1912276171, %edx
+	mull	%edx
+	add	%ecx, %edx
+	add	$4096, %eax
+	adc	$0, %edx
+	mov	%ebp, %esp
+	pop	%ebp
+	ret
+
+	.text
+	.globl	rel
+	.type	rel, @function
+rel:
+	push	%ebp
+	mov	%esp, %ebp
+	mov	8(%ebp), %eax
+	mov	12(%ebp), %ecx
+	mov	16(%ebp), %edx
+	cmp	$10, %eax
+	setl	%al
+	movzbl	%al, %eax
+	cmp	$5000, %ecx
+	setae	%cl
+	movzbl	%cl, %ecx
+	add	%ecx, %eax
+	shl	$16, %edx
+	sar	$16, %edx
+	cmp	$-1, %edx
+	setne	%cl
+	movzbl	%cl, %ecx
+	add	%ecx, %eax
+	mov	%ebp, %esp
+	pop	%ebp
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,75 @@
+	.text
+	.globl	f
+	.type	f, @function
+f:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	sext.w	t0, a0
+	sub	t1, zero, a0
+	slli	t2, a1, 56
+	srai	t2, t2, 56
+	mul	t1, t1, t2
+	li	t2, 3
+	divuw	t2, a2, t2
+	li	t3, 2
+	remuw	t2, t2, t3
+	add	t1, t1, t2
+	sub	t0, t1, t0
+	sext.w	a0, t0
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+
+	.text
+	.globl	big
+	.type	big, @function
+big:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	li	t0, 1234567890123
+	mul	t0, a0, t0
+	li	t1, 4096
+	add	a0, t0, t1
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+
+	.text
+	.globl	rel
+	.type	rel, @function
+rel:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	sext.w	t0, a0
+	li	t1, 10
+	slt	t0, t0, t1
+	sext.w	t1, a1
+	li	t2, 5000
+	sltu	t1, t1, t2
+	xori	t1, t1, 1
+	add	t0, t0, t1
+	slli	t1, a2, 48
+	srai	t1, t1, 48
+	sext.w	t1, t1
+	li	t2, -1
+	xor	t1, t1, t2
+	snez	t1, t1
+	add	t0, t0, t1
+	sext.w	a0, t0
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
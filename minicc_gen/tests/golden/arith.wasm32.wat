@@ -0,0 +1,99 @@
+(module
+  (memory (export "memory") 16)
+  (global $__stack_pointer (mut i32) (i32.const 1048576))
+  (table 1 funcref)
+  (func $f (export "f") (param $r0 i32) (param $r1 i32) (param $r2 i32) (result i32)
+    (local $r3 i64)
+    (local $r4 i32)
+    (local $r5 i32)
+    (local $r6 i32)
+    (local $r7 i32)
+    (local $r8 i32)
+    (local $r9 i32)
+    (local $r10 i32)
+    (local $r11 i32)
+    local.get $r0
+    i64.extend_i32_s
+    local.set $r3
+    i32.const 0
+    local.get $r0
+    i32.sub
+    local.set $r4
+    local.get $r1
+    local.set $r5
+    local.get $r4
+    local.get $r5
+    i32.mul
+    local.set $r6
+    local.get $r2
+    i32.const 3
+    i32.div_u
+    local.set $r7
+    local.get $r7
+    i32.const 2
+    i32.rem_u
+    local.set $r8
+    local.get $r6
+    local.get $r8
+    i32.add
+    local.set $r9
+    local.get $r3
+    i32.wrap_i64
+    local.set $r10
+    local.get $r9
+    local.get $r10
+    i32.sub
+    local.set $r11
+    local.get $r11
+    return
+    unreachable
+  )
+  (func $big (export "big") (param $r0 i64) (result i64)
+    (local $r1 i64)
+    (local $r2 i64)
+    local.get $r0
+    i64.const 1234567890123
+    i64.mul
+    local.set $r1
+    local.get $r1
+    i64.const 4096
+    i64.add
+    local.set $r2
+    local.get $r2
+    return
+    unreachable
+  )
+  (func $rel (export "rel") (param $r0 i32) (param $r1 i32) (param $r2 i32) (result i32)
+    (local $r3 i32)
+    (local $r4 i32)
+    (local $r5 i32)
+    (local $r6 i32)
+    (local $r7 i32)
+    (local $r8 i32)
+    local.get $r0
+    i32.const 10
+    i32.lt_s
+    local.set $r3
+    local.get $r1
+    i32.const 5000
+    i32.ge_u
+    local.set $r4
+    local.get $r3
+    local.get $r4
+    i32.add
+    local.set $r5
+    local.get $r2
+    local.set $r6
+    local.get $r6
+    i32.const -1
+    i32.ne
+    local.set $r7
+    local.get $r5
+    local.get $r7
+    i32.add
+    local.set $r8
+    local.get $r8
+    return
+    unreachable
+  )
+)
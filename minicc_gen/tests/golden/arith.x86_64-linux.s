@@ -0,0 +1,67 @@
+	.text
+	.globl	f
+	.type	f, @function
+f:
+	push	%rbp
+	mov	%rsp, %rbp
+	mov	%rdi, %rcx
+	shl	$32, %rcx
+	sar	$32, %rcx
+	mov	$0, %rax
+	sub	%rdi, %rax
+	shl	$56, %rsi
+	sar	$56, %rsi
+	mov	%rax, %rdi
+	imul	%rsi, %rdi
+	mov	$3, %rsi
+	mov	%rdx, %rax
+	mov	$0, %rdx
+	div	%esi
+	mov	$2, %rsi
+	mov	$0, %rdx
+	div	%esi
+	add	%rdx, %rdi
+	sub	%rcx, %rdi
+	mov	%rdi, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+
+	.text
+	.globl	big
+	.type	big, @function
+big:
+	push	%rbp
+	mov	%rsp, %rbp
+	movabs	$1234567890123, %rax
+	imul	%rax, %rdi
+	add	$4096, %rdi
+	mov	%rdi, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+
+	.text
+	.globl	rel
+	.type	rel, @function
+rel:
+	push	%rbp
+	mov	%rsp, %rbp
+	cmp	$10, %edi
+	setl	%al
+	movzbl	%al, %eax
+	cmp	$5000, %esi
+	setae	%cl
+	movzbl	%cl, %ecx
+	add	%rcx, %rax
+	shl	$48, %rdx
+	sar	$48, %rdx
+	cmp	$-1, %edx
+	setne	%cl
+	movzbl	%cl, %ecx
+	add	%rcx, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,55 @@
+	.text
+	.globl	add
+	.type	add, %function
+add:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	add	x0, x0, x1
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.text
+	.globl	nine
+	.type	nine, %function
+nine:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	ldr	x9, [x29, #16]
+	add	x0, x0, x9
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.text
+	.globl	main
+	.type	main, %function
+main:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	adrp	x9, :got:add
+	ldr	x9, [x9, :got_lo12:add]
+	mov	x0, #1
+	mov	x1, #2
+	blr	x9
+	bl	dbg
+	sub	sp, sp, #16
+	mov	x9, #9
+	str	x9, [sp, #0]
+	mov	x0, #1
+	mov	x1, #2
+	mov	x2, #3
+	mov	x3, #4
+	mov	x4, #5
+	mov	x5, #6
+	mov	x6, #7
+	mov	x7, #8
+	bl	nine
+	add	sp, sp, #16
+	bl	dbg
+	mov	x0, #0
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.section	.note.GNU-stack,"",%progbits
@@ -0,0 +1,19 @@
+void dbg(int v);
+
+int add(int a, int b)
+{
+    return a + b;
+}
+
+int nine(int a, int b, int c, int d, int e, int f, int g, int h, int i)
+{
+    return a + i;
+}
+
+int main()
+{
+    int (*fp)(int, int);
+    fp = add;
+    dbg(fp(1, 2));
+    dbg(nine(1, 2, 3, 4, 5, 6, 7, 8, 9));
+}
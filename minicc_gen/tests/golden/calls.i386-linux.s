@@ -0,0 +1,67 @@
+	.text
+	.globl	add
+	.type	add, @function
+add:
+	push	%ebp
+	mov	%esp, %ebp
+	mov	8(%ebp), %eax
+	mov	12(%ebp), %ecx
+	add	%ecx, %eax
+	mov	%ebp, %esp
+	pop	%ebp
+	ret
+
+	.text
+	.globl	nine
+	.type	nine, @function
+nine:
+	push	%ebp
+	mov	%esp, %ebp
+	mov	8(%ebp), %eax
+	mov	12(%ebp), %ecx
+	mov	16(%ebp), %ecx
+	mov	20(%ebp), %ecx
+	mov	24(%ebp), %ecx
+	mov	28(%ebp), %ecx
+	mov	32(%ebp), %ecx
+	mov	36(%ebp), %ecx
+	mov	40(%ebp), %ecx
+	add	%ecx, %eax
+	mov	%ebp, %esp
+	pop	%ebp
+	ret
+
+	.text
+	.globl	main
+	.type	main, @function
+main:
+	push	%ebp
+	mov	%esp, %ebp
+	mov	$add, %eax
+	push	$2
+	push	$1
+	call	*%eax
+	add	$8, %esp
+	push	%eax
+	call	dbg
+	add	$4, %esp
+	push	$9
+	push	$8
+	push	$7
+	push	$6
+	push	$5
+	push	$4
+	push	$3
+	push	$2
+	push	$1
+	call	nine
+	add	$36, %esp
+	push	%eax
+	call	dbg
+	add	$4, %esp
+	mov	$0, %eax
+	mov	%ebp, %esp
+	pop	%ebp
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
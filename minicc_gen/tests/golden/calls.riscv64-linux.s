@@ -0,0 +1,70 @@
+	.text
+	.globl	add
+	.type	add, @function
+add:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	add	t0, a0, a1
+	sext.w	a0, t0
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+
+	.text
+	.globl	nine
+	.type	nine, @function
+nine:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	ld	t0, 0(s0)
+	add	t0, a0, t0
+	sext.w	a0, t0
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+
+	.text
+	.globl	main
+	.type	main, @function
+main:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	la	t0, add
+	li	a0, 1
+	li	a1, 2
+	jalr	t0
+	sext.w	a0, a0
+	call	dbg
+	addi	sp, sp, -16
+	li	t0, 9
+	sd	t0, 0(sp)
+	li	a0, 1
+	li	a1, 2
+	li	a2, 3
+	li	a3, 4
+	li	a4, 5
+	li	a5, 6
+	li	a6, 7
+	li	a7, 8
+	call	nine
+	addi	sp, sp, 16
+	sext.w	a0, a0
+	call	dbg
+	mv	a0, zero
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
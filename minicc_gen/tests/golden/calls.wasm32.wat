@@ -0,0 +1,57 @@
+(module
+  (import "env" "dbg" (func $dbg (param i32)))
+  (memory (export "memory") 16)
+  (global $__stack_pointer (mut i32) (i32.const 1048576))
+  (table 2 funcref)
+  (elem (i32.const 1) func $add)
+  (func $add (export "add") (param $r0 i32) (param $r1 i32) (result i32)
+    (local $r2 i32)
+    local.get $r0
+    local.get $r1
+    i32.add
+    local.set $r2
+    local.get $r2
+    return
+    unreachable
+  )
+  (func $nine (export "nine") (param $r0 i32) (param $r1 i32) (param $r2 i32) (param $r3 i32) (param $r4 i32) (param $r5 i32) (param $r6 i32) (param $r7 i32) (param $r8 i32) (result i32)
+    (local $r9 i32)
+    local.get $r0
+    local.get $r8
+    i32.add
+    local.set $r9
+    local.get $r9
+    return
+    unreachable
+  )
+  (func $main (export "main") (result i32)
+    (local $r0 i32)
+    (local $r1 i32)
+    (local $r2 i32)
+    i32.const 1
+    local.set $r0
+    i32.const 1
+    i32.const 2
+    local.get $r0
+    call_indirect (param i32 i32) (result i32)
+    local.set $r1
+    local.get $r1
+    call $dbg
+    i32.const 1
+    i32.const 2
+    i32.const 3
+    i32.const 4
+    i32.const 5
+    i32.const 6
+    i32.const 7
+    i32.const 8
+    i32.const 9
+    call $nine
+    local.set $r2
+    local.get $r2
+    call $dbg
+    i32.const 0
+    return
+    unreachable
+  )
+)
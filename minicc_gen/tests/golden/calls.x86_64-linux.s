@@ -0,0 +1,59 @@
+	.text
+	.globl	add
+	.type	add, @function
+add:
+	push	%rbp
+	mov	%rsp, %rbp
+	add	%rsi, %rdi
+	mov	%rdi, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+
+	.text
+	.globl	nine
+	.type	nine, @function
+nine:
+	push	%rbp
+	mov	%rsp, %rbp
+	mov	16(%rbp), %rax
+	mov	24(%rbp), %rax
+	mov	32(%rbp), %rax
+	add	%rax, %rdi
+	mov	%rdi, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+
+	.text
+	.globl	main
+	.type	main, @function
+main:
+	push	%rbp
+	mov	%rsp, %rbp
+	mov	add@GOTPCREL(%rip), %rax
+	mov	$1, %rdi
+	mov	$2, %rsi
+	call	*%rax
+	mov	%rax, %rdi
+	call	dbg@PLT
+	sub	$8, %rsp
+	push	$9
+	push	$8
+	push	$7
+	mov	$1, %rdi
+	mov	$2, %rsi
+	mov	$3, %rdx
+	mov	$4, %rcx
+	mov	$5, %r8
+	mov	$6, %r9
+	call	nine@PLT
+	add	$32, %rsp
+	mov	%rax, %rdi
+	call	dbg@PLT
+	mov	$0, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
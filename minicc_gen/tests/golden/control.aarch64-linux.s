@@ -0,0 +1,61 @@
+	.text
+	.globl	max
+	.type	max, %function
+max:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x9, x0
+	cmp	w9, w1
+	b.gt	.Lmax.3
+.Lmax.1:
+.Lmax.2:
+	mov	x0, x1
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+.Lmax.3:
+	mov	x1, x9
+	b	.Lmax.2
+
+	.text
+	.globl	count
+	.type	count, %function
+count:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	mov	x9, x0
+	mov	x10, #0
+	mov	x11, #0
+.Lcount.1:
+	cmp	w11, w9
+	b.ge	.Lcount.7
+.Lcount.2:
+	cmp	w11, #3
+	b.ne	.Lcount.4
+.Lcount.3:
+	add	x12, x10, #10
+	b	.Lcount.6
+.Lcount.4:
+	cmp	w11, #0
+	b.ne	.Lcount.8
+.Lcount.5:
+	mov	x0, #0
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+.Lcount.6:
+	add	x13, x11, #1
+	mov	x14, x12
+	mov	x10, x14
+	mov	x11, x13
+	b	.Lcount.1
+.Lcount.7:
+	mov	x0, x10
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+.Lcount.8:
+	mov	x12, x10
+	b	.Lcount.6
+
+	.section	.note.GNU-stack,"",%progbits
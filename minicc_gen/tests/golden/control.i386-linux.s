@@ -0,0 +1,75 @@
+	.text
+	.globl	max
+	.type	max, @function
+max:
+	push	%ebp
+	mov	%esp, %ebp
+	mov	8(%ebp), %ecx
+	mov	12(%ebp), %eax
+	cmp	%eax, %ecx
+	jg	.Lmax.3
+.Lmax.1:
+	mov	%eax, %edx
+.Lmax.2:
+	mov	%edx, %eax
+	mov	%ebp, %esp
+	pop	%ebp
+	ret
+.Lmax.3:
+	mov	%ecx, %edx
+	jmp	.Lmax.2
+
+	.text
+	.globl	count
+	.type	count, @function
+count:
+	push	%ebp
+	mov	%esp, %ebp
+	push	%ebx
+	push	%esi
+	push	%edi
+	mov	8(%ebp), %ecx
+	mov	$0, %edx
+	mov	$0, %ebx
+.Lcount.1:
+	cmp	%ecx, %ebx
+	jge	.Lcount.7
+.Lcount.2:
+	cmp	$3, %ebx
+	jne	.Lcount.4
+.Lcount.3:
+	mov	%edx, %eax
+	add	$10, %eax
+	mov	%eax, %esi
+	jmp	.Lcount.6
+.Lcount.4:
+	cmp	$0, %ebx
+	jne	.Lcount.8
+.Lcount.5:
+	mov	$0, %eax
+	lea	-12(%ebp), %esp
+	pop	%edi
+	pop	%esi
+	pop	%ebx
+	pop	%ebp
+	ret
+.Lcount.6:
+	mov	%ebx, %eax
+	add	$1, %eax
+	mov	%esi, %edi
+	mov	%edi, %edx
+	mov	%eax, %ebx
+	jmp	.Lcount.1
+.Lcount.7:
+	mov	%edx, %eax
+	lea	-12(%ebp), %esp
+	pop	%edi
+	pop	%esi
+	pop	%ebx
+	pop	%ebp
+	ret
+.Lcount.8:
+	mov	%edx, %esi
+	jmp	.Lcount.6
+
+	.section	.note.GNU-stack,"",@progbits
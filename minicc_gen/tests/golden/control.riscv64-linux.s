@@ -0,0 +1,74 @@
+	.text
+	.globl	max
+	.type	max, @function
+max:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	mv	t0, a0
+	sext.w	t1, t0
+	sext.w	t2, a1
+	blt	t2, t1, .Lmax.3
+.Lmax.1:
+.Lmax.2:
+	sext.w	a0, a1
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+.Lmax.3:
+	mv	a1, t0
+	j	.Lmax.2
+
+	.text
+	.globl	count
+	.type	count, @function
+count:
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	mv	t0, a0
+	li	t1, 0
+	li	t2, 0
+.Lcount.1:
+	sext.w	t3, t2
+	sext.w	t4, t0
+	bge	t3, t4, .Lcount.7
+.Lcount.2:
+	sext.w	t3, t2
+	li	t4, 3
+	bne	t3, t4, .Lcount.4
+.Lcount.3:
+	addi	t3, t1, 10
+	j	.Lcount.6
+.Lcount.4:
+	sext.w	t4, t2
+	bne	t4, zero, .Lcount.8
+.Lcount.5:
+	mv	a0, zero
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+.Lcount.6:
+	addi	t4, t2, 1
+	mv	t5, t3
+	mv	t1, t5
+	mv	t2, t4
+	j	.Lcount.1
+.Lcount.7:
+	sext.w	a0, t1
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 16
+	ret
+.Lcount.8:
+	mv	t3, t1
+	j	.Lcount.6
+
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,94 @@
+(module
+  (memory (export "memory") 16)
+  (global $__stack_pointer (mut i32) (i32.const 1048576))
+  (table 1 funcref)
+  (func $max (export "max") (param $r0 i32) (param $r1 i32) (result i32)
+    (local $r2 i32)
+    (local $r3 i32)
+    block $bb2
+      local.get $r0
+      local.get $r1
+      i32.gt_s
+      local.set $r2
+      local.get $r2
+      if
+        local.get $r0
+        local.set $r3
+        br $bb2
+      else
+        local.get $r1
+        local.set $r3
+        br $bb2
+      end
+    end
+    local.get $r3
+    return
+    unreachable
+  )
+  (func $count (export "count") (param $r0 i32) (result i32)
+    (local $r1 i32)
+    (local $r2 i32)
+    (local $r3 i32)
+    (local $r4 i32)
+    (local $r5 i32)
+    (local $r6 i32)
+    (local $r7 i32)
+    (local $r8 i32)
+    i32.const 0
+    i32.const 0
+    local.set $r2
+    local.set $r1
+    loop $loop1
+      local.get $r2
+      local.get $r0
+      i32.lt_s
+      local.set $r3
+      local.get $r3
+      if
+        block $bb6
+          local.get $r2
+          i32.const 3
+          i32.eq
+          local.set $r4
+          local.get $r4
+          if
+            local.get $r1
+            i32.const 10
+            i32.add
+            local.set $r5
+            local.get $r5
+            local.set $r7
+            br $bb6
+          else
+            local.get $r2
+            i32.const 0
+            i32.eq
+            local.set $r6
+            local.get $r6
+            if
+              i32.const 0
+              return
+            else
+              local.get $r1
+              local.set $r7
+              br $bb6
+            end
+          end
+        end
+        local.get $r2
+        i32.const 1
+        i32.add
+        local.set $r8
+        local.get $r7
+        local.get $r8
+        local.set $r2
+        local.set $r1
+        br $loop1
+      else
+        local.get $r1
+        return
+      end
+    end
+    unreachable
+  )
+)
@@ -0,0 +1,62 @@
+	.text
+	.globl	max
+	.type	max, @function
+max:
+	push	%rbp
+	mov	%rsp, %rbp
+	cmp	%esi, %edi
+	jg	.Lmax.3
+.Lmax.1:
+.Lmax.2:
+	mov	%rsi, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+.Lmax.3:
+	mov	%rdi, %rsi
+	jmp	.Lmax.2
+
+	.text
+	.globl	count
+	.type	count, @function
+count:
+	push	%rbp
+	mov	%rsp, %rbp
+	mov	$0, %rcx
+	mov	$0, %rdx
+.Lcount.1:
+	cmp	%edi, %edx
+	jge	.Lcount.7
+.Lcount.2:
+	cmp	$3, %edx
+	jne	.Lcount.4
+.Lcount.3:
+	mov	%rcx, %rax
+	add	$10, %rax
+	mov	%rax, %rsi
+	jmp	.Lcount.6
+.Lcount.4:
+	cmp	$0, %edx
+	jne	.Lcount.8
+.Lcount.5:
+	mov	$0, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+.Lcount.6:
+	mov	%rdx, %rax
+	add	$1, %rax
+	mov	%rsi, %r8
+	mov	%r8, %rcx
+	mov	%rax, %rdx
+	jmp	.Lcount.1
+.Lcount.7:
+	mov	%rcx, %rax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+.Lcount.8:
+	mov	%rcx, %rsi
+	jmp	.Lcount.6
+
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,43 @@
+	.text
+	.globl	sum
+	.type	sum, %function
+sum:
+	stp	x29, x30, [sp, #-16]!
+	mov	x29, sp
+	sub	sp, sp, #96
+	stur	x0, [x29, #-96]
+	stur	x1, [x29, #-88]
+	stur	x2, [x29, #-80]
+	stur	x3, [x29, #-72]
+	stur	x4, [x29, #-64]
+	stur	x5, [x29, #-56]
+	stur	x6, [x29, #-48]
+	stur	x7, [x29, #-40]
+	add	x9, x29, #16
+	stur	x9, [x29, #-32]
+	sub	x9, x29, #32
+	stur	x9, [x29, #-24]
+	mov	x9, #0
+	stur	x9, [x29, #-16]
+	mov	x10, #-56
+	stur	w10, [x29, #-8]
+	stur	w9, [x29, #-4]
+	ldur	w9, [x29, #-8]
+	sxtw	x9, w9
+	ldur	x10, [x29, #-32]
+	ldur	x11, [x29, #-24]
+	add	x11, x11, x9
+	add	x12, x9, #8
+	add	x13, x10, #8
+	cmp	x9, #0
+	csel	x11, x11, x10, lt
+	csel	x9, x12, x9, lt
+	csel	x10, x10, x13, lt
+	stur	w9, [x29, #-8]
+	stur	x10, [x29, #-32]
+	ldr	w0, [x11, #0]
+	mov	sp, x29
+	ldp	x29, x30, [sp], #16
+	ret
+
+	.section	.note.GNU-stack,"",%progbits
@@ -0,0 +1,20 @@
+	.text
+	.globl	sum
+	.type	sum, @function
+sum:
+	push	%ebp
+	mov	%esp, %ebp
+	sub	$4, %esp
+	mov	8(%ebp), %eax
+	lea	12(%ebp), %eax
+	movl	%eax, -4(%ebp)
+	mov	-4(%ebp), %eax
+	mov	(%eax), %ecx
+	add	$4, %eax
+	movl	%eax, -4(%ebp)
+	mov	%ecx, %eax
+	mov	%ebp, %esp
+	pop	%ebp
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
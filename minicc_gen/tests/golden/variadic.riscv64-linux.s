@@ -0,0 +1,32 @@
+	.text
+	.globl	sum
+	.type	sum, @function
+sum:
+	addi	sp, sp, -64
+	sd	a0, 0(sp)
+	sd	a1, 8(sp)
+	sd	a2, 16(sp)
+	sd	a3, 24(sp)
+	sd	a4, 32(sp)
+	sd	a5, 40(sp)
+	sd	a6, 48(sp)
+	sd	a7, 56(sp)
+	addi	sp, sp, -16
+	sd	ra, 8(sp)
+	sd	s0, 0(sp)
+	addi	s0, sp, 16
+	addi	sp, sp, -16
+	addi	t0, s0, 8
+	sd	t0, -24(s0)
+	ld	t0, -24(s0)
+	lwu	t1, 0(t0)
+	addi	t0, t0, 8
+	sd	t0, -24(s0)
+	sext.w	a0, t1
+	addi	sp, s0, -16
+	ld	ra, 8(sp)
+	ld	s0, 0(sp)
+	addi	sp, sp, 80
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
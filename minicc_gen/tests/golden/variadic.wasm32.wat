@@ -0,0 +1,38 @@
+(module
+  (memory (export "memory") 16)
+  (global $__stack_pointer (mut i32) (i32.const 1048576))
+  (table 1 funcref)
+  (func $sum (export "sum") (param $r0 i32) (param $va i32) (result i32)
+    (local $r1 i32)
+    (local $r2 i32)
+    (local $fp i32)
+    (local $vp i32)
+    global.get $__stack_pointer
+    i32.const 16
+    i32.sub
+    local.tee $fp
+    global.set $__stack_pointer
+    local.get $fp
+    local.set $r1
+    local.get $r1
+    local.get $va
+    i32.store
+    local.get $r1
+    i32.load
+    local.tee $vp
+    i32.load
+    local.set $r2
+    local.get $r1
+    local.get $vp
+    i32.const 4
+    i32.add
+    i32.store
+    local.get $r2
+    local.get $fp
+    i32.const 16
+    i32.add
+    global.set $__stack_pointer
+    return
+    unreachable
+  )
+)
@@ -0,0 +1,38 @@
+	.text
+	.globl	sum
+	.type	sum, @function
+sum:
+	push	%rbp
+	mov	%rsp, %rbp
+	movq	%rdi, -72(%rbp)
+	movq	%rsi, -64(%rbp)
+	movq	%rdx, -56(%rbp)
+	movq	%rcx, -48(%rbp)
+	movq	%r8, -40(%rbp)
+	movq	%r9, -32(%rbp)
+	movl	$8, -24(%rbp)
+	movl	$176, -20(%rbp)
+	lea	16(%rbp), %rax
+	movq	%rax, -16(%rbp)
+	lea	-72(%rbp), %rax
+	movq	%rax, -8(%rbp)
+	mov	-24(%rbp), %eax
+	mov	-16(%rbp), %rcx
+	mov	-8(%rbp), %rdx
+	add	%rax, %rdx
+	mov	%rax, %rsi
+	add	$8, %rsi
+	mov	%rcx, %rdi
+	add	$8, %rdi
+	cmp	$48, %eax
+	cmovae	%rcx, %rdx
+	cmovae	%rax, %rsi
+	cmovb	%rcx, %rdi
+	movl	%esi, -24(%rbp)
+	movq	%rdi, -16(%rbp)
+	mov	(%rdx), %eax
+	mov	%rbp, %rsp
+	pop	%rbp
+	ret
+
+	.section	.note.GNU-stack,"",@progbits
@@ -0,0 +1,88 @@
+//! Checks the LLVM IR written for a function at each optimisation level:
+//! locals in `alloca`s at `-O0`, promoted by minicc's `mem2reg` above it.
+//! `ccom/tests/llvm.rs` runs the end-to-end tests through LLVM.
+
+use minicc_ir::{OptLevel, PassManager};
+use minicc_pp::Preprocessor;
+
+const SRC: &str = "\
+int f(int a, int b)
+{
+    int q;
+    int r;
+    q = a / b;
+    r = a % b;
+    return (q < r) + (a >= b);
+}
+";
+
+/// The definition of `f` at `level`.
+fn llvm(level: OptLevel) -> String {
+    let target = minicc_gen::target("x86_64-linux").unwrap();
+    let model = target.data_model();
+    let out = Preprocessor::new().preprocess_str("test.c", SRC);
+    let nodes = minicc_parser::parse(&out.text, &out.map, model);
+    let mut module = minicc_ir::lower(&nodes, model);
+    PassManager::for_level(level).run(&mut module);
+    let mut ll = Vec::new();
+    minicc_gen::llvm(&mut ll, &module, target);
+    let ll = String::from_utf8(ll).unwrap();
+    let start = ll.find("define i32 @f").unwrap();
+    let end = start + ll[start..].find("\n}\n").unwrap();
+    ll[start..end + 3].to_string()
+}
+
+#[test]
+fn o0() {
+    assert_eq!(
+        llvm(OptLevel::O0),
+        "\
+define i32 @f(i32 %r0, i32 %r1) {
+bb0:
+  %s0 = alloca i32, align 4
+  %s1 = alloca i32, align 4
+  %s2 = alloca i32, align 4
+  %s3 = alloca i32, align 4
+  store i32 %r0, ptr %s0
+  store i32 %r1, ptr %s1
+  %r5 = load i32, ptr %s0
+  %r7 = load i32, ptr %s1
+  %r8 = sdiv i32 %r5, %r7
+  store i32 %r8, ptr %s2
+  %r11 = load i32, ptr %s0
+  %r13 = load i32, ptr %s1
+  %r14 = srem i32 %r11, %r13
+  store i32 %r14, ptr %s3
+  %r17 = load i32, ptr %s2
+  %r19 = load i32, ptr %s3
+  %r20.b = icmp slt i32 %r17, %r19
+  %r20 = zext i1 %r20.b to i32
+  %r22 = load i32, ptr %s0
+  %r24 = load i32, ptr %s1
+  %r25.b = icmp sge i32 %r22, %r24
+  %r25 = zext i1 %r25.b to i32
+  %r26 = add i32 %r20, %r25
+  ret i32 %r26
+}
+"
+    );
+}
+
+#[test]
+fn o1_o2() {
+    let promoted = "\
+define i32 @f(i32 %r0, i32 %r1) {
+bb0:
+  %r2 = sdiv i32 %r0, %r1
+  %r3 = srem i32 %r0, %r1
+  %r4.b = icmp slt i32 %r2, %r3
+  %r4 = zext i1 %r4.b to i32
+  %r5.b = icmp sge i32 %r0, %r1
+  %r5 = zext i1 %r5.b to i32
+  %r6 = add i32 %r4, %r5
+  ret i32 %r6
+}
+";
+    assert_eq!(llvm(OptLevel::O1), promoted);
+    assert_eq!(llvm(OptLevel::O2), promoted);
+}
@@ -0,0 +1,98 @@
+//! Compiles each of the end-to-end tests for `wasm32` at every optimisation
+//! level, validates and runs the module with `wasmi`, with `dbg` imported
+//! from the host, and compares what it printed with the `.expect` file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use minicc_ir::{OptLevel, PassManager};
+use minicc_pp::Preprocessor;
+use wasmi::{Engine, ExternType, Linker, Module, Store, Val};
+
+fn gen(path: &Path, level: OptLevel) -> String {
+    let target = minicc_gen::target("wasm32").unwrap();
+    let model = target.data_model();
+    let mut pp = Preprocessor::new();
+    for (name, val) in target.macros() {
+        pp.define(name, val);
+    }
+    let out = pp.preprocess_file(path);
+    let nodes = minicc_parser::parse(&out.text, &out.map, model);
+    let mut module = minicc_ir::lower(&nodes, model);
+    PassManager::for_level(level).run(&mut module);
+    let mut wat = Vec::new();
+    minicc_gen::gen(&mut wat, &module, target);
+    String::from_utf8(wat).unwrap()
+}
+
+/// Runs `main` of module `wat`, returning the lines `dbg` printed.
+fn run(wat: &str) -> Result<String, String> {
+    let wasm = wat::parse_str(wat).map_err(|e| e.to_string())?;
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).map_err(|e| e.to_string())?;
+    let mut store = Store::new(&engine, String::new());
+    let mut linker = Linker::<String>::new(&engine);
+    for import in module.imports() {
+        let ExternType::Func(ty) = import.ty() else {
+            continue;
+        };
+        if import.name() != "dbg" {
+            continue;
+        }
+        linker
+            .func_new("env", "dbg", ty.clone(), |mut caller, args, results| {
+                let v = match args[0] {
+                    Val::I32(v) => v as i64,
+                    Val::I64(v) => v,
+                    _ => unreachable!(),
+                };
+                caller.data_mut().push_str(&format!("{}\n", v));
+                results.iter_mut().for_each(|r| *r = Val::I32(0));
+                Ok(())
+            })
+            .unwrap();
+    }
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .and_then(|pre| pre.start(&mut store))
+        .map_err(|e| e.to_string())?;
+    let main = instance.get_func(&store, "main").ok_or("no `main`")?;
+    let mut results = vec![Val::I32(0); main.ty(&store).results().len()];
+    main.call(&mut store, &[], &mut results).map_err(|e| e.to_string())?;
+    Ok(store.into_data())
+}
+
+#[test]
+fn wasm32() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter(|p| p.with_extension("expect").exists())
+        .collect();
+    paths.sort();
+
+    let mut failed = Vec::new();
+    for path in paths {
+        let expected =
+            fs::read_to_string(path.with_extension("expect")).unwrap();
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            let wat = gen(&path, level);
+            match run(&wat) {
+                Ok(out) if out == expected => {}
+                out => {
+                    eprintln!(
+                        "{} at {:?}: {:?}\n{}",
+                        path.display(),
+                        level,
+                        out,
+                        wat
+                    );
+                    failed.push((path.clone(), level));
+                }
+            }
+        }
+    }
+    assert!(failed.is_empty(), "failed: {:?}", failed);
+}
@@ -0,0 +1,714 @@
+//! Interpreter evaluating checked programs directly, for running them
+//! without a code generator and as an oracle for one.
+//!
+//! Values are computed with the sizes of a [`DataModel`], with unsigned
+//! arithmetic wrapping around and `/` truncating towards zero as in C.
+//! Locals are objects in a [`mem::Memory`] whose addresses are never reused
+//! and pointers are plain addresses, so programs may convert them to
+//! integers of the same size. Undefined behaviour the interpreter can see,
+//! such as signed overflow, division by zero, reading an uninitialized local
+//! or dereferencing a null or dangling pointer, stops the program with an
+//! [`Error`] at the offending expression.
+//!
+//! `dbg` is built in unless the program defines it, printing its argument
+//! and a newline. A [`Session`] runs statements one at a time instead of a
+//! whole program, as a REPL does.
+
+mod mem;
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::Write;
+use std::rc::Rc;
+
+use minicc_ast::{
+    Ast, AstKind, Call, DataModel, FnDecl, FnTy, OpBin, OpUn, Span, Symbol, Ty,
+};
+
+use mem::{Fault, Memory};
+
+/// Depth of calls at which the program is stopped, as its stack would
+/// overflow.
+pub const MAX_DEPTH: usize = 10_000;
+
+/// Where `&f` of the `i`th function taken points, below any object.
+const FN_BASE: u64 = 0x1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// Expression at fault, if any.
+    pub span: Option<Span>,
+    pub msg: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+type Result<T> = std::result::Result<T, Error>;
+
+fn error(span: Span, msg: impl Into<String>) -> Error {
+    Error { span: Some(span), msg: msg.into() }
+}
+
+fn ub(span: Span, msg: impl Display) -> Error {
+    error(span, format!("undefined behaviour: {}", msg))
+}
+
+/// Runs `main` of the function declarations `nodes`, checked for `model`,
+/// writing what `dbg` prints to `out`, and returns its exit status.
+pub fn run(
+    nodes: &[Ast],
+    model: DataModel,
+    out: &mut dyn Write,
+) -> Result<i32> {
+    let mut interp = Interp::new(model, out);
+    interp.define(nodes);
+
+    let main = Symbol::intern("main");
+    let Some(f) = interp.fns.get(&main).cloned() else {
+        return Err(Error { span: None, msg: "no `main` function".into() });
+    };
+    // There are no strings for `argv`, so `main` gets zeros.
+    let args = f.decl.params.iter().map(|p| (0, p.ty.clone())).collect();
+    let status = interp.invoke(main, args, f.span)?;
+    Ok(status.unwrap_or(0) as i32)
+}
+
+/// Statements run one at a time at the top level, where the locals they
+/// declare outlive them, with functions defined in between.
+pub struct Session<W> {
+    interp: Interp<W>,
+}
+
+impl<W: Write> Session<W> {
+    /// Session writing what `dbg` prints to `out`.
+    pub fn new(model: DataModel, out: W) -> Self {
+        let mut interp = Interp::new(model, out);
+        interp
+            .frames
+            .push(Frame { scopes: vec![Vec::new()], ..Frame::default() });
+        Self { interp }
+    }
+
+    /// Defines the functions of the function declarations `nodes`, replacing
+    /// those of the same name.
+    pub fn define(&mut self, nodes: &[Ast]) {
+        self.interp.define(nodes);
+    }
+
+    /// Runs the statement `node`, giving its value if it is an expression.
+    /// After an error the locals declared before it are still there.
+    pub fn exec(&mut self, node: &Ast) -> Result<Option<i64>> {
+        let scopes = self.interp.frames[0].scopes.len();
+        let res = match &node.kind {
+            AstKind::CompoundStmt(_)
+            | AstKind::If(_)
+            | AstKind::For(_)
+            | AstKind::VarDecl(_)
+            | AstKind::Return(_) => self.interp.stmt(node).map(|_| None),
+            _ => self.interp.expr(node),
+        };
+        if res.is_err() {
+            self.interp.unwind(1, scopes);
+        }
+        res
+    }
+
+    pub fn out(&mut self) -> &mut W {
+        &mut self.interp.out
+    }
+}
+
+/// How a statement completes.
+enum Flow {
+    Next,
+    Return(Option<i64>),
+}
+
+/// A defined function.
+struct Func {
+    span: Span,
+    decl: FnDecl,
+    ty: Ty,
+}
+
+#[derive(Default)]
+struct Frame {
+    /// Addresses of the locals of each block, innermost last.
+    scopes: Vec<Vec<(Symbol, u64)>>,
+    /// Arguments passed for `...`.
+    varargs: Vec<(i64, Ty)>,
+    /// `va_list`s started in the function, invalid once it returns.
+    va_lists: Vec<usize>,
+}
+
+/// Variadic arguments a `va_list` walks.
+struct VaList {
+    args: Vec<(i64, Ty)>,
+    next: usize,
+    live: bool,
+}
+
+struct Interp<W> {
+    model: DataModel,
+    out: W,
+    mem: Memory,
+    fns: HashMap<Symbol, Rc<Func>>,
+    /// Functions whose address is taken, in order.
+    fn_addrs: Vec<Symbol>,
+    frames: Vec<Frame>,
+    /// The `va_list` a value of that type holds is `va_lists[value - 1]`.
+    va_lists: Vec<VaList>,
+}
+
+impl<W: Write> Interp<W> {
+    fn new(model: DataModel, out: W) -> Self {
+        Self {
+            model,
+            out,
+            mem: Memory::new(model.long),
+            fns: HashMap::new(),
+            fn_addrs: Vec::new(),
+            frames: Vec::new(),
+            va_lists: Vec::new(),
+        }
+    }
+
+    fn define(&mut self, nodes: &[Ast]) {
+        for node in nodes {
+            let AstKind::FnDecl(decl @ FnDecl { body: Some(_), .. }) =
+                &node.kind
+            else {
+                continue;
+            };
+            let ty = Ty::Fn(FnTy {
+                ret: Box::new(decl.ret.clone()),
+                params: decl.prototyped.then(|| {
+                    decl.params.iter().map(|p| p.ty.clone()).collect()
+                }),
+                variadic: decl.variadic,
+            });
+            let f = Func { span: node.span, decl: decl.clone(), ty };
+            self.fns.insert(decl.ident, Rc::new(f));
+        }
+    }
+
+    /// Ends the calls and blocks entered since there were `depth` frames and
+    /// the last had `scopes` scopes.
+    fn unwind(&mut self, depth: usize, scopes: usize) {
+        while self.frames.len() > depth {
+            let frame = self.frames.pop().unwrap();
+            self.free(frame);
+        }
+        let frame = &mut self.frames[depth - 1];
+        for (_, addr) in frame.scopes.drain(scopes..).flatten() {
+            self.mem.free(addr);
+        }
+    }
+
+    /// Ends the lifetime of the locals and `va_list`s of a returning call.
+    fn free(&mut self, frame: Frame) {
+        for &(_, addr) in frame.scopes.iter().flatten() {
+            self.mem.free(addr);
+        }
+        for i in frame.va_lists {
+            self.va_lists[i].live = false;
+        }
+    }
+
+    /// Calls `ident` with the arguments as evaluated and their types.
+    fn invoke(
+        &mut self,
+        ident: Symbol,
+        args: Vec<(i64, Ty)>,
+        span: Span,
+    ) -> Result<Option<i64>> {
+        let Some(f) = self.fns.get(&ident).cloned() else {
+            if ident.as_str() == "dbg" {
+                return self.dbg(&args, span);
+            }
+            return Err(error(
+                span,
+                format!("call to undefined function `{}`", ident),
+            ));
+        };
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(error(span, "stack overflow"));
+        }
+        let decl = &f.decl;
+        let n = decl.params.len();
+        if args.len() < n || args.len() > n && !decl.variadic {
+            return Err(ub(
+                span,
+                format!(
+                    "`{}` takes {} arguments but {} were passed",
+                    ident,
+                    n,
+                    args.len()
+                ),
+            ));
+        }
+
+        let mut frame = Frame {
+            scopes: vec![Vec::new()],
+            varargs: args[n..].to_vec(),
+            va_lists: Vec::new(),
+        };
+        for (i, (p, (val, ty))) in decl.params.iter().zip(args).enumerate() {
+            // Without a prototype the promoted types have to agree.
+            if !passes_as(&ty, &p.ty) && !passes_as(&ty, &p.ty.promoted()) {
+                return Err(ub(
+                    span,
+                    format!(
+                        "argument {} of `{}` is `{}` but the parameter is `{}`",
+                        i + 1,
+                        ident,
+                        ty,
+                        p.ty
+                    ),
+                ));
+            }
+            let addr = self.alloc(&p.ty, span)?;
+            self.store(addr, &p.ty, self.convert(val, &ty, &p.ty), span)?;
+            if let Some(ident) = p.ident {
+                frame.scopes[0].push((ident, addr));
+            }
+        }
+        self.frames.push(frame);
+
+        let flow = self.stmt(decl.body.as_ref().unwrap())?;
+
+        let frame = self.frames.pop().unwrap();
+        self.free(frame);
+        Ok(match flow {
+            Flow::Return(val) => val,
+            // Reaching the end of `main` returns 0 (C99 5.1.2.2.3).
+            Flow::Next if ident.as_str() == "main" => Some(0),
+            Flow::Next => None,
+        })
+    }
+
+    fn dbg(&mut self, args: &[(i64, Ty)], span: Span) -> Result<Option<i64>> {
+        let [(val, ty)] = args else {
+            return Err(error(span, "`dbg` takes 1 argument"));
+        };
+        let val = self.convert(*val, ty, &Ty::Int);
+        writeln!(self.out, "{}", val)
+            .map_err(|e| error(span, e.to_string()))?;
+        Ok(None)
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn stmt(&mut self, node: &Ast) -> Result<Flow> {
+        match &node.kind {
+            AstKind::CompoundStmt(n) => {
+                self.frame().scopes.push(Vec::new());
+                let mut flow = Flow::Next;
+                for item in &n.items {
+                    flow = self.stmt(item)?;
+                    if let Flow::Return(_) = flow {
+                        break;
+                    }
+                }
+                let scope = self.frame().scopes.pop().unwrap();
+                for (_, addr) in scope {
+                    self.mem.free(addr);
+                }
+                Ok(flow)
+            }
+            AstKind::If(n) => {
+                if self.value(&n.cond)? != 0 {
+                    self.stmt(&n.then)
+                } else if let Some(else_) = &n.else_ {
+                    self.stmt(else_)
+                } else {
+                    Ok(Flow::Next)
+                }
+            }
+            AstKind::For(n) => {
+                if let Some(init) = &n.init {
+                    self.expr(init)?;
+                }
+                loop {
+                    if let Some(cond) = &n.cond {
+                        if self.value(cond)? == 0 {
+                            return Ok(Flow::Next);
+                        }
+                    }
+                    if let Flow::Return(val) = self.stmt(&n.body)? {
+                        return Ok(Flow::Return(val));
+                    }
+                    if let Some(inc) = &n.inc {
+                        self.expr(inc)?;
+                    }
+                }
+            }
+            AstKind::VarDecl(n) => {
+                let addr = self.alloc(&n.ty, node.span)?;
+                let scope = self.frame().scopes.last_mut().unwrap();
+                scope.push((n.ident, addr));
+                Ok(Flow::Next)
+            }
+            AstKind::Return(n) => {
+                let val = match &n.expr {
+                    Some(expr) if expr.ty == Ty::Void => self.expr(expr)?,
+                    Some(expr) => Some(self.value(expr)?),
+                    None => None,
+                };
+                Ok(Flow::Return(val))
+            }
+            _ => {
+                self.expr(node)?;
+                Ok(Flow::Next)
+            }
+        }
+    }
+
+    /// Value of a non-`void` expression.
+    fn value(&mut self, node: &Ast) -> Result<i64> {
+        self.expr(node)?.ok_or_else(|| {
+            ub(node.span, "use of the value of a function that returned none")
+        })
+    }
+
+    /// Evaluates `node`, giving `None` if it has no value.
+    fn expr(&mut self, node: &Ast) -> Result<Option<i64>> {
+        let val = match &node.kind {
+            AstKind::IntLit(n) => self.convert(n.val, &Ty::LongLong, &node.ty),
+            AstKind::Ref(_) => {
+                let addr = self.lvalue(node)?;
+                self.load(addr, &node.ty, node.span)?
+            }
+            AstKind::UnOp(n) => self.un_op(n.op.clone(), &n.expr, node)?,
+            AstKind::BinOp(n) if n.op == OpBin::Asign => {
+                let val = self.value(&n.rhs)?;
+                let addr = self.lvalue(&n.lhs)?;
+                self.store(addr, &n.lhs.ty, val, node.span)?;
+                val
+            }
+            AstKind::BinOp(n) => {
+                let (lhs, rhs) = (self.value(&n.lhs)?, self.value(&n.rhs)?);
+                self.bin_op(n.op.clone(), &n.lhs.ty, lhs, rhs, node)?
+            }
+            AstKind::Cond(n) => {
+                return if self.value(&n.cond)? != 0 {
+                    self.expr(&n.then)
+                } else {
+                    self.expr(&n.else_)
+                };
+            }
+            AstKind::Comma(n) => {
+                self.expr(&n.lhs)?;
+                return self.expr(&n.rhs);
+            }
+            AstKind::Cast(n) if node.ty == Ty::Void => {
+                self.expr(&n.expr)?;
+                return Ok(None);
+            }
+            AstKind::Cast(n) => {
+                let val = self.value(&n.expr)?;
+                self.convert(val, &n.expr.ty, &node.ty)
+            }
+            AstKind::Call(n) => return self.call(n, node.span),
+            AstKind::VaStart(n) => {
+                let addr = self.lvalue(&n.ap)?;
+                let args = self.frame().varargs.clone();
+                self.va_lists.push(VaList { args, next: 0, live: true });
+                let handle = self.va_lists.len();
+                self.frame().va_lists.push(handle - 1);
+                self.store(addr, &Ty::VaList, handle as i64, node.span)?;
+                return Ok(None);
+            }
+            AstKind::VaArg(n) => {
+                let addr = self.lvalue(&n.ap)?;
+                let ap = self.va_list(addr, n.ap.span)?;
+                let Some((val, ty)) = ap.args.get(ap.next).cloned() else {
+                    return Err(ub(
+                        node.span,
+                        "`va_arg` past the last argument",
+                    ));
+                };
+                if !passes_as(&ty, &node.ty) {
+                    return Err(ub(
+                        node.span,
+                        format!(
+                            "`va_arg` of `{}` for an argument of `{}`",
+                            node.ty, ty
+                        ),
+                    ));
+                }
+                ap.next += 1;
+                self.convert(val, &ty, &node.ty)
+            }
+            AstKind::VaEnd(n) => {
+                let addr = self.lvalue(&n.ap)?;
+                self.va_list(addr, n.ap.span)?.live = false;
+                return Ok(None);
+            }
+            AstKind::FnDecl(_)
+            | AstKind::CompoundStmt(_)
+            | AstKind::If(_)
+            | AstKind::For(_)
+            | AstKind::VarDecl(_)
+            | AstKind::Return(_) => {
+                unreachable!("{:?} is not an expression", node.kind)
+            }
+        };
+        Ok(Some(val))
+    }
+
+    /// Address of an lvalue.
+    fn lvalue(&mut self, node: &Ast) -> Result<u64> {
+        match &node.kind {
+            AstKind::Ref(r) => Ok(self
+                .frame()
+                .scopes
+                .iter()
+                .rev()
+                .flat_map(|s| s.iter().rev())
+                .find(|(ident, _)| *ident == r.ident)
+                .map(|&(_, addr)| addr)
+                .unwrap_or_else(|| panic!("cannot find value `{}`", r.ident))),
+            AstKind::UnOp(n) if n.op == OpUn::Deref => {
+                Ok(self.value(&n.expr)? as u64)
+            }
+            kind => unreachable!("{:?} is not an lvalue", kind),
+        }
+    }
+
+    fn un_op(&mut self, op: OpUn, expr: &Ast, node: &Ast) -> Result<i64> {
+        Ok(match op {
+            OpUn::Addr => match &expr.kind {
+                AstKind::Ref(r) if matches!(expr.ty, Ty::Fn(_)) => {
+                    self.fn_addr(r.ident)
+                }
+                _ => self.lvalue(expr)? as i64,
+            },
+            OpUn::Deref => {
+                let addr = self.value(expr)? as u64;
+                self.load(addr, &node.ty, node.span)?
+            }
+            OpUn::Neg => {
+                let val = self.value(expr)?;
+                self.bin_op(OpBin::Sub, &node.ty, 0, val, node)?
+            }
+            OpUn::LogNot => (self.value(expr)? == 0) as i64,
+        })
+    }
+
+    /// `lhs op rhs` with both operands of type `ty`.
+    fn bin_op(
+        &mut self,
+        op: OpBin,
+        ty: &Ty,
+        lhs: i64,
+        rhs: i64,
+        node: &Ast,
+    ) -> Result<i64> {
+        let (l, r) = (self.exact(lhs, ty), self.exact(rhs, ty));
+        let val = match op {
+            OpBin::Add => l + r,
+            OpBin::Sub => l - r,
+            // Both unsigned `long long` may not fit, but the low bits do.
+            OpBin::Mul => l.wrapping_mul(r),
+            OpBin::Div | OpBin::Mod if r == 0 => {
+                return Err(ub(node.span, "division by zero"));
+            }
+            // `INT_MIN % -1` is undefined too, as `INT_MIN / -1` is
+            // (C11 6.5.5p6).
+            OpBin::Div | OpBin::Mod if !self.fits(l / r, ty) => {
+                let sym = if op == OpBin::Div { "/" } else { "%" };
+                return Err(self.overflow(ty, l, sym, r, node));
+            }
+            OpBin::Div => l / r,
+            OpBin::Mod => l % r,
+            OpBin::Lt => return Ok((l < r) as i64),
+            OpBin::Gt => return Ok((l > r) as i64),
+            OpBin::Le => return Ok((l <= r) as i64),
+            OpBin::Ge => return Ok((l >= r) as i64),
+            OpBin::Eq => return Ok((l == r) as i64),
+            OpBin::Ne => return Ok((l != r) as i64),
+            OpBin::Asign => unreachable!("assignment is not arithmetic"),
+        };
+        if ty.is_signed() && !self.fits(val, ty) {
+            let sym = match op {
+                OpBin::Add => "+",
+                OpBin::Sub => "-",
+                _ => "*",
+            };
+            return Err(self.overflow(ty, l, sym, r, node));
+        }
+        Ok(self.wrap(val, ty))
+    }
+
+    fn overflow(
+        &self,
+        ty: &Ty,
+        l: i128,
+        op: &str,
+        r: i128,
+        node: &Ast,
+    ) -> Error {
+        ub(
+            node.span,
+            format!(
+                "signed integer overflow: {} {} {} does not fit in `{}`",
+                l, op, r, ty
+            ),
+        )
+    }
+
+    fn call(&mut self, node: &Call, span: Span) -> Result<Option<i64>> {
+        let addr = self.value(&node.callee)? as u64;
+        let mut args = Vec::new();
+        for arg in &node.args {
+            args.push((self.value(arg)?, arg.ty.clone()));
+        }
+
+        let Some(&ident) = addr
+            .checked_sub(FN_BASE)
+            .filter(|off| off % 16 == 0)
+            .and_then(|off| self.fn_addrs.get(off as usize / 16))
+        else {
+            return Err(ub(
+                span,
+                match addr {
+                    0 => "call through a null pointer",
+                    _ => "call through a pointer to no function",
+                },
+            ));
+        };
+        if let (Some(f), Ty::Ptr(callee)) =
+            (self.fns.get(&ident), &node.callee.ty)
+        {
+            if !f.ty.is_compatible(callee) {
+                return Err(ub(
+                    span,
+                    format!(
+                        "call to `{}` through `{}`",
+                        f.ty.declare(ident.as_str()),
+                        callee.declare("")
+                    ),
+                ));
+            }
+        }
+        self.invoke(ident, args, span)
+    }
+
+    /// Address of the function `ident`, given one when it is first taken.
+    fn fn_addr(&mut self, ident: Symbol) -> i64 {
+        let i = match self.fn_addrs.iter().position(|&f| f == ident) {
+            Some(i) => i,
+            None => {
+                self.fn_addrs.push(ident);
+                self.fn_addrs.len() - 1
+            }
+        };
+        (FN_BASE + 16 * i as u64) as i64
+    }
+
+    /// The live `va_list` stored at `addr`.
+    fn va_list(&mut self, addr: u64, span: Span) -> Result<&mut VaList> {
+        let handle = self.load(addr, &Ty::VaList, span)? as usize;
+        match handle.checked_sub(1).and_then(|i| self.va_lists.get_mut(i)) {
+            Some(ap) if ap.live => Ok(ap),
+            _ => Err(ub(span, "use of a `va_list` that is not started")),
+        }
+    }
+
+    fn alloc(&mut self, ty: &Ty, span: Span) -> Result<u64> {
+        let (size, align) = (ty.size(self.model), ty.align(self.model));
+        self.mem.alloc(size, align).map_err(|f| fault(f, span))
+    }
+
+    /// Loads a `ty` from `addr`. Only the handle is loaded of a `va_list`.
+    fn load(&mut self, addr: u64, ty: &Ty, span: Span) -> Result<i64> {
+        let size = ty.size(self.model).min(8);
+        let val = self.mem.load(addr, size).map_err(|f| fault(f, span))?;
+        Ok(self.wrap(val as i128, ty))
+    }
+
+    fn store(
+        &mut self,
+        addr: u64,
+        ty: &Ty,
+        val: i64,
+        span: Span,
+    ) -> Result<()> {
+        let size = ty.size(self.model).min(8);
+        let fill = *ty == Ty::VaList;
+        (self.mem.store(addr, size, val as u64, fill))
+            .map_err(|f| fault(f, span))
+    }
+
+    /// `val` of type `from` converted to `to`, wrapping around if it does not
+    /// fit.
+    fn convert(&self, val: i64, from: &Ty, to: &Ty) -> i64 {
+        self.wrap(self.exact(val, from), to)
+    }
+
+    /// Mathematical value of `val` of type `ty`.
+    fn exact(&self, val: i64, ty: &Ty) -> i128 {
+        match ty.is_signed() {
+            true => val as i128,
+            false => val as u64 as i128,
+        }
+    }
+
+    /// `val` reduced modulo the range of `ty`, as held in an `i64`: sign
+    /// extended if `ty` is signed and zero extended otherwise.
+    fn wrap(&self, val: i128, ty: &Ty) -> i64 {
+        let bits = 8 * ty.size(self.model).min(8) as u32;
+        let shift = 64 - bits;
+        let val = (val as u64) << shift;
+        match ty.is_signed() {
+            true => (val as i64) >> shift,
+            false => (val >> shift) as i64,
+        }
+    }
+
+    fn fits(&self, val: i128, ty: &Ty) -> bool {
+        self.exact(self.wrap(val, ty), ty) == val
+    }
+}
+
+/// Whether an argument of type `arg` may be taken as a parameter or
+/// `va_arg` of type `param`: the same type after promotion, the signed and
+/// unsigned versions of an integer type or any two pointers, which are all
+/// represented alike.
+fn passes_as(arg: &Ty, param: &Ty) -> bool {
+    match (arg, param) {
+        (Ty::Ptr(_), Ty::Ptr(_)) => true,
+        (a, p) if a.is_integer() && p.is_integer() => {
+            unsigned(&a.promoted()) == unsigned(&p.promoted())
+        }
+        (a, p) => a.is_compatible(p),
+    }
+}
+
+fn unsigned(ty: &Ty) -> Ty {
+    match ty {
+        Ty::Int => Ty::UInt,
+        Ty::Long => Ty::ULong,
+        Ty::LongLong => Ty::ULongLong,
+        ty => ty.clone(),
+    }
+}
+
+fn fault(fault: Fault, span: Span) -> Error {
+    match fault {
+        Fault::Null => ub(span, "null pointer dereference"),
+        Fault::Invalid => ub(span, "access through an invalid pointer"),
+        Fault::Dead => ub(span, "access to an object whose lifetime has ended"),
+        Fault::Uninit => ub(span, "use of an uninitialized value"),
+        Fault::Exhausted => error(span, "out of memory"),
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+/// Where objects are placed. Functions are given the addresses below.
+const BASE: u64 = 0x10_0000;
+
+/// Objects of the interpreted program at addresses that are never reused, so
+/// that an access through a pointer to a dead object is caught rather than
+/// reaching a newer one.
+#[derive(Debug)]
+pub struct Memory {
+    objects: BTreeMap<u64, Vec<Option<u8>>>,
+    /// Sizes of the objects that have been freed.
+    dead: BTreeMap<u64, u64>,
+    next: u64,
+    /// One past the highest address pointers can hold.
+    end: u64,
+}
+
+/// Why an access failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    Null,
+    /// Outside of any object.
+    Invalid,
+    /// Within an object whose lifetime has ended.
+    Dead,
+    Uninit,
+    /// There are no addresses left.
+    Exhausted,
+}
+
+impl Memory {
+    /// Memory for pointers of `ptr_size` bytes.
+    pub fn new(ptr_size: usize) -> Self {
+        Self {
+            objects: BTreeMap::new(),
+            dead: BTreeMap::new(),
+            next: BASE,
+            end: 1u64.checked_shl(ptr_size as u32 * 8).unwrap_or(u64::MAX),
+        }
+    }
+
+    /// Allocates an object of bytes that are `None` until written, leaving a
+    /// gap after it so that objects are never adjacent.
+    pub fn alloc(&mut self, size: usize, align: usize) -> Result<u64, Fault> {
+        let (size, align) = (size as u64, align.max(1) as u64);
+        let addr = self.next.next_multiple_of(align);
+        let next = addr + size + 16;
+        if next > self.end {
+            return Err(Fault::Exhausted);
+        }
+        self.next = next;
+        self.objects.insert(addr, vec![None; size as usize]);
+        Ok(addr)
+    }
+
+    /// Ends the lifetime of the object at `addr`.
+    pub fn free(&mut self, addr: u64) {
+        if let Some(bytes) = self.objects.remove(&addr) {
+            self.dead.insert(addr, bytes.len() as u64);
+        }
+    }
+
+    pub fn load(&self, addr: u64, size: usize) -> Result<u64, Fault> {
+        let (start, bytes) = self.object(addr, size)?;
+        let mut val = 0;
+        for (i, b) in bytes[start..start + size].iter().enumerate() {
+            val |= (b.ok_or(Fault::Uninit)? as u64) << (i * 8);
+        }
+        Ok(val)
+    }
+
+    /// Stores the low `size` bytes of `val` at `addr`, then marks the rest
+    /// of the object initialized if `fill`, as for the padding of a
+    /// `va_list` structure.
+    pub fn store(
+        &mut self,
+        addr: u64,
+        size: usize,
+        val: u64,
+        fill: bool,
+    ) -> Result<(), Fault> {
+        let (start, _) = self.object(addr, size)?;
+        let (_, bytes) = self.objects.range_mut(..=addr).next_back().unwrap();
+        for (i, b) in bytes[start..start + size].iter_mut().enumerate() {
+            *b = Some(val.checked_shr(i as u32 * 8).unwrap_or(0) as u8);
+        }
+        if fill {
+            for b in bytes.iter_mut().filter(|b| b.is_none()) {
+                *b = Some(0);
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes of the live object holding `size` bytes at `addr`, with the
+    /// offset of `addr` in them.
+    fn object(
+        &self,
+        addr: u64,
+        size: usize,
+    ) -> Result<(usize, &[Option<u8>]), Fault> {
+        if addr == 0 {
+            return Err(Fault::Null);
+        }
+        let within =
+            |(&start, len): (&u64, u64)| addr - start + size as u64 <= len;
+        match self.objects.range(..=addr).next_back() {
+            Some((start, bytes)) if within((start, bytes.len() as u64)) => {
+                Ok(((addr - start) as usize, bytes))
+            }
+            _ => match self.dead.range(..=addr).next_back() {
+                Some((start, &len)) if within((start, len)) => Err(Fault::Dead),
+                _ => Err(Fault::Invalid),
+            },
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Interprets the end-to-end tests in `test/` for the data models of the
+//! x86 targets, comparing what `dbg` prints with the `.expect` files, and checks that
+//! undefined behaviour is reported where it happens.
+
+use std::fs;
+use std::path::Path;
+
+use minicc_ast::DataModel;
+use minicc_pp::Preprocessor;
+
+/// Output and exit status of `src`, or the error as `line:col: msg`.
+fn run(src: &str, model: DataModel) -> Result<(String, i32), String> {
+    let out = Preprocessor::new().preprocess_str("test.c", src);
+    let nodes = minicc_parser::parse(&out.text, &out.map, model);
+    let mut buf = Vec::new();
+    match minicc_interp::run(&nodes, model, &mut buf) {
+        Ok(status) => Ok((String::from_utf8(buf).unwrap(), status)),
+        Err(e) => {
+            let loc = e.span.map(|s| out.map.span_loc(s).to_string());
+            Err(format!("{}: {}", loc.unwrap_or_default(), e)
+                .trim_start_matches("test.c:")
+                .to_string())
+        }
+    }
+}
+
+#[test]
+fn programs() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter(|p| p.with_extension("expect").exists())
+        .collect();
+    paths.sort();
+
+    for name in ["i386-linux", "x86_64-linux"] {
+        let target = minicc_gen::target(name).unwrap();
+        let model = target.data_model();
+        for path in &paths {
+            let mut pp = Preprocessor::new();
+            for (name, val) in target.macros() {
+                pp.define(name, val);
+            }
+            let out = pp.preprocess_file(path);
+            let nodes = minicc_parser::parse(&out.text, &out.map, model);
+            let mut buf = Vec::new();
+            minicc_interp::run(&nodes, model, &mut buf)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            let expected = fs::read(path.with_extension("expect")).unwrap();
+            assert_eq!(
+                String::from_utf8_lossy(&buf),
+                String::from_utf8_lossy(&expected),
+                "{} for {}",
+                path.display(),
+                name
+            );
+        }
+    }
+}
+
+#[test]
+fn arithmetic() {
+    let src = "
+        int main()
+        {
+            unsigned u;
+            long long ll;
+            u = 0;
+            dbg(u - 1 == 4294967295u);
+            dbg(-7 / 2);
+            dbg(-7 % 2);
+            dbg(7 % -2);
+            dbg((char)200);
+            ll = 2147483647;
+            dbg(ll + 1 > 0);
+            dbg((unsigned long long)-1 * 3 == -3ull);
+            return 300;
+        }";
+    let out = "1\n-3\n-1\n1\n-56\n1\n1\n";
+    assert_eq!(run(src, DataModel::ILP32), Ok((out.to_string(), 300)));
+}
+
+#[test]
+fn pointers_as_integers() {
+    let src = "
+        int main()
+        {
+            int x;
+            int *p;
+            long n;
+            x = 5;
+            n = (long)&x;
+            p = (int *)n;
+            dbg(*p);
+        }";
+    for model in [DataModel::ILP32, DataModel::LP64] {
+        assert_eq!(run(src, model), Ok(("5\n".to_string(), 0)));
+    }
+}
+
+#[test]
+fn undefined() {
+    let cases = [
+        (
+            "int main() { int x; x = 2147483647; return x + 1; }",
+            "1:44: undefined behaviour: signed integer overflow: \
+             2147483647 + 1 does not fit in `int`",
+        ),
+        (
+            "int main() { long long x; x = -9223372036854775807ll - 1; \
+             return -x; }",
+            "1:66: undefined behaviour: signed integer overflow: \
+             0 - -9223372036854775808 does not fit in `long long`",
+        ),
+        (
+            "int main() { int x; x = -2147483647 - 1; return x / -1; }",
+            "1:49: undefined behaviour: signed integer overflow: \
+             -2147483648 / -1 does not fit in `int`",
+        ),
+        (
+            "int main() { int x; x = -2147483647 - 1; return x % -1; }",
+            "1:49: undefined behaviour: signed integer overflow: \
+             -2147483648 % -1 does not fit in `int`",
+        ),
+        (
+            "int main() { int x; x = 0; return 1 / x; }",
+            "1:35: undefined behaviour: division by zero",
+        ),
+        (
+            "int main() { unsigned x; x = 0; return 1u % x; }",
+            "1:40: undefined behaviour: division by zero",
+        ),
+        (
+            "int main() { int x; return x; }",
+            "1:28: undefined behaviour: use of an uninitialized value",
+        ),
+        (
+            "int main() { int *p; p = 0; return *p; }",
+            "1:36: undefined behaviour: null pointer dereference",
+        ),
+        (
+            "int *f() { int x; return &x; }\n\
+             int main() { int *p; p = f(); *p = 1; }",
+            "2:31: undefined behaviour: access to an object whose lifetime \
+             has ended",
+        ),
+        (
+            "int main() { int *p; p = (int *)4; return *p; }",
+            "1:43: undefined behaviour: access through an invalid pointer",
+        ),
+        (
+            "int main() { int (*f)(); f = 0; return f(); }",
+            "1:40: undefined behaviour: call through a null pointer",
+        ),
+        (
+            "int f() { }\nint main() { return f(); }",
+            "2:21: undefined behaviour: use of the value of a function that \
+             returned none",
+        ),
+        (
+            "int f(long long x) { return 0; }\n\
+             int main() { return ((int (*)())f)(1); }",
+            "2:21: undefined behaviour: argument 1 of `f` is `int` but the \
+             parameter is `long long`",
+        ),
+        (
+            "int f(int n, ...) { va_list ap; va_start(ap, n); \
+             return va_arg(ap, long long); }\n\
+             int main() { return f(1, 2); }",
+            "1:57: undefined behaviour: `va_arg` of `long long` for an \
+             argument of `int`",
+        ),
+        (
+            "int f(int n, ...) { va_list ap; va_start(ap, n); \
+             return va_arg(ap, int); }\n\
+             int main() { return f(1); }",
+            "1:57: undefined behaviour: `va_arg` past the last argument",
+        ),
+        (
+            "int f();\nint main() { return f(); }",
+            "2:21: call to undefined function `f`",
+        ),
+        ("int f() { return 0; }", ": no `main` function"),
+    ];
+    for (src, msg) in cases {
+        assert_eq!(run(src, DataModel::ILP32), Err(msg.to_string()), "{}", src);
+    }
+}
+
+#[test]
+fn depth() {
+    let src = "int f(int n) { return n ? f(n - 1) + 1 : 0; }\n\
+               int g() { return g(); }\n\
+               int main() { dbg(f(9000)); return g(); }";
+    // As deep as `ccom --run` allows.
+    let res = std::thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(|| run(src, DataModel::ILP32))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(res, Err("2:18: stack overflow".to_string()));
+}
@@ -0,0 +1,180 @@
+use super::*;
+
+/// Control flow graph of a function, with each edge listed once.
+pub struct Cfg {
+    pub preds: Vec<Vec<BlockId>>,
+    pub succs: Vec<Vec<BlockId>>,
+    /// Blocks reachable from the entry in reverse postorder.
+    pub rpo: Vec<BlockId>,
+}
+
+impl Cfg {
+    pub fn new(func: &Function) -> Self {
+        let n = func.blocks.len();
+        let mut preds = vec![Vec::new(); n];
+        let mut succs = vec![Vec::new(); n];
+        for id in func.block_ids() {
+            let Some(term) = &func.block(id).term else {
+                continue;
+            };
+            for s in term.succs() {
+                if !succs[id.0 as usize].contains(&s) {
+                    succs[id.0 as usize].push(s);
+                    preds[s.0 as usize].push(id);
+                }
+            }
+        }
+
+        // Iterative DFS, pushing a block after all of its successors.
+        let mut post = Vec::new();
+        let mut seen = vec![false; n];
+        let mut stack = Vec::new();
+        if n > 0 {
+            seen[0] = true;
+            stack.push((BlockId(0), 0));
+        }
+        while let Some((b, i)) = stack.pop() {
+            match succs[b.0 as usize].get(i) {
+                Some(&s) => {
+                    stack.push((b, i + 1));
+                    if !seen[s.0 as usize] {
+                        seen[s.0 as usize] = true;
+                        stack.push((s, 0));
+                    }
+                }
+                None => post.push(b),
+            }
+        }
+        post.reverse();
+
+        Self { preds, succs, rpo: post }
+    }
+
+    pub fn preds(&self, b: BlockId) -> &[BlockId] {
+        &self.preds[b.0 as usize]
+    }
+
+    pub fn succs(&self, b: BlockId) -> &[BlockId] {
+        &self.succs[b.0 as usize]
+    }
+}
+
+/// Dominator tree of the reachable blocks, computed with the algorithm of
+/// Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm".
+pub struct DomTree {
+    /// Immediate dominator, the entry's own, `None` if unreachable.
+    idom: Vec<Option<BlockId>>,
+    children: Vec<Vec<BlockId>>,
+}
+
+impl DomTree {
+    pub fn new(cfg: &Cfg) -> Self {
+        let n = cfg.preds.len();
+        let mut order = vec![usize::MAX; n];
+        for (i, b) in cfg.rpo.iter().enumerate() {
+            order[b.0 as usize] = i;
+        }
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; n];
+        let Some(&entry) = cfg.rpo.first() else {
+            return Self { idom, children: Vec::new() };
+        };
+        idom[entry.0 as usize] = Some(entry);
+
+        let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b| {
+            while a != b {
+                while order[a.0 as usize] > order[b.0 as usize] {
+                    a = idom[a.0 as usize].unwrap();
+                }
+                while order[b.0 as usize] > order[a.0 as usize] {
+                    b = idom[b.0 as usize].unwrap();
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &b in &cfg.rpo[1..] {
+                let mut new = None;
+                for &p in cfg.preds(b) {
+                    if idom[p.0 as usize].is_none() {
+                        continue;
+                    }
+                    new = Some(match new {
+                        None => p,
+                        Some(d) => intersect(&idom, p, d),
+                    });
+                }
+                if idom[b.0 as usize] != new {
+                    idom[b.0 as usize] = new;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut children = vec![Vec::new(); n];
+        for &b in &cfg.rpo[1..] {
+            children[idom[b.0 as usize].unwrap().0 as usize].push(b);
+        }
+        Self { idom, children }
+    }
+
+    /// Immediate dominator, `None` for the entry and unreachable blocks.
+    pub fn idom(&self, b: BlockId) -> Option<BlockId> {
+        self.idom[b.0 as usize].filter(|d| *d != b)
+    }
+
+    pub fn is_reachable(&self, b: BlockId) -> bool {
+        self.idom[b.0 as usize].is_some()
+    }
+
+    /// Blocks immediately dominated by `b`.
+    pub fn children(&self, b: BlockId) -> &[BlockId] {
+        &self.children[b.0 as usize]
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`. A block
+    /// dominates itself.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom(b) {
+                Some(d) => b = d,
+                None => return false,
+            }
+        }
+    }
+
+    /// Dominance frontier of each block: the blocks where its dominance
+    /// ends.
+    pub fn frontiers(&self, cfg: &Cfg) -> Vec<Vec<BlockId>> {
+        let mut df = vec![Vec::new(); cfg.preds.len()];
+        for &b in &cfg.rpo {
+            let preds = cfg.preds(b);
+            if preds.len() < 2 {
+                continue;
+            }
+            for &p in preds {
+                let mut runner = p;
+                while self.is_reachable(runner) && Some(runner) != self.idom(b)
+                {
+                    if !df[runner.0 as usize].contains(&b) {
+                        df[runner.0 as usize].push(b);
+                    }
+                    match self.idom(runner) {
+                        Some(d) => runner = d,
+                        None => break,
+                    }
+                }
+            }
+        }
+        df
+    }
+}
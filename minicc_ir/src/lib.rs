@@ -0,0 +1,578 @@
+//! Typed three-address intermediate representation.
+//!
+//! A [`Module`] holds functions made of basic blocks. Instructions operate on
+//! virtual registers, each defined by exactly one instruction or parameter,
+//! and on immediates. Locals live in stack slots accessed through `load` and
+//! `store` until they are promoted to registers.
+//!
+//! The textual form, printed by `Display` and read back by [`parse()`], looks
+//! like
+//!
+//! ```text
+//! fn i32 @inc(i32 %0) {
+//!     $0 = slot 4, 4
+//! bb0:
+//!     %1 = addr $0
+//!     store i32 %0, %1
+//!     %2 = load i32 %1
+//!     %3 = add i32 %2, 1
+//!     ret i32 %3
+//! }
+//! ```
+
+mod cfg;
+mod lower;
+mod opt;
+mod parse;
+mod print;
+mod verify;
+
+use std::collections::HashMap;
+
+use minicc_ast::Symbol;
+
+pub use cfg::{Cfg, DomTree};
+pub use lower::lower;
+pub use opt::{
+    ConstProp, CopyProp, Cse, Dce, Mem2Reg, OptLevel, Pass, PassManager,
+    SimplifyCfg,
+};
+pub use parse::{parse, ParseError};
+pub use verify::{verify, VerifyError};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Module {
+    pub funcs: Vec<Function>,
+}
+
+impl Module {
+    pub fn func(&self, name: Symbol) -> Option<&Function> {
+        self.funcs.iter().find(|f| f.name == name)
+    }
+}
+
+/// A function definition, or a declaration if it has no blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: Symbol,
+    pub sig: Signature,
+    /// Registers holding the parameters on entry.
+    pub params: Vec<Reg>,
+    pub slots: Vec<Slot>,
+    /// The first block is the entry.
+    pub blocks: Vec<Block>,
+    /// Type of each register.
+    pub regs: Vec<Ty>,
+}
+
+impl Function {
+    pub fn new(name: Symbol, sig: Signature) -> Self {
+        Self {
+            name,
+            sig,
+            params: Vec::new(),
+            slots: Vec::new(),
+            blocks: Vec::new(),
+            regs: Vec::new(),
+        }
+    }
+
+    pub fn is_decl(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn new_reg(&mut self, ty: Ty) -> Reg {
+        self.regs.push(ty);
+        Reg(self.regs.len() as u32 - 1)
+    }
+
+    pub fn reg_ty(&self, reg: Reg) -> Ty {
+        self.regs[reg.0 as usize]
+    }
+
+    pub fn new_slot(&mut self, size: u32, align: u32) -> SlotId {
+        self.slots.push(Slot { size, align });
+        SlotId(self.slots.len() as u32 - 1)
+    }
+
+    pub fn new_block(&mut self) -> BlockId {
+        self.blocks.push(Block::default());
+        BlockId(self.blocks.len() as u32 - 1)
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0 as usize]
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> &mut Block {
+        &mut self.blocks[id.0 as usize]
+    }
+
+    pub fn block_ids(&self) -> impl Iterator<Item = BlockId> {
+        (0..self.blocks.len() as u32).map(BlockId)
+    }
+
+    /// Replaces uses of registers by values, following chains such as `%1`
+    /// to `%2` to `3`.
+    pub fn replace_uses(&mut self, map: &HashMap<Reg, Value>) {
+        if map.is_empty() {
+            return;
+        }
+        let resolve = |mut v: Value| {
+            while let Value::Reg(r) = v {
+                match map.get(&r) {
+                    Some(to) if *to != v => v = *to,
+                    _ => break,
+                }
+            }
+            v
+        };
+        for block in &mut self.blocks {
+            let term = block.term.iter_mut().flat_map(|t| t.uses_mut());
+            for v in
+                block.insts.iter_mut().flat_map(|i| i.uses_mut()).chain(term)
+            {
+                *v = resolve(*v);
+            }
+        }
+    }
+
+    /// Renumbers the registers in order of definition, dropping unused
+    /// numbers.
+    pub fn compact_regs(&mut self) {
+        let mut map = HashMap::new();
+        let mut regs = Vec::new();
+        let defs =
+            self.blocks.iter().flat_map(|b| &b.insts).filter_map(|i| i.def());
+        for r in self.params.iter().copied().chain(defs) {
+            map.insert(r, Reg(regs.len() as u32));
+            regs.push(self.reg_ty(r));
+        }
+
+        let rename = |r: &mut Reg| *r = map.get(r).copied().unwrap_or(*r);
+        self.params.iter_mut().for_each(rename);
+        for block in &mut self.blocks {
+            for inst in &mut block.insts {
+                if let Some(dst) = inst.def_mut() {
+                    rename(dst);
+                }
+            }
+        }
+        let uses =
+            map.iter().map(|(from, to)| (*from, Value::Reg(*to))).collect();
+        self.replace_uses_once(&uses);
+        self.regs = regs;
+    }
+
+    /// Like `replace_uses`, without following chains.
+    fn replace_uses_once(&mut self, map: &HashMap<Reg, Value>) {
+        for block in &mut self.blocks {
+            let term = block.term.iter_mut().flat_map(|t| t.uses_mut());
+            for v in
+                block.insts.iter_mut().flat_map(|i| i.uses_mut()).chain(term)
+            {
+                if let Value::Reg(r) = v {
+                    if let Some(to) = map.get(r) {
+                        *v = *to;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Signature {
+    /// `None` for `void`.
+    pub ret: Option<Ty>,
+    pub params: Vec<Ty>,
+    /// Whether more arguments may follow `params`.
+    pub variadic: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ty {
+    I8,
+    I16,
+    I32,
+    I64,
+    /// Pointer, whose size depends on the target.
+    Ptr,
+}
+
+impl Ty {
+    pub const ALL: [Ty; 5] = [Ty::I8, Ty::I16, Ty::I32, Ty::I64, Ty::Ptr];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Ty::I8 => "i8",
+            Ty::I16 => "i16",
+            Ty::I32 => "i32",
+            Ty::I64 => "i64",
+            Ty::Ptr => "ptr",
+        }
+    }
+
+    /// Width of an integer type in bits.
+    pub fn bits(self) -> Option<u32> {
+        match self {
+            Ty::I8 => Some(8),
+            Ty::I16 => Some(16),
+            Ty::I32 => Some(32),
+            Ty::I64 => Some(64),
+            Ty::Ptr => None,
+        }
+    }
+}
+
+/// Virtual register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Reg(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SlotId(pub u32);
+
+/// Stack memory of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub size: u32,
+    pub align: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Value {
+    Reg(Reg),
+    /// Integer of the type of the operand, sign extended from its width.
+    Imm(i64),
+}
+
+impl Value {
+    /// Immediate `val` of type `ty`, truncated to its width and sign extended
+    /// back.
+    pub fn imm(ty: Ty, val: i64) -> Self {
+        Value::Imm(match ty.bits() {
+            Some(bits) if bits < 64 => val << (64 - bits) >> (64 - bits),
+            _ => val,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    /// `None` only while the block is being built.
+    pub term: Option<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Inst {
+    /// `%dst = copy ty val`
+    Copy { dst: Reg, val: Value },
+    /// `%dst = op ty lhs, rhs`
+    Bin { op: BinOp, dst: Reg, lhs: Value, rhs: Value },
+    /// `%dst = op ty lhs, rhs`, where `%dst` is an `i32` of 0 or 1.
+    Cmp { op: CmpOp, ty: Ty, dst: Reg, lhs: Value, rhs: Value },
+    /// `%dst = op from val to ty`
+    Conv { op: ConvOp, dst: Reg, from: Ty, val: Value },
+    /// `%dst = addr $slot`
+    SlotAddr { dst: Reg, slot: SlotId },
+    /// `%dst = func @name`
+    FuncAddr { dst: Reg, name: Symbol },
+    /// `%dst = load ty addr`
+    Load { dst: Reg, addr: Value },
+    /// `store ty val, addr`
+    Store { ty: Ty, val: Value, addr: Value },
+    /// `%dst = call ret (params) callee(ty arg, ...)`
+    Call {
+        dst: Option<Reg>,
+        callee: Callee,
+        sig: Signature,
+        args: Vec<(Ty, Value)>,
+    },
+    /// `vastart ap`, where `ap` points to a `va_list`.
+    VaStart { ap: Value },
+    /// `%dst = vaarg ty ap`
+    VaArg { dst: Reg, ap: Value },
+    /// `vaend ap`
+    VaEnd { ap: Value },
+    /// `%dst = phi ty [val, bb], ...`, the value from the predecessor control
+    /// came from. Phis come first in a block.
+    Phi { dst: Reg, args: Vec<(BlockId, Value)> },
+}
+
+impl Inst {
+    /// Register defined by the instruction.
+    pub fn def(&self) -> Option<Reg> {
+        match *self {
+            Inst::Copy { dst, .. }
+            | Inst::Bin { dst, .. }
+            | Inst::Cmp { dst, .. }
+            | Inst::Conv { dst, .. }
+            | Inst::SlotAddr { dst, .. }
+            | Inst::FuncAddr { dst, .. }
+            | Inst::Load { dst, .. }
+            | Inst::VaArg { dst, .. }
+            | Inst::Phi { dst, .. } => Some(dst),
+            Inst::Call { dst, .. } => dst,
+            Inst::Store { .. } | Inst::VaStart { .. } | Inst::VaEnd { .. } => {
+                None
+            }
+        }
+    }
+
+    pub fn def_mut(&mut self) -> Option<&mut Reg> {
+        match self {
+            Inst::Copy { dst, .. }
+            | Inst::Bin { dst, .. }
+            | Inst::Cmp { dst, .. }
+            | Inst::Conv { dst, .. }
+            | Inst::SlotAddr { dst, .. }
+            | Inst::FuncAddr { dst, .. }
+            | Inst::Load { dst, .. }
+            | Inst::VaArg { dst, .. }
+            | Inst::Phi { dst, .. } => Some(dst),
+            Inst::Call { dst, .. } => dst.as_mut(),
+            Inst::Store { .. } | Inst::VaStart { .. } | Inst::VaEnd { .. } => {
+                None
+            }
+        }
+    }
+
+    /// Operands read by the instruction.
+    pub fn uses(&self) -> Vec<Value> {
+        match self {
+            Inst::Copy { val, .. } | Inst::Conv { val, .. } => vec![*val],
+            Inst::Bin { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => {
+                vec![*lhs, *rhs]
+            }
+            Inst::SlotAddr { .. } | Inst::FuncAddr { .. } => Vec::new(),
+            Inst::Load { addr, .. } => vec![*addr],
+            Inst::Store { val, addr, .. } => vec![*val, *addr],
+            Inst::Call { callee, args, .. } => {
+                let callee = match callee {
+                    Callee::Direct(_) => None,
+                    Callee::Indirect(v) => Some(*v),
+                };
+                callee.into_iter().chain(args.iter().map(|(_, v)| *v)).collect()
+            }
+            Inst::VaStart { ap }
+            | Inst::VaArg { ap, .. }
+            | Inst::VaEnd { ap } => {
+                vec![*ap]
+            }
+            Inst::Phi { args, .. } => args.iter().map(|(_, v)| *v).collect(),
+        }
+    }
+
+    /// Mutable references to the operands read by the instruction.
+    pub fn uses_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Inst::Copy { val, .. } | Inst::Conv { val, .. } => vec![val],
+            Inst::Bin { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => {
+                vec![lhs, rhs]
+            }
+            Inst::SlotAddr { .. } | Inst::FuncAddr { .. } => Vec::new(),
+            Inst::Load { addr, .. } => vec![addr],
+            Inst::Store { val, addr, .. } => vec![val, addr],
+            Inst::Call { callee, args, .. } => {
+                let callee = match callee {
+                    Callee::Direct(_) => None,
+                    Callee::Indirect(v) => Some(v),
+                };
+                callee
+                    .into_iter()
+                    .chain(args.iter_mut().map(|(_, v)| v))
+                    .collect()
+            }
+            Inst::VaStart { ap }
+            | Inst::VaArg { ap, .. }
+            | Inst::VaEnd { ap } => {
+                vec![ap]
+            }
+            Inst::Phi { args, .. } => args.iter_mut().map(|(_, v)| v).collect(),
+        }
+    }
+
+    /// Whether the instruction does something besides defining its register.
+    pub fn has_side_effects(&self) -> bool {
+        matches!(
+            self,
+            Inst::Store { .. }
+                | Inst::Call { .. }
+                | Inst::VaStart { .. }
+                | Inst::VaArg { .. }
+                | Inst::VaEnd { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Callee {
+    Direct(Symbol),
+    /// Pointer to the function.
+    Indirect(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+    SRem,
+    URem,
+}
+
+impl BinOp {
+    pub const ALL: [BinOp; 7] = [
+        BinOp::Add,
+        BinOp::Sub,
+        BinOp::Mul,
+        BinOp::SDiv,
+        BinOp::UDiv,
+        BinOp::SRem,
+        BinOp::URem,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::SDiv => "sdiv",
+            BinOp::UDiv => "udiv",
+            BinOp::SRem => "srem",
+            BinOp::URem => "urem",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    SLt,
+    SLe,
+    SGt,
+    SGe,
+    ULt,
+    ULe,
+    UGt,
+    UGe,
+}
+
+impl CmpOp {
+    pub const ALL: [CmpOp; 10] = [
+        CmpOp::Eq,
+        CmpOp::Ne,
+        CmpOp::SLt,
+        CmpOp::SLe,
+        CmpOp::SGt,
+        CmpOp::SGe,
+        CmpOp::ULt,
+        CmpOp::ULe,
+        CmpOp::UGt,
+        CmpOp::UGe,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "eq",
+            CmpOp::Ne => "ne",
+            CmpOp::SLt => "slt",
+            CmpOp::SLe => "sle",
+            CmpOp::SGt => "sgt",
+            CmpOp::SGe => "sge",
+            CmpOp::ULt => "ult",
+            CmpOp::ULe => "ule",
+            CmpOp::UGt => "ugt",
+            CmpOp::UGe => "uge",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConvOp {
+    /// Sign extension to a wider integer.
+    SExt,
+    /// Zero extension to a wider integer.
+    ZExt,
+    /// Truncation to a narrower integer.
+    Trunc,
+    /// Pointer to an integer of the pointer's size.
+    PtrToInt,
+    /// Integer of the pointer's size to a pointer.
+    IntToPtr,
+}
+
+impl ConvOp {
+    pub const ALL: [ConvOp; 5] = [
+        ConvOp::SExt,
+        ConvOp::ZExt,
+        ConvOp::Trunc,
+        ConvOp::PtrToInt,
+        ConvOp::IntToPtr,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConvOp::SExt => "sext",
+            ConvOp::ZExt => "zext",
+            ConvOp::Trunc => "trunc",
+            ConvOp::PtrToInt => "ptrtoint",
+            ConvOp::IntToPtr => "inttoptr",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    /// `ret [ty val]`
+    Ret(Option<(Ty, Value)>),
+    /// `jmp bb`
+    Jmp(BlockId),
+    /// `br ty cond, then, else`, taking `then` if `cond` is nonzero.
+    Br {
+        ty: Ty,
+        cond: Value,
+        then: BlockId,
+        else_: BlockId,
+    },
+    Unreachable,
+}
+
+impl Term {
+    pub fn succs(&self) -> Vec<BlockId> {
+        match *self {
+            Term::Ret(_) | Term::Unreachable => Vec::new(),
+            Term::Jmp(b) => vec![b],
+            Term::Br { then, else_, .. } => vec![then, else_],
+        }
+    }
+
+    pub fn succs_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Term::Ret(_) | Term::Unreachable => Vec::new(),
+            Term::Jmp(b) => vec![b],
+            Term::Br { then, else_, .. } => vec![then, else_],
+        }
+    }
+
+    pub fn uses(&self) -> Vec<Value> {
+        match self {
+            Term::Ret(Some((_, v))) | Term::Br { cond: v, .. } => vec![*v],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn uses_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Term::Ret(Some((_, v))) | Term::Br { cond: v, .. } => vec![v],
+            _ => Vec::new(),
+        }
+    }
+}
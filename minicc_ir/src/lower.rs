@@ -0,0 +1,531 @@
+use std::collections::{HashMap, HashSet};
+
+use ast::AstKind;
+use minicc_ast as ast;
+use minicc_ast::DataModel;
+
+use super::*;
+
+/// Lowers a translation unit. Locals are kept in slots, every access is a
+/// `load` or `store`.
+///
+/// Prototypes of functions that are not defined become declarations, as do
+/// callees declared implicitly by a call.
+pub fn lower(nodes: &[ast::Ast], model: DataModel) -> Module {
+    let defined: HashSet<_> = nodes
+        .iter()
+        .filter_map(|n| match &n.kind {
+            AstKind::FnDecl(f) if f.body.is_some() => Some(f.ident),
+            _ => None,
+        })
+        .collect();
+
+    let mut decls: Vec<Function> = Vec::new();
+    let mut defs = Vec::new();
+    let declare = |decls: &mut Vec<Function>, name, sig| {
+        if !defined.contains(&name) && !decls.iter().any(|f| f.name == name) {
+            decls.push(Function::new(name, sig));
+        }
+    };
+
+    for node in nodes {
+        let AstKind::FnDecl(f) = &node.kind else {
+            continue;
+        };
+        let sig = Signature {
+            ret: ty(&f.ret, model),
+            params: f
+                .params
+                .iter()
+                .map(|p| ty(&p.ty, model).unwrap())
+                .collect(),
+            variadic: f.variadic,
+        };
+        match &f.body {
+            Some(body) => {
+                let mut l = Lower::new(f.ident, sig, model);
+                l.func_body(f, body);
+                for (name, sig) in l.callees.drain(..) {
+                    declare(&mut decls, name, sig);
+                }
+                defs.push(l.func);
+            }
+            None => declare(&mut decls, f.ident, sig),
+        }
+    }
+
+    decls.extend(defs);
+    Module { funcs: decls }
+}
+
+/// IR type of a value of C type `ty`, `None` for `void`.
+fn ty(ty: &ast::Ty, model: DataModel) -> Option<Ty> {
+    match ty {
+        ast::Ty::Void => None,
+        ast::Ty::VaList | ast::Ty::Ptr(_) | ast::Ty::Fn(_) => Some(Ty::Ptr),
+        ty => Some(match ty.size(model) {
+            1 => Ty::I8,
+            2 => Ty::I16,
+            4 => Ty::I32,
+            _ => Ty::I64,
+        }),
+    }
+}
+
+struct Lower {
+    func: Function,
+    /// Block instructions are appended to, `None` after a terminator until the
+    /// next block starts.
+    cur: Option<BlockId>,
+    /// Blocks in the order they were started, which becomes their final
+    /// order.
+    order: Vec<BlockId>,
+    scopes: Vec<HashMap<Symbol, SlotId>>,
+    /// Functions called directly, with the signature of the call.
+    callees: Vec<(Symbol, Signature)>,
+    model: DataModel,
+}
+
+impl Lower {
+    fn new(name: Symbol, sig: Signature, model: DataModel) -> Self {
+        Self {
+            func: Function::new(name, sig),
+            cur: None,
+            order: Vec::new(),
+            scopes: vec![HashMap::new()],
+            callees: Vec::new(),
+            model,
+        }
+    }
+
+    fn ty(&self, ty: &ast::Ty) -> Option<Ty> {
+        self::ty(ty, self.model)
+    }
+
+    fn func_body(&mut self, node: &ast::FnDecl, body: &ast::Ast) {
+        let entry = self.func.new_block();
+        self.start(entry);
+
+        for ty in self.func.sig.params.clone() {
+            let reg = self.func.new_reg(ty);
+            self.func.params.push(reg);
+        }
+        for (p, reg) in node.params.iter().zip(self.func.params.clone()) {
+            if let Some(ident) = p.ident {
+                let ty = self.func.reg_ty(reg);
+                let slot = self.var(ident, &p.ty);
+                let addr = self.slot_addr(slot);
+                self.emit(Inst::Store { ty, val: Value::Reg(reg), addr });
+            }
+        }
+
+        self.stmt(body);
+
+        if self.cur.is_some() {
+            // Falling off `main` returns 0 (C99 5.1.2.2.3). Other functions
+            // return an undefined value, 0 as well.
+            let ret = self.func.sig.ret.map(|ty| (ty, Value::Imm(0)));
+            self.terminate(Term::Ret(ret));
+        }
+        self.reorder();
+    }
+
+    /// Renumbers the blocks in the order they were started.
+    fn reorder(&mut self) {
+        let mut new_id = vec![BlockId(0); self.func.blocks.len()];
+        for (i, b) in self.order.iter().enumerate() {
+            new_id[b.0 as usize] = BlockId(i as u32);
+        }
+        let mut blocks: Vec<_> = std::mem::take(&mut self.func.blocks)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.func.blocks = self
+            .order
+            .iter()
+            .map(|b| blocks[b.0 as usize].take().unwrap())
+            .collect();
+        for block in &mut self.func.blocks {
+            for succ in block.term.as_mut().unwrap().succs_mut() {
+                *succ = new_id[succ.0 as usize];
+            }
+        }
+    }
+
+    fn stmt(&mut self, node: &ast::Ast) {
+        match &node.kind {
+            AstKind::CompoundStmt(n) => {
+                self.scopes.push(HashMap::new());
+                for item in &n.items {
+                    self.stmt(item);
+                }
+                self.scopes.pop();
+            }
+            AstKind::If(n) => self.if_(n),
+            AstKind::For(n) => self.for_(n),
+            AstKind::VarDecl(n) => {
+                self.var(n.ident, &n.ty);
+            }
+            AstKind::Return(n) => {
+                let ret = n.expr.as_ref().and_then(|e| {
+                    let v = self.expr(e)?;
+                    Some((self.ty(&e.ty).unwrap(), v))
+                });
+                self.terminate(Term::Ret(ret));
+            }
+            _ => {
+                self.expr(node);
+            }
+        }
+    }
+
+    fn if_(&mut self, node: &ast::If) {
+        let then = self.func.new_block();
+        let else_ = self.func.new_block();
+        let end = match node.else_ {
+            Some(_) => self.func.new_block(),
+            None => else_,
+        };
+
+        self.branch(&node.cond, then, else_);
+        self.start(then);
+        self.stmt(&node.then);
+        self.terminate(Term::Jmp(end));
+        if let Some(e) = &node.else_ {
+            self.start(else_);
+            self.stmt(e);
+            self.terminate(Term::Jmp(end));
+        }
+        self.start(end);
+    }
+
+    fn for_(&mut self, node: &ast::For) {
+        let cond = self.func.new_block();
+        let body = self.func.new_block();
+        let end = self.func.new_block();
+
+        if let Some(init) = &node.init {
+            self.expr(init);
+        }
+        self.terminate(Term::Jmp(cond));
+        self.start(cond);
+        match &node.cond {
+            Some(c) => self.branch(c, body, end),
+            None => self.terminate(Term::Jmp(body)),
+        }
+        self.start(body);
+        self.stmt(&node.body);
+        if let Some(inc) = &node.inc {
+            self.expr(inc);
+        }
+        self.terminate(Term::Jmp(cond));
+        self.start(end);
+    }
+
+    /// Jumps to `then` if `cond` is nonzero, otherwise to `else_`.
+    fn branch(&mut self, cond: &ast::Ast, then: BlockId, else_: BlockId) {
+        let ty = self.ty(&cond.ty).unwrap();
+        let cond = self.value(cond);
+        self.terminate(Term::Br { ty, cond, then, else_ });
+    }
+
+    /// Value of an expression, `None` if it is `void`.
+    fn expr(&mut self, node: &ast::Ast) -> Option<Value> {
+        let v = match &node.kind {
+            AstKind::IntLit(n) => Value::imm(self.ty(&node.ty).unwrap(), n.val),
+            AstKind::Ref(_) => {
+                let addr = self.lvalue(node);
+                // A structure `va_list` is an array on x86-64, which decays,
+                // and is passed by reference anyway as the IR has no
+                // aggregate values.
+                if node.ty == ast::Ty::VaList && self.model.va_list_is_struct()
+                {
+                    return Some(addr);
+                }
+                self.load(self.ty(&node.ty).unwrap(), addr)
+            }
+            AstKind::UnOp(n) => self.un_op(n, &node.ty),
+            AstKind::BinOp(n) => self.bin_op(n, &node.ty),
+            AstKind::Cond(n) => return self.cond(n, &node.ty),
+            AstKind::Comma(n) => {
+                self.expr(&n.lhs);
+                return self.expr(&n.rhs);
+            }
+            AstKind::Cast(n) => return self.cast(&n.expr, &node.ty),
+            AstKind::Call(n) => return self.call(n, &node.ty),
+            AstKind::VaStart(n) => {
+                let ap = self.lvalue(&n.ap);
+                self.emit(Inst::VaStart { ap });
+                return None;
+            }
+            AstKind::VaArg(n) => {
+                let ap = self.lvalue(&n.ap);
+                let dst = self.func.new_reg(self.ty(&node.ty).unwrap());
+                self.emit(Inst::VaArg { dst, ap });
+                Value::Reg(dst)
+            }
+            AstKind::VaEnd(n) => {
+                let ap = self.lvalue(&n.ap);
+                self.emit(Inst::VaEnd { ap });
+                return None;
+            }
+            AstKind::FnDecl(_)
+            | AstKind::CompoundStmt(_)
+            | AstKind::If(_)
+            | AstKind::For(_)
+            | AstKind::VarDecl(_)
+            | AstKind::Return(_) => {
+                unreachable!("{:?} is not an expression", node.kind)
+            }
+        };
+        Some(v)
+    }
+
+    /// Value of a non-`void` expression.
+    fn value(&mut self, node: &ast::Ast) -> Value {
+        self.expr(node).expect("expression has a value")
+    }
+
+    /// Address of an lvalue.
+    fn lvalue(&mut self, node: &ast::Ast) -> Value {
+        match &node.kind {
+            AstKind::Ref(r) => {
+                let slot = self.lookup(r.ident);
+                self.slot_addr(slot)
+            }
+            AstKind::UnOp(n) if n.op == ast::OpUn::Deref => self.value(&n.expr),
+            kind => unreachable!("{:?} is not an lvalue", kind),
+        }
+    }
+
+    fn un_op(&mut self, node: &ast::UnOp, result: &ast::Ty) -> Value {
+        match node.op {
+            ast::OpUn::Addr => match &node.expr.kind {
+                AstKind::Ref(r) if matches!(node.expr.ty, ast::Ty::Fn(_)) => {
+                    let dst = self.func.new_reg(Ty::Ptr);
+                    self.emit(Inst::FuncAddr { dst, name: r.ident });
+                    Value::Reg(dst)
+                }
+                _ => self.lvalue(&node.expr),
+            },
+            ast::OpUn::Deref => {
+                let addr = self.value(&node.expr);
+                self.load(self.ty(result).unwrap(), addr)
+            }
+            ast::OpUn::Neg => {
+                let ty = self.ty(result).unwrap();
+                let rhs = self.value(&node.expr);
+                self.bin(BinOp::Sub, ty, Value::Imm(0), rhs)
+            }
+            ast::OpUn::LogNot => {
+                let ty = self.ty(&node.expr.ty).unwrap();
+                let lhs = self.value(&node.expr);
+                self.cmp(CmpOp::Eq, ty, lhs, Value::Imm(0))
+            }
+        }
+    }
+
+    fn bin_op(&mut self, node: &ast::BinOp, result: &ast::Ty) -> Value {
+        if node.op == ast::OpBin::Asign {
+            let val = self.value(&node.rhs);
+            let addr = self.lvalue(&node.lhs);
+            let ty = self.ty(&node.lhs.ty).unwrap();
+            self.emit(Inst::Store { ty, val, addr });
+            return val;
+        }
+
+        let signed = node.lhs.ty.is_signed();
+        let operand = self.ty(&node.lhs.ty).unwrap();
+        let lhs = self.value(&node.lhs);
+        let rhs = self.value(&node.rhs);
+        let bin = |op| (op, self.ty(result).unwrap());
+        let (op, ty) = match &node.op {
+            ast::OpBin::Add => bin(BinOp::Add),
+            ast::OpBin::Sub => bin(BinOp::Sub),
+            ast::OpBin::Mul => bin(BinOp::Mul),
+            ast::OpBin::Div if signed => bin(BinOp::SDiv),
+            ast::OpBin::Div => bin(BinOp::UDiv),
+            ast::OpBin::Mod if signed => bin(BinOp::SRem),
+            ast::OpBin::Mod => bin(BinOp::URem),
+            op => {
+                let op = match (op, signed) {
+                    (ast::OpBin::Lt, true) => CmpOp::SLt,
+                    (ast::OpBin::Gt, true) => CmpOp::SGt,
+                    (ast::OpBin::Le, true) => CmpOp::SLe,
+                    (ast::OpBin::Ge, true) => CmpOp::SGe,
+                    (ast::OpBin::Lt, false) => CmpOp::ULt,
+                    (ast::OpBin::Gt, false) => CmpOp::UGt,
+                    (ast::OpBin::Le, false) => CmpOp::ULe,
+                    (ast::OpBin::Ge, false) => CmpOp::UGe,
+                    (ast::OpBin::Eq, _) => CmpOp::Eq,
+                    (ast::OpBin::Ne, _) => CmpOp::Ne,
+                    _ => unreachable!(),
+                };
+                return self.cmp(op, operand, lhs, rhs);
+            }
+        };
+        self.bin(op, ty, lhs, rhs)
+    }
+
+    /// Both arms store to a temporary slot, which is read at the join.
+    fn cond(&mut self, node: &ast::Cond, result: &ast::Ty) -> Option<Value> {
+        let then = self.func.new_block();
+        let else_ = self.func.new_block();
+        let end = self.func.new_block();
+        let tmp = self.ty(result).map(|ty| {
+            let size = result.size(self.model) as u32;
+            let align = result.align(self.model) as u32;
+            (ty, self.func.new_slot(size, align))
+        });
+
+        self.branch(&node.cond, then, else_);
+        for (block, arm) in [(then, &node.then), (else_, &node.else_)] {
+            self.start(block);
+            let val = self.expr(arm);
+            if let (Some((ty, slot)), Some(val)) = (tmp, val) {
+                let addr = self.slot_addr(slot);
+                self.emit(Inst::Store { ty, val, addr });
+            }
+            self.terminate(Term::Jmp(end));
+        }
+        self.start(end);
+
+        let (ty, slot) = tmp?;
+        let addr = self.slot_addr(slot);
+        Some(self.load(ty, addr))
+    }
+
+    fn cast(&mut self, node: &ast::Ast, to: &ast::Ty) -> Option<Value> {
+        let val = self.expr(node)?;
+        let (from, to) = (self.ty(&node.ty).unwrap(), self.ty(to)?);
+        let op = match (from.bits(), to.bits()) {
+            (Some(f), Some(t)) if f < t && node.ty.is_signed() => ConvOp::SExt,
+            (Some(f), Some(t)) if f < t => ConvOp::ZExt,
+            (Some(f), Some(t)) if f > t => ConvOp::Trunc,
+            (None, Some(_)) => ConvOp::PtrToInt,
+            (Some(_), None) => ConvOp::IntToPtr,
+            // Same representation, e.g. `int` to `unsigned`.
+            _ => return Some(val),
+        };
+        let dst = self.func.new_reg(to);
+        self.emit(Inst::Conv { op, dst, from, val });
+        Some(Value::Reg(dst))
+    }
+
+    fn call(&mut self, node: &ast::Call, ret: &ast::Ty) -> Option<Value> {
+        let ast::Ty::Fn(f) = node.callee.ty.pointee() else {
+            unreachable!("callee is not a function pointer");
+        };
+
+        let callee = match Self::fn_designator(&node.callee) {
+            Some(name) => Callee::Direct(name),
+            None => Callee::Indirect(self.value(&node.callee)),
+        };
+        let args: Vec<_> = node
+            .args
+            .iter()
+            .map(|a| (self.ty(&a.ty).unwrap(), self.value(a)))
+            .collect();
+
+        // Without a prototype the arguments, as promoted, are the parameters.
+        let sig = Signature {
+            ret: self.ty(ret),
+            params: match &f.params {
+                Some(params) => {
+                    params.iter().map(|p| self.ty(p).unwrap()).collect()
+                }
+                None => args.iter().map(|(ty, _)| *ty).collect(),
+            },
+            variadic: f.variadic,
+        };
+        if let Callee::Direct(name) = callee {
+            self.callees.push((name, sig.clone()));
+        }
+
+        let dst = sig.ret.map(|ty| self.func.new_reg(ty));
+        self.emit(Inst::Call { dst, callee, sig, args });
+        dst.map(Value::Reg)
+    }
+
+    /// Name of the function a callee refers to directly.
+    fn fn_designator(node: &ast::Ast) -> Option<Symbol> {
+        match &node.kind {
+            AstKind::UnOp(ast::UnOp { op: ast::OpUn::Addr, expr }) => {
+                match &expr.kind {
+                    AstKind::Ref(r) if matches!(expr.ty, ast::Ty::Fn(_)) => {
+                        Some(r.ident)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Allocates a slot for a local in the innermost scope.
+    fn var(&mut self, ident: Symbol, ty: &ast::Ty) -> SlotId {
+        let size = ty.size(self.model) as u32;
+        let align = ty.align(self.model) as u32;
+        let slot = self.func.new_slot(size, align);
+        self.scopes.last_mut().unwrap().insert(ident, slot);
+        slot
+    }
+
+    fn lookup(&self, ident: Symbol) -> SlotId {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|s| s.get(&ident))
+            .copied()
+            .unwrap_or_else(|| panic!("cannot find value `{}`", ident))
+    }
+
+    fn slot_addr(&mut self, slot: SlotId) -> Value {
+        let dst = self.func.new_reg(Ty::Ptr);
+        self.emit(Inst::SlotAddr { dst, slot });
+        Value::Reg(dst)
+    }
+
+    fn load(&mut self, ty: Ty, addr: Value) -> Value {
+        let dst = self.func.new_reg(ty);
+        self.emit(Inst::Load { dst, addr });
+        Value::Reg(dst)
+    }
+
+    fn bin(&mut self, op: BinOp, ty: Ty, lhs: Value, rhs: Value) -> Value {
+        let dst = self.func.new_reg(ty);
+        self.emit(Inst::Bin { op, dst, lhs, rhs });
+        Value::Reg(dst)
+    }
+
+    fn cmp(&mut self, op: CmpOp, ty: Ty, lhs: Value, rhs: Value) -> Value {
+        let dst = self.func.new_reg(Ty::I32);
+        self.emit(Inst::Cmp { op, ty, dst, lhs, rhs });
+        Value::Reg(dst)
+    }
+
+    /// Makes `block` the current block.
+    fn start(&mut self, block: BlockId) {
+        self.cur = Some(block);
+        self.order.push(block);
+    }
+
+    fn emit(&mut self, inst: Inst) {
+        let cur = match self.cur {
+            Some(cur) => cur,
+            // Code after a terminator is unreachable, but still lowered.
+            None => {
+                let b = self.func.new_block();
+                self.start(b);
+                b
+            }
+        };
+        self.func.block_mut(cur).insts.push(inst);
+    }
+
+    fn terminate(&mut self, term: Term) {
+        if let Some(cur) = self.cur.take() {
+            self.func.block_mut(cur).term = Some(term);
+        }
+    }
+}
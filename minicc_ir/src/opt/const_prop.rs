@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// Folds instructions whose operands are constants, and arithmetic
+/// identities such as `x + 0`, replacing their uses with the result.
+pub struct ConstProp;
+
+impl Pass for ConstProp {
+    fn name(&self) -> &'static str {
+        "const-prop"
+    }
+
+    fn run(&mut self, func: &mut Function) -> bool {
+        let mut changed = false;
+        loop {
+            let mut map = HashMap::new();
+            for inst in func.blocks.iter().flat_map(|b| &b.insts) {
+                if let Some(v) = fold(func, inst) {
+                    map.insert(inst.def().unwrap(), v);
+                }
+            }
+            if map.is_empty() {
+                return changed;
+            }
+            replace_defs(func, &map);
+            changed = true;
+        }
+    }
+}
+
+fn fold(func: &Function, inst: &Inst) -> Option<Value> {
+    match *inst {
+        Inst::Copy { val: val @ Value::Imm(_), .. } => Some(val),
+        Inst::Bin { op, dst, lhs, rhs } => {
+            fold_bin(op, func.reg_ty(dst), lhs, rhs)
+        }
+        Inst::Cmp { op, ty, lhs, rhs, .. } => {
+            let res = match (lhs, rhs) {
+                (Value::Imm(l), Value::Imm(r)) => cmp(op, ty, l, r),
+                (l, r) if l == r => matches!(
+                    op,
+                    CmpOp::Eq
+                        | CmpOp::SLe
+                        | CmpOp::SGe
+                        | CmpOp::ULe
+                        | CmpOp::UGe
+                ),
+                _ => return None,
+            };
+            Some(Value::Imm(res as i64))
+        }
+        Inst::Conv { op, dst, from, val: Value::Imm(v) } => {
+            let to = func.reg_ty(dst);
+            Some(match op {
+                ConvOp::ZExt => Value::imm(to, unsigned(from, v) as i64),
+                _ => Value::imm(to, v),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn fold_bin(op: BinOp, ty: Ty, lhs: Value, rhs: Value) -> Option<Value> {
+    let (l, r) = match (lhs, rhs) {
+        (Value::Imm(l), Value::Imm(r)) => (l, r),
+        // Identities, the constant is on the right after `sub 0, x`.
+        (x, Value::Imm(0)) if matches!(op, BinOp::Add | BinOp::Sub) => {
+            return Some(x)
+        }
+        (Value::Imm(0), x) if op == BinOp::Add => return Some(x),
+        (x, Value::Imm(1))
+            if matches!(op, BinOp::Mul | BinOp::SDiv | BinOp::UDiv) =>
+        {
+            return Some(x)
+        }
+        (Value::Imm(1), x) if op == BinOp::Mul => return Some(x),
+        (_, Value::Imm(0)) | (Value::Imm(0), _) if op == BinOp::Mul => {
+            return Some(Value::Imm(0))
+        }
+        (x, y) if x == y && op == BinOp::Sub => return Some(Value::Imm(0)),
+        _ => return None,
+    };
+
+    let (ul, ur) = (unsigned(ty, l), unsigned(ty, r));
+    let v = match op {
+        BinOp::Add => l.wrapping_add(r),
+        BinOp::Sub => l.wrapping_sub(r),
+        BinOp::Mul => l.wrapping_mul(r),
+        // Division by zero is left to happen at run time.
+        BinOp::SDiv | BinOp::SRem | BinOp::UDiv | BinOp::URem if r == 0 => {
+            return None
+        }
+        BinOp::SDiv => l.wrapping_div(r),
+        BinOp::SRem => l.wrapping_rem(r),
+        BinOp::UDiv => (ul / ur) as i64,
+        BinOp::URem => (ul % ur) as i64,
+    };
+    Some(Value::imm(ty, v))
+}
+
+fn cmp(op: CmpOp, ty: Ty, l: i64, r: i64) -> bool {
+    let (ul, ur) = (unsigned(ty, l), unsigned(ty, r));
+    match op {
+        CmpOp::Eq => l == r,
+        CmpOp::Ne => l != r,
+        CmpOp::SLt => l < r,
+        CmpOp::SLe => l <= r,
+        CmpOp::SGt => l > r,
+        CmpOp::SGe => l >= r,
+        CmpOp::ULt => ul < ur,
+        CmpOp::ULe => ul <= ur,
+        CmpOp::UGt => ul > ur,
+        CmpOp::UGe => ul >= ur,
+    }
+}
+
+/// Immediate of type `ty` as an unsigned integer of its width.
+fn unsigned(ty: Ty, v: i64) -> u64 {
+    match ty.bits() {
+        Some(bits) if bits < 64 => v as u64 & ((1 << bits) - 1),
+        _ => v as u64,
+    }
+}
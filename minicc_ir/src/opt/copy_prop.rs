@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use super::*;
+
+/// Replaces the uses of copies, and of phis merging a single value, with the
+/// copied value.
+pub struct CopyProp;
+
+impl Pass for CopyProp {
+    fn name(&self) -> &'static str {
+        "copy-prop"
+    }
+
+    fn run(&mut self, func: &mut Function) -> bool {
+        let mut changed = false;
+        loop {
+            let mut map = HashMap::new();
+            for inst in func.blocks.iter().flat_map(|b| &b.insts) {
+                match inst {
+                    Inst::Copy { dst, val } => {
+                        map.insert(*dst, *val);
+                    }
+                    Inst::Phi { dst, args } => {
+                        // A loop may feed the phi back into itself.
+                        let mut vals = args
+                            .iter()
+                            .map(|(_, v)| *v)
+                            .filter(|v| *v != Value::Reg(*dst));
+                        let Some(first) = vals.next() else {
+                            continue;
+                        };
+                        if vals.all(|v| v == first) {
+                            map.insert(*dst, first);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if map.is_empty() {
+                return changed;
+            }
+            replace_defs(func, &map);
+            changed = true;
+        }
+    }
+}
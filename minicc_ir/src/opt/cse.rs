@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::cfg::{Cfg, DomTree};
+
+/// Replaces an instruction computing the same value as one dominating it,
+/// walking the dominator tree with a scoped table of available values.
+/// Memory may change between loads, so only pure instructions are merged.
+pub struct Cse;
+
+impl Pass for Cse {
+    fn name(&self) -> &'static str {
+        "cse"
+    }
+
+    fn run(&mut self, func: &mut Function) -> bool {
+        let cfg = Cfg::new(func);
+        let dom = DomTree::new(&cfg);
+        let mut c = Walk { table: HashMap::new(), map: HashMap::new() };
+        c.block(func, &dom, BlockId(0));
+        if c.map.is_empty() {
+            return false;
+        }
+        replace_defs(func, &c.map);
+        true
+    }
+}
+
+struct Walk {
+    /// Register holding the value of each available instruction, keyed by
+    /// the instruction with its destination cleared, and the result type.
+    table: HashMap<(Inst, Ty), Reg>,
+    /// Redundant registers and the register replacing each.
+    map: HashMap<Reg, Value>,
+}
+
+impl Walk {
+    fn block(&mut self, func: &Function, dom: &DomTree, b: BlockId) {
+        let mut added = Vec::new();
+        for inst in &func.block(b).insts {
+            let Some(dst) = inst.def() else {
+                continue;
+            };
+            if !matches!(
+                inst,
+                Inst::Bin { .. }
+                    | Inst::Cmp { .. }
+                    | Inst::Conv { .. }
+                    | Inst::SlotAddr { .. }
+                    | Inst::FuncAddr { .. }
+            ) {
+                continue;
+            }
+
+            let key = (self.key(inst), func.reg_ty(dst));
+            match self.table.get(&key) {
+                Some(r) => {
+                    self.map.insert(dst, Value::Reg(*r));
+                }
+                None => {
+                    self.table.insert(key.clone(), dst);
+                    added.push(key);
+                }
+            }
+        }
+
+        for &child in dom.children(b) {
+            self.block(func, dom, child);
+        }
+        for key in added {
+            self.table.remove(&key);
+        }
+    }
+
+    /// `inst` with replaced operands, commutative ones in a fixed order.
+    fn key(&self, inst: &Inst) -> Inst {
+        let mut inst = inst.clone();
+        *inst.def_mut().unwrap() = Reg(u32::MAX);
+        for v in inst.uses_mut() {
+            if let Value::Reg(r) = v {
+                if let Some(to) = self.map.get(r) {
+                    *v = *to;
+                }
+            }
+        }
+        match &mut inst {
+            Inst::Bin { op: BinOp::Add | BinOp::Mul, lhs, rhs, .. }
+            | Inst::Cmp { op: CmpOp::Eq | CmpOp::Ne, lhs, rhs, .. } => {
+                let order = |v: &Value| match *v {
+                    Value::Reg(r) => (0, r.0 as i64),
+                    Value::Imm(i) => (1, i),
+                };
+                if order(lhs) > order(rhs) {
+                    std::mem::swap(lhs, rhs);
+                }
+            }
+            _ => {}
+        }
+        inst
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use super::mem2reg::retain_slots;
+use super::*;
+
+/// Removes instructions whose results are unused and that have no side
+/// effects, then the slots no longer addressed.
+pub struct Dce;
+
+impl Pass for Dce {
+    fn name(&self) -> &'static str {
+        "dce"
+    }
+
+    fn run(&mut self, func: &mut Function) -> bool {
+        let mut def_of = HashMap::new();
+        let mut work = Vec::new();
+        for block in &func.blocks {
+            for inst in &block.insts {
+                if let Some(d) = inst.def() {
+                    def_of.insert(d, inst);
+                }
+                if inst.has_side_effects() {
+                    work.extend(inst.uses());
+                }
+            }
+            work.extend(block.term.iter().flat_map(|t| t.uses()));
+        }
+
+        let mut is_live = vec![false; func.regs.len()];
+        while let Some(v) = work.pop() {
+            let Value::Reg(r) = v else {
+                continue;
+            };
+            if std::mem::replace(&mut is_live[r.0 as usize], true) {
+                continue;
+            }
+            if let Some(inst) = def_of.get(&r) {
+                work.extend(inst.uses());
+            }
+        }
+
+        let mut changed = false;
+        for block in &mut func.blocks {
+            let len = block.insts.len();
+            block.insts.retain(|i| {
+                i.has_side_effects()
+                    || i.def().is_some_and(|d| is_live[d.0 as usize])
+            });
+            changed |= block.insts.len() != len;
+        }
+
+        let mut addressed = vec![false; func.slots.len()];
+        for inst in func.blocks.iter().flat_map(|b| &b.insts) {
+            if let Inst::SlotAddr { slot, .. } = inst {
+                addressed[slot.0 as usize] = true;
+            }
+        }
+        if addressed.contains(&false) {
+            retain_slots(func, &addressed);
+            changed = true;
+        }
+        changed
+    }
+}
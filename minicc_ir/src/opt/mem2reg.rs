@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use super::simplify_cfg::remove_unreachable;
+use super::*;
+use crate::cfg::{Cfg, DomTree};
+
+/// Promotes slots only accessed by `load` and `store` of a single type to
+/// registers, placing phis at the iterated dominance frontiers of the stores
+/// (Cytron et al., "Efficiently Computing Static Single Assignment Form and
+/// the Control Dependence Graph").
+pub struct Mem2Reg;
+
+impl Pass for Mem2Reg {
+    fn name(&self) -> &'static str {
+        "mem2reg"
+    }
+
+    fn run(&mut self, func: &mut Function) -> bool {
+        // Renaming walks the dominator tree, which misses unreachable code.
+        remove_unreachable(func);
+
+        let types = promotable(func);
+        if types.iter().all(|t| t.is_none()) {
+            return false;
+        }
+        let slot_of: HashMap<Reg, usize> = func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.insts)
+            .filter_map(|i| match *i {
+                Inst::SlotAddr { dst, slot } => {
+                    types[slot.0 as usize].map(|_| (dst, slot.0 as usize))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let cfg = Cfg::new(func);
+        let dom = DomTree::new(&cfg);
+        let phis = place_phis(func, &cfg, &dom, &types, &slot_of);
+
+        let mut r = Rename {
+            slot_of,
+            phis,
+            stacks: vec![Vec::new(); types.len()],
+            map: HashMap::new(),
+        };
+        r.block(func, &cfg, &dom, BlockId(0));
+
+        // The accesses are gone, so are the promoted slots.
+        for block in &mut func.blocks {
+            block.insts.retain(|i| match i {
+                Inst::SlotAddr { dst, .. } => !r.slot_of.contains_key(dst),
+                Inst::Load { dst, .. } => !r.map.contains_key(dst),
+                Inst::Store { addr: Value::Reg(a), .. } => {
+                    !r.slot_of.contains_key(a)
+                }
+                _ => true,
+            });
+        }
+        func.replace_uses(&r.map);
+        for inst in func.blocks.iter_mut().flat_map(|b| &mut b.insts) {
+            if let Inst::Phi { args, .. } = inst {
+                args.sort_by_key(|(b, _)| *b);
+            }
+        }
+        let keep: Vec<_> = types.iter().map(|t| t.is_none()).collect();
+        retain_slots(func, &keep);
+        true
+    }
+}
+
+/// Type of the values each slot holds if it can be promoted, `Some(None)`
+/// for a slot never accessed.
+fn promotable(func: &Function) -> Vec<Option<Option<Ty>>> {
+    let mut types = vec![Some(None); func.slots.len()];
+    let slot_of: HashMap<Reg, usize> = func
+        .blocks
+        .iter()
+        .flat_map(|b| &b.insts)
+        .filter_map(|i| match *i {
+            Inst::SlotAddr { dst, slot } => Some((dst, slot.0 as usize)),
+            _ => None,
+        })
+        .collect();
+
+    let mut access = |slot: usize, ty: Option<Ty>| {
+        types[slot] = match (types[slot], ty) {
+            (Some(None), Some(ty)) => Some(Some(ty)),
+            (Some(Some(t)), Some(ty)) if t == ty => Some(Some(t)),
+            _ => None,
+        };
+    };
+
+    for block in &func.blocks {
+        for inst in &block.insts {
+            // The address may only be used to load or store.
+            let (addr, ty) = match *inst {
+                Inst::Load { dst, addr } => (Some(addr), func.reg_ty(dst)),
+                Inst::Store { ty, addr, .. } => (Some(addr), ty),
+                _ => (None, Ty::I32),
+            };
+            for v in inst.uses() {
+                if let Value::Reg(r) = v {
+                    if let Some(&slot) = slot_of.get(&r) {
+                        let ok = addr == Some(v)
+                            && !matches!(inst, Inst::Store { val, .. } if *val == v);
+                        access(slot, ok.then_some(ty));
+                    }
+                }
+            }
+        }
+        for v in block.term.iter().flat_map(|t| t.uses()) {
+            if let Value::Reg(r) = v {
+                if let Some(&slot) = slot_of.get(&r) {
+                    access(slot, None);
+                }
+            }
+        }
+    }
+    types
+}
+
+/// Inserts empty phis for the promoted slots, returning the slot of each.
+fn place_phis(
+    func: &mut Function,
+    cfg: &Cfg,
+    dom: &DomTree,
+    types: &[Option<Option<Ty>>],
+    slot_of: &HashMap<Reg, usize>,
+) -> HashMap<Reg, usize> {
+    let df = dom.frontiers(cfg);
+    let mut phis = HashMap::new();
+
+    // Blocks storing to each slot.
+    let mut stores = vec![Vec::new(); types.len()];
+    for b in func.block_ids() {
+        for inst in &func.block(b).insts {
+            if let Inst::Store { addr: Value::Reg(a), .. } = inst {
+                if let Some(&slot) = slot_of.get(a) {
+                    if stores[slot].last() != Some(&b) {
+                        stores[slot].push(b);
+                    }
+                }
+            }
+        }
+    }
+
+    for (slot, ty) in types.iter().enumerate() {
+        let Some(Some(ty)) = *ty else {
+            continue;
+        };
+        let mut defs = std::mem::take(&mut stores[slot]);
+
+        let mut has_phi = vec![false; func.blocks.len()];
+        while let Some(b) = defs.pop() {
+            for &f in &df[b.0 as usize] {
+                if has_phi[f.0 as usize] {
+                    continue;
+                }
+                has_phi[f.0 as usize] = true;
+                let dst = func.new_reg(ty);
+                func.block_mut(f)
+                    .insts
+                    .insert(0, Inst::Phi { dst, args: Vec::new() });
+                phis.insert(dst, slot);
+                defs.push(f);
+            }
+        }
+    }
+    phis
+}
+
+struct Rename {
+    /// Promoted slot each address refers to.
+    slot_of: HashMap<Reg, usize>,
+    /// Promoted slot of each inserted phi.
+    phis: HashMap<Reg, usize>,
+    /// Current value of each slot.
+    stacks: Vec<Vec<Value>>,
+    /// Value of each removed load.
+    map: HashMap<Reg, Value>,
+}
+
+impl Rename {
+    fn current(&self, slot: usize) -> Value {
+        // Reading an uninitialised local is undefined, 0 will do.
+        self.stacks[slot].last().copied().unwrap_or(Value::Imm(0))
+    }
+
+    fn block(
+        &mut self,
+        func: &mut Function,
+        cfg: &Cfg,
+        dom: &DomTree,
+        b: BlockId,
+    ) {
+        let mut pushed = Vec::new();
+        for inst in &func.block(b).insts {
+            match *inst {
+                Inst::Phi { dst, .. } => {
+                    if let Some(&slot) = self.phis.get(&dst) {
+                        self.stacks[slot].push(Value::Reg(dst));
+                        pushed.push(slot);
+                    }
+                }
+                Inst::Load { dst, addr: Value::Reg(a) } => {
+                    if let Some(&slot) = self.slot_of.get(&a) {
+                        self.map.insert(dst, self.current(slot));
+                    }
+                }
+                Inst::Store { val, addr: Value::Reg(a), .. } => {
+                    if let Some(&slot) = self.slot_of.get(&a) {
+                        self.stacks[slot].push(val);
+                        pushed.push(slot);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for &s in cfg.succs(b) {
+            for i in 0..func.block(s).insts.len() {
+                let Inst::Phi { dst, .. } = func.block(s).insts[i] else {
+                    break;
+                };
+                if let Some(&slot) = self.phis.get(&dst) {
+                    let val = self.current(slot);
+                    if let Inst::Phi { args, .. } =
+                        &mut func.block_mut(s).insts[i]
+                    {
+                        args.push((b, val));
+                    }
+                }
+            }
+        }
+
+        for &child in dom.children(b) {
+            self.block(func, cfg, dom, child);
+        }
+        for slot in pushed {
+            self.stacks[slot].pop();
+        }
+    }
+}
+
+/// Removes the slots not kept and renumbers the others.
+pub(super) fn retain_slots(func: &mut Function, keep: &[bool]) {
+    let mut new_id = Vec::new();
+    let mut n = 0;
+    for k in keep {
+        new_id.push(SlotId(n));
+        n += *k as u32;
+    }
+    func.slots = func
+        .slots
+        .iter()
+        .zip(keep)
+        .filter(|(_, k)| **k)
+        .map(|(s, _)| *s)
+        .collect();
+    for inst in func.blocks.iter_mut().flat_map(|b| &mut b.insts) {
+        if let Inst::SlotAddr { slot, .. } = inst {
+            *slot = new_id[slot.0 as usize];
+        }
+    }
+}
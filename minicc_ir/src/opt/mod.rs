@@ -0,0 +1,166 @@
+//! Optimisation passes over functions and the pass manager running them.
+
+mod const_prop;
+mod copy_prop;
+mod cse;
+mod dce;
+mod mem2reg;
+mod simplify_cfg;
+
+use std::collections::HashMap;
+
+pub use const_prop::ConstProp;
+pub use copy_prop::CopyProp;
+pub use cse::Cse;
+pub use dce::Dce;
+pub use mem2reg::Mem2Reg;
+pub use simplify_cfg::SimplifyCfg;
+
+use super::*;
+
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    /// Transforms `func`, returning whether it changed.
+    fn run(&mut self, func: &mut Function) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// Locals stay in slots.
+    #[default]
+    O0,
+    /// Promotes locals to registers and runs each cleanup pass once.
+    O1,
+    /// Adds common subexpression elimination and repeats the passes until
+    /// nothing changes.
+    O2,
+}
+
+/// Upper bound on the rounds of an iterating pass manager.
+const MAX_ROUNDS: usize = 16;
+
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    /// Whether to repeat the passes until none changes the function.
+    iterate: bool,
+}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The passes of an optimisation level.
+    pub fn for_level(level: OptLevel) -> Self {
+        let mut pm = Self::new();
+        if level == OptLevel::O0 {
+            return pm;
+        }
+        pm.add(Mem2Reg);
+        pm.add(ConstProp);
+        pm.add(CopyProp);
+        if level >= OptLevel::O2 {
+            pm.add(Cse);
+            pm.iterate = true;
+        }
+        pm.add(SimplifyCfg);
+        pm.add(Dce);
+        pm
+    }
+
+    pub fn add(&mut self, pass: impl Pass + 'static) -> &mut Self {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn iterate(&mut self, iterate: bool) -> &mut Self {
+        self.iterate = iterate;
+        self
+    }
+
+    pub fn run(&mut self, module: &mut Module) {
+        for func in module.funcs.iter_mut().filter(|f| !f.is_decl()) {
+            self.run_func(func);
+        }
+    }
+
+    pub fn run_func(&mut self, func: &mut Function) {
+        if self.passes.is_empty() {
+            return;
+        }
+        for _ in 0..MAX_ROUNDS {
+            let mut changed = false;
+            for pass in &mut self.passes {
+                changed |= pass.run(func);
+                // Catch a broken pass where it broke the function.
+                if cfg!(debug_assertions) {
+                    if let Err(errs) = verify::verify_func(func) {
+                        let errs: Vec<_> =
+                            errs.iter().map(|e| e.to_string()).collect();
+                        panic!(
+                            "invalid IR after {}:\n{}\n{}",
+                            pass.name(),
+                            errs.join("\n"),
+                            func
+                        );
+                    }
+                }
+            }
+            if !changed || !self.iterate {
+                break;
+            }
+        }
+        func.compact_regs();
+    }
+}
+
+/// Removes the blocks not kept, which no kept block may jump to, and the
+/// phi arguments flowing from them.
+fn retain_blocks(func: &mut Function, keep: &[bool]) {
+    let mut new_id = HashMap::new();
+    for (i, _) in keep.iter().enumerate().filter(|(_, k)| **k) {
+        new_id.insert(BlockId(i as u32), BlockId(new_id.len() as u32));
+    }
+
+    let blocks = std::mem::take(&mut func.blocks);
+    func.blocks = blocks
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(b, k)| k.then_some(b))
+        .collect();
+    for block in &mut func.blocks {
+        if let Some(term) = &mut block.term {
+            for succ in term.succs_mut() {
+                *succ = new_id[succ];
+            }
+        }
+        for inst in &mut block.insts {
+            if let Inst::Phi { args, .. } = inst {
+                args.retain(|(b, _)| new_id.contains_key(b));
+                for (b, _) in args {
+                    *b = new_id[b];
+                }
+            }
+        }
+    }
+}
+
+/// Removes the phi arguments of `block` flowing from `pred`.
+fn remove_phi_args(func: &mut Function, block: BlockId, pred: BlockId) {
+    for inst in &mut func.block_mut(block).insts {
+        if let Inst::Phi { args, .. } = inst {
+            args.retain(|(b, _)| *b != pred);
+        }
+    }
+}
+
+/// Removes the instructions defining registers in `map` and replaces their
+/// uses.
+fn replace_defs(func: &mut Function, map: &HashMap<Reg, Value>) {
+    for block in &mut func.blocks {
+        block.insts.retain(|i| !i.def().is_some_and(|d| map.contains_key(&d)));
+    }
+    func.replace_uses(map);
+}
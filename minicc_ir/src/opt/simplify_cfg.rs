@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::cfg::Cfg;
+
+/// Folds constant branches, removes unreachable blocks, merges a block into
+/// its only predecessor and skips empty blocks that only jump on.
+pub struct SimplifyCfg;
+
+impl Pass for SimplifyCfg {
+    fn name(&self) -> &'static str {
+        "simplify-cfg"
+    }
+
+    fn run(&mut self, func: &mut Function) -> bool {
+        let mut changed = false;
+        loop {
+            let round = fold_branches(func)
+                | remove_unreachable(func)
+                | thread_jumps(func)
+                | merge_blocks(func);
+            if !round {
+                return changed;
+            }
+            changed = true;
+        }
+    }
+}
+
+/// Turns `br` with a constant condition or equal targets into `jmp`.
+fn fold_branches(func: &mut Function) -> bool {
+    let mut changed = false;
+    for b in func.block_ids() {
+        let Some(Term::Br { cond, then, else_, .. }) = func.block(b).term
+        else {
+            continue;
+        };
+        let (taken, dropped) = match cond {
+            _ if then == else_ => (then, None),
+            Value::Imm(0) => (else_, Some(then)),
+            Value::Imm(_) => (then, Some(else_)),
+            Value::Reg(_) => continue,
+        };
+        if let Some(d) = dropped {
+            remove_phi_args(func, d, b);
+        }
+        func.block_mut(b).term = Some(Term::Jmp(taken));
+        changed = true;
+    }
+    changed
+}
+
+pub(super) fn remove_unreachable(func: &mut Function) -> bool {
+    let cfg = Cfg::new(func);
+    if cfg.rpo.len() == func.blocks.len() {
+        return false;
+    }
+    let mut keep = vec![false; func.blocks.len()];
+    for b in &cfg.rpo {
+        keep[b.0 as usize] = true;
+    }
+    retain_blocks(func, &keep);
+    true
+}
+
+/// Sends predecessors of an empty block ending in `jmp` straight to its
+/// target. If the target has phis, the value from the empty block becomes the
+/// value from its predecessor, so there must be one which is not a
+/// predecessor of the target already.
+fn thread_jumps(func: &mut Function) -> bool {
+    let mut changed = false;
+    for e in func.block_ids().skip(1) {
+        let block = func.block(e);
+        let Some(Term::Jmp(target)) = block.term else {
+            continue;
+        };
+        if !block.insts.is_empty() || target == e {
+            continue;
+        }
+        let preds: Vec<_> = func
+            .block_ids()
+            .filter(|b| {
+                func.block(*b)
+                    .term
+                    .as_ref()
+                    .is_some_and(|t| t.succs().contains(&e))
+            })
+            .collect();
+        if has_phis(func, target) {
+            let &[p] = &preds[..] else {
+                continue;
+            };
+            let term = func.block(p).term.as_ref().unwrap();
+            if term.succs().contains(&target) {
+                continue;
+            }
+            rename_phi_pred(func, target, e, p);
+        }
+        for p in preds {
+            let term = func.block_mut(p).term.as_mut().unwrap();
+            for succ in term.succs_mut().into_iter().filter(|s| **s == e) {
+                *succ = target;
+            }
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Appends a block to its only predecessor if it is that predecessor's only
+/// successor.
+fn merge_blocks(func: &mut Function) -> bool {
+    let cfg = Cfg::new(func);
+    let mut keep = vec![true; func.blocks.len()];
+    // Block each block was merged into.
+    let mut merged_into: Vec<BlockId> = func.block_ids().collect();
+    let mut map = HashMap::new();
+
+    for &b in &cfg.rpo[1..] {
+        let &[p] = cfg.preds(b) else {
+            continue;
+        };
+        if cfg.succs(p).len() != 1 || p == b {
+            continue;
+        }
+        let into = merged_into[p.0 as usize];
+
+        let mut insts = std::mem::take(&mut func.block_mut(b).insts);
+        // With one predecessor a phi has one value.
+        insts.retain(|i| match i {
+            Inst::Phi { dst, args } => {
+                map.insert(*dst, args[0].1);
+                false
+            }
+            _ => true,
+        });
+        let term = func.block_mut(b).term.take();
+        let block = func.block_mut(into);
+        block.insts.extend(insts);
+        block.term = term;
+
+        for &s in cfg.succs(b) {
+            rename_phi_pred(func, s, b, into);
+        }
+        merged_into[b.0 as usize] = into;
+        keep[b.0 as usize] = false;
+    }
+
+    if keep.iter().all(|k| *k) {
+        return false;
+    }
+    // Removed blocks are no longer jumped to, but still need a terminator.
+    for (b, _) in keep.iter().enumerate().filter(|(_, k)| !**k) {
+        func.blocks[b].term = Some(Term::Unreachable);
+    }
+    retain_blocks(func, &keep);
+    func.replace_uses(&map);
+    true
+}
+
+fn has_phis(func: &Function, b: BlockId) -> bool {
+    matches!(func.block(b).insts.first(), Some(Inst::Phi { .. }))
+}
+
+/// Makes phis of `block` take the value flowing from `from` from `to`.
+fn rename_phi_pred(
+    func: &mut Function,
+    block: BlockId,
+    from: BlockId,
+    to: BlockId,
+) {
+    for inst in &mut func.block_mut(block).insts {
+        if let Inst::Phi { args, .. } = inst {
+            for (b, _) in args {
+                if *b == from {
+                    *b = to;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+/// Error in the textual IR at a 1-based line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Reads the textual form printed by `Module`'s `Display`. `;` starts a
+/// comment.
+pub fn parse(src: &str) -> Result<Module, ParseError> {
+    let mut p = Parser { toks: tokenize(src)?, pos: 0 };
+    let mut module = Module::default();
+    while p.peek().is_some() {
+        module.funcs.push(p.func()?);
+    }
+    Ok(module)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tok {
+    /// Keyword, type, opcode or block label.
+    Word(String),
+    Reg(u32),
+    Slot(u32),
+    Global(String),
+    Int(i64),
+    Punct(&'static str),
+}
+
+impl Display for Tok {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Word(w) => write!(f, "{}", w),
+            Tok::Reg(r) => write!(f, "%{}", r),
+            Tok::Slot(s) => write!(f, "${}", s),
+            Tok::Global(g) => write!(f, "@{}", g),
+            Tok::Int(i) => write!(f, "{}", i),
+            Tok::Punct(p) => write!(f, "{}", p),
+        }
+    }
+}
+
+const PUNCTS: &[&str] = &["...", "(", ")", "[", "]", ",", "=", "{", "}", ":"];
+
+fn tokenize(src: &str) -> Result<Vec<(Tok, usize)>, ParseError> {
+    let mut toks = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |msg: String| ParseError { line: line_no, msg };
+        let s = line.split(';').next().unwrap().as_bytes();
+        let mut i = 0;
+        while i < s.len() {
+            let c = s[i];
+            if c.is_ascii_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            let word = |i: usize| {
+                let end = s[i..]
+                    .iter()
+                    .position(|c| {
+                        !(c.is_ascii_alphanumeric() || *c == b'_' || *c == b'.')
+                    })
+                    .map_or(s.len(), |n| i + n);
+                (std::str::from_utf8(&s[i..end]).unwrap(), end)
+            };
+            let number = |w: &str| {
+                w.parse::<i64>()
+                    .map_err(|_| err(format!("invalid number `{}`", w)))
+            };
+
+            let tok = match c {
+                b'%' | b'$' => {
+                    let (w, end) = word(i + 1);
+                    let n = number(w)? as u32;
+                    i = end;
+                    if c == b'%' {
+                        Tok::Reg(n)
+                    } else {
+                        Tok::Slot(n)
+                    }
+                }
+                b'@' => {
+                    let (w, end) = word(i + 1);
+                    if w.is_empty() {
+                        return Err(err(
+                            "expected a name after `@`".to_string()
+                        ));
+                    }
+                    i = end;
+                    Tok::Global(w.to_string())
+                }
+                b'-' | b'0'..=b'9' => {
+                    let (_, end) = word(i + 1);
+                    let text = std::str::from_utf8(&s[i..end]).unwrap();
+                    i = end;
+                    Tok::Int(number(text)?)
+                }
+                c if c.is_ascii_alphabetic() || c == b'_' => {
+                    let (w, end) = word(i);
+                    i = end;
+                    Tok::Word(w.to_string())
+                }
+                _ => match PUNCTS
+                    .iter()
+                    .find(|p| s[i..].starts_with(p.as_bytes()))
+                {
+                    Some(p) => {
+                        i += p.len();
+                        Tok::Punct(p)
+                    }
+                    None => {
+                        let c = line[i..].chars().next().unwrap();
+                        return Err(err(format!(
+                            "unexpected character `{}`",
+                            c
+                        )));
+                    }
+                },
+            };
+            toks.push((tok, line_no));
+        }
+    }
+    Ok(toks)
+}
+
+struct Parser {
+    toks: Vec<(Tok, usize)>,
+    pos: usize,
+}
+
+/// State of the function being parsed.
+struct FuncCtx {
+    func: Function,
+    regs: HashMap<u32, Ty>,
+    /// Registers used, with the line of the first use.
+    used: HashMap<u32, usize>,
+}
+
+impl Parser {
+    /// ```ebnf
+    /// func ::= "declare" ret_ty global "(" (ty ("," ty)* ("," "...")? | "...")? ")"
+    ///        | "fn" ret_ty global "(" (ty reg ("," ty reg)* ("," "...")? | "...")? ")"
+    ///          "{" slot* block* "}"
+    /// ```
+    fn func(&mut self) -> Result<Function, ParseError> {
+        let decl = match self.word()?.as_str() {
+            "declare" => true,
+            "fn" => false,
+            w => {
+                return self.err_prev(format!(
+                    "expected `fn` or `declare`, found `{}`",
+                    w
+                ))
+            }
+        };
+        let ret = self.ret_ty()?;
+        let name = self.global()?;
+
+        let mut sig = Signature { ret, params: Vec::new(), variadic: false };
+        let mut ctx = FuncCtx {
+            func: Function::new(Symbol::intern(&name), sig.clone()),
+            regs: HashMap::new(),
+            used: HashMap::new(),
+        };
+        let mut params = Vec::new();
+
+        self.expect("(")?;
+        if !self.eat(")") {
+            loop {
+                if self.eat("...") {
+                    sig.variadic = true;
+                    self.expect(")")?;
+                    break;
+                }
+                let ty = self.ty()?;
+                sig.params.push(ty);
+                if !decl {
+                    let r = self.reg()?;
+                    self.define(&mut ctx, r, ty)?;
+                    params.push(r);
+                }
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        ctx.func.sig = sig;
+        if decl {
+            return Ok(ctx.func);
+        }
+
+        self.expect("{")?;
+        while let Some(Tok::Slot(n)) = self.peek() {
+            let n = *n;
+            self.pos += 1;
+            if n as usize != ctx.func.slots.len() {
+                return self
+                    .err_prev(format!("expected `${}`", ctx.func.slots.len()));
+            }
+            self.expect("=")?;
+            self.keyword("slot")?;
+            let size = self.int()? as u32;
+            self.expect(",")?;
+            let align = self.int()? as u32;
+            ctx.func.new_slot(size, align);
+        }
+        while !self.eat("}") {
+            self.block(&mut ctx)?;
+        }
+        if ctx.func.blocks.is_empty() {
+            return self.err_prev("function has no blocks".to_string());
+        }
+
+        let FuncCtx { mut func, regs, used } = ctx;
+        if let Some((r, line)) =
+            used.iter().find(|(r, _)| !regs.contains_key(r))
+        {
+            return Err(ParseError {
+                line: *line,
+                msg: format!("undefined register `%{}`", r),
+            });
+        }
+        let count = regs.keys().max().map_or(0, |m| m + 1);
+        // Registers are numbered densely, gaps become unused `i32`s.
+        func.regs = (0..count)
+            .map(|r| regs.get(&r).copied().unwrap_or(Ty::I32))
+            .collect();
+        func.params = params.into_iter().map(Reg).collect();
+        Ok(func)
+    }
+
+    /// ```ebnf
+    /// block ::= label ":" inst* term
+    /// ```
+    fn block(&mut self, ctx: &mut FuncCtx) -> Result<(), ParseError> {
+        let label = self.label()?;
+        if label.0 as usize != ctx.func.blocks.len() {
+            return self
+                .err_prev(format!("expected `bb{}`", ctx.func.blocks.len()));
+        }
+        self.expect(":")?;
+        let id = ctx.func.new_block();
+
+        loop {
+            if let Some(term) = self.term(ctx)? {
+                ctx.func.block_mut(id).term = Some(term);
+                return Ok(());
+            }
+            let inst = self.inst(ctx)?;
+            ctx.func.block_mut(id).insts.push(inst);
+        }
+    }
+
+    /// ```ebnf
+    /// term ::= "ret" (ty value)? | "jmp" label
+    ///        | "br" ty value "," label "," label | "unreachable"
+    /// ```
+    fn term(&mut self, ctx: &mut FuncCtx) -> Result<Option<Term>, ParseError> {
+        let Some(Tok::Word(w)) = self.peek() else {
+            return Ok(None);
+        };
+        let term = match w.as_str() {
+            "ret" => {
+                let line = self.line();
+                self.pos += 1;
+                // `ret` without a value ends the line.
+                if self.peek().is_some() && self.toks[self.pos].1 == line {
+                    let ty = self.ty()?;
+                    Term::Ret(Some((ty, self.value(ctx)?)))
+                } else {
+                    Term::Ret(None)
+                }
+            }
+            "jmp" => {
+                self.pos += 1;
+                Term::Jmp(self.label()?)
+            }
+            "br" => {
+                self.pos += 1;
+                let ty = self.ty()?;
+                let cond = self.value(ctx)?;
+                self.expect(",")?;
+                let then = self.label()?;
+                self.expect(",")?;
+                let else_ = self.label()?;
+                Term::Br { ty, cond, then, else_ }
+            }
+            "unreachable" => {
+                self.pos += 1;
+                Term::Unreachable
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(term))
+    }
+
+    /// ```ebnf
+    /// inst ::= (reg "=")? opcode operands
+    /// ```
+    fn inst(&mut self, ctx: &mut FuncCtx) -> Result<Inst, ParseError> {
+        let dst = match self.peek() {
+            Some(Tok::Reg(_)) => {
+                let r = self.reg()?;
+                self.expect("=")?;
+                Some(r)
+            }
+            _ => None,
+        };
+        let op = self.word()?;
+
+        // Defines `dst` as a register of type `ty`.
+        let def = |p: &mut Self,
+                   ctx: &mut FuncCtx,
+                   ty: Ty|
+         -> Result<Reg, ParseError> {
+            match dst {
+                Some(r) => p.define(ctx, r, ty).map(|_| Reg(r)),
+                None => p.err_prev(format!("`{}` must define a register", op)),
+            }
+        };
+
+        let inst = if let Some(bin) = BinOp::ALL.iter().find(|o| o.name() == op)
+        {
+            let ty = self.ty()?;
+            let lhs = self.value(ctx)?;
+            self.expect(",")?;
+            let rhs = self.value(ctx)?;
+            Inst::Bin { op: *bin, dst: def(self, ctx, ty)?, lhs, rhs }
+        } else if let Some(cmp) = CmpOp::ALL.iter().find(|o| o.name() == op) {
+            let ty = self.ty()?;
+            let lhs = self.value(ctx)?;
+            self.expect(",")?;
+            let rhs = self.value(ctx)?;
+            Inst::Cmp { op: *cmp, ty, dst: def(self, ctx, Ty::I32)?, lhs, rhs }
+        } else if let Some(conv) = ConvOp::ALL.iter().find(|o| o.name() == op) {
+            let from = self.ty()?;
+            let val = self.value(ctx)?;
+            self.keyword("to")?;
+            let to = self.ty()?;
+            Inst::Conv { op: *conv, dst: def(self, ctx, to)?, from, val }
+        } else {
+            match op.as_str() {
+                "copy" => {
+                    let ty = self.ty()?;
+                    let val = self.value(ctx)?;
+                    Inst::Copy { dst: def(self, ctx, ty)?, val }
+                }
+                "addr" => {
+                    let slot = match self.next()? {
+                        Tok::Slot(s) if (s as usize) < ctx.func.slots.len() => {
+                            SlotId(s)
+                        }
+                        t => {
+                            return self.err_prev(format!(
+                                "expected a slot, found `{}`",
+                                t
+                            ))
+                        }
+                    };
+                    Inst::SlotAddr { dst: def(self, ctx, Ty::Ptr)?, slot }
+                }
+                "func" => {
+                    let name = Symbol::intern(&self.global()?);
+                    Inst::FuncAddr { dst: def(self, ctx, Ty::Ptr)?, name }
+                }
+                "load" => {
+                    let ty = self.ty()?;
+                    let addr = self.value(ctx)?;
+                    Inst::Load { dst: def(self, ctx, ty)?, addr }
+                }
+                "store" => {
+                    let ty = self.ty()?;
+                    let val = self.value(ctx)?;
+                    self.expect(",")?;
+                    let addr = self.value(ctx)?;
+                    Inst::Store { ty, val, addr }
+                }
+                "call" => {
+                    let sig = self.sig()?;
+                    let callee = match self.peek() {
+                        Some(Tok::Global(_)) => {
+                            Callee::Direct(Symbol::intern(&self.global()?))
+                        }
+                        _ => Callee::Indirect(self.value(ctx)?),
+                    };
+                    self.expect("(")?;
+                    let mut args = Vec::new();
+                    if !self.eat(")") {
+                        loop {
+                            let ty = self.ty()?;
+                            args.push((ty, self.value(ctx)?));
+                            if self.eat(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    let dst = match (dst, sig.ret) {
+                        (Some(_), Some(ty)) => Some(def(self, ctx, ty)?),
+                        (None, _) => None,
+                        (Some(_), None) => {
+                            return self.err_prev(
+                                "void call cannot define a register"
+                                    .to_string(),
+                            )
+                        }
+                    };
+                    Inst::Call { dst, callee, sig, args }
+                }
+                "vastart" => Inst::VaStart { ap: self.value(ctx)? },
+                "vaarg" => {
+                    let ty = self.ty()?;
+                    let ap = self.value(ctx)?;
+                    Inst::VaArg { dst: def(self, ctx, ty)?, ap }
+                }
+                "vaend" => Inst::VaEnd { ap: self.value(ctx)? },
+                "phi" => {
+                    let ty = self.ty()?;
+                    let mut args = Vec::new();
+                    loop {
+                        self.expect("[")?;
+                        let val = self.value(ctx)?;
+                        self.expect(",")?;
+                        args.push((self.label()?, val));
+                        self.expect("]")?;
+                        if !self.eat(",") {
+                            break;
+                        }
+                    }
+                    Inst::Phi { dst: def(self, ctx, ty)?, args }
+                }
+                _ => {
+                    return self
+                        .err_prev(format!("unknown instruction `{}`", op))
+                }
+            }
+        };
+
+        if inst.def().is_none() && dst.is_some() {
+            return self
+                .err_prev(format!("`{}` does not define a register", op));
+        }
+        Ok(inst)
+    }
+
+    /// ```ebnf
+    /// sig ::= ret_ty "(" (ty ("," ty)* ("," "...")? | "...")? ")"
+    /// ```
+    fn sig(&mut self) -> Result<Signature, ParseError> {
+        let ret = self.ret_ty()?;
+        let mut sig = Signature { ret, params: Vec::new(), variadic: false };
+        self.expect("(")?;
+        if self.eat(")") {
+            return Ok(sig);
+        }
+        loop {
+            if self.eat("...") {
+                sig.variadic = true;
+                self.expect(")")?;
+                return Ok(sig);
+            }
+            sig.params.push(self.ty()?);
+            if self.eat(")") {
+                return Ok(sig);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn define(
+        &self,
+        ctx: &mut FuncCtx,
+        r: u32,
+        ty: Ty,
+    ) -> Result<(), ParseError> {
+        if ctx.regs.insert(r, ty).is_some() {
+            return self
+                .err_prev(format!("register `%{}` is defined twice", r));
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, ctx: &mut FuncCtx) -> Result<Value, ParseError> {
+        let line = self.line();
+        match self.next()? {
+            Tok::Reg(r) => {
+                ctx.used.entry(r).or_insert(line);
+                Ok(Value::Reg(Reg(r)))
+            }
+            Tok::Int(i) => Ok(Value::Imm(i)),
+            t => self.err_prev(format!("expected a value, found `{}`", t)),
+        }
+    }
+
+    fn ty(&mut self) -> Result<Ty, ParseError> {
+        let w = self.word()?;
+        match Ty::ALL.iter().find(|t| t.name() == w) {
+            Some(ty) => Ok(*ty),
+            None => self.err_prev(format!("expected a type, found `{}`", w)),
+        }
+    }
+
+    fn ret_ty(&mut self) -> Result<Option<Ty>, ParseError> {
+        if matches!(self.peek(), Some(Tok::Word(w)) if w == "void") {
+            self.pos += 1;
+            return Ok(None);
+        }
+        self.ty().map(Some)
+    }
+
+    fn label(&mut self) -> Result<BlockId, ParseError> {
+        let w = self.word()?;
+        match w.strip_prefix("bb").and_then(|n| n.parse().ok()) {
+            Some(n) => Ok(BlockId(n)),
+            None => {
+                self.err_prev(format!("expected a block label, found `{}`", w))
+            }
+        }
+    }
+
+    fn reg(&mut self) -> Result<u32, ParseError> {
+        match self.next()? {
+            Tok::Reg(r) => Ok(r),
+            t => self.err_prev(format!("expected a register, found `{}`", t)),
+        }
+    }
+
+    fn global(&mut self) -> Result<String, ParseError> {
+        match self.next()? {
+            Tok::Global(g) => Ok(g),
+            t => self.err_prev(format!("expected `@name`, found `{}`", t)),
+        }
+    }
+
+    fn int(&mut self) -> Result<i64, ParseError> {
+        match self.next()? {
+            Tok::Int(i) => Ok(i),
+            t => self.err_prev(format!("expected an integer, found `{}`", t)),
+        }
+    }
+
+    fn word(&mut self) -> Result<String, ParseError> {
+        match self.next()? {
+            Tok::Word(w) => Ok(w),
+            t => self.err_prev(format!("expected a word, found `{}`", t)),
+        }
+    }
+
+    fn keyword(&mut self, kw: &str) -> Result<(), ParseError> {
+        match self.next()? {
+            Tok::Word(w) if w == kw => Ok(()),
+            t => self.err_prev(format!("expected `{}`, found `{}`", kw, t)),
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), ParseError> {
+        match self.next()? {
+            Tok::Punct(p) if p == punct => Ok(()),
+            t => self.err_prev(format!("expected `{}`, found `{}`", punct, t)),
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = matches!(self.peek(), Some(Tok::Punct(p)) if *p == punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn next(&mut self) -> Result<Tok, ParseError> {
+        match self.toks.get(self.pos) {
+            Some((t, _)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => self.err_prev("unexpected end of input".to_string()),
+        }
+    }
+
+    /// Line of the next token.
+    fn line(&self) -> usize {
+        self.toks.get(self.pos).or(self.toks.last()).map_or(1, |(_, l)| *l)
+    }
+
+    /// Error at the last consumed token.
+    fn err_prev<T>(&self, msg: String) -> Result<T, ParseError> {
+        let line =
+            self.toks.get(self.pos.saturating_sub(1)).map_or(1, |(_, l)| *l);
+        Err(ParseError { line, msg })
+    }
+}
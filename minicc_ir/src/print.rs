@@ -0,0 +1,223 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, func) in self.funcs.iter().enumerate() {
+            // Blank lines around definitions, declarations are grouped.
+            let prev_decl = i > 0 && self.funcs[i - 1].is_decl();
+            if i > 0 && !(prev_decl && func.is_decl()) {
+                writeln!(f)?;
+            }
+            write!(f, "{}", func)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_decl() {
+            write!(f, "declare {} @{}(", RetTy(self.sig.ret), self.name)?;
+            for (i, ty) in self.sig.params.iter().enumerate() {
+                write!(f, "{}{}", if i > 0 { ", " } else { "" }, ty)?;
+            }
+            if self.sig.variadic {
+                write!(
+                    f,
+                    "{}...",
+                    if self.sig.params.is_empty() { "" } else { ", " }
+                )?;
+            }
+            return writeln!(f, ")");
+        }
+
+        write!(f, "fn {} @{}(", RetTy(self.sig.ret), self.name)?;
+        for (i, p) in self.params.iter().enumerate() {
+            write!(
+                f,
+                "{}{} %{}",
+                if i > 0 { ", " } else { "" },
+                self.reg_ty(*p),
+                p.0
+            )?;
+        }
+        if self.sig.variadic {
+            write!(f, "{}...", if self.params.is_empty() { "" } else { ", " })?;
+        }
+        writeln!(f, ") {{")?;
+
+        for (i, slot) in self.slots.iter().enumerate() {
+            writeln!(f, "    ${} = slot {}, {}", i, slot.size, slot.align)?;
+        }
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "bb{}:", i)?;
+            for inst in &block.insts {
+                writeln!(f, "    {}", InstDisplay(self, inst))?;
+            }
+            match &block.term {
+                Some(term) => writeln!(f, "    {}", term)?,
+                None => writeln!(f, "    <unterminated>")?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Return type, `void` if `None`.
+struct RetTy(Option<Ty>);
+
+impl Display for RetTy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(ty) => write!(f, "{}", ty),
+            None => f.write_str("void"),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Reg(r) => write!(f, "{}", r),
+            Value::Imm(i) => write!(f, "{}", i),
+        }
+    }
+}
+
+impl Display for Reg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl Display for SlotId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "${}", self.0)
+    }
+}
+
+impl Display for Signature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (", RetTy(self.ret))?;
+        for (i, ty) in self.params.iter().enumerate() {
+            write!(f, "{}{}", if i > 0 { ", " } else { "" }, ty)?;
+        }
+        if self.variadic {
+            write!(f, "{}...", if self.params.is_empty() { "" } else { ", " })?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// An instruction, whose types are those of the registers of the function.
+struct InstDisplay<'a>(&'a Function, &'a Inst);
+
+impl Display for InstDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let InstDisplay(func, inst) = *self;
+        let ty = |r: &Reg| func.reg_ty(*r);
+        match inst {
+            Inst::Copy { dst, val } => {
+                write!(f, "{} = copy {} {}", dst, ty(dst), val)
+            }
+            Inst::Bin { op, dst, lhs, rhs } => {
+                write!(
+                    f,
+                    "{} = {} {} {}, {}",
+                    dst,
+                    op.name(),
+                    ty(dst),
+                    lhs,
+                    rhs
+                )
+            }
+            Inst::Cmp { op, ty, dst, lhs, rhs } => {
+                write!(f, "{} = {} {} {}, {}", dst, op.name(), ty, lhs, rhs)
+            }
+            Inst::Conv { op, dst, from, val } => {
+                write!(
+                    f,
+                    "{} = {} {} {} to {}",
+                    dst,
+                    op.name(),
+                    from,
+                    val,
+                    ty(dst)
+                )
+            }
+            Inst::SlotAddr { dst, slot } => {
+                write!(f, "{} = addr {}", dst, slot)
+            }
+            Inst::FuncAddr { dst, name } => {
+                write!(f, "{} = func @{}", dst, name)
+            }
+            Inst::Load { dst, addr } => {
+                write!(f, "{} = load {} {}", dst, ty(dst), addr)
+            }
+            Inst::Store { ty, val, addr } => {
+                write!(f, "store {} {}, {}", ty, val, addr)
+            }
+            Inst::Call { dst, callee, sig, args } => {
+                if let Some(dst) = dst {
+                    write!(f, "{} = ", dst)?;
+                }
+                write!(f, "call {} ", sig)?;
+                match callee {
+                    Callee::Direct(name) => write!(f, "@{}", name)?,
+                    Callee::Indirect(v) => write!(f, "{}", v)?,
+                }
+                write!(f, "(")?;
+                for (i, (ty, v)) in args.iter().enumerate() {
+                    write!(f, "{}{} {}", if i > 0 { ", " } else { "" }, ty, v)?;
+                }
+                write!(f, ")")
+            }
+            Inst::VaStart { ap } => write!(f, "vastart {}", ap),
+            Inst::VaArg { dst, ap } => {
+                write!(f, "{} = vaarg {} {}", dst, ty(dst), ap)
+            }
+            Inst::VaEnd { ap } => write!(f, "vaend {}", ap),
+            Inst::Phi { dst, args } => {
+                write!(f, "{} = phi {}", dst, ty(dst))?;
+                for (i, (b, v)) in args.iter().enumerate() {
+                    write!(
+                        f,
+                        "{} [{}, {}]",
+                        if i > 0 { "," } else { "" },
+                        v,
+                        b
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Ret(None) => write!(f, "ret"),
+            Term::Ret(Some((ty, v))) => write!(f, "ret {} {}", ty, v),
+            Term::Jmp(b) => write!(f, "jmp {}", b),
+            Term::Br { ty, cond, then, else_ } => {
+                write!(f, "br {} {}, {}, {}", ty, cond, then, else_)
+            }
+            Term::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
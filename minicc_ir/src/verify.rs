@@ -0,0 +1,377 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::*;
+use crate::cfg::{Cfg, DomTree};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    pub func: Symbol,
+    pub msg: String,
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}: {}", self.func, self.msg)
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Checks that blocks are terminated and branch to existing blocks, that each
+/// register is defined once, by a definition dominating its uses, and that
+/// operands have the types instructions expect.
+pub fn verify(module: &Module) -> Result<(), Vec<VerifyError>> {
+    let mut errs = Vec::new();
+    for (i, func) in module.funcs.iter().enumerate() {
+        if module.funcs[..i].iter().any(|f| f.name == func.name) {
+            errs.push(VerifyError {
+                func: func.name,
+                msg: "function is defined twice".to_string(),
+            });
+        }
+        if let Err(e) = verify_func(func) {
+            errs.extend(e);
+        }
+    }
+
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(errs)
+    }
+}
+
+pub(crate) fn verify_func(func: &Function) -> Result<(), Vec<VerifyError>> {
+    let mut errs = Vec::new();
+    if !func.is_decl() {
+        Verifier { func, defs: vec![None; func.regs.len()], errs: &mut errs }
+            .func();
+    }
+    if errs.is_empty() {
+        Ok(())
+    } else {
+        Err(errs)
+    }
+}
+
+/// Where a register is defined.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Def {
+    Param,
+    /// Instruction of a block.
+    Inst(BlockId, usize),
+}
+
+struct Verifier<'a> {
+    func: &'a Function,
+    defs: Vec<Option<Def>>,
+    errs: &'a mut Vec<VerifyError>,
+}
+
+impl Verifier<'_> {
+    fn func(&mut self) {
+        let func = self.func;
+        if func.params.len() != func.sig.params.len() {
+            self.err(format!(
+                "function has {} parameters but its signature {}",
+                func.params.len(),
+                func.sig.params.len()
+            ));
+        }
+        for p in &func.params {
+            self.def(*p, Def::Param);
+        }
+        for id in func.block_ids() {
+            for (i, inst) in func.block(id).insts.iter().enumerate() {
+                if let Some(r) = inst.def() {
+                    self.def(r, Def::Inst(id, i));
+                }
+            }
+        }
+        // The checks below index registers and blocks.
+        if !self.errs.is_empty() || !self.structure() {
+            return;
+        }
+
+        for (p, ty) in func.params.iter().zip(&func.sig.params) {
+            self.check_ty(Value::Reg(*p), *ty, "parameter");
+        }
+        let cfg = Cfg::new(func);
+        let dom = DomTree::new(&cfg);
+        if !cfg.preds(BlockId(0)).is_empty() {
+            self.err("bb0: the entry block has predecessors".to_string());
+        }
+
+        for id in func.block_ids() {
+            let block = func.block(id);
+            let mut in_phis = true;
+            for (i, inst) in block.insts.iter().enumerate() {
+                match inst {
+                    Inst::Phi { args, .. } => {
+                        if !in_phis {
+                            self.err(format!(
+                                "{}: phi after other instructions",
+                                id
+                            ));
+                        }
+                        if dom.is_reachable(id) {
+                            self.phi(id, args, &cfg, &dom);
+                        }
+                    }
+                    _ => {
+                        in_phis = false;
+                        for v in inst.uses() {
+                            self.use_(v, id, i, &dom);
+                        }
+                    }
+                }
+                self.inst(inst, id);
+            }
+
+            let term = block.term.as_ref().unwrap();
+            for v in term.uses() {
+                self.use_(v, id, block.insts.len(), &dom);
+            }
+            match term {
+                Term::Ret(ret) => match (ret, func.sig.ret) {
+                    (None, None) => {}
+                    (Some((ty, v)), Some(ret)) => {
+                        if *ty != ret {
+                            self.err(format!(
+                                "{}: returns {} from a function returning {}",
+                                id, ty, ret
+                            ));
+                        }
+                        self.check_ty(*v, *ty, "return value");
+                    }
+                    (None, Some(_)) => {
+                        self.err(format!("{}: missing return value", id))
+                    }
+                    (Some(_), None) => self.err(format!(
+                        "{}: returns a value from a void function",
+                        id
+                    )),
+                },
+                Term::Br { ty, cond, .. } => {
+                    self.check_ty(*cond, *ty, "condition")
+                }
+                Term::Jmp(_) | Term::Unreachable => {}
+            }
+        }
+    }
+
+    /// Checks that blocks are terminated and branch to existing blocks, and
+    /// that phis name existing blocks.
+    fn structure(&mut self) -> bool {
+        let func = self.func;
+        let n = func.blocks.len();
+        let errs = self.errs.len();
+        for id in func.block_ids() {
+            let block = func.block(id);
+            match &block.term {
+                Some(term) => {
+                    for succ in term.succs() {
+                        if succ.0 as usize >= n {
+                            self.err(format!(
+                                "{}: branch to undefined block {}",
+                                id, succ
+                            ));
+                        }
+                    }
+                }
+                None => self.err(format!("{} is not terminated", id)),
+            }
+            for inst in &block.insts {
+                if let Inst::Phi { args, .. } = inst {
+                    for (b, _) in args {
+                        if b.0 as usize >= n {
+                            self.err(format!(
+                                "{}: phi of undefined block {}",
+                                id, b
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        self.errs.len() == errs
+    }
+
+    /// A phi has one value for each predecessor, available at its end.
+    fn phi(
+        &mut self,
+        id: BlockId,
+        args: &[(BlockId, Value)],
+        cfg: &Cfg,
+        dom: &DomTree,
+    ) {
+        let preds = cfg.preds(id);
+        for (i, (b, v)) in args.iter().enumerate() {
+            if !preds.contains(b) {
+                self.err(format!(
+                    "{}: phi of {}, which is not a predecessor",
+                    id, b
+                ));
+            } else if args[..i].iter().any(|(p, _)| p == b) {
+                self.err(format!("{}: phi has two values for {}", id, b));
+            }
+            let end = self.func.block(*b).insts.len();
+            self.use_(*v, *b, end, dom);
+        }
+        for p in preds {
+            if !args.iter().any(|(b, _)| b == p) {
+                self.err(format!("{}: phi has no value for {}", id, p));
+            }
+        }
+    }
+
+    fn inst(&mut self, inst: &Inst, block: BlockId) {
+        let func = self.func;
+        let ctx = |what: &str| format!("{}: {}", block, what);
+        match inst {
+            Inst::Copy { dst, val } => {
+                self.check_ty(*val, func.reg_ty(*dst), &ctx("copy"))
+            }
+            Inst::Bin { dst, lhs, rhs, .. } => {
+                let ty = func.reg_ty(*dst);
+                if ty == Ty::Ptr {
+                    self.err(ctx("arithmetic on ptr"));
+                }
+                self.check_ty(*lhs, ty, &ctx("lhs"));
+                self.check_ty(*rhs, ty, &ctx("rhs"));
+            }
+            Inst::Cmp { ty, dst, lhs, rhs, .. } => {
+                self.check_ty(Value::Reg(*dst), Ty::I32, &ctx("comparison"));
+                self.check_ty(*lhs, *ty, &ctx("lhs"));
+                self.check_ty(*rhs, *ty, &ctx("rhs"));
+            }
+            Inst::Conv { op, dst, from, val } => {
+                self.check_ty(*val, *from, &ctx(op.name()));
+                let (f, t) = (from.bits(), func.reg_ty(*dst).bits());
+                let ok = match op {
+                    ConvOp::SExt | ConvOp::ZExt => {
+                        matches!((f, t), (Some(f), Some(t)) if f < t)
+                    }
+                    ConvOp::Trunc => {
+                        matches!((f, t), (Some(f), Some(t)) if f > t)
+                    }
+                    ConvOp::PtrToInt => f.is_none() && t.is_some(),
+                    ConvOp::IntToPtr => f.is_some() && t.is_none(),
+                };
+                if !ok {
+                    self.err(ctx(&format!(
+                        "invalid {} from {} to {}",
+                        op.name(),
+                        from,
+                        func.reg_ty(*dst)
+                    )));
+                }
+            }
+            Inst::SlotAddr { dst, slot } => {
+                if slot.0 as usize >= func.slots.len() {
+                    self.err(ctx(&format!("undefined slot {}", slot)));
+                }
+                self.check_ty(Value::Reg(*dst), Ty::Ptr, &ctx("addr"));
+            }
+            Inst::FuncAddr { dst, .. } => {
+                self.check_ty(Value::Reg(*dst), Ty::Ptr, &ctx("func"))
+            }
+            Inst::Load { addr, .. } => {
+                self.check_ty(*addr, Ty::Ptr, &ctx("load address"))
+            }
+            Inst::Store { ty, val, addr } => {
+                self.check_ty(*val, *ty, &ctx("stored value"));
+                self.check_ty(*addr, Ty::Ptr, &ctx("store address"));
+            }
+            Inst::Call { dst, callee, sig, args } => {
+                if let Callee::Indirect(v) = callee {
+                    self.check_ty(*v, Ty::Ptr, &ctx("callee"));
+                }
+                let n = sig.params.len();
+                if args.len() < n || args.len() > n && !sig.variadic {
+                    self.err(ctx(&format!(
+                        "call with {} arguments to a function taking {}{}",
+                        args.len(),
+                        if sig.variadic { "at least " } else { "" },
+                        n
+                    )));
+                }
+                for (i, (ty, v)) in args.iter().enumerate() {
+                    if sig.params.get(i).is_some_and(|p| p != ty) {
+                        self.err(ctx(&format!(
+                            "argument {} is {} but the parameter is {}",
+                            i, ty, sig.params[i]
+                        )));
+                    }
+                    self.check_ty(*v, *ty, &ctx("argument"));
+                }
+                match (dst, sig.ret) {
+                    (Some(r), Some(ty)) => {
+                        self.check_ty(Value::Reg(*r), ty, &ctx("call result"))
+                    }
+                    (Some(_), None) => {
+                        self.err(ctx("void call defines a register"))
+                    }
+                    (None, _) => {}
+                }
+            }
+            Inst::VaStart { ap }
+            | Inst::VaArg { ap, .. }
+            | Inst::VaEnd { ap } => {
+                if matches!(inst, Inst::VaStart { .. }) && !func.sig.variadic {
+                    self.err(ctx("vastart in a function that is not variadic"));
+                }
+                self.check_ty(*ap, Ty::Ptr, &ctx("va_list address"));
+            }
+            Inst::Phi { dst, args } => {
+                for (_, v) in args {
+                    self.check_ty(*v, func.reg_ty(*dst), &ctx("phi value"));
+                }
+            }
+        }
+    }
+
+    fn def(&mut self, r: Reg, def: Def) {
+        match self.defs.get_mut(r.0 as usize) {
+            Some(Some(_)) => self.err(format!("{} is defined twice", r)),
+            Some(d) => *d = Some(def),
+            None => self.err(format!("{} has no type", r)),
+        }
+    }
+
+    /// Checks that a register read before instruction `i` of `block` is
+    /// defined there. Unreachable blocks only need it defined somewhere.
+    fn use_(&mut self, v: Value, block: BlockId, i: usize, dom: &DomTree) {
+        let Value::Reg(r) = v else {
+            return;
+        };
+        let ok = match self.defs.get(r.0 as usize).copied().flatten() {
+            None => false,
+            _ if !dom.is_reachable(block) => true,
+            Some(Def::Param) => true,
+            Some(Def::Inst(b, j)) if b == block => j < i,
+            Some(Def::Inst(b, _)) => dom.dominates(b, block),
+        };
+        if !ok {
+            self.err(format!("{}: {} is used before it is defined", block, r));
+        }
+    }
+
+    /// Checks that a register operand has type `ty`. Immediates fit any type.
+    fn check_ty(&mut self, v: Value, ty: Ty, what: &str) {
+        if let Value::Reg(r) = v {
+            if let Some(&actual) = self.func.regs.get(r.0 as usize) {
+                if actual != ty {
+                    self.err(format!(
+                        "{} {} is {}, expected {}",
+                        what, r, actual, ty
+                    ));
+                }
+            }
+        }
+    }
+
+    fn err(&mut self, msg: String) {
+        self.errs.push(VerifyError { func: self.func.name, msg });
+    }
+}
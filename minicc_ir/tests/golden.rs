@@ -0,0 +1,130 @@
+//! Lowers each `golden/*.c` and compares the printed IR with `golden/*.ir`,
+//! and the IR optimised at `-O2` with `golden/*.O2.ir`. `UPDATE_GOLDEN=1`
+//! rewrites the expected output instead.
+
+use std::fs;
+use std::path::Path;
+
+use minicc_ast::DataModel;
+use minicc_ir::{OptLevel, PassManager};
+use minicc_pp::Preprocessor;
+
+fn lower(path: &Path) -> minicc_ir::Module {
+    let out = Preprocessor::new().preprocess_file(path);
+    let nodes = minicc_parser::parse(&out.text, &out.map, DataModel::ILP32);
+    minicc_ir::lower(&nodes, DataModel::ILP32)
+}
+
+/// The printed form reads back to the same module and verifies.
+fn check(path: &Path, module: &minicc_ir::Module) -> String {
+    let text = module.to_string();
+    if let Err(errs) = minicc_ir::verify(module) {
+        let errs: Vec<_> = errs.iter().map(|e| e.to_string()).collect();
+        panic!("{}: {}\n{}", path.display(), errs.join("\n"), text);
+    }
+    let parsed = minicc_ir::parse(&text)
+        .unwrap_or_else(|e| panic!("{}:{}\n{}", path.display(), e, text));
+    assert_eq!(&parsed, module, "{}: round trip", path.display());
+    assert_eq!(parsed.to_string(), text);
+    text
+}
+
+fn sources(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failed = Vec::new();
+
+    for path in sources(&dir) {
+        let mut module = lower(&path);
+        let o0 = check(&path, &module);
+        PassManager::for_level(OptLevel::O2).run(&mut module);
+        let o2 = check(&path, &module);
+
+        for (ext, text) in [("ir", o0), ("O2.ir", o2)] {
+            let expected = path.with_extension(ext);
+            if update {
+                fs::write(&expected, &text).unwrap();
+            } else if fs::read_to_string(&expected).ok().as_deref()
+                != Some(&*text)
+            {
+                eprintln!("{} differs:\n{}", expected.display(), text);
+                failed.push(expected);
+            }
+        }
+    }
+    assert!(failed.is_empty(), "mismatched golden files: {:?}", failed);
+}
+
+/// Every program of the end-to-end tests lowers to valid IR, which stays
+/// valid at each optimisation level.
+#[test]
+fn lower_tests() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test");
+    // Negative tests, which have no `.expect` file, do not compile.
+    let programs = sources(&dir).into_iter();
+    for path in programs.filter(|p| p.with_extension("expect").exists()) {
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            let mut module = lower(&path);
+            PassManager::for_level(level).run(&mut module);
+            check(&path, &module);
+        }
+    }
+}
+
+#[test]
+fn parse_errors() {
+    let err = |src| minicc_ir::parse(src).unwrap_err().to_string();
+    assert_eq!(
+        err("fn i32 @f() {\nbb0:\n    ret i32 %1\n}\n"),
+        "3: undefined register `%1`"
+    );
+    assert_eq!(err("fn i32 @f() {\nbb1:\n"), "2: expected `bb0`");
+    assert_eq!(
+        err("fn i32 @f() {\nbb0:\n    %0 = frob i32 1\n"),
+        "3: unknown instruction `frob`"
+    );
+}
+
+#[test]
+fn verify_errors() {
+    let errs = |src| -> Vec<String> {
+        let module = minicc_ir::parse(src).unwrap();
+        let errs = minicc_ir::verify(&module).unwrap_err();
+        errs.iter().map(|e| e.to_string()).collect()
+    };
+    assert_eq!(
+        errs("fn i32 @f(ptr %0) {\nbb0:\n    jmp bb1\n}\n"),
+        ["@f: bb0: branch to undefined block bb1"]
+    );
+    assert_eq!(
+        errs(
+            "fn i32 @f(ptr %0, i32 %1) {
+bb0:
+    br i32 %1, bb1, bb2
+bb1:
+    %2 = add i32 %0, 1
+    jmp bb2
+bb2:
+    %3 = phi i32 [%2, bb1]
+    ret i32 %2
+}
+"
+        ),
+        [
+            "@f: bb1: lhs %0 is ptr, expected i32",
+            "@f: bb2: phi has no value for bb0",
+            "@f: bb2: %2 is used before it is defined",
+        ]
+    );
+}
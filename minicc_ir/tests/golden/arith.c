@@ -0,0 +1,6 @@
+int f(int a, char c, unsigned u)
+{
+    long long l;
+    l = a;
+    return -a * c + u / 3 % 2 - (int)l;
+}
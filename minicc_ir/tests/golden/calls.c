@@ -0,0 +1,14 @@
+void dbg(int v);
+
+int add(int a, int b)
+{
+    return a + b;
+}
+
+int main()
+{
+    int (*fp)(int, int);
+    fp = add;
+    dbg(fp(1, 2));
+    dbg(twice(add(3, 4)));
+}
@@ -0,0 +1,18 @@
+int max(int a, int b)
+{
+    return a > b ? a : b;
+}
+
+int count(int n)
+{
+    int i;
+    int s;
+    s = 0;
+    for (i = 0; i < n; i = i + 1) {
+        if (i == 3)
+            s = s + 10;
+        else if (!i)
+            return 0;
+    }
+    return s;
+}
@@ -0,0 +1,31 @@
+int g(int x);
+
+int fold(int a)
+{
+    int k;
+    k = 2 * 3 + 4;
+    if (k > 5)
+        a = a + k;
+    else
+        a = a * 100;
+    return a - 0;
+}
+
+int cse(int a, int b)
+{
+    int x;
+    int y;
+    x = a * b + 1;
+    y = b * a + 1;
+    if (a)
+        return g(x) + g(y);
+    return x == y;
+}
+
+int unused(int a)
+{
+    int dead;
+    dead = a / 3;
+    for (;;)
+        return a;
+}
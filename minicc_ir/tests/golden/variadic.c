@@ -0,0 +1,9 @@
+int sum(int n, ...)
+{
+    va_list ap;
+    int s;
+    va_start(ap, n);
+    s = va_arg(ap, int);
+    va_end(ap);
+    return s;
+}
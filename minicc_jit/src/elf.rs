@@ -0,0 +1,154 @@
+//! Reading the ELF64 relocatable objects `minicc_as` writes for x86-64.
+
+use crate::Error;
+
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+pub const SHF_WRITE: u64 = 0x1;
+pub const SHF_ALLOC: u64 = 0x2;
+pub const SHF_EXECINSTR: u64 = 0x4;
+const SHN_UNDEF: u16 = 0;
+
+/// The parts of an object the loader needs.
+#[derive(Debug, Default)]
+pub struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Sym>,
+}
+
+#[derive(Debug)]
+pub struct Section {
+    /// `SHF_*` flags.
+    pub flags: u64,
+    pub align: u64,
+    /// Contents, zeros for `SHT_NOBITS`.
+    pub data: Vec<u8>,
+    pub relocs: Vec<Reloc>,
+}
+
+#[derive(Debug)]
+pub struct Sym {
+    pub name: String,
+    /// Index into [`Object::sections`] and offset, `None` if undefined.
+    pub def: Option<(usize, u64)>,
+}
+
+#[derive(Debug)]
+pub struct Reloc {
+    pub offset: u64,
+    /// Index into [`Object::symbols`].
+    pub sym: usize,
+    /// `R_X86_64_*` type.
+    pub kind: u32,
+    pub addend: i64,
+}
+
+/// Fields of the object, each read as a little endian number.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn get(&self, at: u64, n: usize) -> Result<u64, Error> {
+        match self.bytes(at, n as u64).ok() {
+            Some(b) => Ok(b.iter().rev().fold(0, |v, &b| v << 8 | b as u64)),
+            None => Err(invalid()),
+        }
+    }
+
+    fn bytes(&self, at: u64, n: u64) -> Result<&[u8], Error> {
+        let rest = self.0.get(at as usize..).ok_or_else(invalid)?;
+        rest.get(..n as usize).ok_or_else(invalid)
+    }
+
+    /// The NUL terminated string at `at`.
+    fn str(&self, at: u64) -> Result<String, Error> {
+        let rest = self.0.get(at as usize..).ok_or_else(invalid)?;
+        let len = rest.iter().position(|&b| b == 0).ok_or_else(invalid)?;
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+}
+
+fn invalid() -> Error {
+    Error::Object("truncated or malformed".to_string())
+}
+
+/// Section header fields, by the offsets of `Elf64_Shdr`.
+struct Header {
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+}
+
+pub fn read(bytes: &[u8]) -> Result<Object, Error> {
+    let r = Reader(bytes);
+    if r.bytes(0, 5)? != b"\x7fELF\x02" || r.get(18, 2)? != 62 {
+        return Err(Error::Object("not an x86-64 ELF64 object".to_string()));
+    }
+    if r.get(16, 2)? != 1 {
+        return Err(Error::Object("not relocatable".to_string()));
+    }
+    let (shoff, shnum) = (r.get(40, 8)?, r.get(60, 2)?);
+    let mut headers = Vec::new();
+    for i in 0..shnum {
+        let at = shoff + i * 64;
+        headers.push(Header {
+            kind: r.get(at + 4, 4)? as u32,
+            flags: r.get(at + 8, 8)?,
+            offset: r.get(at + 24, 8)?,
+            size: r.get(at + 32, 8)?,
+            link: r.get(at + 40, 4)? as u32,
+            info: r.get(at + 44, 4)? as u32,
+            align: r.get(at + 48, 8)?,
+        });
+    }
+
+    // Sections keep their ELF indices, the null one included, so that
+    // symbols and relocation sections refer to them as they are.
+    let mut obj = Object::default();
+    for h in &headers {
+        let data = match h.kind {
+            SHT_NOBITS => vec![0; h.size as usize],
+            _ if h.flags & SHF_ALLOC != 0 => {
+                r.bytes(h.offset, h.size)?.to_vec()
+            }
+            _ => Vec::new(),
+        };
+        obj.sections.push(Section {
+            flags: h.flags,
+            align: h.align.max(1),
+            data,
+            relocs: Vec::new(),
+        });
+    }
+
+    for h in headers.iter().filter(|h| h.kind == SHT_SYMTAB) {
+        let strtab = headers.get(h.link as usize).ok_or_else(invalid)?;
+        for at in (h.offset..h.offset + h.size).step_by(24) {
+            let shndx = r.get(at + 6, 2)? as u16;
+            let def = match shndx {
+                SHN_UNDEF => None,
+                _ => Some((shndx as usize, r.get(at + 8, 8)?)),
+            };
+            obj.symbols
+                .push(Sym { name: r.str(strtab.offset + r.get(at, 4)?)?, def });
+        }
+    }
+
+    for h in headers.iter().filter(|h| h.kind == SHT_RELA) {
+        let sec = obj.sections.get_mut(h.info as usize).ok_or_else(invalid)?;
+        for at in (h.offset..h.offset + h.size).step_by(24) {
+            let info = r.get(at + 8, 8)?;
+            sec.relocs.push(Reloc {
+                offset: r.get(at, 8)?,
+                sym: (info >> 32) as usize,
+                kind: info as u32,
+                addend: r.get(at + 16, 8)? as i64,
+            });
+        }
+    }
+    Ok(obj)
+}
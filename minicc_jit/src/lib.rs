@@ -0,0 +1,92 @@
+//! Running programs compiled for `x86_64-linux` in the process itself,
+//! without writing an executable: the assembly `minicc_gen` writes is
+//! assembled by `minicc_as`, loaded into memory mapped executable and
+//! relocated there, and `main` called.
+//!
+//! `dbg` prints its argument and a newline to standard error, flushing
+//! standard output first so that the two interleave as they would without
+//! buffering. Other undefined symbols are looked up in the process with
+//! `dlsym`, so that programs can call the C library. A program calling
+//! `exit` ends the process, not just [`run`].
+//!
+//! This needs an x86-64 Linux host; elsewhere [`run`] fails with
+//! [`Error::Unsupported`].
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod elf;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod load;
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use minicc_as::AsmError;
+
+/// The `minicc_gen` target of the code that can be run.
+pub const TARGET: &str = "x86_64-linux";
+
+#[derive(Debug)]
+pub enum Error {
+    /// The host cannot run code for [`TARGET`].
+    Unsupported,
+    Asm(AsmError),
+    /// The object `minicc_as` wrote cannot be read.
+    Object(String),
+    /// A symbol neither the program nor the process defines.
+    Undefined(String),
+    /// A relocation that cannot be applied.
+    Reloc(String),
+    /// Memory could not be mapped or protected.
+    Map(io::Error),
+    NoMain,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unsupported => {
+                write!(f, "running code needs an x86-64 Linux host")
+            }
+            Error::Asm(e) => write!(f, "cannot assemble line {}", e),
+            Error::Object(msg) => write!(f, "invalid object: {}", msg),
+            Error::Undefined(name) => {
+                write!(f, "undefined reference to `{}`", name)
+            }
+            Error::Reloc(msg) => write!(f, "cannot relocate: {}", msg),
+            Error::Map(e) => write!(f, "cannot map memory: {}", e),
+            Error::NoMain => write!(f, "no `main` function"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Assembles `asm`, written for [`TARGET`], and runs its `main`, returning
+/// the status it returns.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub fn run(asm: &str) -> Result<i32, Error> {
+    let obj = minicc_as::assemble(asm, minicc_as::Arch::X86_64)
+        .map_err(Error::Asm)?;
+    let image = load::load(&elf::read(&obj)?, |name| match name {
+        "dbg" => Some(dbg as extern "C" fn(i32) as usize as u64),
+        _ => load::dlsym(name),
+    })?;
+    let main = image.symbol("main").ok_or(Error::NoMain)?;
+    let status = unsafe {
+        let main: extern "C" fn() -> i32 = std::mem::transmute(main);
+        main()
+    };
+    unsafe { libc::fflush(std::ptr::null_mut()) };
+    Ok(status)
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+pub fn run(_asm: &str) -> Result<i32, Error> {
+    Err(Error::Unsupported)
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+extern "C" fn dbg(v: i32) {
+    unsafe { libc::fflush(std::ptr::null_mut()) };
+    eprintln!("{}", v);
+}
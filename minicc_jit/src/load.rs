@@ -0,0 +1,217 @@
+//! Loading an object into memory mapped for the process itself, with its
+//! relocations applied, like a static linker and loader in one.
+//!
+//! Each allocated section starts a page of its own so that it can be given
+//! its own protection once relocated: code is executable and not writable.
+//! Symbols taken through the GOT get a slot in a table after the sections,
+//! and calls to undefined symbols go through a stub jumping through theirs,
+//! as those may be further away than a 32 bit displacement reaches. The
+//! mapping is in the low 2 GiB so that absolute 32 bit relocations work.
+
+use std::ffi::{c_void, CString};
+use std::io;
+use std::ptr;
+
+use crate::elf::{Object, SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE};
+use crate::Error;
+
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_GOTPCREL: u32 = 9;
+const R_X86_64_32: u32 = 10;
+const R_X86_64_32S: u32 = 11;
+const R_X86_64_GOTPCRELX: u32 = 41;
+const R_X86_64_REX_GOTPCRELX: u32 = 42;
+
+/// Size of a stub, `jmp *slot(%rip)` padded.
+const STUB: usize = 8;
+
+/// An object loaded into memory, unmapped when dropped.
+pub struct Image {
+    base: *mut u8,
+    len: usize,
+    /// Address of each symbol the object defines.
+    symbols: Vec<(String, u64)>,
+}
+
+impl Image {
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols.iter().find(|(n, _)| n == name).map(|&(_, a)| a)
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base as *mut c_void, self.len) };
+    }
+}
+
+/// Loads `obj`, taking the addresses of its undefined symbols from
+/// `resolve`.
+pub fn load(
+    obj: &Object,
+    resolve: impl Fn(&str) -> Option<u64>,
+) -> Result<Image, Error> {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+
+    let mut offsets = vec![0; obj.sections.len()];
+    let mut len = 0usize;
+    for (i, sec) in obj.sections.iter().enumerate() {
+        if sec.flags & SHF_ALLOC != 0 && !sec.data.is_empty() {
+            offsets[i] = len.next_multiple_of(page.max(sec.align as usize));
+            len = offsets[i] + sec.data.len();
+        }
+    }
+
+    // Symbols needing a GOT slot, and a stub if undefined.
+    let mut slots: Vec<usize> = Vec::new();
+    for r in obj.sections.iter().flat_map(|s| &s.relocs) {
+        let undefined = obj.symbols[r.sym].def.is_none();
+        let got = matches!(
+            r.kind,
+            R_X86_64_GOTPCREL | R_X86_64_GOTPCRELX | R_X86_64_REX_GOTPCRELX
+        );
+        if (got || undefined) && !slots.contains(&r.sym) {
+            slots.push(r.sym);
+        }
+    }
+    let got = len.next_multiple_of(page);
+    let stubs = (got + slots.len() * 8).next_multiple_of(page);
+    let len = (stubs + slots.len() * STUB).next_multiple_of(page).max(page);
+
+    let base = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_32BIT,
+            -1,
+            0,
+        )
+    };
+    if base == libc::MAP_FAILED {
+        return Err(Error::Map(io::Error::last_os_error()));
+    }
+    let mut image = Image { base: base as *mut u8, len, symbols: Vec::new() };
+    let mem = unsafe { std::slice::from_raw_parts_mut(image.base, len) };
+    let addr = |off: usize| base as u64 + off as u64;
+
+    let mut syms = Vec::new();
+    for sym in &obj.symbols {
+        syms.push(match sym.def {
+            Some((sec, val)) => addr(offsets[sec]) + val,
+            None if sym.name.is_empty() => 0,
+            None => resolve(&sym.name)
+                .ok_or_else(|| Error::Undefined(sym.name.clone()))?,
+        });
+    }
+    for (i, sym) in obj.symbols.iter().enumerate() {
+        if sym.def.is_some() && !sym.name.is_empty() {
+            image.symbols.push((sym.name.clone(), syms[i]));
+        }
+    }
+
+    for (n, &sym) in slots.iter().enumerate() {
+        let slot = got + n * 8;
+        mem[slot..slot + 8].copy_from_slice(&syms[sym].to_le_bytes());
+        let stub = stubs + n * STUB;
+        let rel = (slot as i64 - (stub + 6) as i64) as i32;
+        mem[stub..stub + 2].copy_from_slice(&[0xff, 0x25]);
+        mem[stub + 2..stub + 6].copy_from_slice(&rel.to_le_bytes());
+    }
+    let slot = |sym: usize| {
+        let n = slots.iter().position(|&s| s == sym).unwrap();
+        (addr(got + n * 8), addr(stubs + n * STUB))
+    };
+
+    for (i, sec) in obj.sections.iter().enumerate() {
+        if sec.flags & SHF_ALLOC == 0 || sec.data.is_empty() {
+            continue;
+        }
+        let start = offsets[i];
+        mem[start..start + sec.data.len()].copy_from_slice(&sec.data);
+        for r in &sec.relocs {
+            let at = start + r.offset as usize;
+            let p = addr(at) as i64;
+            let s = syms[r.sym] as i64;
+            let undefined = obj.symbols[r.sym].def.is_none();
+            let (val, size) = match r.kind {
+                R_X86_64_64 => (s + r.addend, 8),
+                R_X86_64_PC32 => (s + r.addend - p, 4),
+                R_X86_64_PLT32 if undefined => {
+                    (slot(r.sym).1 as i64 + r.addend - p, 4)
+                }
+                R_X86_64_PLT32 => (s + r.addend - p, 4),
+                R_X86_64_GOTPCREL
+                | R_X86_64_GOTPCRELX
+                | R_X86_64_REX_GOTPCRELX => {
+                    (slot(r.sym).0 as i64 + r.addend - p, 4)
+                }
+                R_X86_64_32 | R_X86_64_32S => (s + r.addend, 4),
+                kind => {
+                    return Err(Error::Reloc(format!(
+                        "unsupported relocation type {}",
+                        kind
+                    )))
+                }
+            };
+            let fits = match r.kind {
+                R_X86_64_64 => true,
+                R_X86_64_32 => u32::try_from(val).is_ok(),
+                _ => i32::try_from(val).is_ok(),
+            };
+            if !fits {
+                return Err(Error::Reloc(format!(
+                    "`{}` is out of range of a 32 bit relocation",
+                    obj.symbols[r.sym].name
+                )));
+            }
+            mem[at..at + size].copy_from_slice(&val.to_le_bytes()[..size]);
+        }
+    }
+
+    for (i, sec) in obj.sections.iter().enumerate() {
+        if sec.flags & SHF_ALLOC == 0 || sec.data.is_empty() {
+            continue;
+        }
+        let prot = match sec.flags {
+            f if f & SHF_EXECINSTR != 0 => libc::PROT_READ | libc::PROT_EXEC,
+            f if f & SHF_WRITE != 0 => libc::PROT_READ | libc::PROT_WRITE,
+            _ => libc::PROT_READ,
+        };
+        protect(&image, offsets[i], sec.data.len(), prot)?;
+    }
+    protect(&image, got, slots.len() * 8, libc::PROT_READ)?;
+    protect(
+        &image,
+        stubs,
+        slots.len() * STUB,
+        libc::PROT_READ | libc::PROT_EXEC,
+    )?;
+    Ok(image)
+}
+
+fn protect(
+    image: &Image,
+    off: usize,
+    len: usize,
+    prot: i32,
+) -> Result<(), Error> {
+    if len == 0 {
+        return Ok(());
+    }
+    let at = unsafe { image.base.add(off) } as *mut c_void;
+    match unsafe { libc::mprotect(at, len, prot) } {
+        0 => Ok(()),
+        _ => Err(Error::Map(io::Error::last_os_error())),
+    }
+}
+
+/// Address of `name` in the process, from the C library or any other
+/// shared object loaded.
+pub fn dlsym(name: &str) -> Option<u64> {
+    let name = CString::new(name).ok()?;
+    let addr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+    (!addr.is_null()).then_some(addr as u64)
+}
@@ -0,0 +1,73 @@
+//! Compiles small programs for `x86_64-linux` and runs them in the test
+//! process, checking the status `main` returns, calls into the C library
+//! and how loading fails.
+
+use minicc_ir::{OptLevel, PassManager};
+use minicc_jit::Error;
+use minicc_pp::Preprocessor;
+
+fn asm(src: &str, opt: OptLevel) -> String {
+    let target = minicc_gen::target(minicc_jit::TARGET).unwrap();
+    let out = Preprocessor::new().preprocess_str("test.c", src);
+    let nodes = minicc_parser::parse(&out.text, &out.map, target.data_model());
+    let mut module = minicc_ir::lower(&nodes, target.data_model());
+    PassManager::for_level(opt).run(&mut module);
+    let mut asm = Vec::new();
+    minicc_gen::gen(&mut asm, &module, target);
+    String::from_utf8(asm).unwrap()
+}
+
+fn run(src: &str) -> Result<i32, Error> {
+    let status = minicc_jit::run(&asm(src, OptLevel::O0));
+    assert_eq!(
+        status.as_ref().ok(),
+        minicc_jit::run(&asm(src, OptLevel::O2)).as_ref().ok()
+    );
+    status
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn status() {
+    let src = "
+int fib(int n) { return n < 2 ? n : fib(n - 1) + fib(n - 2); }
+int twice(int (*f)(int), int x) { return f(f(x)); }
+int main() { return twice(fib, 6) + 1; }
+";
+    assert_eq!(run(src).unwrap(), 22);
+    assert_eq!(run("int main(void) { return -1; }").unwrap(), -1);
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn libc() {
+    let src = "
+int abs(int x);
+long labs(long x);
+int apply(int (*f)(int), int x) { return f(x); }
+int main() { return apply(abs, -40) + labs(-2); }
+";
+    assert_eq!(run(src).unwrap(), 42);
+}
+
+#[test]
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn errors() {
+    let src =
+        "int minicc_nowhere(void);\nint main() { return minicc_nowhere(); }";
+    match run(src) {
+        Err(Error::Undefined(name)) => assert_eq!(name, "minicc_nowhere"),
+        res => panic!("{:?}", res),
+    }
+    assert!(matches!(run("int f() { return 0; }"), Err(Error::NoMain)));
+    assert!(matches!(minicc_jit::run("\tfoo"), Err(Error::Asm(_))));
+}
+
+#[test]
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn unsupported() {
+    assert!(matches!(
+        minicc_jit::run(&asm("int main() {}", OptLevel::O0)),
+        Err(Error::Unsupported)
+    ));
+}
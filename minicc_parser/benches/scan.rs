@@ -0,0 +1,83 @@
+//! Throughput of the scanner and parser on a generated multi-megabyte input.
+//!
+//! ```sh
+//! cargo bench -p minicc_parser
+//! ```
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use minicc_ast::{DataModel, SourceMap};
+use minicc_parser::scanner::{Scanner, TokenKind};
+
+/// Size of the generated input in bytes.
+const SIZE: usize = 8 << 20;
+
+fn main() {
+    let src = generate(SIZE);
+    let map = SourceMap::from_src("<bench>", &src);
+
+    bench("scan", &src, || {
+        Scanner::new(&src, &map, DataModel::ILP32)
+            .map(Result::unwrap)
+            .take_while(|t| t.kind != TokenKind::Eof)
+            .count()
+    });
+    bench("parse", &src, || {
+        minicc_parser::parse(&src, &map, DataModel::ILP32).len()
+    });
+}
+
+fn bench<T>(name: &str, src: &str, mut f: impl FnMut() -> T) {
+    // Warm up, e.g. the symbol interner.
+    black_box(f());
+
+    let mut runs = 0;
+    let start = Instant::now();
+    while runs < 3 || start.elapsed() < Duration::from_secs(2) {
+        black_box(f());
+        runs += 1;
+    }
+    let per_run = start.elapsed() / runs;
+
+    let mib = src.len() as f64 / (1 << 20) as f64;
+    println!(
+        "{:<6} {:>8.2?}/iter {:>8.1} MiB/s ({:.1} MiB, {} runs)",
+        name,
+        per_run,
+        mib / per_run.as_secs_f64(),
+        mib,
+        runs
+    );
+}
+
+/// Functions exercising most tokens, until `size` bytes.
+fn generate(size: usize) -> String {
+    let mut src = String::from("void dbg(int v);\n\n");
+    let mut i = 0;
+    while src.len() < size {
+        src.push_str(&format!(
+            "long long f{i}(int n, unsigned char c, ...)
+{{
+    int i;
+    long long acc;
+    long long (*fp)(int n, unsigned char c, ...);
+    fp = f{i};
+    acc = 0;
+    for (i = 0; i < n; i = i + 1) {{
+        if (i % 3 == 0 != !(c < 1)) {{
+            acc = acc + i * 2 - (c / 7);
+        }} else {{
+            acc = i >= 10 ? acc - 1 : (long long)i;
+        }}
+        dbg(!i + (acc <= 4294967295u) - c);
+    }}
+    return acc;
+}}
+
+"
+        ));
+        i += 1;
+    }
+    src
+}
@@ -1,11 +1,55 @@
 pub mod parser;
 pub mod scanner;
 
-use minicc_ast::Ast;
+use std::fmt::{self, Display, Formatter};
+use std::process::exit;
 
-pub fn parse(src: &str) -> Vec<Ast> {
-    let scanner = scanner::Scanner::new(src);
+use minicc_ast::{Ast, DataModel, SourceMap, Span};
 
-    let mut p = parser::Parser::new(scanner);
-    p.parse()
+use scanner::{Token, TokenKind};
+
+/// The first syntax or type error in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses preprocessed `src` with the type sizes of `model`, reporting errors
+/// at the locations `map` gives.
+pub fn parse(src: &str, map: &SourceMap, model: DataModel) -> Vec<Ast> {
+    try_parse(src, map, model).unwrap_or_else(|e| {
+        eprintln!("{}: {}\n{}", map.span_loc(e.span), e, map.underline(e.span));
+        exit(1);
+    })
+}
+
+/// Like [`parse()`], but returns the first error rather than exiting, e.g.
+/// for a REPL.
+pub fn try_parse(
+    src: &str,
+    map: &SourceMap,
+    model: DataModel,
+) -> Result<Vec<Ast>, Error> {
+    let scanner = scanner::Scanner::new(src, map, model);
+    parser::Parser::new(scanner, map)?.parse()
+}
+
+/// Tokens of preprocessed `src`, without the final `Eof`.
+pub fn tokens(
+    src: &str,
+    map: &SourceMap,
+    model: DataModel,
+) -> Result<Vec<Token>, Error> {
+    scanner::Scanner::new(src, map, model)
+        .take_while(|t| t.as_ref().map_or(true, |t| t.kind != TokenKind::Eof))
+        .collect()
 }
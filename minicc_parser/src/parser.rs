@@ -1,145 +1,434 @@
-use std::iter::Peekable;
-use std::process::exit;
+use std::collections::HashMap;
 
-use ast::Ast;
+use ast::{Ast, Ty};
 use minicc_ast as ast;
-use minicc_ast::AstKind;
+use minicc_ast::{AstKind, SourceMap, Span, Symbol};
 
 use super::scanner::{Scanner, Token, TokenKind};
+use crate::Error;
 
 pub(crate) struct Parser<'a> {
-    scanner: Peekable<Scanner<'a>>,
+    scanner: Scanner<'a>,
+    /// The next token.
+    tok: Token,
+    /// Block scopes of the current function, innermost last.
+    scopes: Vec<HashMap<Symbol, Ty>>,
+    fns: HashMap<Symbol, ast::FnTy>,
+    /// Return type of the current function.
+    ret: Ty,
+    /// Last named parameter of the current function if it is variadic.
+    va_last: Option<Symbol>,
+    /// Span of the last consumed token.
+    prev: Span,
+    map: &'a SourceMap,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(scanner: Scanner<'a>) -> Self {
-        Self { scanner: scanner.peekable() }
+    pub fn new(
+        mut scanner: Scanner<'a>,
+        map: &'a SourceMap,
+    ) -> Result<Self, Error> {
+        let tok = scanner.token()?;
+        Ok(Self {
+            scanner,
+            tok,
+            scopes: Vec::new(),
+            fns: HashMap::new(),
+            ret: Ty::Int,
+            va_last: None,
+            prev: Span::default(),
+            map,
+        })
     }
 
-    pub fn parse(&mut self) -> Vec<Ast> {
+    pub fn parse(&mut self) -> Result<Vec<Ast>, Error> {
         let mut fns = Vec::new();
         while self.peek().kind != TokenKind::Eof {
-            fns.push(self.fn_());
+            fns.push(self.fn_()?);
         }
-        fns
+        Ok(fns)
     }
 
     /// ```ebnf
-    /// primary ::= [0-9]+
-    ///           | [a-zA-Z][a-zA-Z0-9]* "(" arg_list? ")"
-    ///           | "(" eq ")"
+    /// primary ::= [0-9]+ ("u" | "l" | "ll")*
+    ///           | "va_start" "(" assign "," [a-zA-Z_][a-zA-Z0-9_]* ")"
+    ///           | "va_arg" "(" assign "," type_name ")"
+    ///           | "va_end" "(" assign ")"
+    ///           | [a-zA-Z_][a-zA-Z0-9_]*
+    ///           | "(" type_name ")" unary
+    ///           | "(" expr ")"
     /// ```
-    fn primary(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    fn primary(&mut self) -> Result<Ast, Error> {
+        let start = self.peek().span;
 
-        match self.peek().kind.clone() {
-            TokenKind::IntLit(val) => {
-                self.next();
-                Ast { kind: AstKind::IntLit(ast::IntLit { val }), loc }
+        Ok(match self.peek().kind.clone() {
+            TokenKind::IntLit(val, ty) => {
+                self.next()?;
+                Ast {
+                    kind: AstKind::IntLit(ast::IntLit { val }),
+                    span: start,
+                    ty,
+                }
             }
-            TokenKind::Ident(ident) => {
-                self.next();
+            TokenKind::VaStart => {
+                self.next()?;
+                self.skip(&TokenKind::LParen)?;
+                let ap = self.va_list()?;
+                self.skip(&TokenKind::Comma)?;
+                let last = self.ident()?;
+                self.skip(&TokenKind::RParen)?;
+                let span = self.span_from(start);
+
+                match &self.va_last {
+                    None => {
+                        return Err(self.err_at(
+                            span,
+                            "`va_start` used in function with fixed arguments",
+                        ))
+                    }
+                    Some(l) if *l != last => return Err(self.err_at(
+                        span,
+                        "second argument of `va_start` is not the last named \
+                         parameter",
+                    )),
+                    _ => {}
+                }
 
-                if self.peek().kind == TokenKind::LParen {
-                    self.next();
+                Ast {
+                    kind: AstKind::VaStart(ast::VaStart {
+                        ap: Box::new(ap),
+                        last,
+                    }),
+                    span,
+                    ty: Ty::Void,
+                }
+            }
+            TokenKind::VaArg => {
+                self.next()?;
+                self.skip(&TokenKind::LParen)?;
+                let ap = self.va_list()?;
+                self.skip(&TokenKind::Comma)?;
+                let ty = self.type_name()?;
+                self.skip(&TokenKind::RParen)?;
+                let span = self.span_from(start);
+                if !ty.is_scalar() {
+                    return Err(self
+                        .err_at(span, &format!("`va_arg` of type `{}`", ty)));
+                }
 
-                    let args = if self.peek().kind == TokenKind::RParen {
-                        self.next();
-                        Vec::new()
-                    } else {
-                        let args = self.arg_list();
-                        self.skip(&TokenKind::RParen);
-                        args
-                    };
+                Ast {
+                    kind: AstKind::VaArg(ast::VaArg { ap: Box::new(ap) }),
+                    span,
+                    ty,
+                }
+            }
+            TokenKind::VaEnd => {
+                self.next()?;
+                self.skip(&TokenKind::LParen)?;
+                let ap = self.va_list()?;
+                self.skip(&TokenKind::RParen)?;
 
-                    Ast { kind: AstKind::Call(ast::Call { ident, args }), loc }
-                } else {
-                    Ast { kind: AstKind::Ref(ast::Ref { ident }), loc }
+                Ast {
+                    kind: AstKind::VaEnd(ast::VaEnd { ap: Box::new(ap) }),
+                    span: self.span_from(start),
+                    ty: Ty::Void,
                 }
             }
+            TokenKind::Ident(ident) => {
+                self.next()?;
+
+                if let Some(ty) = self.lookup(ident) {
+                    let ty = ty.clone();
+                    return Ok(Ast {
+                        kind: AstKind::Ref(ast::Ref { ident }),
+                        span: start,
+                        ty,
+                    });
+                }
+
+                let f = match self.fns.get(&ident).cloned() {
+                    Some(f) => f,
+                    // Implicitly declared as `int ident()` (C89 3.3.2.2).
+                    None if self.peek().kind == TokenKind::LParen => {
+                        let f = ast::FnTy {
+                            ret: Box::new(Ty::Int),
+                            params: None,
+                            variadic: false,
+                        };
+                        self.fns.insert(ident, f.clone());
+                        f
+                    }
+                    None => {
+                        return Err(self.err_at(
+                            start,
+                            &format!("cannot find value `{}`", ident),
+                        ))
+                    }
+                };
+
+                // Function designators decay to pointers (C99 6.3.2.1p4).
+                let designator = Ast {
+                    kind: AstKind::Ref(ast::Ref { ident }),
+                    span: start,
+                    ty: Ty::Fn(f),
+                };
+                Self::addr(designator, start)
+            }
             TokenKind::LParen => {
-                self.next();
-                let node = self.eq();
-                self.skip(&TokenKind::RParen);
-                node
+                self.next()?;
+                if self.peek().kind.is_ty_spec() {
+                    let ty = self.type_name()?;
+                    self.skip(&TokenKind::RParen)?;
+                    let expr = self.unary()?;
+                    let span = start.to(expr.span);
+                    // A null pointer constant converts to any pointer
+                    // (C99 6.3.2.3p3).
+                    let null = matches!(ty, Ty::Ptr(_)) && Self::is_null(&expr);
+                    if ty != Ty::Void && !null {
+                        self.check_scalar(&expr)?;
+                        if !ty.is_scalar()
+                            || ty.is_integer() != expr.ty.is_integer()
+                                && ty.size(self.scanner.model())
+                                    != expr.ty.size(self.scanner.model())
+                        {
+                            return Err(self.err_at(
+                                span,
+                                &format!(
+                                    "cannot cast `{}` to `{}`",
+                                    expr.ty, ty
+                                ),
+                            ));
+                        }
+                    }
+                    return Ok(Self::cast_explicit(expr, ty, span));
+                }
+
+                let node = self.expr()?;
+                self.skip(&TokenKind::RParen)?;
+                Ast { span: self.span_from(start), ..node }
             }
             ref kind => {
-                self.err(&format!("expected expression, found `{kind}`"));
+                return Err(
+                    self.err(&format!("expected expression, found `{kind}`"))
+                );
             }
+        })
+    }
+
+    /// ```ebnf
+    /// postfix ::= primary ("(" arg_list? ")")*
+    /// ```
+    fn postfix(&mut self) -> Result<Ast, Error> {
+        let mut node = self.primary()?;
+
+        while self.peek().kind == TokenKind::LParen {
+            self.next()?;
+
+            let args = if self.peek().kind == TokenKind::RParen {
+                self.next()?;
+                Vec::new()
+            } else {
+                let args = self.arg_list()?;
+                self.skip(&TokenKind::RParen)?;
+                args
+            };
+
+            let span = node.span.to(self.prev);
+            node = self.call(node, args, span)?;
         }
+
+        Ok(node)
+    }
+
+    fn call(
+        &mut self,
+        callee: Ast,
+        args: Vec<Ast>,
+        span: Span,
+    ) -> Result<Ast, Error> {
+        for arg in &args {
+            self.check_value(arg)?;
+        }
+
+        let f = match &callee.ty {
+            Ty::Ptr(ty) => match &**ty {
+                Ty::Fn(f) => f.clone(),
+                _ => {
+                    return Err(self.err_at(
+                        callee.span,
+                        "called object is not a function",
+                    ))
+                }
+            },
+            _ => {
+                return Err(
+                    self.err_at(callee.span, "called object is not a function")
+                )
+            }
+        };
+
+        let args = match &f.params {
+            Some(params) => {
+                if args.len() < params.len()
+                    || args.len() > params.len() && !f.variadic
+                {
+                    return Err(self.err_at(
+                        span,
+                        &format!(
+                            "function takes {}{} arguments but {} were \
+                             supplied",
+                            if f.variadic { "at least " } else { "" },
+                            params.len(),
+                            args.len()
+                        ),
+                    ));
+                }
+
+                // Variadic arguments undergo the default argument
+                // promotions (C99 6.5.2.2p7).
+                let mut args = args.into_iter();
+                // `params` goes first so that `zip` stops before taking
+                // the first variadic argument.
+                let mut fixed: Vec<_> = params
+                    .iter()
+                    .zip(args.by_ref())
+                    .map(|(ty, arg)| self.convert(arg, ty))
+                    .collect::<Result<_, _>>()?;
+                fixed.extend(args.map(Self::promote));
+                fixed
+            }
+            None => args.into_iter().map(Self::promote).collect(),
+        };
+
+        Ok(Ast {
+            kind: AstKind::Call(ast::Call { callee: Box::new(callee), args }),
+            span,
+            ty: *f.ret,
+        })
     }
 
     /// ```ebnf
     /// arg_list ::= assign ("," assign)*
     /// ```
-    fn arg_list(&mut self) -> Vec<Ast> {
-        let mut args = vec![self.assign()];
+    fn arg_list(&mut self) -> Result<Vec<Ast>, Error> {
+        let mut args = vec![self.assign()?];
         while self.peek().kind == TokenKind::Comma {
-            self.next();
-            args.push(self.assign());
+            self.next()?;
+            args.push(self.assign()?);
         }
-        args
+        Ok(args)
     }
 
     /// ```ebnf
-    /// unary ::= ("+" | "-") unary
-    ///         | primary
+    /// unary ::= ("+" | "-" | "!" | "&" | "*") unary
+    ///         | postfix
     /// ```
-    fn unary(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    fn unary(&mut self) -> Result<Ast, Error> {
+        let start = self.peek().span;
 
         let op = match self.peek().kind {
             TokenKind::Plus => {
-                self.next();
-                return self.unary();
+                self.next()?;
+                let expr = self.unary()?;
+                let span = start.to(expr.span);
+                return Ok(Ast { span, ..Self::promote(expr) });
             }
             TokenKind::Minus => {
-                self.next();
+                self.next()?;
                 ast::OpUn::Neg
             }
             TokenKind::Exclaim => {
-                self.next();
+                self.next()?;
                 ast::OpUn::LogNot
             }
+            TokenKind::Amp => {
+                self.next()?;
+                let expr = self.unary()?;
+                let span = start.to(expr.span);
+                // `&f` of a function designator is the decayed pointer.
+                if Self::is_fn_designator(&expr) {
+                    return Ok(Ast { span, ..expr });
+                }
+                if !matches!(expr.kind, AstKind::Ref(_))
+                    && !Self::is_deref(&expr)
+                {
+                    return Err(self.err_at(
+                        expr.span,
+                        "cannot take the address of an rvalue",
+                    ));
+                }
+                return Ok(Self::addr(expr, span));
+            }
+            TokenKind::Asterisk => {
+                self.next()?;
+                ast::OpUn::Deref
+            }
 
-            _ => return self.primary(),
+            _ => return self.postfix(),
         };
 
-        Ast {
-            kind: AstKind::UnOp(ast::UnOp { op, expr: Box::new(self.unary()) }),
-            loc,
-        }
+        let expr = self.unary()?;
+        let span = start.to(expr.span);
+        let (expr, ty) = match op {
+            ast::OpUn::Neg => {
+                self.check_integer(&expr)?;
+                let expr = Self::promote(expr);
+                let ty = expr.ty.clone();
+                (expr, ty)
+            }
+            ast::OpUn::LogNot => {
+                self.check_scalar(&expr)?;
+                (expr, Ty::Int)
+            }
+            ast::OpUn::Deref => match &expr.ty {
+                // `*fp` is a function designator, which decays back to `fp`.
+                ty if ty.is_fn_ptr() => return Ok(Ast { span, ..expr }),
+                Ty::Ptr(ty) if **ty == Ty::Void => {
+                    return Err(
+                        self.err_at(span, "dereferencing `void *` pointer")
+                    )
+                }
+                Ty::Ptr(ty) => {
+                    let ty = (**ty).clone();
+                    (expr, ty)
+                }
+                ty => {
+                    return Err(self.err_at(
+                        expr.span,
+                        &format!("cannot dereference a value of type `{}`", ty),
+                    ))
+                }
+            },
+            ast::OpUn::Addr => unreachable!(),
+        };
+
+        Ok(Ast {
+            kind: AstKind::UnOp(ast::UnOp { op, expr: Box::new(expr) }),
+            span,
+            ty,
+        })
     }
 
     /// ```ebnf
     /// mul ::= unary ("*" unary | "/" unary | "%" unary)*
     /// ```
-    fn mul(&mut self) -> Ast {
-        let lhs = self.unary();
+    fn mul(&mut self) -> Result<Ast, Error> {
+        let lhs = self.unary()?;
         self.mul_rhs(lhs)
     }
 
-    fn mul_rhs(&mut self, lhs: Ast) -> Ast {
-        let loc = self.peek().loc;
-
+    fn mul_rhs(&mut self, lhs: Ast) -> Result<Ast, Error> {
         let op = match self.peek().kind {
             TokenKind::Asterisk => ast::OpBin::Mul,
             TokenKind::Slash => ast::OpBin::Div,
             TokenKind::Percent => ast::OpBin::Mod,
-            _ => return lhs,
+            _ => return Ok(lhs),
         };
-        self.next();
+        self.next()?;
 
-        let rhs = self.unary();
+        let rhs = self.unary()?;
 
-        let lhs = Ast {
-            kind: AstKind::BinOp(ast::BinOp {
-                op,
-                lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
-            }),
-            loc,
-        };
+        let lhs = self.bin_op(op, lhs, rhs)?;
 
         self.mul_rhs(lhs)
     }
@@ -147,31 +436,22 @@ impl<'a> Parser<'a> {
     /// ```ebnf
     /// add ::= mul ("+" mul | "-" mul)*
     /// ```
-    fn add(&mut self) -> Ast {
-        let lhs = self.mul();
+    fn add(&mut self) -> Result<Ast, Error> {
+        let lhs = self.mul()?;
         self.add_rhs(lhs)
     }
 
-    fn add_rhs(&mut self, lhs: Ast) -> Ast {
-        let loc = self.peek().loc;
-
+    fn add_rhs(&mut self, lhs: Ast) -> Result<Ast, Error> {
         let op = match self.peek().kind {
             TokenKind::Plus => ast::OpBin::Add,
             TokenKind::Minus => ast::OpBin::Sub,
-            _ => return lhs,
+            _ => return Ok(lhs),
         };
-        self.next();
+        self.next()?;
 
-        let rhs = self.mul();
+        let rhs = self.mul()?;
 
-        let lhs = Ast {
-            kind: AstKind::BinOp(ast::BinOp {
-                op,
-                lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
-            }),
-            loc,
-        };
+        let lhs = self.bin_op(op, lhs, rhs)?;
 
         self.add_rhs(lhs)
     }
@@ -179,34 +459,25 @@ impl<'a> Parser<'a> {
     /// ```ebnf
     /// rel := add ("<" add | ">" add | "<=" add | ">=" add)*
     /// ```
-    fn rel(&mut self) -> Ast {
-        let lhs = self.add();
+    fn rel(&mut self) -> Result<Ast, Error> {
+        let lhs = self.add()?;
 
         self.rel_rhs(lhs)
     }
 
-    fn rel_rhs(&mut self, lhs: Ast) -> Ast {
-        let loc = self.peek().loc;
-
+    fn rel_rhs(&mut self, lhs: Ast) -> Result<Ast, Error> {
         let op = match self.peek().kind {
             TokenKind::Lt => ast::OpBin::Lt,
             TokenKind::Gt => ast::OpBin::Gt,
             TokenKind::LtEq => ast::OpBin::Le,
             TokenKind::GtEq => ast::OpBin::Ge,
-            _ => return lhs,
+            _ => return Ok(lhs),
         };
-        self.next();
+        self.next()?;
 
-        let rhs = self.add();
+        let rhs = self.add()?;
 
-        let lhs = Ast {
-            kind: AstKind::BinOp(ast::BinOp {
-                op,
-                lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
-            }),
-            loc,
-        };
+        let lhs = self.bin_op(op, lhs, rhs)?;
 
         self.rel_rhs(lhs)
     }
@@ -214,301 +485,887 @@ impl<'a> Parser<'a> {
     /// ```ebnf
     /// eq := rel ("==" rel | "!=" rel)*
     /// ```
-    fn eq(&mut self) -> Ast {
-        let lhs = self.rel();
+    fn eq(&mut self) -> Result<Ast, Error> {
+        let lhs = self.rel()?;
 
         self.eq_rhs(lhs)
     }
 
-    fn eq_rhs(&mut self, lhs: Ast) -> Ast {
-        let loc = self.peek().loc;
-
+    fn eq_rhs(&mut self, lhs: Ast) -> Result<Ast, Error> {
         let op = match self.peek().kind {
             TokenKind::EqEq => ast::OpBin::Eq,
             TokenKind::ExclaimEq => ast::OpBin::Ne,
-            _ => return lhs,
+            _ => return Ok(lhs),
         };
-        self.next();
+        self.next()?;
 
-        let rhs = self.rel();
+        let rhs = self.rel()?;
 
-        let lhs = Ast {
-            kind: AstKind::BinOp(ast::BinOp {
-                op,
-                lhs: Box::new(lhs),
-                rhs: Box::new(rhs),
-            }),
-            loc,
-        };
+        let lhs = self.bin_op(op, lhs, rhs)?;
 
         self.eq_rhs(lhs)
     }
 
     /// ```ebnf
-    /// assign ::= add "=" assign
+    /// cond ::= eq ("?" expr ":" cond)?
     /// ```
-    fn assign(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    fn cond(&mut self) -> Result<Ast, Error> {
+        let cond = self.eq()?;
+        if self.peek().kind != TokenKind::Question {
+            return Ok(cond);
+        }
+        self.next()?;
+
+        let then = self.expr()?;
+        self.skip(&TokenKind::Colon)?;
+        let else_ = self.cond()?;
+        let span = cond.span.to(else_.span);
+
+        self.check_scalar(&cond)?;
+        let ty = if then.ty == else_.ty
+            || !then.ty.is_integer() && Self::is_null(&else_)
+        {
+            then.ty.clone()
+        } else if !else_.ty.is_integer() && Self::is_null(&then) {
+            else_.ty.clone()
+        } else {
+            self.check_integer(&then)?;
+            self.check_integer(&else_)?;
+            then.ty.common(&else_.ty, self.scanner.model())
+        };
+        let then = Self::cast(then, ty.clone());
+        let else_ = Self::cast(else_, ty.clone());
 
-        let lhs = self.eq();
+        Ok(Ast {
+            kind: AstKind::Cond(ast::Cond {
+                cond: Box::new(cond),
+                then: Box::new(then),
+                else_: Box::new(else_),
+            }),
+            span,
+            ty,
+        })
+    }
+
+    /// ```ebnf
+    /// assign ::= cond ("=" assign)?
+    /// ```
+    fn assign(&mut self) -> Result<Ast, Error> {
+        let lhs = self.cond()?;
         let op = match self.peek().kind {
             TokenKind::Eq => ast::OpBin::Asign,
-            _ => return lhs,
+            _ => return Ok(lhs),
         };
-        self.next();
+        self.next()?;
 
-        let rhs = self.assign();
+        let rhs = self.assign()?;
+        if !matches!(lhs.kind, AstKind::Ref(_)) && !Self::is_deref(&lhs) {
+            return Err(self.err_at(lhs.span, "expression is not assignable"));
+        }
 
-        Ast {
+        let span = lhs.span.to(rhs.span);
+        let ty = lhs.ty.clone();
+        let rhs = self.convert(rhs, &ty)?;
+
+        Ok(Ast {
             kind: AstKind::BinOp(ast::BinOp {
                 op,
                 lhs: Box::new(lhs),
                 rhs: Box::new(rhs),
             }),
-            loc,
+            span,
+            ty,
+        })
+    }
+
+    /// ```ebnf
+    /// expr ::= assign ("," assign)*
+    /// ```
+    fn expr(&mut self) -> Result<Ast, Error> {
+        let lhs = self.assign()?;
+        self.expr_rhs(lhs)
+    }
+
+    fn expr_rhs(&mut self, lhs: Ast) -> Result<Ast, Error> {
+        if self.peek().kind != TokenKind::Comma {
+            return Ok(lhs);
         }
+        self.next()?;
+
+        let rhs = self.assign()?;
+
+        let span = lhs.span.to(rhs.span);
+        let ty = rhs.ty.clone();
+        let lhs = Ast {
+            kind: AstKind::Comma(ast::Comma {
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            }),
+            span,
+            ty,
+        };
+
+        self.expr_rhs(lhs)
+    }
+
+    /// ```ebnf
+    /// decl_spec ::= "va_list"
+    ///             | ("void" | "char" | "short" | "int" | "long" | "signed"
+    ///                | "unsigned")+
+    /// ```
+    fn decl_spec(&mut self) -> Result<Ty, Error> {
+        let start = self.peek().span;
+
+        let kind = self.peek().kind.clone();
+        if !kind.is_ty_spec() {
+            return Err(self.err(&format!("expected type, found `{}`", kind)));
+        }
+
+        let mut specs = Vec::new();
+        while self.peek().kind.is_ty_spec() {
+            specs.push(self.next()?.kind);
+        }
+        let span = self.span_from(start);
+
+        if specs.contains(&TokenKind::VaList) {
+            if specs.len() != 1 {
+                return Err(
+                    self.err_at(span, "invalid combination of type specifiers")
+                );
+            }
+            return Ok(Ty::VaList);
+        }
+
+        let count = |k: TokenKind| specs.iter().filter(|s| **s == k).count();
+        let void = count(TokenKind::Void);
+        let signed = count(TokenKind::Signed);
+        let unsigned = count(TokenKind::Unsigned);
+        let base = (
+            count(TokenKind::Char),
+            count(TokenKind::Short),
+            count(TokenKind::Int),
+            count(TokenKind::Long),
+        );
+
+        let ty = match (void, signed, unsigned, base) {
+            (1, 0, 0, (0, 0, 0, 0)) => Ty::Void,
+            (0, 0, 0, (1, 0, 0, 0)) => Ty::Char,
+            (0, 1, 0, (1, 0, 0, 0)) => Ty::SChar,
+            (0, 0, 1, (1, 0, 0, 0)) => Ty::UChar,
+            (0, _, 0, (0, 1, 0 | 1, 0)) => Ty::Short,
+            (0, 0, 1, (0, 1, 0 | 1, 0)) => Ty::UShort,
+            (0, _, 0, (0, 0, 0 | 1, 0)) => Ty::Int,
+            (0, 0, 1, (0, 0, 0 | 1, 0)) => Ty::UInt,
+            (0, _, 0, (0, 0, 0 | 1, 1)) => Ty::Long,
+            (0, 0, 1, (0, 0, 0 | 1, 1)) => Ty::ULong,
+            (0, _, 0, (0, 0, 0 | 1, 2)) => Ty::LongLong,
+            (0, 0, 1, (0, 0, 0 | 1, 2)) => Ty::ULongLong,
+            _ => {
+                return Err(
+                    self.err_at(span, "invalid combination of type specifiers")
+                )
+            }
+        };
+
+        if signed > 1 || unsigned > 1 {
+            return Err(self.err_at(span, "duplicate type specifier"));
+        }
+
+        Ok(ty)
     }
 
+    /// Returns the declared identifier, which is omitted in an abstract
+    /// declarator, and its type.
+    ///
     /// ```ebnf
-    /// decl ::= [a-zA-Z][a-zA-Z0-9]*
+    /// declarator ::= "*"* ("(" declarator ")" | [a-zA-Z_][a-zA-Z0-9_]*)?
+    ///                ("(" param_ty_list? ")")?
     /// ```
-    fn decl(&mut self) -> Ast {
-        let loc = self.peek().loc;
-        if let TokenKind::Ident(i) = self.peek().kind.clone() {
-            self.next();
-            Ast { kind: AstKind::VarDecl(ast::VarDecl { ident: i }), loc }
+    fn declarator(
+        &mut self,
+        mut ty: Ty,
+    ) -> Result<(Option<Symbol>, Ty), Error> {
+        while self.peek().kind == TokenKind::Asterisk {
+            self.next()?;
+            ty = Ty::Ptr(Box::new(ty));
+        }
+
+        let ident = match self.peek().kind.clone() {
+            TokenKind::Ident(ident) => {
+                self.next()?;
+                Some(ident)
+            }
+            TokenKind::LParen => {
+                self.next()?;
+                if self.peek().kind == TokenKind::Asterisk {
+                    // The nested declarator applies to the type built by the
+                    // suffix, e.g. `int (*fp)(int)`.
+                    let (ident, inner) = self.declarator(Ty::Void)?;
+                    self.skip(&TokenKind::RParen)?;
+                    let ty = self.declarator_suffix(ty)?;
+                    return Ok((ident, Self::substitute(inner, ty)));
+                }
+                return Ok((None, self.fn_suffix(ty)?));
+            }
+            _ => None,
+        };
+
+        Ok((ident, self.declarator_suffix(ty)?))
+    }
+
+    fn declarator_suffix(&mut self, ty: Ty) -> Result<Ty, Error> {
+        if self.peek().kind == TokenKind::LParen {
+            self.next()?;
+            self.fn_suffix(ty)
         } else {
-            self.err("expected identifier")
+            Ok(ty)
+        }
+    }
+
+    /// The part of a function declarator after `(`.
+    fn fn_suffix(&mut self, ret: Ty) -> Result<Ty, Error> {
+        let (params, variadic, prototyped) = self.params()?;
+        Ok(Ty::Fn(ast::FnTy {
+            ret: Box::new(ret),
+            params: prototyped
+                .then(|| params.into_iter().map(|p| p.ty).collect()),
+            variadic,
+        }))
+    }
+
+    /// Replaces the innermost type of a nested declarator.
+    fn substitute(inner: Ty, ty: Ty) -> Ty {
+        match inner {
+            Ty::Ptr(t) => Ty::Ptr(Box::new(Self::substitute(*t, ty))),
+            Ty::Fn(f) => Ty::Fn(ast::FnTy {
+                ret: Box::new(Self::substitute(*f.ret, ty)),
+                ..f
+            }),
+            _ => ty,
         }
     }
 
     /// ```ebnf
-    /// param_ty_list ::= param_decl ("," param_decl)*
+    /// type_name ::= decl_spec declarator
     /// ```
-    fn param_ty_list(&mut self) -> Vec<String> {
-        let mut params = vec![self.param_decl()];
-        while self.peek().kind == TokenKind::Comma {
-            self.next();
-            params.push(self.param_decl());
+    fn type_name(&mut self) -> Result<Ty, Error> {
+        let start = self.peek().span;
+        let ty = self.decl_spec()?;
+        match self.declarator(ty)? {
+            (None, ty) => Ok(ty),
+            (Some(_), _) => Err(self.err_at(
+                self.span_from(start),
+                "unexpected identifier in type name",
+            )),
         }
-        params
     }
 
     /// ```ebnf
-    /// param_decl ::= "int" [a-zA-Z][a-zA-Z0-9]*
+    /// decl ::= declarator
     /// ```
-    fn param_decl(&mut self) -> String {
-        self.skip(&TokenKind::Int);
-        if let TokenKind::Ident(ident) = self.peek().kind.clone() {
-            self.next();
-            ident
+    fn decl(&mut self, ty: Ty) -> Result<Ast, Error> {
+        let start = self.peek().span;
+        let (ident, ty) = self.declarator(ty)?;
+        let span = self.span_from(start);
+        let Some(ident) = ident else {
+            return Err(self.err_at(span, "expected identifier"));
+        };
+        match ty {
+            Ty::Void => {
+                return Err(
+                    self.err_at(span, "variable has incomplete type `void`")
+                )
+            }
+            Ty::Fn(_) => {
+                return Err(self.err_at(
+                    span,
+                    "function declarations in a block are not supported",
+                ))
+            }
+            _ => {}
+        }
+
+        self.declare(ident, ty.clone(), span)?;
+        Ok(Ast {
+            kind: AstKind::VarDecl(ast::VarDecl { ident, ty }),
+            span,
+            ty: Ty::Void,
+        })
+    }
+
+    /// The part of a parameter list after `(`. Returns the parameters,
+    /// whether it ends with `...` and whether it is a prototype.
+    fn params(&mut self) -> Result<(Vec<ast::Param>, bool, bool), Error> {
+        if self.peek().kind == TokenKind::RParen {
+            self.next()?;
+            Ok((Vec::new(), false, false))
         } else {
-            self.err("expected identifier");
+            let (params, variadic) = self.param_ty_list()?;
+            self.skip(&TokenKind::RParen)?;
+            Ok((params, variadic, true))
         }
     }
 
+    /// Returns the parameters and whether the list ends with `...`.
+    ///
+    /// ```ebnf
+    /// param_ty_list ::= "void"
+    ///                 | param_decl ("," param_decl)* ("," "...")?
+    /// ```
+    fn param_ty_list(&mut self) -> Result<(Vec<ast::Param>, bool), Error> {
+        let ty = self.decl_spec()?;
+        if ty == Ty::Void && self.peek().kind == TokenKind::RParen {
+            return Ok((Vec::new(), false));
+        }
+
+        let mut params = vec![self.param_decl(ty)?];
+        while self.peek().kind == TokenKind::Comma {
+            self.next()?;
+            if self.peek().kind == TokenKind::Ellipsis {
+                self.next()?;
+                return Ok((params, true));
+            }
+            let ty = self.decl_spec()?;
+            params.push(self.param_decl(ty)?);
+        }
+        Ok((params, false))
+    }
+
+    /// ```ebnf
+    /// param_decl ::= decl_spec declarator
+    /// ```
+    fn param_decl(&mut self, ty: Ty) -> Result<ast::Param, Error> {
+        let start = self.peek().span;
+        let (ident, ty) = match self.declarator(ty)? {
+            (_, Ty::Void) => {
+                return Err(self.err_at(
+                    self.span_from(start),
+                    "parameter has incomplete type `void`",
+                ))
+            }
+            // Parameters of function type are adjusted to pointers
+            // (C99 6.7.5.3p8).
+            (ident, ty @ Ty::Fn(_)) => (ident, Ty::Ptr(Box::new(ty))),
+            param => param,
+        };
+        Ok(ast::Param { ident, ty })
+    }
+
     /// ```ebnf
     /// stmt ::= "{" compound_stmt
-    ///        | "int" decl ";"
-    ///        | "return" assign ";"
+    ///        | decl_spec decl ";"
+    ///        | "return" expr? ";"
     ///        | "if" if_
-    ///        | assign ";"
+    ///        | "for" for_
+    ///        | expr ";"
     /// ```
-    fn stmt(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    fn stmt(&mut self) -> Result<Ast, Error> {
+        let start = self.peek().span;
 
-        match self.peek().kind {
+        Ok(match self.peek().kind {
             TokenKind::LBrace => {
-                self.next();
-                self.compound_stmt()
-            }
-            TokenKind::Int => {
-                self.next();
-                let node = self.decl();
-                self.skip(&TokenKind::Semi);
+                self.next()?;
+                self.scopes.push(HashMap::new());
+                let node = self.compound_stmt()?;
+                self.scopes.pop();
                 node
             }
+            ref k if k.is_ty_spec() => {
+                let ty = self.decl_spec()?;
+                let node = self.decl(ty)?;
+                self.skip(&TokenKind::Semi)?;
+                Ast { span: self.span_from(start), ..node }
+            }
             TokenKind::Return => {
-                self.next();
-                let expr = self.assign();
-                self.skip(&TokenKind::Semi);
+                self.next()?;
+                let expr = if self.peek().kind == TokenKind::Semi {
+                    None
+                } else {
+                    Some(self.expr()?)
+                };
+                self.skip(&TokenKind::Semi)?;
+                let span = self.span_from(start);
+
+                let expr = match expr {
+                    Some(_) if self.ret == Ty::Void => {
+                        return Err(self.err_at(
+                            span,
+                            "void function should not return a value",
+                        ))
+                    }
+                    Some(expr) => {
+                        Some(Box::new(self.convert(expr, &self.ret)?))
+                    }
+                    None if self.ret == Ty::Void => None,
+                    None => {
+                        return Err(self.err_at(
+                            span,
+                            "non-void function should return a value",
+                        ))
+                    }
+                };
+
                 Ast {
-                    kind: AstKind::Return(ast::Return { expr: Box::new(expr) }),
-                    loc,
+                    kind: AstKind::Return(ast::Return { expr }),
+                    span,
+                    ty: Ty::Void,
                 }
             }
             TokenKind::If => {
-                self.next();
-                self.if_()
+                self.next()?;
+                self.if_()?
             }
             TokenKind::For => {
-                self.next();
-                self.for_()
+                self.next()?;
+                self.for_()?
             }
             _ => {
-                let node = self.assign();
-                self.skip(&TokenKind::Semi);
-                node
+                let node = self.expr()?;
+                self.skip(&TokenKind::Semi)?;
+                Ast { span: self.span_from(start), ..node }
             }
-        }
+        })
     }
 
+    /// The span starts at the `{` consumed by the caller.
+    ///
     /// ```ebnf
     /// compound_stmt ::= stmt* "}"
     /// ```
-    fn compound_stmt(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    fn compound_stmt(&mut self) -> Result<Ast, Error> {
+        let start = self.prev;
 
         let mut item = Vec::new();
         loop {
             if self.peek().kind == TokenKind::RBrace {
-                self.next();
+                self.next()?;
                 break;
             }
 
-            let n = self.stmt();
+            let n = self.stmt()?;
             item.push(n);
         }
 
-        Ast {
+        Ok(Ast {
             kind: AstKind::CompoundStmt(ast::CompoundStmt { items: item }),
-            loc,
-        }
+            span: self.span_from(start),
+            ty: Ty::Void,
+        })
     }
 
     /// ```ebnf
-    /// if_ := "(" assign ")" stmt
+    /// if_ := "(" expr ")" stmt ("else" stmt)?
     /// ```
-    fn if_(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    fn if_(&mut self) -> Result<Ast, Error> {
+        let start = self.prev;
 
-        self.skip(&TokenKind::LParen);
-        let cond = self.assign();
-        self.skip(&TokenKind::RParen);
-        let then = self.stmt();
+        self.skip(&TokenKind::LParen)?;
+        let cond = self.expr()?;
+        self.check_scalar(&cond)?;
+        self.skip(&TokenKind::RParen)?;
+        let then = self.stmt()?;
 
         let else_ = if self.peek().kind == TokenKind::Else {
-            self.next();
-            Some(Box::new(self.stmt()))
+            self.next()?;
+            Some(Box::new(self.stmt()?))
         } else {
             None
         };
 
-        Ast {
+        Ok(Ast {
             kind: AstKind::If(ast::If {
                 cond: Box::new(cond),
                 then: Box::new(then),
                 else_,
             }),
-            loc,
-        }
+            span: self.span_from(start),
+            ty: Ty::Void,
+        })
     }
 
     /// ```ebnf
-    /// for_ := "(" assign? ";" assign? ";" assign? ")" stmt
+    /// for_ := "(" expr? ";" expr? ";" expr? ")" stmt
     /// ```
-    fn for_(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    fn for_(&mut self) -> Result<Ast, Error> {
+        let start = self.prev;
 
-        self.skip(&TokenKind::LParen);
+        self.skip(&TokenKind::LParen)?;
         let init = if self.peek().kind == TokenKind::Semi {
-            self.next();
+            self.next()?;
             None
         } else {
-            let init = Some(Box::new(self.assign()));
-            self.skip(&TokenKind::Semi);
+            let init = Some(Box::new(self.expr()?));
+            self.skip(&TokenKind::Semi)?;
             init
         };
 
         let cond = if self.peek().kind == TokenKind::Semi {
-            self.next();
+            self.next()?;
             None
         } else {
-            let cond = Some(Box::new(self.assign()));
-            self.skip(&TokenKind::Semi);
+            let cond = self.expr()?;
+            self.check_scalar(&cond)?;
+            let cond = Some(Box::new(cond));
+            self.skip(&TokenKind::Semi)?;
             cond
         };
 
         let inc = if self.peek().kind == TokenKind::RParen {
-            self.next();
+            self.next()?;
             None
         } else {
-            let inc = Some(Box::new(self.assign()));
-            self.skip(&TokenKind::RParen);
+            let inc = Some(Box::new(self.expr()?));
+            self.skip(&TokenKind::RParen)?;
             inc
         };
 
-        let body = self.stmt();
+        let body = self.stmt()?;
 
-        Ast {
+        Ok(Ast {
             kind: AstKind::For(ast::For {
                 init,
                 cond,
                 inc,
                 body: Box::new(body),
             }),
-            loc,
-        }
+            span: self.span_from(start),
+            ty: Ty::Void,
+        })
     }
 
-    fn fn_(&mut self) -> Ast {
-        let loc = self.peek().loc;
+    /// ```ebnf
+    /// fn_ ::= decl_spec "*"* [a-zA-Z_][a-zA-Z0-9_]* "(" param_ty_list? ")"
+    ///         (";" | "{" compound_stmt)
+    /// ```
+    fn fn_(&mut self) -> Result<Ast, Error> {
+        let start = self.peek().span;
 
-        self.skip(&TokenKind::Int);
+        let mut ret = self.decl_spec()?;
+        while self.peek().kind == TokenKind::Asterisk {
+            self.next()?;
+            ret = Ty::Ptr(Box::new(ret));
+        }
+        let ident = self.ident()?;
 
-        let kind = self.peek().kind.clone();
-        let ident = if let TokenKind::Ident(ident) = kind {
-            self.next();
-            ident
-        } else {
-            self.err(&format!("expected identifier, found `{}`", kind))
-        };
+        self.skip(&TokenKind::LParen)?;
+        let (params, variadic, prototyped) = self.params()?;
+        let decl_span = self.span_from(start);
 
-        self.skip(&TokenKind::LParen);
-        let params = if self.peek().kind == TokenKind::RParen {
-            self.next();
-            Vec::new()
-        } else {
-            let params = self.param_ty_list();
-            self.skip(&TokenKind::RParen);
-            params
+        let f = ast::FnTy {
+            ret: Box::new(ret.clone()),
+            params: prototyped
+                .then(|| params.iter().map(|p| p.ty.clone()).collect()),
+            variadic,
         };
+        match self.fns.get(&ident) {
+            Some(prev)
+                if !Ty::Fn(prev.clone()).is_compatible(&Ty::Fn(f.clone())) =>
+            {
+                return Err(self.err_at(
+                    decl_span,
+                    &format!("conflicting types for `{}`", ident),
+                ))
+            }
+            Some(prev) if prev.params.is_some() => {}
+            _ => {
+                self.fns.insert(ident, f);
+            }
+        }
 
-        self.skip(&TokenKind::LBrace);
-        let body = self.compound_stmt();
+        if self.peek().kind == TokenKind::Semi {
+            self.next()?;
+            return Ok(Ast {
+                kind: AstKind::FnDecl(ast::FnDecl {
+                    ident,
+                    ret,
+                    params,
+                    variadic,
+                    prototyped,
+                    body: None,
+                }),
+                span: self.span_from(start),
+                ty: Ty::Void,
+            });
+        }
 
-        Ast {
+        self.ret = ret.clone();
+        self.va_last =
+            if variadic { params.last().and_then(|p| p.ident) } else { None };
+
+        self.scopes.push(HashMap::new());
+        for p in &params {
+            match &p.ident {
+                Some(ident) => self.declare(*ident, p.ty.clone(), decl_span)?,
+                None => {
+                    return Err(self.err_at(decl_span, "parameter name omitted"))
+                }
+            }
+        }
+        self.skip(&TokenKind::LBrace)?;
+        let body = self.compound_stmt()?;
+        self.scopes.pop();
+
+        // Reaching the `}` of `main` returns 0 (C99 5.1.2.2.3).
+        if ret != Ty::Void && ident != "main" && !Self::returns(&body) {
+            eprintln!(
+                "{}: warning: control reaches end of non-void function `{}`",
+                self.map.span_loc(decl_span),
+                ident
+            );
+        }
+
+        Ok(Ast {
             kind: AstKind::FnDecl(ast::FnDecl {
                 ident,
+                ret,
                 params,
-                body: Box::new(body),
+                variadic,
+                prototyped,
+                body: Some(Box::new(body)),
+            }),
+            span: self.span_from(start),
+            ty: Ty::Void,
+        })
+    }
+
+    /// Whether control never reaches the end of `node`.
+    fn returns(node: &Ast) -> bool {
+        match &node.kind {
+            AstKind::Return(_) => true,
+            AstKind::CompoundStmt(n) => n.items.iter().any(Self::returns),
+            AstKind::If(n) => {
+                Self::returns(&n.then)
+                    && n.else_.as_deref().is_some_and(Self::returns)
+            }
+            AstKind::For(n) => n.cond.is_none(),
+            _ => false,
+        }
+    }
+
+    /// Applies the usual arithmetic conversions to the operands.
+    fn bin_op(&self, op: ast::OpBin, lhs: Ast, rhs: Ast) -> Result<Ast, Error> {
+        let is_eq = matches!(op, ast::OpBin::Eq | ast::OpBin::Ne);
+        if is_eq && (!lhs.ty.is_integer() || !rhs.ty.is_integer()) {
+            return self.ptr_eq(op, lhs, rhs);
+        }
+
+        self.check_integer(&lhs)?;
+        self.check_integer(&rhs)?;
+
+        let span = lhs.span.to(rhs.span);
+        let common = lhs.ty.common(&rhs.ty, self.scanner.model());
+        let ty = match op {
+            ast::OpBin::Lt
+            | ast::OpBin::Gt
+            | ast::OpBin::Le
+            | ast::OpBin::Ge
+            | ast::OpBin::Eq
+            | ast::OpBin::Ne => Ty::Int,
+            _ => common.clone(),
+        };
+
+        Ok(Ast {
+            kind: AstKind::BinOp(ast::BinOp {
+                op,
+                lhs: Box::new(Self::cast(lhs, common.clone())),
+                rhs: Box::new(Self::cast(rhs, common)),
+            }),
+            span,
+            ty,
+        })
+    }
+
+    /// `==` and `!=` on pointers of compatible types, or a pointer and a null
+    /// pointer constant.
+    fn ptr_eq(&self, op: ast::OpBin, lhs: Ast, rhs: Ast) -> Result<Ast, Error> {
+        let span = lhs.span.to(rhs.span);
+        let ty = if lhs.ty.is_integer() { &rhs.ty } else { &lhs.ty }.clone();
+        let lhs = self.convert(lhs, &ty)?;
+        let rhs = self.convert(rhs, &ty)?;
+
+        Ok(Ast {
+            kind: AstKind::BinOp(ast::BinOp {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
             }),
-            loc,
+            span,
+            ty: Ty::Int,
+        })
+    }
+
+    /// Conversion as if by assignment (C99 6.5.16.1).
+    fn convert(&self, node: Ast, ty: &Ty) -> Result<Ast, Error> {
+        if ty.is_integer() {
+            self.check_integer(&node)?;
+        } else if !(node.ty.is_compatible(ty)
+            || matches!(ty, Ty::Ptr(_)) && Self::is_null(&node))
+        {
+            self.check_value(&node)?;
+            return Err(self.err_at(
+                node.span,
+                &format!("expected `{}`, found `{}`", ty, node.ty),
+            ));
         }
+        Ok(Self::cast(node, ty.clone()))
     }
 
-    fn err(&mut self, msg: &str) -> ! {
-        eprintln!("{pos}: {msg}", pos = self.peek().loc);
-        exit(1);
+    /// Null pointer constant.
+    fn is_null(node: &Ast) -> bool {
+        matches!(node.kind, AstKind::IntLit(ast::IntLit { val: 0 }))
     }
 
-    fn peek(&mut self) -> &Token {
-        self.scanner.peek().unwrap_or(&Token { kind: TokenKind::Eof, loc: 0 })
+    fn is_deref(node: &Ast) -> bool {
+        matches!(&node.kind, AstKind::UnOp(n) if n.op == ast::OpUn::Deref)
     }
 
-    fn next(&mut self) -> Token {
-        self.scanner.next().unwrap_or(Token { kind: TokenKind::Eof, loc: 0 })
+    fn is_fn_designator(node: &Ast) -> bool {
+        matches!(
+            &node.kind,
+            AstKind::UnOp(n) if n.op == ast::OpUn::Addr
+                && matches!(n.expr.ty, Ty::Fn(_))
+        )
     }
 
-    fn skip(&mut self, kind: &TokenKind) {
+    fn addr(node: Ast, span: Span) -> Ast {
+        let ty = Ty::Ptr(Box::new(node.ty.clone()));
+        Ast {
+            kind: AstKind::UnOp(ast::UnOp {
+                op: ast::OpUn::Addr,
+                expr: Box::new(node),
+            }),
+            span,
+            ty,
+        }
+    }
+
+    fn promote(node: Ast) -> Ast {
+        let ty = node.ty.promoted();
+        Self::cast(node, ty)
+    }
+
+    /// Implicit conversion, omitted when the type already matches.
+    fn cast(node: Ast, ty: Ty) -> Ast {
+        if node.ty == ty {
+            return node;
+        }
+        let span = node.span;
+        Self::cast_explicit(node, ty, span)
+    }
+
+    fn cast_explicit(node: Ast, ty: Ty, span: Span) -> Ast {
+        Ast {
+            kind: AstKind::Cast(ast::Cast { expr: Box::new(node) }),
+            span,
+            ty,
+        }
+    }
+
+    fn check_value(&self, node: &Ast) -> Result<(), Error> {
+        if node.ty == Ty::Void {
+            return Err(self.err_at(
+                node.span,
+                "void value not ignored as it ought to be",
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_scalar(&self, node: &Ast) -> Result<(), Error> {
+        self.check_value(node)?;
+        if !node.ty.is_scalar() {
+            return Err(self.err_at(
+                node.span,
+                &format!("expected scalar, found `{}`", node.ty),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_integer(&self, node: &Ast) -> Result<(), Error> {
+        self.check_value(node)?;
+        if !node.ty.is_integer() {
+            return Err(self.err_at(
+                node.span,
+                &format!("expected integer, found `{}`", node.ty),
+            ));
+        }
+        Ok(())
+    }
+
+    /// An lvalue of type `va_list`.
+    fn va_list(&mut self) -> Result<Ast, Error> {
+        let ap = self.assign()?;
+        if ap.ty != Ty::VaList || !matches!(ap.kind, AstKind::Ref(_)) {
+            return Err(self.err_at(ap.span, "expected a `va_list` variable"));
+        }
+        Ok(ap)
+    }
+
+    fn ident(&mut self) -> Result<Symbol, Error> {
+        let kind = self.peek().kind.clone();
+        if let TokenKind::Ident(ident) = kind {
+            self.next()?;
+            Ok(ident)
+        } else {
+            Err(self.err(&format!("expected identifier, found `{}`", kind)))
+        }
+    }
+
+    fn declare(
+        &mut self,
+        ident: Symbol,
+        ty: Ty,
+        span: Span,
+    ) -> Result<(), Error> {
+        let scope = self.scopes.last_mut().unwrap();
+        if scope.contains_key(&ident) {
+            return Err(
+                self.err_at(span, &format!("redefinition of `{}`", ident))
+            );
+        }
+        scope.insert(ident, ty);
+        Ok(())
+    }
+
+    fn lookup(&self, ident: Symbol) -> Option<&Ty> {
+        self.scopes.iter().rev().find_map(|s| s.get(&ident))
+    }
+
+    /// Span from `start` to the last consumed token.
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev)
+    }
+
+    fn err(&self, msg: &str) -> Error {
+        self.err_at(self.peek().span, msg)
+    }
+
+    fn err_at(&self, span: Span, msg: &str) -> Error {
+        Error { span, msg: msg.to_string() }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tok
+    }
+
+    fn next(&mut self) -> Result<Token, Error> {
+        let next = self.scanner.token()?;
+        let tok = std::mem::replace(&mut self.tok, next);
+        self.prev = tok.span;
+        Ok(tok)
+    }
+
+    fn skip(&mut self, kind: &TokenKind) -> Result<(), Error> {
         let k = self.peek().kind.clone();
         if k != *kind {
-            self.err(&format!(
+            return Err(self.err(&format!(
                 "expected `{expected}`, found `{found}`",
                 expected = kind,
                 found = k,
-            ));
+            )));
         }
-        self.next();
+        self.next()?;
+        Ok(())
     }
 }
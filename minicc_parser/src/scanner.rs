@@ -1,14 +1,15 @@
-use std::process::exit;
-use std::str::Chars;
+use minicc_ast::{DataModel, SourceMap, Span, Symbol, Ty};
+
+use crate::Error;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) struct Token {
+pub struct Token {
     pub kind: TokenKind,
-    pub loc: usize,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub(crate) enum TokenKind {
+pub enum TokenKind {
     Plus,      // `+`
     Minus,     //`-`
     Asterisk,  //`*`
@@ -28,20 +29,44 @@ pub(crate) enum TokenKind {
     Semi,      // `;`
     Eq,        // `=`
     Comma,     // `,`
+    Question,  // `?`
+    Colon,     // `:`
+    Ellipsis,  // `...`
+    Amp,       // `&`
 
     If,
     Else,
     For,
     Int,
     Return,
+    Char,
+    Short,
+    Long,
+    Signed,
+    Unsigned,
+    Void,
+    VaList,
+    VaStart,
+    VaArg,
+    VaEnd,
 
-    IntLit(i64), // Integer literals e.g. `123`
+    IntLit(i64, Ty), // Integer literals e.g. `123`, `42u`
 
-    Ident(String),
+    Ident(Symbol),
 
     Eof, // End Of File
 }
 
+impl TokenKind {
+    pub fn is_ty_spec(&self) -> bool {
+        use TokenKind::*;
+        matches!(
+            self,
+            Void | Char | Short | Int | Long | Signed | Unsigned | VaList
+        )
+    }
+}
+
 impl std::fmt::Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use TokenKind::*;
@@ -65,14 +90,28 @@ impl std::fmt::Display for TokenKind {
             Semi => write!(f, ";"),
             Eq => write!(f, "="),
             Comma => write!(f, ","),
+            Question => write!(f, "?"),
+            Colon => write!(f, ":"),
+            Ellipsis => write!(f, "..."),
+            Amp => write!(f, "&"),
 
             If => write!(f, "if"),
             Else => write!(f, "else"),
             For => write!(f, "for"),
             Int => write!(f, "int"),
             Return => write!(f, "return"),
+            Char => write!(f, "char"),
+            Short => write!(f, "short"),
+            Long => write!(f, "long"),
+            Signed => write!(f, "signed"),
+            Unsigned => write!(f, "unsigned"),
+            Void => write!(f, "void"),
+            VaList => write!(f, "va_list"),
+            VaStart => write!(f, "va_start"),
+            VaArg => write!(f, "va_arg"),
+            VaEnd => write!(f, "va_end"),
 
-            IntLit(x) => write!(f, "{}", x),
+            IntLit(x, _) => write!(f, "{}", x),
 
             Ident(x) => write!(f, "{}", x),
             Eof => write!(f, "EOF"),
@@ -80,177 +119,191 @@ impl std::fmt::Display for TokenKind {
     }
 }
 
+/// Yields `Eof` forever at the end of the input, and an error for an unknown
+/// token or invalid literal.
 #[derive(Debug)]
-pub(crate) struct Scanner<'a> {
-    // Cannot use enumerate iterator, because cannot get count after iterator
-    // finished.
-    s: Chars<'a>,
-    loc: usize,
+pub struct Scanner<'a> {
+    s: &'a [u8],
+    /// Offset of the next byte.
+    pos: usize,
+    map: &'a SourceMap,
+    model: DataModel,
 }
 
 impl<'a> Iterator for Scanner<'a> {
-    type Item = Token;
+    type Item = Result<Token, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        Some(self.token())
+    }
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(src: &'a str, map: &'a SourceMap, model: DataModel) -> Self {
+        Self { s: src.as_bytes(), pos: 0, map, model }
+    }
+
+    pub fn model(&self) -> DataModel {
+        self.model
+    }
+
+    pub(crate) fn token(&mut self) -> Result<Token, Error> {
         // Skip white spaces.
-        while matches!(self.peek_char(), Some(c) if c.is_whitespace()) {
-            self.next_char();
+        while self.peek().is_ascii_whitespace() {
+            self.pos += 1;
         }
 
-        let loc = self.loc;
-        let kind = match self.peek_char()? {
-            '+' => {
-                self.next_char();
-                TokenKind::Plus
-            }
-            '-' => {
-                self.next_char();
-                TokenKind::Minus
-            }
-            '*' => {
-                self.next_char();
-                TokenKind::Asterisk
-            }
-            '/' => {
-                self.next_char();
-                TokenKind::Slash
-            }
-            '%' => {
-                self.next_char();
-                TokenKind::Percent
-            }
-            '!' => {
-                self.next_char();
-                if let Some('=') = self.peek_char() {
-                    self.next_char();
-                    TokenKind::ExclaimEq
-                } else {
-                    TokenKind::Exclaim
-                }
-            }
-            '<' => {
-                self.next_char();
-                if let Some('=') = self.peek_char() {
-                    self.next_char();
-                    TokenKind::LtEq
-                } else {
-                    TokenKind::Lt
-                }
-            }
-            '>' => {
-                self.next_char();
-                if let Some('=') = self.peek_char() {
-                    self.next_char();
-                    TokenKind::GtEq
-                } else {
-                    TokenKind::Gt
-                }
-            }
-            '=' => {
-                self.next_char();
-                if let Some('=') = self.peek_char() {
-                    self.next_char();
-                    TokenKind::EqEq
+        let start = self.pos;
+        let Some(&c) = self.s.get(self.pos) else {
+            let span = self.map.span(start, start);
+            return Ok(Token { kind: TokenKind::Eof, span });
+        };
+        self.pos += 1;
+
+        let kind = match c {
+            b'+' => TokenKind::Plus,
+            b'-' => TokenKind::Minus,
+            b'*' => TokenKind::Asterisk,
+            b'/' => TokenKind::Slash,
+            b'%' => TokenKind::Percent,
+            b'!' => self.with_eq(TokenKind::Exclaim, TokenKind::ExclaimEq),
+            b'<' => self.with_eq(TokenKind::Lt, TokenKind::LtEq),
+            b'>' => self.with_eq(TokenKind::Gt, TokenKind::GtEq),
+            b'=' => self.with_eq(TokenKind::Eq, TokenKind::EqEq),
+            b'(' => TokenKind::LParen,
+            b')' => TokenKind::RParen,
+            b'{' => TokenKind::LBrace,
+            b'}' => TokenKind::RBrace,
+            b';' => TokenKind::Semi,
+            b',' => TokenKind::Comma,
+            b'?' => TokenKind::Question,
+            b':' => TokenKind::Colon,
+            b'&' => TokenKind::Amp,
+            b'.' => {
+                if self.s[self.pos..].starts_with(b"..") {
+                    self.pos += 2;
+                    TokenKind::Ellipsis
                 } else {
-                    TokenKind::Eq
+                    return Err(self.err("unknown token `.`"));
                 }
             }
-            '(' => {
-                self.next_char();
-                TokenKind::LParen
-            }
-            ')' => {
-                self.next_char();
-                TokenKind::RParen
-            }
-            '{' => {
-                self.next_char();
-                TokenKind::LBrace
+            b'0'..=b'9' => {
+                self.pos = start;
+                self.int_lit()?
             }
-            '}' => {
-                self.next_char();
-                TokenKind::RBrace
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                self.pos = start;
+                self.ident()
             }
-            ';' => {
-                self.next_char();
-                TokenKind::Semi
+            _ => {
+                self.pos = start;
+                let c = String::from_utf8_lossy(&self.s[start..])
+                    .chars()
+                    .next()
+                    .unwrap();
+                return Err(self.err(&format!("unknown token `{}`", c)));
             }
-            ',' => {
-                self.next_char();
-                TokenKind::Comma
-            }
-            c if c.is_ascii_digit() => TokenKind::IntLit(self.read_int()),
-            c if c.is_ascii_alphabetic() => self.ident(),
-
-            c => self.err(&format!("unknown token `{}`", c)),
         };
 
-        Some(Token { kind, loc })
+        Ok(Token { kind, span: self.map.span(start, self.pos) })
     }
-}
 
-impl<'a> Scanner<'a> {
-    pub fn new(src: &'a str) -> Self {
-        Self { s: src.chars(), loc: 0 }
+    /// `kind`, or `with_eq` if followed by `=`.
+    fn with_eq(&mut self, kind: TokenKind, with_eq: TokenKind) -> TokenKind {
+        if self.peek() == b'=' {
+            self.pos += 1;
+            with_eq
+        } else {
+            kind
+        }
     }
 
     fn ident(&mut self) -> TokenKind {
-        let s = self.read_ident();
-        match s.as_str() {
-            "if" => TokenKind::If,
-            "else" => TokenKind::Else,
-            "for" => TokenKind::For,
-            "int" => TokenKind::Int,
-            "return" => TokenKind::Return,
-            _ => TokenKind::Ident(s),
+        let start = self.pos;
+        while matches!(self.peek(), b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_')
+        {
+            self.pos += 1;
         }
-    }
 
-    fn read_ident(&mut self) -> String {
-        let mut s = self.peek_char().unwrap().to_string();
-        self.next_char();
-        loop {
-            match self.peek_char() {
-                Some(c) if c.is_ascii_alphanumeric() => {
-                    s.push(c);
-                    self.next_char();
-                }
-                _ => return s,
-            }
+        match &self.s[start..self.pos] {
+            b"if" => TokenKind::If,
+            b"else" => TokenKind::Else,
+            b"for" => TokenKind::For,
+            b"int" => TokenKind::Int,
+            b"return" => TokenKind::Return,
+            b"char" => TokenKind::Char,
+            b"short" => TokenKind::Short,
+            b"long" => TokenKind::Long,
+            b"signed" => TokenKind::Signed,
+            b"unsigned" => TokenKind::Unsigned,
+            b"void" => TokenKind::Void,
+            b"va_list" => TokenKind::VaList,
+            b"va_start" => TokenKind::VaStart,
+            b"va_arg" => TokenKind::VaArg,
+            b"va_end" => TokenKind::VaEnd,
+            // Identifiers are ASCII.
+            s => TokenKind::Ident(Symbol::intern(
+                std::str::from_utf8(s).unwrap(),
+            )),
         }
     }
 
-    fn read_int(&mut self) -> i64 {
-        let mut s = String::new();
+    /// The type of a decimal literal is the first of the candidates for its
+    /// suffix that can represent the value (C99 6.4.4.1p5).
+    fn int_lit(&mut self) -> Result<TokenKind, Error> {
+        let val = self.read_int()?;
 
-        loop {
-            match self.peek_char() {
-                Some(c) if c.is_ascii_digit() => {
-                    s.push(c);
-                    self.next_char();
-                }
-                _ => break,
-            }
+        let start = self.pos;
+        while matches!(self.peek(), b'u' | b'U' | b'l' | b'L') {
+            self.pos += 1;
         }
+        let suffix = self.s[start..self.pos].to_ascii_lowercase();
 
-        s.parse().unwrap()
-    }
+        let candidates: &[Ty] = match &*suffix {
+            b"" => &[Ty::Int, Ty::Long, Ty::LongLong],
+            b"u" => &[Ty::UInt, Ty::ULong, Ty::ULongLong],
+            b"l" => &[Ty::Long, Ty::LongLong],
+            b"ul" | b"lu" => &[Ty::ULong, Ty::ULongLong],
+            b"ll" => &[Ty::LongLong],
+            b"ull" | b"llu" => &[Ty::ULongLong],
+            _ => {
+                return Err(self.err(&format!(
+                    "invalid suffix `{}` on integer",
+                    String::from_utf8_lossy(&suffix)
+                )))
+            }
+        };
+
+        let ty = candidates
+            .iter()
+            .find(|ty| {
+                let bits =
+                    ty.size(self.model) as u32 * 8 - ty.is_signed() as u32;
+                bits == 64 || val < 1 << bits
+            })
+            .ok_or_else(|| self.err("integer literal is too large"))?;
 
-    fn err(&self, msg: &str) -> ! {
-        eprintln!("{pos}: {msg}", pos = self.loc);
-        exit(1);
+        Ok(TokenKind::IntLit(val as i64, ty.clone()))
     }
 
-    fn next_char(&mut self) -> Option<char> {
-        if let Some(c) = self.s.next() {
-            self.loc += 1;
-            Some(c)
-        } else {
-            None
+    fn read_int(&mut self) -> Result<u64, Error> {
+        let mut val: u64 = 0;
+        while let c @ b'0'..=b'9' = self.peek() {
+            val = val
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((c - b'0') as u64))
+                .ok_or_else(|| self.err("integer literal is too large"))?;
+            self.pos += 1;
         }
+        Ok(val)
+    }
+
+    fn err(&self, msg: &str) -> Error {
+        Error { span: self.map.span(self.pos, self.pos), msg: msg.to_string() }
     }
 
-    fn peek_char(&self) -> Option<char> {
-        self.s.clone().next()
+    /// The next byte, or `0` at the end of the input.
+    fn peek(&self) -> u8 {
+        self.s.get(self.pos).copied().unwrap_or(0)
     }
 }
@@ -0,0 +1,117 @@
+//! Checks the spans the parser gives nodes, and that [`Ast::node_at`] finds
+//! the innermost node at an offset.
+
+use std::ops::Range;
+
+use minicc_ast::{Ast, AstKind, DataModel, OpBin, SourceMap};
+
+const SRC: &str = "\
+int f(int x, int z);
+int g(long y)
+{
+    int a;
+    for (a = 0; a < 9; a = a + 1)
+        a = -y + f((int)y, 2) * 3;
+    return a;
+}
+";
+
+fn parse(src: &str) -> Vec<Ast> {
+    let map = SourceMap::from_src("test.c", src);
+    minicc_parser::try_parse(src, &map, DataModel::ILP32)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Offsets of the `n`th occurrence of `text` in [`SRC`], counting from 0.
+fn at(text: &str, n: usize) -> Range<usize> {
+    let start = SRC.match_indices(text).nth(n).unwrap().0;
+    start..start + text.len()
+}
+
+/// Span of the first node in `nodes`, in source order, matching `pred`.
+fn span_of(nodes: &[Ast], pred: impl Fn(&Ast) -> bool) -> Range<usize> {
+    fn find<'a>(n: &'a Ast, pred: &dyn Fn(&Ast) -> bool) -> Option<&'a Ast> {
+        if pred(n) {
+            return Some(n);
+        }
+        n.children().into_iter().find_map(|c| find(c, pred))
+    }
+    let node = nodes.iter().find_map(|n| find(n, &pred)).unwrap();
+    node.span.start..node.span.end
+}
+
+fn bin(n: &Ast, op: OpBin) -> bool {
+    matches!(&n.kind, AstKind::BinOp(b) if b.op == op)
+}
+
+#[test]
+fn bin_op() {
+    let nodes = parse(SRC);
+    assert_eq!(span_of(&nodes, |n| bin(n, OpBin::Lt)), at("a < 9", 0));
+    assert_eq!(span_of(&nodes, |n| bin(n, OpBin::Add)), at("a + 1", 0));
+    assert_eq!(
+        span_of(&nodes, |n| bin(n, OpBin::Mul)),
+        at("f((int)y, 2) * 3", 0)
+    );
+}
+
+#[test]
+fn call() {
+    let nodes = parse(SRC);
+    let span = span_of(&nodes, |n| matches!(n.kind, AstKind::Call(_)));
+    assert_eq!(span, at("f((int)y, 2)", 0));
+}
+
+#[test]
+fn cast() {
+    let nodes = parse(SRC);
+    let explicit = |n: &Ast| match &n.kind {
+        AstKind::Cast(c) => c.expr.span.start > n.span.start,
+        _ => false,
+    };
+    assert_eq!(span_of(&nodes, explicit), at("(int)y", 0));
+}
+
+#[test]
+fn unary() {
+    let nodes = parse(SRC);
+    let span = span_of(&nodes, |n| matches!(n.kind, AstKind::UnOp(_)));
+    assert_eq!(span, at("-y", 0));
+}
+
+#[test]
+fn assignment() {
+    let nodes = parse(SRC);
+    assert_eq!(span_of(&nodes, |n| bin(n, OpBin::Asign)), at("a = 0", 0));
+    let inc = |n: &Ast| bin(n, OpBin::Asign) && n.span.start > at("9", 0).start;
+    assert_eq!(span_of(&nodes, inc), at("a = a + 1", 0));
+}
+
+#[test]
+fn node_at() {
+    let nodes = parse(SRC);
+    let node_at = |offset: usize| {
+        let node = nodes.iter().find_map(|n| n.node_at(0, offset))?;
+        Some((node.span.start..node.span.end, &SRC[node.span.start..]))
+    };
+    let is = |offset: usize, text: &str, n: usize| {
+        let (span, rest) = node_at(offset).unwrap();
+        assert_eq!(span, at(text, n), "at {:?}", rest);
+    };
+
+    // Names and literals, not the nodes around them.
+    is(at("-y", 0).start + 1, "y", 1);
+    is(at("2)", 0).start, "2", 0);
+    is(at("f((", 0).start, "f", 2);
+    // Operators give the node they are the operator of.
+    is(at("-y", 0).start, "-y", 0);
+    is(at(" + f", 0).start + 1, "-y + f((int)y, 2) * 3", 0);
+    is(at(" * 3", 0).start + 1, "f((int)y, 2) * 3", 0);
+    is(at("(int)", 0).start, "(int)y", 0);
+    is(at(", 2)", 0).start, "f((int)y, 2)", 0);
+    is(at(" < ", 0).start + 1, "a < 9", 0);
+    // Between statements, the enclosing block.
+    is(at("    int a", 0).start, &SRC[at("{", 0).start..SRC.len() - 1], 0);
+    // Outside any function.
+    assert_eq!(node_at(SRC.len()), None);
+}
@@ -0,0 +1,3 @@
+#pragma once
+
+// `va_list`, `va_start`, `va_arg` and `va_end` are built into the compiler.
@@ -0,0 +1,6 @@
+#pragma once
+
+int putchar(int c);
+int getchar(void);
+int puts(char *s);
+int printf(char *fmt, ...);
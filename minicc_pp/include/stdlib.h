@@ -0,0 +1,3 @@
+#pragma once
+
+void exit(int status);
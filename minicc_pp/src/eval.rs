@@ -0,0 +1,188 @@
+use super::token::{Token, TokenKind};
+
+/// Evaluates the controlling expression of `#if` after macro expansion and
+/// replacement of the remaining identifiers by `0` (C99 6.10.1).
+pub(crate) fn eval(toks: &[Token]) -> Result<i64, (usize, String)> {
+    let mut e = Eval { toks, pos: 0, dead: 0 };
+    let val = e.cond()?;
+    match e.toks.get(e.pos) {
+        Some(tok) => Err((e.pos, format!("unexpected `{}` in #if", tok.text))),
+        None => Ok(val),
+    }
+}
+
+struct Eval<'a> {
+    toks: &'a [Token],
+    pos: usize,
+    /// Nonzero inside operands that are not evaluated, e.g. the right of
+    /// `0 && x`.
+    dead: usize,
+}
+
+/// Binary operators by increasing precedence.
+const LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl Eval<'_> {
+    fn cond(&mut self) -> Result<i64, (usize, String)> {
+        let cond = self.bin(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.operand(cond == 0, Self::cond)?;
+        self.expect(":")?;
+        let else_ = self.operand(cond != 0, Self::cond)?;
+        Ok(if cond != 0 { then } else { else_ })
+    }
+
+    fn bin(&mut self, level: usize) -> Result<i64, (usize, String)> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.bin(level + 1)?;
+        while let Some(op) = LEVELS[level].iter().find(|op| self.peek_is(op)) {
+            let pos = self.pos;
+            self.pos += 1;
+            let dead = *op == "||" && lhs != 0 || *op == "&&" && lhs == 0;
+            let rhs = self.operand(dead, |e| e.bin(level + 1))?;
+            lhs = match *op {
+                "||" => (lhs != 0 || rhs != 0) as i64,
+                "&&" => (lhs != 0 && rhs != 0) as i64,
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => (lhs == rhs) as i64,
+                "!=" => (lhs != rhs) as i64,
+                "<" => (lhs < rhs) as i64,
+                ">" => (lhs > rhs) as i64,
+                "<=" => (lhs <= rhs) as i64,
+                ">=" => (lhs >= rhs) as i64,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                "/" | "%" if rhs == 0 && self.dead > 0 => 0,
+                "/" | "%" if rhs == 0 => {
+                    return Err((pos, "division by zero in #if".to_string()));
+                }
+                "/" => lhs.wrapping_div(rhs),
+                "%" => lhs.wrapping_rem(rhs),
+                _ => unreachable!(),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn operand(
+        &mut self,
+        dead: bool,
+        f: impl FnOnce(&mut Self) -> Result<i64, (usize, String)>,
+    ) -> Result<i64, (usize, String)> {
+        self.dead += dead as usize;
+        let val = f(self);
+        self.dead -= dead as usize;
+        val
+    }
+
+    fn unary(&mut self) -> Result<i64, (usize, String)> {
+        if self.eat("+") {
+            self.unary()
+        } else if self.eat("-") {
+            Ok(self.unary()?.wrapping_neg())
+        } else if self.eat("~") {
+            Ok(!self.unary()?)
+        } else if self.eat("!") {
+            Ok((self.unary()? == 0) as i64)
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<i64, (usize, String)> {
+        if self.eat("(") {
+            let val = self.cond()?;
+            self.expect(")")?;
+            return Ok(val);
+        }
+
+        let pos = self.pos;
+        let Some(tok) = self.toks.get(pos) else {
+            return Err((pos, "expected expression in #if".to_string()));
+        };
+        self.pos += 1;
+        match tok.kind {
+            TokenKind::Number => int(&tok.text).ok_or_else(|| {
+                (pos, format!("invalid integer `{}` in #if", tok.text))
+            }),
+            TokenKind::Char => char_val(&tok.text).ok_or_else(|| {
+                (pos, format!("invalid character `{}` in #if", tok.text))
+            }),
+            _ => Err((pos, format!("unexpected `{}` in #if", tok.text))),
+        }
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.toks
+            .get(self.pos)
+            .is_some_and(|t| t.kind == TokenKind::Punct && t.is(text))
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        let found = self.peek_is(text);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), (usize, String)> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err((self.pos, format!("expected `{}` in #if", text)))
+        }
+    }
+}
+
+/// Integer constant with an optional `u`/`l` suffix.
+fn int(text: &str) -> Option<i64> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let (digits, radix) = if let Some(hex) =
+        digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (&digits[1..], 8)
+    } else {
+        (digits, 10)
+    };
+    u64::from_str_radix(digits, radix).ok().map(|v| v as i64)
+}
+
+fn char_val(text: &str) -> Option<i64> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    let c = match chars.next()? {
+        '\\' => match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            c => c,
+        },
+        c => c,
+    };
+    chars.next().is_none().then_some(c as i64)
+}
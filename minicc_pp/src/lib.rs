@@ -0,0 +1,850 @@
+mod eval;
+mod predefined;
+mod token;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::rc::Rc;
+
+use minicc_ast::SourceMap;
+use token::{Token, TokenKind};
+
+/// Headers shipped with the compiler, searched after the include paths.
+const BUILTIN_HEADERS: &[(&str, &str)] = &[
+    ("stdarg.h", include_str!("../include/stdarg.h")),
+    ("stdio.h", include_str!("../include/stdio.h")),
+    ("stdlib.h", include_str!("../include/stdlib.h")),
+];
+
+/// How deeply `#include`s may nest, as in GCC, so that a header including
+/// itself without a guard is an error rather than endless.
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+pub struct Output {
+    pub text: String,
+    pub map: SourceMap,
+}
+
+impl Output {
+    /// `text` with `# line "file"` markers where lines do not follow each
+    /// other in the source, as printed by `ccom -E`.
+    pub fn with_line_markers(&self) -> String {
+        let mut s = String::new();
+        let mut prev: Option<(&str, usize)> = None;
+        let mut offset = 0;
+        for line in self.text.lines() {
+            let loc = self.map.resolve(offset);
+            offset += line.len() + 1;
+
+            match prev {
+                Some((file, n))
+                    if file == loc.file
+                        && (n + 1..n + 9).contains(&loc.line) =>
+                {
+                    // Keep short gaps as blank lines.
+                    for _ in n + 1..loc.line {
+                        s.push('\n');
+                    }
+                }
+                _ => s.push_str(&format!("# {} \"{}\"\n", loc.line, loc.file)),
+            }
+            s.push_str(line);
+            s.push('\n');
+            prev = Some((loc.file, loc.line));
+        }
+        s
+    }
+}
+
+pub struct Preprocessor {
+    map: SourceMap,
+    /// Path of each file in `map`, `None` for standard input and builtin
+    /// headers.
+    paths: Vec<Option<PathBuf>>,
+    /// Number of `#include`s each file in `map` is nested in.
+    depths: Vec<usize>,
+    macros: HashMap<String, Macro>,
+    include_paths: Vec<PathBuf>,
+    /// Searched after `include_paths`.
+    system_paths: Vec<PathBuf>,
+    /// Files containing `#pragma once`.
+    once: HashSet<PathBuf>,
+    conds: Vec<Cond>,
+    /// Tokens to be read, the next one last.
+    input: Vec<Token>,
+    text: String,
+    last: Option<Token>,
+}
+
+#[derive(Debug, Clone)]
+enum Macro {
+    Obj(Vec<Token>),
+    Fn {
+        params: Vec<String>,
+        variadic: bool,
+        body: Vec<Token>,
+    },
+    /// Expands to a token depending on where it is used, e.g. `__LINE__`.
+    Dynamic(fn(&Preprocessor, &Token) -> Token),
+}
+
+struct Cond {
+    /// Token of the `#if`.
+    tok: Token,
+    /// Whether a group of this conditional has been included.
+    taken: bool,
+    in_else: bool,
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor {
+    /// Creates a preprocessor with the predefined macros (C99 6.10.8)
+    /// defined.
+    pub fn new() -> Self {
+        let mut pp = Self {
+            map: SourceMap::new(),
+            paths: Vec::new(),
+            depths: Vec::new(),
+            macros: HashMap::new(),
+            include_paths: Vec::new(),
+            system_paths: Vec::new(),
+            once: HashSet::new(),
+            conds: Vec::new(),
+            input: Vec::new(),
+            text: String::new(),
+            last: None,
+        };
+        pp.predefine();
+        pp
+    }
+
+    /// Adds a directory searched by `#include`.
+    pub fn include_path(&mut self, path: impl Into<PathBuf>) {
+        self.include_paths.push(path.into());
+    }
+
+    /// Adds a directory searched by `#include` after those added by
+    /// `include_path`.
+    pub fn system_include_path(&mut self, path: impl Into<PathBuf>) {
+        self.system_paths.push(path.into());
+    }
+
+    /// Defines an object-like macro as if by `#define name value`.
+    pub fn define(&mut self, name: &str, value: &str) {
+        let file = self.add_file(&format!("<define {}>", name), value, None);
+        let body = self.tokenize(value, file);
+        self.macros.insert(name.to_string(), Macro::Obj(body));
+    }
+
+    pub fn undef(&mut self, name: &str) {
+        self.macros.remove(name);
+    }
+
+    pub fn preprocess_file(self, path: &Path) -> Output {
+        let src = std::fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path.display(), e);
+            exit(1);
+        });
+        let name = path.display().to_string();
+        self.preprocess(&name, &src, Some(path.to_path_buf()))
+    }
+
+    /// Preprocesses `src` read from somewhere other than a file, `#include
+    /// "..."` is relative to the current directory.
+    pub fn preprocess_str(self, name: &str, src: &str) -> Output {
+        self.preprocess(name, src, None)
+    }
+
+    fn preprocess(
+        mut self,
+        name: &str,
+        src: &str,
+        path: Option<PathBuf>,
+    ) -> Output {
+        let file = self.add_file(name, src, path);
+        let toks = self.tokenize(src, file);
+        self.input.extend(toks.into_iter().rev());
+
+        loop {
+            let tok = self.next();
+            if tok.kind == TokenKind::Eof {
+                break;
+            }
+            if tok.bol && tok.is("#") && tok.hideset.is_empty() {
+                self.directive(tok);
+                continue;
+            }
+            if self.expand(&tok) {
+                continue;
+            }
+            self.emit(tok);
+        }
+
+        if let Some(cond) = self.conds.last() {
+            let tok = cond.tok.clone();
+            self.err(&tok, "unterminated conditional directive");
+        }
+
+        if !self.text.is_empty() {
+            self.text.push('\n');
+        }
+        Output { text: self.text, map: self.map }
+    }
+
+    fn emit(&mut self, tok: Token) {
+        if let Some(last) = &self.last {
+            if tok.bol {
+                self.text.push('\n');
+            } else if tok.space || Self::would_paste(last, &tok) {
+                self.text.push(' ');
+            }
+        }
+        self.map.push(self.text.len(), tok.file, tok.offset);
+        self.text.push_str(&tok.text);
+        self.last = Some(tok);
+    }
+
+    /// Whether adjacent tokens produced by macro expansion would be scanned
+    /// as one, e.g. `-` followed by `-1`.
+    fn would_paste(last: &Token, tok: &Token) -> bool {
+        if last.hideset.is_empty() && tok.hideset.is_empty() {
+            return false;
+        }
+        let word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+        let (a, b) = (last.text.chars().last(), tok.text.chars().next());
+        match (a, b) {
+            (Some(a), Some(b)) => {
+                word(a) && word(b)
+                    || !word(a) && !word(b) && a != '(' && b != ')'
+            }
+            _ => false,
+        }
+    }
+
+    fn directive(&mut self, hash: Token) {
+        let tok = self.next();
+        if tok.bol || tok.kind == TokenKind::Eof {
+            // Null directive.
+            self.input.push(tok);
+            return;
+        }
+
+        match &*tok.text {
+            "include" => self.include(&tok),
+            "define" => self.define_directive(),
+            "undef" => {
+                let name = self.macro_name(&tok);
+                self.macros.remove(&name);
+                self.end_of_line("undef");
+            }
+            "if" => {
+                let val = self.eval_line(&tok);
+                self.push_cond(tok, val);
+            }
+            "ifdef" | "ifndef" => {
+                let name = self.macro_name(&tok);
+                self.end_of_line(&tok.text);
+                let val = self.macros.contains_key(&name) == tok.is("ifdef");
+                self.push_cond(tok, val);
+            }
+            "elif" => {
+                let Some(cond) = self.conds.last() else {
+                    self.err(&tok, "#elif without #if");
+                };
+                if cond.in_else {
+                    self.err(&tok, "#elif after #else");
+                }
+                if cond.taken {
+                    self.read_line();
+                    self.skip_group();
+                } else if self.eval_line(&tok) {
+                    self.conds.last_mut().unwrap().taken = true;
+                } else {
+                    self.skip_group();
+                }
+            }
+            "else" => {
+                self.end_of_line("else");
+                let Some(cond) = self.conds.last_mut() else {
+                    self.err(&tok, "#else without #if");
+                };
+                if cond.in_else {
+                    self.err(&tok, "#else after #else");
+                }
+                cond.in_else = true;
+                if cond.taken {
+                    self.skip_group();
+                } else {
+                    cond.taken = true;
+                }
+            }
+            "endif" => {
+                self.end_of_line("endif");
+                if self.conds.pop().is_none() {
+                    self.err(&tok, "#endif without #if");
+                }
+            }
+            "error" => {
+                let msg = Self::spell(&self.read_line());
+                self.err(&hash, &format!("#error {}", msg));
+            }
+            "pragma" => {
+                let line = self.read_line();
+                if line.len() == 1 && line[0].is("once") {
+                    if let Some(path) = &self.paths[hash.file] {
+                        let path = path.canonicalize().unwrap_or(path.clone());
+                        self.once.insert(path);
+                    }
+                }
+            }
+            _ => self.err(
+                &tok,
+                &format!("invalid preprocessing directive `#{}`", tok.text),
+            ),
+        }
+    }
+
+    fn include(&mut self, tok: &Token) {
+        let mut line = self.read_line();
+        // `#include pp-tokens` is macro expanded first (C99 6.10.2p4).
+        if !line.first().is_some_and(|t| t.kind == TokenKind::Str || t.is("<"))
+        {
+            line = self.expand_all(line);
+        }
+
+        let (name, quoted) = match line.first() {
+            Some(t) if t.kind == TokenKind::Str && line.len() == 1 => {
+                (t.text[1..t.text.len() - 1].to_string(), true)
+            }
+            Some(t) if t.is("<") && line.last().is_some_and(|t| t.is(">")) => {
+                (Self::spell(&line[1..line.len() - 1]), false)
+            }
+            _ => self.err(tok, "expected \"FILENAME\" or <FILENAME>"),
+        };
+        let depth = self.depths[tok.file] + 1;
+        if depth > MAX_INCLUDE_DEPTH {
+            self.err(tok, "#include nested too deeply");
+        }
+
+        let mut dirs = Vec::new();
+        if quoted {
+            let dir = match &self.paths[tok.file] {
+                Some(path) => {
+                    path.parent().unwrap_or(Path::new("")).to_path_buf()
+                }
+                None => PathBuf::new(),
+            };
+            dirs.push(dir);
+        }
+        dirs.extend(self.include_paths.iter().cloned());
+        dirs.extend(self.system_paths.iter().cloned());
+
+        let found = dirs.iter().map(|d| d.join(&name)).find(|p| p.is_file());
+        let (display, src, path) = match found {
+            Some(path) => {
+                let canon = path.canonicalize().unwrap_or(path.clone());
+                if self.once.contains(&canon) {
+                    return;
+                }
+                let src = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                    self.err(tok, &format!("{}: {}", path.display(), e))
+                });
+                (path.display().to_string(), src, Some(path))
+            }
+            None => match BUILTIN_HEADERS.iter().find(|(n, _)| *n == name) {
+                Some((n, src)) => {
+                    (format!("<builtin>/{}", n), src.to_string(), None)
+                }
+                None => self.err(tok, &format!("`{}` file not found", name)),
+            },
+        };
+
+        let file = self.add_file(&display, &src, path);
+        self.depths[file] = depth;
+        let toks = self.tokenize(&src, file);
+        self.input.extend(toks.into_iter().rev());
+    }
+
+    fn define_directive(&mut self) {
+        let name_tok = self.next();
+        if name_tok.kind != TokenKind::Ident || name_tok.bol {
+            self.err(&name_tok, "macro name must be an identifier");
+        }
+        let name = name_tok.text.to_string();
+
+        let next = self.next();
+        let mac = if next.is("(") && !next.space && !next.bol {
+            let mut params = Vec::new();
+            let mut variadic = false;
+            let mut tok = self.next();
+            if !tok.is(")") {
+                loop {
+                    if tok.is("...") {
+                        variadic = true;
+                        tok = self.next();
+                        if !tok.is(")") {
+                            self.err(&tok, "expected `)` after `...`");
+                        }
+                        break;
+                    }
+                    if tok.kind != TokenKind::Ident || tok.bol {
+                        self.err(&tok, "expected parameter name");
+                    }
+                    params.push(tok.text.to_string());
+
+                    tok = self.next();
+                    if tok.is(")") {
+                        break;
+                    }
+                    if !tok.is(",") || tok.bol {
+                        self.err(
+                            &tok,
+                            "expected `,` or `)` in macro parameters",
+                        );
+                    }
+                    tok = self.next();
+                }
+            }
+            Macro::Fn { params, variadic, body: self.read_line() }
+        } else {
+            self.input.push(next);
+            Macro::Obj(self.read_line())
+        };
+
+        self.macros.insert(name, mac);
+    }
+
+    fn push_cond(&mut self, tok: Token, val: bool) {
+        self.conds.push(Cond { tok, taken: val, in_else: false });
+        if !val {
+            self.skip_group();
+        }
+    }
+
+    /// Skips to the `#elif`, `#else` or `#endif` ending the current group,
+    /// which is left to be read next.
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        loop {
+            let tok = self.next();
+            if tok.kind == TokenKind::Eof {
+                return;
+            }
+            if !(tok.bol && tok.is("#")) {
+                continue;
+            }
+
+            let name = self.next();
+            match &*name.text {
+                "if" | "ifdef" | "ifndef" => depth += 1,
+                "endif" if depth > 0 => depth -= 1,
+                "elif" | "else" | "endif" if depth == 0 => {
+                    self.input.push(name);
+                    self.input.push(tok);
+                    return;
+                }
+                _ => {}
+            }
+            if !name.bol {
+                self.read_line();
+            } else {
+                self.input.push(name);
+            }
+        }
+    }
+
+    fn eval_line(&mut self, tok: &Token) -> bool {
+        let line = self.read_line();
+
+        // `defined` is evaluated before macro expansion.
+        let mut toks = Vec::new();
+        let mut iter = line.into_iter();
+        while let Some(t) = iter.next() {
+            if !t.is("defined") {
+                toks.push(t);
+                continue;
+            }
+            let mut name = iter.next();
+            let paren = name.as_ref().is_some_and(|n| n.is("("));
+            if paren {
+                name = iter.next();
+            }
+            let Some(name) = name.filter(|n| n.kind == TokenKind::Ident) else {
+                self.err(&t, "macro name must be an identifier");
+            };
+            if paren && !iter.next().is_some_and(|t| t.is(")")) {
+                self.err(&name, "expected `)` after `defined`");
+            }
+            let val =
+                if self.macros.contains_key(&*name.text) { "1" } else { "0" };
+            toks.push(Token { text: val.into(), kind: TokenKind::Number, ..t });
+        }
+
+        let toks: Vec<_> = self
+            .expand_all(toks)
+            .into_iter()
+            .map(|t| match t.kind {
+                TokenKind::Ident => {
+                    Token { text: "0".into(), kind: TokenKind::Number, ..t }
+                }
+                _ => t,
+            })
+            .collect();
+
+        match eval::eval(&toks) {
+            Ok(val) => val != 0,
+            Err((pos, msg)) => {
+                self.err(toks.get(pos).or(toks.last()).unwrap_or(tok), &msg)
+            }
+        }
+    }
+
+    /// Expands `tok` if it names a macro, pushing the result back to the
+    /// input. Returns whether it was expanded.
+    fn expand(&mut self, tok: &Token) -> bool {
+        if tok.kind != TokenKind::Ident || tok.is_hidden(&tok.text) {
+            return false;
+        }
+        let Some(mac) = self.macros.get(&*tok.text).cloned() else {
+            return false;
+        };
+
+        let name: Rc<str> = tok.text.clone();
+        let result = match mac {
+            Macro::Obj(body) => {
+                let hideset = Self::hideset_add(&tok.hideset, &name);
+                self.subst(&body, &[], &[], false, tok, &hideset)
+            }
+            Macro::Fn { params, variadic, body } => {
+                let next = self.next();
+                if !next.is("(") {
+                    self.input.push(next);
+                    return false;
+                }
+                let (args, rparen) =
+                    self.read_args(tok, params.len(), variadic);
+                // (HS(name) ∩ HS(rparen)) ∪ {name}
+                let common: Vec<Rc<str>> = tok
+                    .hideset
+                    .iter()
+                    .filter(|n| rparen.is_hidden(n))
+                    .cloned()
+                    .collect();
+                let hideset = Self::hideset_add(&common, &name);
+                self.subst(&body, &params, &args, variadic, tok, &hideset)
+            }
+            Macro::Dynamic(f) => vec![f(self, tok)],
+        };
+
+        self.input.extend(result.into_iter().rev());
+        true
+    }
+
+    /// Reads the arguments of a function-like macro invocation after `(`.
+    fn read_args(
+        &mut self,
+        name: &Token,
+        nparams: usize,
+        variadic: bool,
+    ) -> (Vec<Vec<Token>>, Token) {
+        let mut args = vec![Vec::new()];
+        let mut depth = 0;
+        let rparen = loop {
+            let tok = self.next();
+            match &*tok.text {
+                _ if tok.kind == TokenKind::Eof => {
+                    self.err(name, "unterminated macro invocation");
+                }
+                ")" if depth == 0 => break tok,
+                "," if depth == 0 && !(variadic && args.len() > nparams) => {
+                    args.push(Vec::new());
+                    continue;
+                }
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => {}
+            }
+            args.last_mut().unwrap().push(tok);
+        };
+
+        // `f()` passes one empty argument.
+        if nparams == 0 && args.len() == 1 && args[0].is_empty() && !variadic {
+            args.clear();
+        }
+        if variadic && args.len() == nparams {
+            args.push(Vec::new());
+        }
+        let expected = nparams + variadic as usize;
+        if args.len() != expected {
+            self.err(
+                name,
+                &format!(
+                    "macro `{}` takes {} arguments but {} were given",
+                    name.text,
+                    expected,
+                    args.len()
+                ),
+            );
+        }
+        (args, rparen)
+    }
+
+    /// Substitutes the arguments into a macro body (C99 6.10.3.1-3).
+    fn subst(
+        &mut self,
+        body: &[Token],
+        params: &[String],
+        args: &[Vec<Token>],
+        variadic: bool,
+        at: &Token,
+        hideset: &Rc<[Rc<str>]>,
+    ) -> Vec<Token> {
+        let arg = |t: &Token| -> Option<&Vec<Token>> {
+            if t.kind != TokenKind::Ident {
+                return None;
+            }
+            if variadic && t.is("__VA_ARGS__") {
+                return args.last();
+            }
+            params.iter().position(|p| t.is(p)).map(|i| &args[i])
+        };
+
+        let mut out: Vec<Token> = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let t = &body[i];
+
+            if t.is("#") && (!params.is_empty() || variadic) {
+                if let Some(a) = body.get(i + 1).and_then(arg) {
+                    let mut s = Self::stringize(a, at);
+                    s.space = t.space;
+                    out.push(s);
+                    i += 2;
+                    continue;
+                }
+            }
+
+            if i == 0 && t.is("##") {
+                self.err(t, "`##` cannot appear at start of macro expansion");
+            }
+
+            if t.is("##") {
+                let Some(rhs) = body.get(i + 1) else {
+                    self.err(t, "`##` cannot appear at end of macro expansion");
+                };
+                let mut rhs_toks = match arg(rhs) {
+                    Some(a) if a.is_empty() => vec![Self::placemarker(at)],
+                    Some(a) => a.clone(),
+                    None => vec![Self::relocate(rhs, at)],
+                };
+                i += 2;
+                if let Some(lhs) = out.pop() {
+                    let first = rhs_toks.remove(0);
+                    let pasted = self.paste(&lhs, &first);
+                    out.push(pasted);
+                }
+                out.extend(rhs_toks);
+                continue;
+            }
+
+            if let Some(a) = arg(t) {
+                let pasted = body.get(i + 1).is_some_and(|n| n.is("##"));
+                let mut a = match pasted {
+                    true if a.is_empty() => vec![Self::placemarker(at)],
+                    true => a.clone(),
+                    false => self.expand_all(a.clone()),
+                };
+                if let Some(first) = a.first_mut() {
+                    first.space = t.space;
+                }
+                out.extend(a);
+                i += 1;
+                continue;
+            }
+
+            out.push(Self::relocate(t, at));
+            i += 1;
+        }
+
+        out.retain(|t| t.kind != TokenKind::Placemarker);
+        for (i, t) in out.iter_mut().enumerate() {
+            t.hideset = Self::hideset_union(&t.hideset, hideset);
+            t.bol = false;
+            if i == 0 {
+                t.bol = at.bol;
+                t.space = at.space;
+            }
+        }
+        out
+    }
+
+    fn placemarker(at: &Token) -> Token {
+        Token { kind: TokenKind::Placemarker, text: "".into(), ..at.clone() }
+    }
+
+    /// Tokens of a macro body are located at the invocation.
+    fn relocate(tok: &Token, at: &Token) -> Token {
+        Token { file: at.file, offset: at.offset, ..tok.clone() }
+    }
+
+    /// `#arg` (C99 6.10.3.2).
+    fn stringize(arg: &[Token], at: &Token) -> Token {
+        let mut s = String::from("\"");
+        for (i, t) in arg.iter().enumerate() {
+            if i > 0 && (t.space || t.bol) {
+                s.push(' ');
+            }
+            if matches!(t.kind, TokenKind::Str | TokenKind::Char) {
+                for c in t.text.chars() {
+                    if c == '"' || c == '\\' {
+                        s.push('\\');
+                    }
+                    s.push(c);
+                }
+            } else {
+                s.push_str(&t.text);
+            }
+        }
+        s.push('"');
+        Token::new(TokenKind::Str, &s, at.file, at.offset)
+    }
+
+    /// `lhs ## rhs` (C99 6.10.3.3).
+    fn paste(&mut self, lhs: &Token, rhs: &Token) -> Token {
+        // A placemarker pasted to a token gives the token.
+        if rhs.kind == TokenKind::Placemarker {
+            return lhs.clone();
+        }
+        if lhs.kind == TokenKind::Placemarker {
+            return Token { space: lhs.space, ..rhs.clone() };
+        }
+        let text = format!("{}{}", lhs.text, rhs.text);
+        match token::tokenize(&text, lhs.file).as_deref() {
+            Ok([t]) => Token {
+                kind: t.kind,
+                text: t.text.clone(),
+                hideset: lhs.hideset.clone(),
+                ..lhs.clone()
+            },
+            _ => self.err(
+                lhs,
+                &format!(
+                    "pasting `{}` and `{}` does not give a valid token",
+                    lhs.text, rhs.text
+                ),
+            ),
+        }
+    }
+
+    /// Fully macro expands a token sequence in isolation.
+    fn expand_all(&mut self, toks: Vec<Token>) -> Vec<Token> {
+        let saved = std::mem::take(&mut self.input);
+        self.input.extend(toks.into_iter().rev());
+
+        let mut out = Vec::new();
+        while let Some(tok) = self.input.pop() {
+            if !self.expand(&tok) {
+                out.push(tok);
+            }
+        }
+
+        self.input = saved;
+        out
+    }
+
+    fn hideset_add(hideset: &[Rc<str>], name: &Rc<str>) -> Rc<[Rc<str>]> {
+        let mut v = hideset.to_vec();
+        if !v.contains(name) {
+            v.push(name.clone());
+        }
+        v.into()
+    }
+
+    fn hideset_union(a: &[Rc<str>], b: &[Rc<str>]) -> Rc<[Rc<str>]> {
+        let mut v = a.to_vec();
+        for n in b {
+            if !v.contains(n) {
+                v.push(n.clone());
+            }
+        }
+        v.into()
+    }
+
+    fn macro_name(&mut self, directive: &Token) -> String {
+        let tok = self.next();
+        if tok.kind != TokenKind::Ident || tok.bol {
+            let tok = if tok.bol { directive.clone() } else { tok };
+            self.err(&tok, "macro name must be an identifier");
+        }
+        tok.text.to_string()
+    }
+
+    fn end_of_line(&mut self, directive: &str) {
+        let line = self.read_line();
+        if let Some(tok) = line.first() {
+            self.err(
+                tok,
+                &format!("extra tokens at end of #{} directive", directive),
+            );
+        }
+    }
+
+    /// Reads the remaining tokens of the current line.
+    fn read_line(&mut self) -> Vec<Token> {
+        let mut line = Vec::new();
+        while let Some(tok) = self.input.last() {
+            if tok.bol || tok.kind == TokenKind::Eof {
+                break;
+            }
+            line.push(self.input.pop().unwrap());
+        }
+        if let Some(first) = line.first_mut() {
+            first.space = false;
+        }
+        line
+    }
+
+    fn next(&mut self) -> Token {
+        self.input.pop().unwrap_or_else(|| Token::new(TokenKind::Eof, "", 0, 0))
+    }
+
+    fn spell(toks: &[Token]) -> String {
+        let mut s = String::new();
+        for (i, t) in toks.iter().enumerate() {
+            if i > 0 && t.space {
+                s.push(' ');
+            }
+            s.push_str(&t.text);
+        }
+        s
+    }
+
+    fn add_file(
+        &mut self,
+        name: &str,
+        src: &str,
+        path: Option<PathBuf>,
+    ) -> usize {
+        self.paths.push(path);
+        self.depths.push(0);
+        self.map.add_file(name, src)
+    }
+
+    fn tokenize(&self, src: &str, file: usize) -> Vec<Token> {
+        token::tokenize(src, file).unwrap_or_else(|(offset, msg)| {
+            eprintln!("{}: {}", self.map.loc(file, offset), msg);
+            exit(1);
+        })
+    }
+
+    fn err(&self, tok: &Token, msg: &str) -> ! {
+        eprintln!("{}: {}", self.map.loc(tok.file, tok.offset), msg);
+        exit(1);
+    }
+}
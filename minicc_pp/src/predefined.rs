@@ -0,0 +1,87 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::token::{Token, TokenKind};
+use super::{Macro, Preprocessor};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct",
+    "Nov", "Dec",
+];
+
+impl Preprocessor {
+    pub(crate) fn predefine(&mut self) {
+        let (date, time) = date_time();
+        self.define("__DATE__", &format!("\"{}\"", date));
+        self.define("__TIME__", &format!("\"{}\"", time));
+        self.define("__STDC__", "1");
+        self.define("__STDC_VERSION__", "199901L");
+        self.define("__STDC_HOSTED__", "1");
+        self.define("__minicc__", &version());
+
+        self.macros.insert("__FILE__".to_string(), Macro::Dynamic(file));
+        self.macros.insert("__LINE__".to_string(), Macro::Dynamic(line));
+    }
+}
+
+fn file(pp: &Preprocessor, tok: &Token) -> Token {
+    let name =
+        pp.map.file(tok.file).name.replace('\\', "\\\\").replace('"', "\\\"");
+    Token {
+        kind: TokenKind::Str,
+        text: format!("\"{}\"", name).into(),
+        ..tok.clone()
+    }
+}
+
+fn line(pp: &Preprocessor, tok: &Token) -> Token {
+    let line = pp.map.loc(tok.file, tok.offset).line;
+    Token {
+        kind: TokenKind::Number,
+        text: line.to_string().into(),
+        ..tok.clone()
+    }
+}
+
+/// `major * 10000 + minor * 100 + patch`, e.g. `100` for 0.1.0.
+fn version() -> String {
+    let part = |s: &str| s.parse::<u32>().unwrap_or(0);
+    let v = part(env!("CARGO_PKG_VERSION_MAJOR")) * 10000
+        + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+        + part(env!("CARGO_PKG_VERSION_PATCH"));
+    v.to_string()
+}
+
+/// `__DATE__` and `__TIME__` in UTC. `SOURCE_DATE_EPOCH` overrides the current
+/// time for reproducible builds.
+fn date_time() -> (String, String) {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+
+    let (days, rem) = (secs / 86400, secs % 86400);
+    let (y, m, d) = civil_from_days(days as i64);
+    let date = format!("{} {:2} {}", MONTHS[m as usize - 1], d, y);
+    let time =
+        format!("{:02}:{:02}:{:02}", rem / 3600, rem / 60 % 60, rem % 60);
+    (date, time)
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) of the proleptic
+/// Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + (m <= 2) as i64;
+    (y, m, d)
+}
@@ -0,0 +1,179 @@
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    pub text: Rc<str>,
+    pub file: usize,
+    pub offset: usize,
+    /// First token of a line.
+    pub bol: bool,
+    /// Preceded by white space.
+    pub space: bool,
+    /// Macros that must not be expanded again in this token.
+    pub hideset: Rc<[Rc<str>]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TokenKind {
+    Ident,
+    Number,
+    Str,
+    Char,
+    Punct,
+    Eof,
+    /// Stands for an empty argument next to `##` during substitution
+    /// (C99 6.10.3.3p2), so that pasting joins only the operands.
+    Placemarker,
+}
+
+impl Token {
+    pub fn new(
+        kind: TokenKind,
+        text: &str,
+        file: usize,
+        offset: usize,
+    ) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+            file,
+            offset,
+            bol: false,
+            space: false,
+            hideset: Rc::new([]),
+        }
+    }
+
+    pub fn is(&self, text: &str) -> bool {
+        self.kind != TokenKind::Eof && &*self.text == text
+    }
+
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.hideset.iter().any(|n| &**n == name)
+    }
+}
+
+const PUNCTS: &[&str] = &[
+    "...", "<<=", ">>=", "->", "++", "--", "<<", ">>", "<=", ">=", "==", "!=",
+    "&&", "||", "*=", "/=", "%=", "+=", "-=", "&=", "^=", "|=", "##",
+];
+
+/// Splits `src` into preprocessing tokens (C99 6.4). Comments are replaced by
+/// white space and escaped newlines are removed.
+pub(crate) fn tokenize(
+    src: &str,
+    file: usize,
+) -> Result<Vec<Token>, (usize, String)> {
+    let s = src.as_bytes();
+    let mut toks = Vec::new();
+    let mut i = 0;
+    let mut bol = true;
+    let mut space = false;
+
+    while i < s.len() {
+        let c = s[i];
+
+        if c == b'\n' {
+            i += 1;
+            bol = true;
+            space = false;
+            continue;
+        }
+        if c == b'\\' && s.get(i + 1) == Some(&b'\n') {
+            i += 2;
+            space = true;
+            continue;
+        }
+        if c.is_ascii_whitespace() {
+            i += 1;
+            space = true;
+            continue;
+        }
+        if s[i..].starts_with(b"//") {
+            while i < s.len() && s[i] != b'\n' {
+                i += 1;
+            }
+            space = true;
+            continue;
+        }
+        if s[i..].starts_with(b"/*") {
+            match src[i + 2..].find("*/") {
+                Some(end) => i += end + 4,
+                None => return Err((i, "unterminated comment".to_string())),
+            }
+            space = true;
+            continue;
+        }
+
+        let start = i;
+        let kind = if c.is_ascii_digit()
+            || c == b'.' && s.get(i + 1).is_some_and(u8::is_ascii_digit)
+        {
+            i += 1;
+            while i < s.len() {
+                if matches!(s[i], b'e' | b'E' | b'p' | b'P')
+                    && matches!(s.get(i + 1), Some(b'+' | b'-'))
+                {
+                    i += 2;
+                } else if s[i].is_ascii_alphanumeric()
+                    || s[i] == b'_'
+                    || s[i] == b'.'
+                {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            TokenKind::Number
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            while i < s.len() && (s[i].is_ascii_alphanumeric() || s[i] == b'_')
+            {
+                i += 1;
+            }
+            TokenKind::Ident
+        } else if c == b'"' || c == b'\'' {
+            i += 1;
+            loop {
+                match s.get(i) {
+                    None | Some(b'\n') => {
+                        return Err((
+                            start,
+                            "unterminated literal".to_string(),
+                        ));
+                    }
+                    Some(b'\\') => i += 2,
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    _ => i += 1,
+                }
+            }
+            if c == b'"' {
+                TokenKind::Str
+            } else {
+                TokenKind::Char
+            }
+        } else {
+            let len = PUNCTS
+                .iter()
+                .find(|p| s[i..].starts_with(p.as_bytes()))
+                .map_or_else(
+                    || src[i..].chars().next().unwrap().len_utf8(),
+                    |p| p.len(),
+                );
+            i += len;
+            TokenKind::Punct
+        };
+
+        let mut tok = Token::new(kind, &src[start..i], file, start);
+        tok.bol = bol;
+        tok.space = space;
+        toks.push(tok);
+        bol = false;
+        space = false;
+    }
+
+    Ok(toks)
+}
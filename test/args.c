@@ -0,0 +1,54 @@
+void dbg(int v);
+int sum(int n, ...);
+
+int seven(int a, int b, int c, int d, int e, int f, int g)
+{
+    return a - b + c - d + e - f + g * 100;
+}
+
+int eight(char a, short b, int c, long d, int e, int f, int g, int h)
+{
+    return a + b + c + d + e + f + g * 1000 + h * 10000;
+}
+
+long long wide(int a, int b, int c, int d, int e, int f, long long g)
+{
+    return g + a + b + c + d + e + f;
+}
+
+int leaf(int a, int b)
+{
+    int x;
+    int y;
+    x = a * 3;
+    y = b * 5;
+    return x - y;
+}
+
+int sum(int n, ...)
+{
+    va_list ap;
+    int i;
+    int s;
+    va_start(ap, n);
+    s = 0;
+    for (i = 0; i < n; i = i + 1) {
+        s = s * 2 + va_arg(ap, int);
+    }
+    va_end(ap);
+    return s;
+}
+
+int main()
+{
+    int (*f)(int, int, int, int, int, int, int);
+    f = seven;
+    dbg(seven(1, 2, 3, 4, 5, 6, 7));
+    dbg(f(7, 6, 5, 4, 3, 2, 1));
+    dbg(eight(-1, -2, 3, 4, 5, 6, 7, 8));
+    dbg(wide(1, 2, 3, 4, 5, 6, 123456789012ll) / 1000);
+    dbg(wide(1, 2, 3, 4, 5, 6, 123456789012ll) % 1000);
+    dbg(seven(leaf(1, 2), leaf(3, 4), 0, 0, 0, 0, leaf(5, 1)));
+    dbg(sum(10, 1, 0, 1, 1, 0, 0, 1, 0, 1, 1));
+    dbg(sum(7, 1, 1, 1, 1, 1, 1, 1));
+}
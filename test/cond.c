@@ -0,0 +1,21 @@
+int max(int a, int b)
+{
+    return a > b ? a : b;
+}
+
+int main()
+{
+    int a;
+    int b;
+    dbg(max(3, 7));
+    dbg(max((a = 9, a + 1), 4));
+    dbg(0 ? 1 : 0 ? 2 : 3);
+    b = 0;
+    a = 1 ? 5 : (b = 100);
+    dbg(a + b);
+    a = 0 ? (b = 1) : (b = 2);
+    dbg(b);
+    for (a = 0, b = 10; a < b; a = a + 3, b = b - 1) {
+        dbg(a * 100 + b);
+    }
+}
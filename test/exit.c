@@ -0,0 +1,13 @@
+// EXPECT-EXIT: 42
+void dbg(int v);
+
+int half(int n)
+{
+    return n / 2;
+}
+
+int main()
+{
+    dbg(half(85));
+    return half(84);
+}
@@ -0,0 +1,59 @@
+void dbg(int v);
+
+int add(int a, int b)
+{
+    return a + b;
+}
+
+int sub(int a, int b)
+{
+    return a - b;
+}
+
+int apply(int (*op)(int, int), int a, int b)
+{
+    return op(a, b);
+}
+
+int fold(int f(int, int), int n)
+{
+    int i;
+    int acc;
+    acc = 0;
+    for (i = 1; i <= n; i = i + 1) {
+        acc = (*f)(acc, i);
+    }
+    return acc;
+}
+
+void call_dbg(void (*f)(int), int v)
+{
+    f(v);
+}
+
+int main()
+{
+    int (*fp)(int, int);
+    int x;
+    int *p;
+
+    x = 0;
+    fp = add;
+    dbg(fp(2, 3));
+    fp = &sub;
+    dbg((*fp)(2, 3));
+    dbg(apply(add, 10, 20));
+    dbg(apply(sub, 10, 20));
+    dbg(fold(add, 10));
+    dbg((x ? add : &sub)(7, 1));
+    dbg(fp == sub);
+    dbg(fp != 0);
+    fp = 0;
+    dbg(!fp);
+    call_dbg(dbg, 99);
+
+    p = &x;
+    *p = 42;
+    dbg(x);
+    dbg(*p + 1);
+}
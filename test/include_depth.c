@@ -0,0 +1,6 @@
+// EXPECT-ERROR: #include nested too deeply
+#include "include_depth.h"
+
+int main()
+{
+}
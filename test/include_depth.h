@@ -0,0 +1,2 @@
+// Includes itself without a guard.
+#include "include_depth.h"
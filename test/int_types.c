@@ -0,0 +1,67 @@
+int sum(char a, short b, long c)
+{
+    return a + b + c;
+}
+
+long long mul64(long long a, long long b)
+{
+    return a * b;
+}
+
+unsigned char inc(unsigned char c)
+{
+    return c + 1;
+}
+
+int main()
+{
+    char c;
+    unsigned char uc;
+    short s;
+    unsigned short us;
+    unsigned u;
+    long long ll;
+    unsigned long long ull;
+
+    c = 127;
+    c = c + 1;
+    dbg(c);
+    uc = 255;
+    uc = uc + 1;
+    dbg(uc);
+    dbg(inc(255));
+    s = 40000;
+    dbg(s);
+    us = -1;
+    dbg(us);
+    dbg(sum(-1, -2, -3));
+
+    dbg((char)300);
+    dbg((unsigned char)-1);
+    dbg((short)(unsigned short)65535);
+    dbg((signed char)200 / 2);
+
+    u = 4294967295u;
+    dbg(u > 0);
+    dbg(-1 < 1u);
+    dbg(u / 2 == 2147483647);
+    dbg(-7 / 2);
+    dbg(-7 % 2);
+    dbg(4294967289u % 10);
+
+    ll = 4000000000ll;
+    dbg(ll / 1000);
+    dbg(ll > 2147483647);
+    dbg(-ll < 0);
+    ll = mul64(ll, 3);
+    dbg(ll / 1000000);
+    dbg(ll % 1000000007);
+    dbg((int)(ll >= ll) + (ll <= ll - 1));
+    ull = -1;
+    dbg(ull / 1099511627776u);
+    dbg(ull > 0);
+    dbg((long long)-1 < 0);
+    dbg(-1ll + 4294967296 == 4294967295);
+    dbg(!ll);
+    dbg(ll ? 7 : 8);
+}
@@ -0,0 +1,13 @@
+// EXPECT-ERROR: non-void function should return a value
+int half(int n)
+{
+    if (n < 0) {
+        return;
+    }
+    return n / 2;
+}
+
+int main()
+{
+    return half(4);
+}
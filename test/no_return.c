@@ -0,0 +1,15 @@
+// EXPECT-WARNING: warning: control reaches end of non-void function `sign`
+int sign(int x)
+{
+    if (x < 0) {
+        return -1;
+    } else if (x > 0) {
+        return 1;
+    }
+}
+
+int main()
+{
+    dbg(sign(-4));
+    dbg(sign(9));
+}
@@ -0,0 +1,7 @@
+// EXPECT-ERROR: expression is not assignable
+int main()
+{
+    int x;
+    x + 1 = 2;
+    return x;
+}
@@ -0,0 +1,74 @@
+#include "pp.h"
+#include "pp.h"
+
+#define N 3
+#define ADD(a, b) ((a) + (b))
+#define CAT(a, b) a##b
+#define FIRST(x, ...) x
+#define COUNT(...) count(__VA_ARGS__)
+#define CALL(f, a, b) f a##b
+#define CAT3(a, b, c) a##b##c
+
+void dbg(int v);
+int count(int n, ...);
+
+/* A comment
+   spanning lines. */
+int twice(int n)
+{
+    return ADD(n, n); // Line comment.
+}
+
+int count(int n, ...)
+{
+    return n;
+}
+
+int main()
+{
+    int CAT(x, 1);
+    int g;
+    x1 = SQUARE(N + 1);
+    dbg(x1);
+    dbg(twice(N));
+    dbg(FIRST(7, 8, 9));
+    dbg(COUNT(2, 5, 6));
+    g = 1;
+#define g (g + 2)
+    dbg(g);
+
+#if N > 2 && defined(ADD)
+    dbg(1);
+#elif N > 1
+    dbg(2);
+#else
+    dbg(3);
+#endif
+
+#ifdef UNDEFINED
+    dbg(4);
+#elif !defined N
+    dbg(5);
+#else
+    dbg(6);
+#endif
+
+#undef N
+#ifndef N
+    dbg(7);
+#endif
+
+#if 0
+#if 1
+    dbg(8);
+#endif
+#error not reached
+#endif
+
+    dbg(CALL(twice, , (4)));
+    dbg(CAT3(, , 9));
+    dbg(CAT3(1, , 2));
+
+    dbg(ADD(1, \
+            2));
+}
@@ -0,0 +1,5 @@
+#pragma once
+
+#define SQUARE(x) ((x) * (x))
+
+int twice(int n);
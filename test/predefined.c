@@ -0,0 +1,21 @@
+void dbg(int v);
+
+#define HERE __LINE__
+
+int main()
+{
+    dbg(__LINE__);
+    dbg(HERE);
+    dbg(__STDC__);
+    dbg(__STDC_VERSION__);
+#if ((defined(__i386__) || defined(__x86_64__) || defined(__aarch64__) \
+      || defined(__riscv)) \
+         && defined(__linux__) \
+     || defined(__wasm32__) && !defined(__linux__)) \
+    && defined(__minicc__)
+    dbg(1);
+#endif
+#ifdef __FILE__
+    dbg(2);
+#endif
+}
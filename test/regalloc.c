@@ -0,0 +1,72 @@
+int many(int a, int b, int c, int d)
+{
+    int e;
+    int f;
+    int g;
+    int h;
+    int i;
+    int j;
+    int k;
+    int l;
+
+    e = a + b;
+    f = b * c;
+    g = c - d;
+    h = d / (a + 1);
+    i = e * f;
+    j = g % 7;
+    k = h + i;
+    l = j - k;
+    dbg(e);
+    return a + b + c + d + e + f + g + h + i + j + k + l;
+}
+
+int div(int a, int b)
+{
+    return a / b * 100 + a % b;
+}
+
+unsigned udiv(unsigned a, unsigned b)
+{
+    return a / b * 100 + a % b;
+}
+
+int cdiv(char a, char b)
+{
+    return a / b;
+}
+
+long long div64(long long a, long long b)
+{
+    return a / b + a % b;
+}
+
+int cmp64(long long a, long long b)
+{
+    return (a < b) + 2 * (a <= b) + 4 * (a > b) + 8 * (a >= b) +
+        16 * (a == b) + 32 * (a != b);
+}
+
+int main()
+{
+    long long x;
+    int i;
+
+    dbg(many(1, 2, 3, 4));
+    dbg(many(-10, 20, -30, 40));
+    dbg(div(-17, 5));
+    dbg(udiv(17, 5));
+    dbg(div(100, -7));
+    dbg(cdiv(-100, 7));
+    dbg(div64(-1000000000000, 7));
+    dbg(cmp64(-5, 3));
+    dbg(cmp64(4294967296, 1));
+    dbg(cmp64(3, 3));
+
+    x = 123456789;
+    for (i = 0; i < 5; i = i + 1) {
+        x = x * 3 + i;
+    }
+    dbg(x / 1000000);
+    dbg(x % 1000000);
+}
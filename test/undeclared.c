@@ -0,0 +1,7 @@
+// EXPECT-ERROR: cannot find value `y`
+int main()
+{
+    int x;
+    x = y + 1;
+    return x;
+}
@@ -0,0 +1,53 @@
+void dbg(int v);
+int sum(int n, ...);
+long long sum64(int n, ...);
+
+void show(char tag, ...)
+{
+    va_list ap;
+    va_start(ap, tag);
+    dbg(tag);
+    dbg(va_arg(ap, int));
+    va_end(ap);
+}
+
+int sum(int n, ...)
+{
+    va_list ap;
+    int i;
+    int s;
+    va_start(ap, n);
+    s = 0;
+    for (i = 0; i < n; i = i + 1) {
+        s = s + va_arg(ap, int);
+    }
+    va_end(ap);
+    return s;
+}
+
+long long sum64(int n, ...)
+{
+    va_list ap;
+    int i;
+    long long s;
+    va_start(ap, n);
+    s = 0;
+    for (i = 0; i < n; i = i + 1) {
+        s = s + va_arg(ap, long long);
+    }
+    va_end(ap);
+    return s;
+}
+
+int main()
+{
+    char c;
+    short s;
+    c = -3;
+    s = 1000;
+    dbg(sum(0));
+    dbg(sum(3, 1, 2, 3));
+    dbg(sum(3, c, s, (unsigned char)255));
+    dbg(sum64(2, 4000000000ll, 5000000000ll) / 1000);
+    show(65, 66);
+}
@@ -0,0 +1,35 @@
+void show(int x)
+{
+    if (x < 0) {
+        return;
+    }
+    dbg(x);
+}
+
+void nothing(void)
+{
+}
+
+int twice(int x)
+{
+    return x * 2;
+}
+
+int sign(int x)
+{
+    if (x < 0) {
+        return -1;
+    } else {
+        return x > 0;
+    }
+}
+
+int main(void)
+{
+    show(-5);
+    show(3);
+    nothing();
+    (void)twice(4);
+    1 ? show(10) : show(20);
+    dbg(sign(-9) + sign(0) * 10 + sign(9) * 100);
+}
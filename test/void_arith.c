@@ -0,0 +1,7 @@
+// EXPECT-ERROR: expected integer, found `void *`
+int main()
+{
+    void *p;
+    p = 0;
+    return p + 1;
+}
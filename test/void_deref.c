@@ -0,0 +1,7 @@
+// EXPECT-ERROR: dereferencing `void *` pointer
+int main()
+{
+    void *p;
+    p = 0;
+    *p;
+}
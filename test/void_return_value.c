@@ -0,0 +1,10 @@
+// EXPECT-ERROR: void function should not return a value
+void show(int x)
+{
+    return x;
+}
+
+int main()
+{
+    show(1);
+}
@@ -0,0 +1,11 @@
+// EXPECT-ERROR: void value not ignored as it ought to be
+void nothing(void)
+{
+}
+
+int main()
+{
+    int x;
+    x = nothing() + 1;
+    return x;
+}
@@ -0,0 +1,5 @@
+// EXPECT-ERROR: variable has incomplete type `void`
+int main()
+{
+    void v;
+}